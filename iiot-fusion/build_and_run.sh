@@ -47,4 +47,12 @@ echo ""
 echo "▶ 執行..."
 echo "════════════════════════════════════════════════"
 echo ""
-"$SCRIPT_DIR/target/release/iiot-flow-host" "$SCRIPT_DIR/wasm_out"
+# Tag Registry 設定檔（tags.toml）
+export IIOT_FLOW_TAGS="$SCRIPT_DIR/tags.toml"
+"$SCRIPT_DIR/target/release/iiot-flow-host" "$SCRIPT_DIR/wasm_out" "$SCRIPT_DIR/flow.json"
+
+# ── Fusion（選用）────────────────────────────────────────────────
+# cargo build -p iiot-flow-fusion --release
+# target/release/iiot-flow-fusion wasm_out flow.json --tags tags.toml --deploy flow-deploy
+# target/release/iiot-flow-host wasm_out wasm_out/flow-flow.wasm
+# target/release/iiot-flow-host flow-deploy        # 依 manifest.json 啟動 Runtime（先設定 host-endpoints）
@@ -0,0 +1,163 @@
+// fusion/src/compose.rs
+// WAC composition（WasmFlowFusionTool §3）：glue + 所有 Node → 單一 component
+//
+//   每個 flow node 各自一個 instance（同一個 .wasm 可實例化多次，狀態互不影響）
+//   node 的 iiot:flow/node@0.2.0 export → glue 的 import n{p}
+//   其餘 import（WASI / host-api / types）同名合併，成為 Fused component 的 import
+//   對外只 export glue 的 iiot:flow/pipeline@0.2.0
+
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+use std::path::Path;
+use wac_graph::types::Package;
+use wac_graph::{CompositionGraph, EncodeOptions};
+
+use crate::glue::{Layout, Slot};
+
+const NODE_EXPORT:     &str = "iiot:flow/node@0.2.0";
+const PIPELINE_EXPORT: &str = "iiot:flow/pipeline@0.2.0";
+
+pub fn compose(dag: &Layout, slots: &[Slot], glue: &Path, wasm_dir: &Path) -> Result<Vec<u8>> {
+    let mut graph = CompositionGraph::new();
+
+    let bytes = std::fs::read(glue).with_context(|| format!("讀取失敗：{}", glue.display()))?;
+    let pkg   = Package::from_bytes("iiot:glue", None, bytes, graph.types_mut())?;
+    let glue_pkg  = graph.register_package(pkg)?;
+    let glue_inst = graph.instantiate(glue_pkg);
+
+    // 同一個 .wasm 只註冊一次 package
+    let mut packages = HashMap::new();
+    for (p, slot) in slots.iter().enumerate() {
+        let Slot::Wasm { import } = slot else { continue; };
+        let def = dag.ir.node(&dag.ids[p]).context("DAG 與 IR 不一致")?;
+        let pkg_id = match packages.get(def.wasm.as_str()) {
+            Some(&id) => id,
+            None => {
+                let path  = wasm_dir.join(&def.wasm);
+                let bytes = std::fs::read(&path)
+                    .with_context(|| format!("讀取失敗：{}", path.display()))?;
+                let pkg = Package::from_bytes(&format!("iiot:node{}", packages.len()), None,
+                                              bytes, graph.types_mut())
+                    .with_context(|| format!("不是合法的 component：{}", path.display()))?;
+                let id = graph.register_package(pkg)?;
+                packages.insert(def.wasm.as_str(), id);
+                id
+            }
+        };
+        let inst = graph.instantiate(pkg_id);
+        let node = graph.alias_instance_export(inst, NODE_EXPORT)
+            .with_context(|| format!("{} 沒有 export {NODE_EXPORT}", def.id))?;
+        graph.set_instantiation_argument(glue_inst, import, node)
+            .with_context(|| format!("{} 無法接上 glue 的 {import}", def.id))?;
+    }
+
+    let pipeline = graph.alias_instance_export(glue_inst, PIPELINE_EXPORT)?;
+    graph.export(pipeline, PIPELINE_EXPORT)?;
+    Ok(graph.encode(EncodeOptions::default())?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::glue::Role;
+    use iiot_flow_host::flow::FlowDef;
+    use iiot_flow_host::iiot::flow::types::ValueKind;
+    use wasmtime::component::{Component, Linker};
+    use wasmtime::{Engine, Store};
+
+    // Node：export iiot:flow/node@0.2.0，value 回傳 v
+    fn node(v: u32) -> Vec<u8> {
+        wat::parse_str(format!(r#"(component
+            (core module $m (func (export "value") (result i32) (i32.const {v})))
+            (core instance $i (instantiate $m))
+            (func $value (result u32) (canon lift (core func $i "value")))
+            (instance $node (export "value" (func $value)))
+            (export "{NODE_EXPORT}" (instance $node)))"#)).unwrap()
+    }
+
+    // glue：import n0、n2（位置 1 是 Cast Node），export 的 pipeline 把兩者的 value 轉出（只驗證接線）
+    fn glue() -> Vec<u8> {
+        wat::parse_str(format!(r#"(component
+            (import "n0" (instance $n0 (export "value" (func (result u32)))))
+            (import "n2" (instance $n2 (export "value" (func (result u32)))))
+            (alias export $n0 "value" (func $first))
+            (alias export $n2 "value" (func $second))
+            (instance $p (export "first" (func $first)) (export "second" (func $second)))
+            (export "{PIPELINE_EXPORT}" (instance $p)))"#)).unwrap()
+    }
+
+    struct Dir(std::path::PathBuf);
+
+    impl Dir {
+        fn new(name: &str) -> Self {
+            let dir = std::env::temp_dir().join(format!("iiot-fusion-test-{}-{name}", std::process::id()));
+            std::fs::create_dir_all(&dir).unwrap();
+            Dir(dir)
+        }
+    }
+
+    impl Drop for Dir {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    // a → (Cast) → c：Cast Node 在 glue 內 inline，不接線
+    const IR: &str = r#"{
+        "nodes": [
+            { "id": "a", "wasm": "seven.wasm" },
+            { "id": "c", "wasm": "nine.wasm" },
+            { "id": "cast#0:a:0→c:0", "wasm": "" }
+        ],
+        "edges": [
+            { "from": "a", "to": "cast#0:a:0→c:0" },
+            { "from": "cast#0:a:0→c:0", "to": "c" }
+        ]
+    }"#;
+
+    fn call(bytes: &[u8], name: &str) -> u32 {
+        let engine = Engine::default();
+        let component = Component::new(&engine, bytes).unwrap();
+        let mut store = Store::new(&engine, ());
+        let instance = Linker::new(&engine).instantiate(&mut store, &component).unwrap();
+        let pipeline = instance.get_export_index(&mut store, None, PIPELINE_EXPORT).unwrap();
+        let func = instance.get_export_index(&mut store, Some(&pipeline), name).unwrap();
+        let f = instance.get_typed_func::<(), (u32,)>(&mut store, &func).unwrap();
+        f.call(&mut store, ()).unwrap().0
+    }
+
+    #[test]
+    fn nodes_are_wired_to_their_imports() {
+        let dir = Dir::new("compose");
+        std::fs::write(dir.0.join("seven.wasm"), node(7)).unwrap();
+        std::fs::write(dir.0.join("nine.wasm"), node(9)).unwrap();
+        std::fs::write(dir.0.join("glue.wasm"), glue()).unwrap();
+
+        let ir = FlowDef::parse(IR).unwrap();
+        let ids = ["a", "cast#0:a:0→c:0", "c"].map(String::from).to_vec();
+        let layout = Layout { ids: &ids, roles: vec![Role::Source, Role::Cast(ValueKind::F64Val), Role::Sink],
+                              init_args: &[], ir: &ir };
+
+        let bytes = compose(&layout, &layout.slots(), &dir.0.join("glue.wasm"), &dir.0).unwrap();
+        assert_eq!(call(&bytes, "first"), 7, "n0 應接到 a");
+        assert_eq!(call(&bytes, "second"), 9, "n2 應接到 c");
+    }
+
+    #[test]
+    fn node_without_the_node_export_is_rejected() {
+        let dir = Dir::new("compose-bad");
+        let bad = wat::parse_str(r#"(component
+            (core module $m (func (export "value") (result i32) (i32.const 7)))
+            (core instance $i (instantiate $m))
+            (func $value (result u32) (canon lift (core func $i "value")))
+            (export "value" (func $value)))"#).unwrap();
+        std::fs::write(dir.0.join("seven.wasm"), bad).unwrap();
+        std::fs::write(dir.0.join("glue.wasm"), glue()).unwrap();
+
+        let ir = FlowDef::parse(IR).unwrap();
+        let ids = vec!["a".to_string()];
+        let layout = Layout { ids: &ids, roles: vec![Role::Source], init_args: &[], ir: &ir };
+        let e = compose(&layout, &layout.slots(), &dir.0.join("glue.wasm"), &dir.0).unwrap_err();
+        assert_eq!(e.to_string(), format!("a 沒有 export {NODE_EXPORT}"));
+    }
+}
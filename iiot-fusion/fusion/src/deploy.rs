@@ -0,0 +1,94 @@
+// fusion/src/deploy.rs
+// 產出 flow-deploy/ artifact 目錄（IIoTFlowArchitecture §12）
+//
+//   flow.wasm      ← fused pipeline
+//   flow.cwasm     ← Native AOT（與 iiot-flow-host 相同的 Engine Config 預編譯）
+//   manifest.json  ← 已存在就保留（endpoint 位址由現場設定），只更新 version / artifact，
+//                    node-limits 依目前 flow 重建（仍存在的 node 保留現場值，已移除的 node 丟掉）
+
+use anyhow::{Context, Result};
+use std::collections::BTreeMap;
+use std::path::Path;
+use wasmtime::Engine;
+
+use iiot_flow_host::dag::FlowDag;
+use iiot_flow_host::iiot::flow::node_descriptor::NodeKind;
+use iiot_flow_host::limits::ExecLimits;
+use iiot_flow_host::manifest::{ArtifactDef, EndpointDef, Manifest, SinkEndDef, SourceDef};
+
+const DEFAULT_INTERVAL_MS: u64 = 100;
+
+pub fn write(dir: &Path, engine: &Engine, dag: &FlowDag, name: &str, version: &str,
+             fused: &[u8]) -> Result<()> {
+    std::fs::create_dir_all(dir).with_context(|| format!("無法建立 {}", dir.display()))?;
+    std::fs::write(dir.join("flow.wasm"), fused)?;
+    let aot = engine.precompile_component(fused).map_err(anyhow::Error::from)
+        .context("AOT 預編譯失敗")?;
+    std::fs::write(dir.join("flow.cwasm"), aot)?;
+
+    let artifact = ArtifactDef {
+        wasm:          "flow.wasm".to_string(),
+        native_aot:    Some("flow.cwasm".to_string()),
+        prefer_native: true,
+    };
+    let manifest = if dir.join("manifest.json").is_file() {
+        let old = Manifest::load(dir)?;
+        let node_limits = node_limits(dag, &old.node_limits);
+        Manifest { version: version.to_string(), artifact, node_limits, ..old }
+    } else {
+        Manifest { version: version.to_string(), artifact, ..skeleton(dag, name) }
+    };
+    let text = serde_json::to_string_pretty(&manifest)?;
+    std::fs::write(dir.join("manifest.json"), text + "\n")?;
+    Ok(())
+}
+
+// 每個 node 的執行上限：現場在 manifest 改過的值優先，其次是 flow.json 指定的；
+// 不在目前 flow 裡的 node 一律丟掉，避免改名 / 刪除後殘留的設定在載入時變成警告
+fn node_limits(dag: &FlowDag, current: &BTreeMap<String, ExecLimits>) -> BTreeMap<String, ExecLimits> {
+    dag.ir.nodes.iter()
+        .filter_map(|def| match current.get(&def.id) {
+            Some(l)                          => Some((def.id.clone(), *l)),
+            None if !def.limits.is_default() => Some((def.id.clone(), def.limits)),
+            None                             => None,
+        })
+        .collect()
+}
+
+// 第一次產出：每個 source 一個 nng-pull endpoint（Driver PUSH 進來），sink 輸出到 stdout，sink-end 先用 log
+fn skeleton(dag: &FlowDag, name: &str) -> Manifest {
+    let mut m = Manifest {
+        version: String::new(), flow_id: name.to_string(),
+        artifact: ArtifactDef { wasm: String::new(), native_aot: None, prefer_native: false },
+        sources: Vec::new(), host_endpoints: Vec::new(), subscribe: Vec::new(), sink_ends: Vec::new(),
+        batch: Default::default(), limits: Default::default(), node_limits: Default::default(),
+        supervisor: Default::default(), crash_dump: Default::default(),
+        snapshot: Default::default(), redeploy: Default::default(), dir: Default::default(),
+    };
+    m.node_limits = node_limits(dag, &BTreeMap::new());
+    for (id, node) in dag.ids.iter().zip(&dag.nodes) {
+        let endpoint = m.host_endpoints.len() as u32;
+        match node.spec().kind {
+            NodeKind::Source => {
+                m.sources.push(SourceDef {
+                    tick_export: id.clone(), interval_ms: DEFAULT_INTERVAL_MS, overrun: Default::default(),
+                    endpoint_id: Some(endpoint), description: node.name().to_string(),
+                });
+                m.host_endpoints.push(EndpointDef {
+                    id: endpoint, kind: "nng-pull".to_string(),
+                    address: format!("tcp://127.0.0.1:{}", 5555 + endpoint), role: "source".to_string(),
+                    node: None, queue: 4096,
+                });
+            }
+            NodeKind::Sink => m.host_endpoints.push(EndpointDef {
+                id: endpoint, kind: "stdout".to_string(), address: String::new(),
+                role: "sink".to_string(), node: Some(id.clone()), queue: 4096,
+            }),
+            NodeKind::SinkEnd => m.sink_ends.push(SinkEndDef {
+                id: id.clone(), handler: "log".to_string(), target: String::new(), timeout_ms: 3000,
+            }),
+            _ => {}
+        }
+    }
+    m
+}
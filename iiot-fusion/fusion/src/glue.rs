@@ -0,0 +1,334 @@
+// fusion/src/glue.rs
+// 產生 glue crate（WasmFlowFusionTool §4.1：動態生成 Glue Code → 編譯成 glue.wasm）
+//
+//   <work>/Cargo.toml
+//   <work>/wit/glue.wit              ← 每個 Node 一個 import（n0、n1…）+ export iiot:flow/pipeline
+//   <work>/wit/deps/iiot-flow/*.wit  ← iiot:flow@0.2.0
+//   <work>/src/lib.rs                ← template/glue.rs（固定邏輯）
+//   <work>/src/nodes.rs              ← 此 flow 的 Node 表 / Route 表 / dispatch
+//   <work>/src/cast_value.rs         ← 與 host 共用的數值族轉換
+
+use anyhow::{bail, Context, Result};
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use iiot_flow_host::dag::{DagNode, FlowDag};
+use iiot_flow_host::flow::FlowDef;
+use iiot_flow_host::iiot::flow::node_descriptor::NodeKind;
+use iiot_flow_host::iiot::flow::types::ValueKind;
+use iiot_flow_host::kind_name;
+
+const TEMPLATE:   &str = include_str!("../template/glue.rs");
+const CAST_VALUE: &str = include_str!("../../host/src/cast_value.rs");
+const WIT_DEPS: &[(&str, &str)] = &[
+    ("types.wit",           include_str!("../../wit/0.2.0/types.wit")),
+    ("node-descriptor.wit", include_str!("../../wit/0.2.0/node-descriptor.wit")),
+    ("flow-node.wit",       include_str!("../../wit/0.2.0/flow-node.wit")),
+    ("host-api.wit",        include_str!("../../wit/0.2.0/host-api.wit")),
+    ("fused-pipeline.wit",  include_str!("../../wit/0.2.0/fused-pipeline.wit")),
+];
+
+const CARGO_TOML: &str = r#"[package]
+name    = "iiot-flow-glue"
+version = "0.1.0"
+edition = "2021"
+
+[lib]
+crate-type = ["cdylib"]
+
+[dependencies]
+wit-bindgen = "0.53.1"
+prost = { version = "0.14.3", default-features = false, features = ["derive"] }
+
+[profile.release]
+opt-level = "s"
+lto       = true
+strip     = true
+
+# 獨立於任何 workspace
+[workspace]
+"#;
+
+/// glue 中每個 DAG 位置的角色
+pub enum Slot {
+    Wasm { import: String },  // 對應 glue 的 import 名稱
+    Cast,                     // inline，不需要 import
+}
+
+/// DAG 位置在 glue 內的種類（nodes.rs 的 Kind）
+#[derive(Clone, Copy)]
+pub enum Role {
+    Source,
+    Node,
+    Sink,
+    Cast(ValueKind), // 轉換後的型別
+}
+
+/// 產生 glue 需要的 DAG 資訊（FlowDag 的子集；不需要 Node instance）
+pub struct Layout<'a> {
+    pub ids:       &'a [String],                    // topo 順序（含 Cast Node）
+    pub roles:     Vec<Role>,                       // 與 ids 對齊
+    pub init_args: &'a [Option<(String, String)>], // 與 ids 對齊
+    pub ir:        &'a FlowDef,                     // 插入 Cast Node 後的 flow
+}
+
+impl<'a> Layout<'a> {
+    pub fn of(dag: &'a FlowDag) -> Self {
+        let roles = dag.nodes.iter().map(|n| match n {
+            DagNode::Cast(c) => Role::Cast(c.to),
+            DagNode::Sink(_) => Role::Sink,
+            DagNode::Node(n) if n.spec.kind == NodeKind::Source => Role::Source,
+            DagNode::Node(_) => Role::Node,
+        }).collect();
+        Layout { ids: &dag.ids, roles, init_args: &dag.init_args, ir: &dag.ir }
+    }
+
+    /// Cast Node 在 glue 內 inline，其餘各自一個 import
+    pub fn slots(&self) -> Vec<Slot> {
+        self.roles.iter().enumerate().map(|(p, r)| match r {
+            Role::Cast(_) => Slot::Cast,
+            _ => Slot::Wasm { import: format!("n{p}") },
+        }).collect()
+    }
+}
+
+/// 寫出 glue crate，回傳每個 DAG 位置的 Slot（與 dag.ids 對齊）
+pub fn generate(work: &Path, dag: &Layout, name: &str, version: &str) -> Result<Vec<Slot>> {
+    let slots = dag.slots();
+
+    let deps = work.join("wit/deps/iiot-flow");
+    std::fs::create_dir_all(&deps)?;
+    std::fs::create_dir_all(work.join("src"))?;
+    for (file, text) in WIT_DEPS {
+        std::fs::write(deps.join(file), text)?;
+    }
+    write_if_changed(&work.join("Cargo.toml"),        CARGO_TOML)?;
+    write_if_changed(&work.join("wit/glue.wit"),      &glue_wit(&slots))?;
+    write_if_changed(&work.join("src/lib.rs"),        TEMPLATE)?;
+    write_if_changed(&work.join("src/cast_value.rs"), CAST_VALUE)?;
+    write_if_changed(&work.join("src/nodes.rs"),      &nodes_rs(dag, &slots, name, version)?)?;
+    Ok(slots)
+}
+
+/// cargo build --release --target wasm32-wasip2，回傳 glue component 路徑
+pub fn build(work: &Path) -> Result<PathBuf> {
+    let status = Command::new(std::env::var("CARGO").unwrap_or_else(|_| "cargo".to_string()))
+        .args(["build", "--release", "--target", "wasm32-wasip2", "--manifest-path"])
+        .arg(work.join("Cargo.toml"))
+        .status()
+        .context("無法執行 cargo（需要 Rust toolchain 與 wasm32-wasip2 target）")?;
+    if !status.success() { bail!("glue 編譯失敗：{}", work.display()); }
+    Ok(work.join("target/wasm32-wasip2/release/iiot_flow_glue.wasm"))
+}
+
+// 內容沒變就不寫，避免 cargo 每次重新編譯
+fn write_if_changed(path: &Path, text: &str) -> Result<()> {
+    if std::fs::read_to_string(path).is_ok_and(|old| old == text) { return Ok(()); }
+    std::fs::write(path, text).with_context(|| format!("寫入失敗：{}", path.display()))
+}
+
+fn glue_wit(slots: &[Slot]) -> String {
+    let mut w = String::from("// 由 iiot-flow-fusion 產生，請勿手動修改\npackage iiot:flow-glue@0.2.0;\n\nworld glue {\n");
+    w.push_str("    import iiot:flow/types@0.2.0;\n\n");
+    for slot in slots {
+        let Slot::Wasm { import } = slot else { continue; };
+        let _ = writeln!(w, "    import {import}: interface {{
+        use iiot:flow/types@0.2.0.{{flow-msg, node-output}};
+        init:        func(props: string, wiring: string) -> result<_, string>;
+        process:     func(input-port: u32, msgs: list<flow-msg>) -> node-output;
+        process-raw: func(tag-id: u32, msg-id: u32, raw-bytes: list<u8>) -> node-output;
+        save-state:  func() -> list<u8>;
+        load-state:  func(state: list<u8>);
+    }}
+");
+    }
+    w.push_str("    import iiot:flow/trace@0.2.0;\n    export iiot:flow/pipeline@0.2.0;\n}\n");
+    w
+}
+
+fn nodes_rs(dag: &Layout, slots: &[Slot], name: &str, version: &str) -> Result<String> {
+    let mut r = String::from("// glue/src/nodes.rs（由 iiot-flow-fusion 產生，請勿手動修改）\n\n");
+    let has_cast = slots.iter().any(|s| matches!(s, Slot::Cast));
+    r.push_str(if has_cast { "use crate::iiot::flow::types::{FlowMsg, NodeOutput, ValueKind};\n" }
+               else        { "use crate::iiot::flow::types::{FlowMsg, NodeOutput};\n" });
+    r.push_str("use crate::{Entry, Kind, Route};\n\n");
+    let _ = writeln!(r, "pub const NAME:    &str = {name:?};");
+    let _ = writeln!(r, "pub const VERSION: &str = {version:?};\n");
+
+    let _ = writeln!(r, "pub static NODES: [Entry; {}] = [", dag.roles.len());
+    for (p, role) in dag.roles.iter().enumerate() {
+        let kind = match role {
+            Role::Cast(to) => format!("Kind::Cast(ValueKind::{})", camel(kind_name(*to))),
+            Role::Sink     => "Kind::Sink".to_string(),
+            Role::Source   => "Kind::Source".to_string(),
+            Role::Node     => "Kind::Node".to_string(),
+        };
+        let (props, wiring) = dag.init_args[p].clone().unwrap_or_default();
+        let _ = writeln!(r, "    Entry {{ id: {:?}, kind: {kind}, props: {props:?}, wiring: {wiring:?} }},",
+            dag.ids[p]);
+    }
+    r.push_str("];\n\n");
+
+    let pos: HashMap<&str, usize> = dag.ids.iter().enumerate().map(|(p, id)| (id.as_str(), p)).collect();
+    let _ = writeln!(r, "pub static ROUTES: [Route; {}] = [", dag.ir.edges.len());
+    for e in &dag.ir.edges {
+        let (Some(from), Some(to)) = (pos.get(e.from.as_str()), pos.get(e.to.as_str())) else {
+            bail!("edge {} → {} 不在 DAG 內", e.from, e.to);
+        };
+        let _ = writeln!(r, "    Route {{ from: {from}, port: {}, to: {to}, to_port: {} }},",
+            e.from_port, e.to_port);
+    }
+    r.push_str("];\n");
+
+    // dispatch：n 為 DAG 位置，對應 import n{n}；Cast Node 不會走到這裡
+    // 呼叫 Node 前先 trace.enter-node，Host 切換成該 Node 的執行上限
+    let dispatch = |sig: &str, ret: &str, call: &str, default: &str| {
+        let mut f = format!("\npub fn {sig} -> {ret} {{\n    match n {{\n");
+        for (p, slot) in slots.iter().enumerate() {
+            if let Slot::Wasm { import } = slot {
+                let _ = writeln!(f, "        {p} => {{ crate::iiot::flow::trace::enter_node({p}); crate::{import}::{call} }}");
+            }
+        }
+        let _ = write!(f, "        _ => {default},\n    }}\n}}\n");
+        f
+    };
+    r.push_str(&dispatch("init(n: usize, props: &str, wiring: &str)", "Result<(), String>",
+        "init(props, wiring)", "Ok(())"));
+    r.push_str(&dispatch("process(n: usize, port: u32, msgs: &[FlowMsg])", "NodeOutput",
+        "process(port, msgs)", "NodeOutput { outputs: Vec::new() }"));
+    r.push_str(&dispatch("process_raw(n: usize, tag_id: u32, msg_id: u32, raw: &[u8])", "NodeOutput",
+        "process_raw(tag_id, msg_id, raw)", "NodeOutput { outputs: Vec::new() }"));
+    r.push_str(&dispatch("save_state(n: usize)", "Vec<u8>", "save_state()", "Vec::new()"));
+    r.push_str(&dispatch("load_state(n: usize, state: &[u8])", "()", "load_state(state)", "()"));
+    Ok(r)
+}
+
+/// "f64-val" → "F64Val"（wit-bindgen 的 Rust 名稱）
+fn camel(kebab: &str) -> String {
+    kebab.split('-').map(|s| {
+        let mut c = s.chars();
+        c.next().map(|f| f.to_ascii_uppercase().to_string() + c.as_str()).unwrap_or_default()
+    }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // src → (Cast f64-val) → math → sink，ids 為插入 Cast Node 後的 topo 順序
+    const IR: &str = r#"{
+        "nodes": [
+            { "id": "src",  "wasm": "src.wasm" },
+            { "id": "math", "wasm": "math.wasm", "props": { "k": 2 } },
+            { "id": "sink", "wasm": "sink.wasm" },
+            { "id": "cast#0:src:0→math:0", "wasm": "" }
+        ],
+        "edges": [
+            { "from": "src",  "to": "cast#0:src:0→math:0" },
+            { "from": "math", "to": "sink", "to-port": 1 },
+            { "from": "cast#0:src:0→math:0", "to": "math" }
+        ]
+    }"#;
+
+    struct Fixture {
+        ids:       Vec<String>,
+        init_args: Vec<Option<(String, String)>>,
+        ir:        FlowDef,
+    }
+
+    fn fixture() -> Fixture {
+        let args = |p: &str, w: &str| Some((p.to_string(), w.to_string()));
+        Fixture {
+            ids:       ["src", "cast#0:src:0→math:0", "math", "sink"].map(String::from).to_vec(),
+            init_args: vec![args("{}", r#"{"w":1}"#), None, args(r#"{"k":2}"#, "{}"), args("{}", "{}")],
+            ir:        FlowDef::parse(IR).unwrap(),
+        }
+    }
+
+    fn layout(f: &Fixture) -> Layout<'_> {
+        let roles = vec![Role::Source, Role::Cast(ValueKind::F64Val), Role::Node, Role::Sink];
+        Layout { ids: &f.ids, roles, init_args: &f.init_args, ir: &f.ir }
+    }
+
+    // static 表的內容（宣告到 "];"）
+    fn table<'a>(rs: &'a str, decl: &str) -> &'a str {
+        let start = rs.find(decl).unwrap_or_else(|| panic!("缺少 {decl}"));
+        let len = rs[start..].find("];").unwrap();
+        &rs[start..start + len + 2]
+    }
+
+    #[test]
+    fn nodes_follow_the_dag() {
+        let f = fixture();
+        let dag = layout(&f);
+        let rs = nodes_rs(&dag, &dag.slots(), "flow", "1.2.0").unwrap();
+        assert_eq!(table(&rs, "pub static NODES"), r#"pub static NODES: [Entry; 4] = [
+    Entry { id: "src", kind: Kind::Source, props: "{}", wiring: "{\"w\":1}" },
+    Entry { id: "cast#0:src:0→math:0", kind: Kind::Cast(ValueKind::F64Val), props: "", wiring: "" },
+    Entry { id: "math", kind: Kind::Node, props: "{\"k\":2}", wiring: "{}" },
+    Entry { id: "sink", kind: Kind::Sink, props: "{}", wiring: "{}" },
+];"#);
+        assert!(rs.contains(r#"pub const NAME:    &str = "flow";"#));
+        assert!(rs.contains(r#"pub const VERSION: &str = "1.2.0";"#));
+    }
+
+    #[test]
+    fn routes_use_dag_positions() {
+        let f = fixture();
+        let dag = layout(&f);
+        let rs = nodes_rs(&dag, &dag.slots(), "flow", "1.2.0").unwrap();
+        assert_eq!(table(&rs, "pub static ROUTES"), "pub static ROUTES: [Route; 3] = [
+    Route { from: 0, port: 0, to: 1, to_port: 0 },
+    Route { from: 2, port: 0, to: 3, to_port: 1 },
+    Route { from: 1, port: 0, to: 2, to_port: 0 },
+];");
+    }
+
+    #[test]
+    fn cast_slots_get_no_import() {
+        let f = fixture();
+        let dag = layout(&f);
+        let slots = dag.slots();
+        assert!(matches!(slots[1], Slot::Cast));
+
+        let wit = glue_wit(&slots);
+        for import in ["n0", "n2", "n3"] {
+            assert!(wit.contains(&format!("import {import}: interface")), "缺少 import {import}");
+        }
+        assert!(!wit.contains("import n1:"), "Cast Node 不應有 import");
+
+        let rs = nodes_rs(&dag, &slots, "flow", "1.2.0").unwrap();
+        assert!(rs.contains("        0 => { crate::iiot::flow::trace::enter_node(0); crate::n0::init(props, wiring) }"));
+        assert!(!rs.contains("        1 => "), "Cast Node 不應出現在 dispatch");
+        assert!(rs.contains("use crate::iiot::flow::types::{FlowMsg, NodeOutput, ValueKind};"));
+    }
+
+    #[test]
+    fn value_kind_is_imported_only_with_casts() {
+        let f = fixture();
+        let mut dag = layout(&f);
+        dag.roles[1] = Role::Node;
+        let rs = nodes_rs(&dag, &dag.slots(), "flow", "1.2.0").unwrap();
+        assert!(rs.contains("use crate::iiot::flow::types::{FlowMsg, NodeOutput};\n"));
+    }
+
+    #[test]
+    fn edge_outside_the_dag_is_rejected() {
+        let mut f = fixture();
+        f.ids.pop();
+        f.init_args.pop();
+        let mut dag = layout(&f);
+        dag.roles.pop();
+        let e = nodes_rs(&dag, &dag.slots(), "flow", "1.2.0").unwrap_err();
+        assert_eq!(e.to_string(), "edge math → sink 不在 DAG 內");
+    }
+
+    #[test]
+    fn camel_matches_wit_bindgen() {
+        assert_eq!(camel("f64-val"), "F64Val");
+        assert_eq!(camel("short-str"), "ShortStr");
+        assert_eq!(camel("blob"), "Blob");
+    }
+}
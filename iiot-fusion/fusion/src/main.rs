@@ -0,0 +1,132 @@
+// fusion/src/main.rs
+// iiot-flow-fusion：flow.json + Node .wasm → 單一 fused-pipeline component
+//
+//   Step 1  載入 + Validate（與 host 相同的 FlowDag::load，含 Cast Node 插入與 init 檢查）
+//   Step 2  產生 glue crate（routing table 燒進程式）並編譯成 glue.wasm
+//   Step 3  WAC composition：glue + Nodes → flow-<name>.wasm
+//   Step 4  （--deploy）flow-deploy/：flow.wasm + flow.cwasm + manifest.json
+//
+// 用法：iiot-flow-fusion <wasm_dir> <flow.json> [-o out.wasm] [--name NAME]
+//                         [--work DIR] [--tags FILE] [--tag NAME=VALUE-KIND]... [--deploy DIR]
+
+mod compose;
+mod deploy;
+mod glue;
+
+use anyhow::{bail, Context, Result};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use std::time::Instant;
+use wasmtime::component::Linker;
+
+use iiot_flow_host::aot::CompileCache;
+use iiot_flow_host::dag::FlowDag;
+use iiot_flow_host::flow::FlowDef;
+use iiot_flow_host::limits;
+use iiot_flow_host::tags::TagTable;
+use iiot_flow_host::{parse_kind, HostState, TagMeta, TagRegistry};
+
+struct Args {
+    wasm_dir: PathBuf,
+    flow:     PathBuf,
+    out:      Option<PathBuf>,
+    name:     Option<String>,
+    work:     Option<PathBuf>,
+    tag_file: Option<PathBuf>,
+    tags:     Vec<(String, String)>,
+    deploy:   Option<PathBuf>,
+}
+
+fn parse_args() -> Result<Args> {
+    let mut positional = Vec::new();
+    let (mut out, mut name, mut work, mut tag_file, mut tags, mut deploy) = (None, None, None, None, Vec::new(), None);
+    let mut it = std::env::args().skip(1);
+    while let Some(a) = it.next() {
+        let mut value = || it.next().with_context(|| format!("{a} 缺少參數"));
+        match a.as_str() {
+            "-o" | "--out" => out  = Some(PathBuf::from(value()?)),
+            "--name"       => name = Some(value()?),
+            "--work"       => work = Some(PathBuf::from(value()?)),
+            "--deploy"     => deploy = Some(PathBuf::from(value()?)),
+            "--tags"       => tag_file = Some(PathBuf::from(value()?)),
+            "--tag"        => {
+                let v = value()?;
+                let (tag, kind) = v.split_once('=').with_context(|| format!("--tag 格式應為 NAME=VALUE-KIND：{v}"))?;
+                tags.push((tag.to_string(), kind.to_string()));
+            }
+            _ => positional.push(a),
+        }
+    }
+    let [wasm_dir, flow] = positional.as_slice() else {
+        bail!("用法：iiot-flow-fusion <wasm_dir> <flow.json> [-o out.wasm] [--name NAME] \
+               [--work DIR] [--tags FILE] [--tag NAME=VALUE-KIND]... [--deploy DIR]");
+    };
+    Ok(Args { wasm_dir: wasm_dir.into(), flow: flow.into(), out, name, work, tag_file, tags, deploy })
+}
+
+fn main() -> Result<()> {
+    let args = parse_args()?;
+    let name = args.name.clone().unwrap_or_else(|| {
+        args.flow.file_stem().map_or("flow".to_string(), |s| s.to_string_lossy().into_owned())
+    });
+    let out  = args.out.clone().unwrap_or_else(|| args.wasm_dir.join(format!("flow-{name}.wasm")));
+    let work = args.work.clone()
+        .unwrap_or_else(|| std::env::temp_dir().join("iiot-flow-fusion").join(&name));
+
+    // ── Step 1：載入 + Validate ──────────────────────────────────────────────
+    println!("▶ Step 1：載入 {} 並驗證...", args.flow.display());
+    let t0 = Instant::now();
+
+    // 與 iiot-flow-host 相同的 Engine 設定，flow.cwasm 才能直接載入
+    let engine = limits::new_engine()?;
+    let mut linker: Linker<HostState> = Linker::new(&engine);
+    wasmtime_wasi::p2::add_to_linker_sync(&mut linker)?;
+
+    // Source 的 any output 依 tag 型別推導，Fusion 時只需要型別（--tags 的設定檔，再加上各 --tag）
+    let registry = Arc::new(RwLock::new(TagRegistry::new()));
+    if let Some(path) = &args.tag_file {
+        TagTable::load(path)?.apply(&registry);
+    }
+    for (tag, kind) in &args.tags {
+        let value_kind = parse_kind(kind).with_context(|| format!("未知的 value-kind：{kind}"))?;
+        registry.write().unwrap().get_or_create(tag, TagMeta::new(tag, value_kind));
+    }
+
+    let flow = FlowDef::load(&args.flow)?;
+    let cache = CompileCache::new(&engine, work.join("cwasm"))?;
+    let dag   = FlowDag::load(&engine, &linker, &cache, registry, &flow, &args.wasm_dir)?;
+    for w in &dag.warnings {
+        println!("  ⚠️  [{}] {}", w.code, w.message);
+    }
+    println!("  ✅ {} nodes（含 Cast {}）/ {} edges ({:.1}ms)\n",
+        dag.nodes.len(), dag.nodes.len() - flow.nodes.len(), dag.ir.edges.len(),
+        t0.elapsed().as_secs_f64() * 1000.0);
+
+    // ── Step 2：產生並編譯 glue ─────────────────────────────────────────────
+    println!("▶ Step 2：產生 glue crate → {}", work.display());
+    let t1 = Instant::now();
+    let layout = glue::Layout::of(&dag);
+    let slots  = glue::generate(&work, &layout, &name, &flow.version)?;
+    let glue  = glue::build(&work)?;
+    println!("  ✅ {} ({:.1}s)\n", glue.display(), t1.elapsed().as_secs_f64());
+
+    // ── Step 3：WAC composition ─────────────────────────────────────────────
+    println!("▶ Step 3：WAC composition...");
+    let bytes = compose::compose(&layout, &slots, &glue, &args.wasm_dir)?;
+    write(&out, &bytes)?;
+    println!("  ✅ {}（{} KB）", out.display(), bytes.len() / 1024);
+
+    // ── Step 4：flow-deploy artifact ────────────────────────────────────────
+    if let Some(dir) = &args.deploy {
+        println!("\n▶ Step 4：產出 flow-deploy → {}", dir.display());
+        let t3 = Instant::now();
+        deploy::write(dir, &engine, &dag, &name, &flow.version, &bytes)?;
+        println!("  ✅ flow.wasm + flow.cwasm + manifest.json ({:.1}s)", t3.elapsed().as_secs_f64());
+    }
+    Ok(())
+}
+
+fn write(path: &Path, bytes: &[u8]) -> Result<()> {
+    if let Some(dir) = path.parent() { std::fs::create_dir_all(dir)?; }
+    std::fs::write(path, bytes).with_context(|| format!("寫入失敗：{}", path.display()))
+}
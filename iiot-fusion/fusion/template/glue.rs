@@ -0,0 +1,207 @@
+// glue/src/lib.rs（由 iiot-flow-fusion 產生）
+// Fused Pipeline 的 glue component：把 edge table 燒進程式，
+// 依 topo 順序直接呼叫各 Node 的 import（n0、n1…），對外只 export iiot:flow/pipeline
+//
+//   nodes.rs    ：此 flow 的 Node 表、Route 表與 import dispatch（每次 Fusion 重新產生）
+//   cast_value.rs：與 host 相同的數值族轉換
+
+wit_bindgen::generate!({
+    world: "glue",
+    path:  "wit",
+    generate_all, // iiot:flow@0.2.0 在 wit/deps 內
+});
+
+mod nodes;
+
+use exports::iiot::flow::pipeline::{Guest, RawUpdate};
+use iiot::flow::types::{FlowMsg, PortMsgs, TagValue, ValueKind};
+
+include!("cast_value.rs");
+
+pub enum Kind {
+    Source,
+    Node,
+    Sink,
+    Cast(ValueKind), // Deploy 插入的 Cast Node，直接 inline
+}
+
+pub struct Entry {
+    pub id:     &'static str,
+    pub kind:   Kind,
+    pub props:  &'static str, // 已驗證、補上預設值的 props（編譯期常數）
+    pub wiring: &'static str,
+}
+
+pub struct Route {
+    pub from:    usize,
+    pub port:    u32,
+    pub to:      usize,
+    pub to_port: u32,
+}
+
+mod proto {
+    #[derive(prost::Message)]
+    pub struct SunkMsg {
+        #[prost(string, tag = "1")]           pub sink:      String,
+        #[prost(uint32, tag = "2")]           pub tag_id:    u32,
+        #[prost(uint32, tag = "3")]           pub msg_id:    u32,
+        #[prost(double, optional, tag = "4")] pub value:     Option<f64>,
+        #[prost(uint64, tag = "5")]           pub timestamp: u64,
+        #[prost(uint32, tag = "6")]           pub quality:   u32,
+    }
+
+    #[derive(prost::Message)]
+    pub struct FusedOutput {
+        #[prost(message, repeated, tag = "1")] pub sunk: Vec<SunkMsg>,
+    }
+
+    #[derive(prost::Message)]
+    pub struct NodeState {
+        #[prost(string, tag = "1")] pub node:  String,
+        #[prost(bytes,  tag = "2")] pub state: Vec<u8>,
+    }
+
+    #[derive(prost::Message)]
+    pub struct NodeStates {
+        #[prost(message, repeated, tag = "1")] pub states: Vec<NodeState>,
+    }
+}
+
+// 第一次呼叫 pipeline 前對每個 Node 呼叫 init(props, wiring)
+static mut READY: bool = false;
+
+fn ensure_init() {
+    if unsafe { READY } { return; }
+    for (n, e) in nodes::NODES.iter().enumerate() {
+        if matches!(e.kind, Kind::Cast(_)) { continue; }
+        if let Err(err) = nodes::init(n, e.props, e.wiring) {
+            panic!("{} init 失敗：{err}", e.id);
+        }
+    }
+    unsafe { READY = true; }
+}
+
+struct Glue;
+
+impl Guest for Glue {
+    fn run(source: u32, tag_id: u32, msg_id: u32, raw_bytes: Vec<u8>) -> Vec<u8> {
+        ensure_init();
+        let mut inbox: Vec<Vec<(u32, FlowMsg)>> = nodes::NODES.iter().map(|_| Vec::new()).collect();
+        run_one(source, tag_id, msg_id, &raw_bytes, &mut inbox)
+    }
+
+    fn run_batch(updates: Vec<RawUpdate>) -> Vec<Vec<u8>> {
+        ensure_init();
+        // inbox 整批共用，每筆跑完後各 slot 都已清空
+        let mut inbox: Vec<Vec<(u32, FlowMsg)>> = nodes::NODES.iter().map(|_| Vec::new()).collect();
+        updates.iter()
+            .map(|u| run_one(u.source, u.tag_id, u.msg_id, &u.raw_bytes, &mut inbox))
+            .collect()
+    }
+
+    fn save_states() -> Vec<u8> {
+        use prost::Message;
+        ensure_init();
+        let states = nodes::NODES.iter().enumerate()
+            .filter(|(_, e)| !matches!(e.kind, Kind::Cast(_)))
+            .map(|(n, e)| proto::NodeState { node: e.id.to_string(), state: nodes::save_state(n) })
+            .collect();
+        proto::NodeStates { states }.encode_to_vec()
+    }
+
+    // 格式錯誤或含有此 flow 沒有的 node id 一律 trap，讓 host 的還原明確失敗，
+    // 而不是部分 Node 還原、部分從初始狀態開始
+    fn load_states(states: Vec<u8>) {
+        use prost::Message;
+        ensure_init();
+        let states = match proto::NodeStates::decode(states.as_slice()) {
+            Ok(states) => states,
+            Err(e)     => panic!("load-states：state 格式錯誤：{e}"),
+        };
+        let slots: Vec<usize> = states.states.iter()
+            .map(|s| match nodes::NODES.iter().position(|e| e.id == s.node && !matches!(e.kind, Kind::Cast(_))) {
+                Some(n) => n,
+                None    => panic!("load-states：此 flow 沒有 node {}", s.node),
+            })
+            .collect();
+        for (n, s) in slots.into_iter().zip(&states.states) {
+            nodes::load_state(n, &s.state);
+        }
+    }
+
+    fn nodes()   -> Vec<String> { nodes::NODES.iter().map(|e| e.id.to_string()).collect() }
+    fn name()    -> String { nodes::NAME.to_string() }
+    fn version() -> String { nodes::VERSION.to_string() }
+}
+
+// 跑一筆 raw update：只有 source 這個 Source Node 處理 raw bytes，再依 topo 順序走過其餘 Node，
+// 回傳 FusedOutput（沒有訊息抵達 sink 則為空）
+// inbox 由呼叫端提供，批次呼叫時重複使用；每個 Node 執行前會取走自己的 slot，結束時全部清空
+fn run_one(source: u32, tag_id: u32, msg_id: u32, raw_bytes: &[u8], inbox: &mut [Vec<(u32, FlowMsg)>]) -> Vec<u8> {
+    use prost::Message;
+    let source = source as usize;
+    if !matches!(nodes::NODES.get(source).map(|e| &e.kind), Some(Kind::Source)) {
+        panic!("run：nodes() 的第 {source} 個不是 Source Node");
+    }
+    let mut sunk = Vec::new();
+    for (n, e) in nodes::NODES.iter().enumerate() {
+        let msgs = core::mem::take(&mut inbox[n]);
+        let outputs: Vec<PortMsgs> = match e.kind {
+            // 其他 endpoint 的 Source 這一筆不執行
+            Kind::Source if n != source => continue,
+            Kind::Source => nodes::process_raw(n, tag_id, msg_id, raw_bytes).outputs,
+            Kind::Node   => by_port(msgs).into_iter()
+                .flat_map(|(port, batch)| nodes::process(n, port, &batch).outputs)
+                .collect(),
+            Kind::Sink   => {
+                for (port, batch) in by_port(msgs) {
+                    nodes::process(n, port, &batch);
+                    sunk.extend(batch.into_iter().map(|m| sunk_msg(e.id, m)));
+                }
+                continue;
+            }
+            Kind::Cast(to) => vec![PortMsgs {
+                port_id: 0,
+                msgs:    msgs.into_iter()
+                    .map(|(_, m)| FlowMsg { value: cast_value(m.value, to), ..m })
+                    .collect(),
+            }],
+        };
+        for out in outputs { deliver(n, out.port_id, out.msgs, inbox); }
+    }
+
+    if sunk.is_empty() { return Vec::new(); }
+    proto::FusedOutput { sunk }.encode_to_vec()
+}
+
+// 同一 output port 的多條 edge 依 flow.json 宣告順序投遞，與 host 的 router 相同
+fn deliver(from: usize, port: u32, msgs: Vec<FlowMsg>, inbox: &mut [Vec<(u32, FlowMsg)>]) {
+    for r in nodes::ROUTES.iter().filter(|r| r.from == from && r.port == port) {
+        inbox[r.to].extend(msgs.iter().cloned().map(|m| (r.to_port, m)));
+    }
+}
+
+fn by_port(msgs: Vec<(u32, FlowMsg)>) -> Vec<(u32, Vec<FlowMsg>)> {
+    let mut batches: Vec<(u32, Vec<FlowMsg>)> = Vec::new();
+    for (port, msg) in msgs {
+        match batches.last_mut() {
+            Some((p, batch)) if *p == port => batch.push(msg),
+            _ => batches.push((port, vec![msg])),
+        }
+    }
+    batches
+}
+
+fn sunk_msg(sink: &str, m: FlowMsg) -> proto::SunkMsg {
+    let value = match cast_value(m.value, ValueKind::F64Val) {
+        TagValue::F64Val(v)  => Some(v),
+        TagValue::BoolVal(v) => Some(if v { 1.0 } else { 0.0 }),
+        _ => None,
+    };
+    proto::SunkMsg {
+        sink: sink.to_string(), tag_id: m.tag_id, msg_id: m.msg_id,
+        value, timestamp: m.timestamp, quality: m.quality.into(),
+    }
+}
+
+export!(Glue);
@@ -0,0 +1,351 @@
+// host/src/aot.rs
+// AOT 編譯快取：每個載入的 component 序列化成 .cwasm，下次啟動直接 mmap，省掉 JIT
+//
+//   <cache_dir>/<engine 指紋>/<sha256(wasm)>.cwasm
+//   <cache_dir>/<engine 指紋>/.used              ← 每次 load 更新 mtime
+//
+//   - wasm 內容變了 → sha256 不同 → 重新編譯
+//   - wasmtime 版本 / Config 變了 → 指紋不同 → 換一個子目錄重新編譯；不同 Config 的 host / fusion
+//     共用快取目錄時各用各的子目錄，不會互刪
+//   - 啟動時只清掉超過 STALE_MAX_AGE 沒被使用的其他指紋子目錄（升級 wasmtime 後留下的舊快取）
+//   - 檔案損毀或 deserialize 不相容 → 丟棄並重新編譯，不讓 Deploy 失敗
+//   - 寫入走 tmp + rename，不會留下寫一半的 .cwasm；tmp 檔名含 pid + 序號，平行載入（同 process 的多條 flow
+//     或另一個 process）各寫各的，rename 只會發布自己寫完的檔案
+//   - 啟動時只清掉超過 TMP_MAX_AGE 的 tmp（其他 process 可能正在寫）
+
+use anyhow::{Context, Result};
+use sha2::{Digest, Sha256};
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::time::Duration;
+use wasmtime::component::Component;
+use wasmtime::{Engine, Precompiled};
+
+// 比這更舊的 tmp 視為中斷的寫入（process 當掉），啟動時清除
+const TMP_MAX_AGE: Duration = Duration::from_secs(10 * 60);
+
+// 其他指紋的子目錄超過這麼久沒被任何 process 使用才清除
+const STALE_MAX_AGE: Duration = Duration::from_secs(30 * 24 * 60 * 60);
+
+// 子目錄的使用記錄（mtime = 最後一次 load）
+const USED: &str = ".used";
+
+// tmp 檔名的序號（同 process 內的平行寫入）
+static TMP_SEQ: AtomicU32 = AtomicU32::new(0);
+
+pub struct CompileCache {
+    dir:         Option<PathBuf>, // <cache_dir>/<指紋>；None = 停用，每次 JIT
+    fingerprint: String,
+    hits:        AtomicU32,
+    misses:      AtomicU32,
+}
+
+impl CompileCache {
+    /// 啟用快取；目錄不存在就建立，並清掉長期沒被使用的其他指紋子目錄
+    pub fn new(engine: &Engine, dir: impl Into<PathBuf>) -> Result<Self> {
+        let root = dir.into();
+        let cache = Self::disabled(engine);
+        let dir = root.join(&cache.fingerprint);
+        std::fs::create_dir_all(&dir)
+            .with_context(|| format!("無法建立 AOT 快取目錄：{}", dir.display()))?;
+        touch(&dir);
+        prune(&root, &cache.fingerprint)?;
+        Ok(CompileCache { dir: Some(dir), ..cache })
+    }
+
+    /// 停用快取（行為等同 Component::from_file）
+    pub fn disabled(engine: &Engine) -> Self {
+        CompileCache {
+            dir:         None,
+            fingerprint: fingerprint(engine),
+            hits:        AtomicU32::new(0),
+            misses:      AtomicU32::new(0),
+        }
+    }
+
+    /// 同一個快取目錄的另一個 handle（背景載入用，命中 / 編譯次數各自計算）
+    pub fn fork(&self) -> Self {
+        CompileCache {
+            dir:         self.dir.clone(),
+            fingerprint: self.fingerprint.clone(),
+            hits:        AtomicU32::new(0),
+            misses:      AtomicU32::new(0),
+        }
+    }
+
+    /// 載入 component：.cwasm 直接 deserialize，.wasm 先查快取，沒有才編譯並寫回
+    pub fn load(&self, engine: &Engine, path: &Path) -> Result<Component> {
+        if path.extension().is_some_and(|e| e == "cwasm") {
+            return deserialize(engine, path)
+                .with_context(|| format!("載入 AOT 失敗：{}", path.display()));
+        }
+
+        let bytes = std::fs::read(path).with_context(|| format!("載入失敗：{}", path.display()))?;
+        let Some(dir) = &self.dir else {
+            return compile(engine, &bytes, path);
+        };
+
+        touch(dir);
+        let cwasm = dir.join(format!("{}.cwasm", sha256_hex(&bytes)));
+        if cwasm.exists() {
+            match deserialize(engine, &cwasm) {
+                Ok(component) => {
+                    self.hits.fetch_add(1, Ordering::Relaxed);
+                    return Ok(component);
+                }
+                Err(e) => {
+                    eprintln!("⚠️  AOT 快取不可用，重新編譯 {}：{e:#}", path.display());
+                    let _ = std::fs::remove_file(&cwasm);
+                }
+            }
+        }
+
+        self.misses.fetch_add(1, Ordering::Relaxed);
+        let component = compile(engine, &bytes, path)?;
+        // 寫快取失敗只影響下次啟動速度
+        if let Err(e) = write_atomic(&cwasm, &component.serialize()?) {
+            eprintln!("⚠️  AOT 快取寫入失敗 {}：{e:#}", cwasm.display());
+        }
+        Ok(component)
+    }
+
+    /// (命中, 編譯) 次數
+    pub fn stats(&self) -> (u32, u32) {
+        (self.hits.load(Ordering::Relaxed), self.misses.load(Ordering::Relaxed))
+    }
+}
+
+// 自己的子目錄：清掉中斷的 tmp；其他指紋的子目錄：整個超過 STALE_MAX_AGE 沒用才刪；
+// 舊版扁平格式（<cache_dir>/<sha256>-<指紋>.cwasm）同樣看 mtime
+fn prune(root: &Path, fingerprint: &str) -> Result<()> {
+    for entry in std::fs::read_dir(root)? {
+        let path = entry?.path();
+        let name = path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+        if name == fingerprint {
+            for entry in std::fs::read_dir(&path)? {
+                let tmp = entry?.path();
+                if tmp.extension().is_some_and(|e| e == "tmp") && older_than(&tmp, TMP_MAX_AGE) {
+                    let _ = std::fs::remove_file(&tmp);
+                }
+            }
+        } else if path.is_dir() {
+            if older_than(&path.join(USED), STALE_MAX_AGE) {
+                let _ = std::fs::remove_dir_all(&path);
+            }
+        } else if name.ends_with(".cwasm") && older_than(&path, STALE_MAX_AGE) {
+            let _ = std::fs::remove_file(&path);
+        }
+    }
+    Ok(())
+}
+
+// 更新子目錄的使用時間（失敗只影響清除判斷）
+fn touch(dir: &Path) {
+    let _ = std::fs::write(dir.join(USED), b"");
+}
+
+// wasmtime 版本 + 影響機器碼的 Config（target、tunables、wasm features…）
+fn fingerprint(engine: &Engine) -> String {
+    struct Sha(Sha256);
+    impl Hasher for Sha {
+        fn finish(&self) -> u64 { 0 }
+        fn write(&mut self, bytes: &[u8]) { self.0.update(bytes); }
+    }
+    let mut h = Sha(Sha256::new());
+    engine.precompile_compatibility_hash().hash(&mut h);
+    hex(&h.0.finalize()[..8])
+}
+
+fn compile(engine: &Engine, bytes: &[u8], path: &Path) -> Result<Component> {
+    Component::new(engine, bytes).map_err(anyhow::Error::from)
+        .with_context(|| format!("編譯失敗：{}", path.display()))
+}
+
+fn deserialize(engine: &Engine, path: &Path) -> Result<Component> {
+    match Engine::detect_precompiled_file(path)? {
+        Some(Precompiled::Component) => {}
+        _ => anyhow::bail!("不是 wasmtime 預編譯的 component"),
+    }
+    // 安全性：只讀取自己寫入的快取目錄，或使用者明確指定的 .cwasm；
+    // wasmtime 會再檢查版本與 Config 相容性
+    Ok(unsafe { Component::deserialize_file(engine, path) }?)
+}
+
+fn write_atomic(path: &Path, bytes: &[u8]) -> Result<()> {
+    let seq = TMP_SEQ.fetch_add(1, Ordering::Relaxed);
+    let tmp = path.with_extension(format!("cwasm.{}-{seq}.tmp", std::process::id()));
+    // 子目錄可能剛被另一個 process 當成過期清掉
+    if let Some(dir) = path.parent() { std::fs::create_dir_all(dir)?; }
+    if let Err(e) = std::fs::write(&tmp, bytes).and_then(|_| std::fs::rename(&tmp, path)) {
+        let _ = std::fs::remove_file(&tmp);
+        return Err(e.into());
+    }
+    Ok(())
+}
+
+// 最後修改超過 max（時間無法判斷時保留；沒有 .used 的子目錄不是本快取建立的，也保留）
+fn older_than(path: &Path, max: Duration) -> bool {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+        .and_then(|t| t.elapsed().ok())
+        .is_some_and(|age| age > max)
+}
+
+fn sha256_hex(bytes: &[u8]) -> String {
+    hex(&Sha256::digest(bytes))
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::TempDir;
+    use std::time::SystemTime;
+
+    const WAT: &str = "(component (core module (func (export \"f\"))))";
+
+    fn engine(fuel: bool) -> Engine {
+        let mut config = wasmtime::Config::new();
+        config.consume_fuel(fuel);
+        Engine::new(&config).unwrap()
+    }
+
+    fn wasm(tmp: &TempDir) -> PathBuf {
+        let path = tmp.path().join("node.wasm");
+        std::fs::write(&path, WAT).unwrap();
+        path
+    }
+
+    fn age(path: &Path, by: Duration) {
+        let file = std::fs::File::options().write(true).open(path).unwrap();
+        file.set_modified(SystemTime::now() - by).unwrap();
+    }
+
+    fn cwasm_files(dir: &Path) -> Vec<PathBuf> {
+        std::fs::read_dir(dir).unwrap().map(|e| e.unwrap().path())
+            .filter(|p| p.extension().is_some_and(|e| e == "cwasm")).collect()
+    }
+
+    #[test]
+    fn second_load_hits_the_cache() {
+        let tmp = TempDir::new("aot-hit");
+        let (engine, path) = (engine(false), wasm(&tmp));
+        let cache = CompileCache::new(&engine, tmp.path().join("cache")).unwrap();
+
+        cache.load(&engine, &path).unwrap();
+        assert_eq!(cache.stats(), (0, 1), "第一次應編譯");
+        cache.load(&engine, &path).unwrap();
+        assert_eq!(cache.stats(), (1, 1), "第二次應命中快取");
+
+        let reopened = CompileCache::new(&engine, tmp.path().join("cache")).unwrap();
+        reopened.load(&engine, &path).unwrap();
+        assert_eq!(reopened.stats(), (1, 0), "重開後應命中同一份 .cwasm");
+    }
+
+    #[test]
+    fn changed_wasm_misses() {
+        let tmp = TempDir::new("aot-miss");
+        let (engine, path) = (engine(false), wasm(&tmp));
+        let cache = CompileCache::new(&engine, tmp.path().join("cache")).unwrap();
+
+        cache.load(&engine, &path).unwrap();
+        std::fs::write(&path, "(component (core module (func (export \"g\"))))").unwrap();
+        cache.load(&engine, &path).unwrap();
+        assert_eq!(cache.stats(), (0, 2), "內容不同應重新編譯");
+        assert_eq!(cwasm_files(cache.dir.as_deref().unwrap()).len(), 2);
+    }
+
+    #[test]
+    fn fingerprints_use_separate_dirs() {
+        let tmp = TempDir::new("aot-fingerprint");
+        let path = wasm(&tmp);
+        let (a, b) = (engine(false), engine(true));
+        assert_ne!(fingerprint(&a), fingerprint(&b), "Config 不同指紋應不同");
+
+        let cache_a = CompileCache::new(&a, tmp.path().join("cache")).unwrap();
+        cache_a.load(&a, &path).unwrap();
+        let cache_b = CompileCache::new(&b, tmp.path().join("cache")).unwrap();
+        cache_b.load(&b, &path).unwrap();
+        assert_eq!(cache_b.stats(), (0, 1), "其他指紋的 .cwasm 不應被使用");
+
+        // 兩邊輪流啟動不互刪
+        let cache_a = CompileCache::new(&a, tmp.path().join("cache")).unwrap();
+        cache_a.load(&a, &path).unwrap();
+        assert_eq!(cache_a.stats(), (1, 0), "另一個指紋啟動後快取應仍在");
+    }
+
+    #[test]
+    fn incompatible_cwasm_is_recompiled() {
+        let tmp = TempDir::new("aot-stale");
+        let path = wasm(&tmp);
+        let (a, b) = (engine(false), engine(true));
+        let cache = CompileCache::new(&a, tmp.path().join("cache")).unwrap();
+        cache.load(&a, &path).unwrap();
+
+        // 同一個檔名放進另一個 engine 編出的內容（指紋碰撞 / 檔案被換掉）
+        let cwasm = cwasm_files(cache.dir.as_deref().unwrap()).remove(0);
+        std::fs::write(&cwasm, Component::new(&b, WAT).unwrap().serialize().unwrap()).unwrap();
+        let cache = cache.fork();
+        cache.load(&a, &path).unwrap();
+        assert_eq!(cache.stats(), (0, 1), "不相容的 .cwasm 應丟棄並重新編譯");
+
+        std::fs::write(&cwasm, b"garbage").unwrap();
+        let cache = cache.fork();
+        cache.load(&a, &path).unwrap();
+        assert_eq!(cache.stats(), (0, 1), "損毀的 .cwasm 應丟棄並重新編譯");
+
+        let cache = cache.fork();
+        cache.load(&a, &path).unwrap();
+        assert_eq!(cache.stats(), (1, 0), "重新編譯後應寫回可用的 .cwasm");
+    }
+
+    #[test]
+    fn prune_removes_only_stale_entries() {
+        let tmp = TempDir::new("aot-prune");
+        let engine = engine(false);
+        let root = tmp.path().join("cache");
+        let own = root.join(fingerprint(&engine));
+        std::fs::create_dir_all(&own).unwrap();
+
+        let fresh_tmp = own.join("a.cwasm.1-0.tmp");
+        let old_tmp   = own.join("b.cwasm.1-1.tmp");
+        std::fs::write(&fresh_tmp, b"").unwrap();
+        std::fs::write(&old_tmp, b"").unwrap();
+        age(&old_tmp, TMP_MAX_AGE * 2);
+
+        let (live, stale) = (root.join("live"), root.join("stale"));
+        for dir in [&live, &stale] {
+            std::fs::create_dir_all(dir).unwrap();
+            touch(dir);
+        }
+        age(&stale.join(USED), STALE_MAX_AGE * 2);
+        let foreign = root.join("foreign");
+        std::fs::create_dir_all(&foreign).unwrap();
+
+        let flat = root.join("0123-deadbeef.cwasm");
+        std::fs::write(&flat, b"").unwrap();
+        age(&flat, STALE_MAX_AGE * 2);
+
+        CompileCache::new(&engine, &root).unwrap();
+
+        assert!(fresh_tmp.exists(),  "可能還在寫的 tmp 不應刪除");
+        assert!(!old_tmp.exists(),   "中斷的 tmp 應清除");
+        assert!(live.exists(),       "最近使用的其他指紋不應刪除");
+        assert!(!stale.exists(),     "長期未使用的其他指紋應清除");
+        assert!(foreign.exists(),    "沒有 .used 的目錄不應刪除");
+        assert!(!flat.exists(),      "過期的舊格式 .cwasm 應清除");
+    }
+
+    #[test]
+    fn disabled_cache_always_compiles() {
+        let tmp = TempDir::new("aot-disabled");
+        let (engine, path) = (engine(false), wasm(&tmp));
+        let cache = CompileCache::disabled(&engine);
+        cache.load(&engine, &path).unwrap();
+        cache.load(&engine, &path).unwrap();
+        assert_eq!(cache.stats(), (0, 0), "停用時不計命中 / 編譯");
+    }
+}
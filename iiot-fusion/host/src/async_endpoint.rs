@@ -0,0 +1,372 @@
+// host/src/async_endpoint.rs
+// host-endpoints 與 sink-end handler 的 async 版（tokio Host，async_runtime.rs）
+//
+//   source：每個 endpoint 一個 bounded queue（endpoint 的 queue 設定），Runtime 的 tick 只取出已到達的 frame
+//     nng-pull    AsyncPullSocket（ingest.rs）
+//     tcp-client  讀取 task：斷線後每 RECONNECT 重連；queue 滿時暫停讀取，壓力回到對端
+//     file        讀取 task：依序讀完後關閉 queue，視為結束
+//   sink：每個 endpoint 一個寫入 task；sink-end 每個 handler 一個 task（webhook 以 timeout-ms 為上限）
+//     queue 滿時丟棄並計入 overflow，慢的對端不會卡住 pipeline
+//     tcp-client 尚未連線時丟棄並計入 I/O 錯誤，下一筆再重連（與 endpoint.rs 相同）
+//   bus 只用於 flow manager（同步 worker pool），async Host 不支援
+//
+// frame 格式與 endpoint.rs 相同
+
+use anyhow::{bail, Context, Result};
+use prost::Message as _;
+use std::fs::{File, OpenOptions};
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::net::TcpStream;
+use tokio::sync::mpsc::{self, error::TryRecvError, error::TrySendError};
+use tokio::task::JoinHandle;
+
+use crate::endpoint::{CONNECT_TIMEOUT, MAX_FRAME};
+use crate::ingest::{AsyncPullSocket, IngestStats};
+use crate::manifest::{EndpointDef, Manifest, SinkEndDef};
+use crate::proto;
+use crate::runtime::{http_post, http_status, http_target};
+
+const RECONNECT: Duration = Duration::from_secs(1);
+
+/// sink / sink-end task 的計數（task 與 Runtime 共用）
+#[derive(Debug, Default)]
+pub struct IoStats {
+    pub sent:      AtomicU64, // 寫出成功 / handler 完成
+    pub io_errors: AtomicU64,
+    pub overflow:  AtomicU64, // queue 滿而丟棄
+}
+
+// ════════════════════════════════════════════════════════════════════════════
+// Source
+// ════════════════════════════════════════════════════════════════════════════
+
+pub struct AsyncSource {
+    pub id: u32,
+    rx:     mpsc::Receiver<Vec<u8>>,
+    closed: bool,
+    ingest: Option<AsyncPullSocket>, // nng-pull：持有 listener（Drop 時關閉）
+    reader: Option<JoinHandle<()>>,  // tcp-client / file 的讀取 task（Drop 時 abort）
+}
+
+impl AsyncSource {
+    /// file 的相對路徑以 base（manifest 目錄）為準；必須在 tokio runtime 內呼叫
+    pub fn open(def: &EndpointDef, base: &Path) -> Result<Self> {
+        let (id, queue) = (def.id, def.queue.max(1));
+        let (ingest, rx, reader) = match def.kind.as_str() {
+            "nng-pull" => {
+                let (pull, rx) = AsyncPullSocket::listen(&def.address, queue)
+                    .with_context(|| format!("endpoint {id}"))?;
+                (Some(pull), rx, None)
+            }
+            "tcp-client" => {
+                let (tx, rx) = mpsc::channel(queue);
+                (None, rx, Some(tokio::spawn(read_tcp(id, def.address.clone(), tx))))
+            }
+            "file" => {
+                let path = base.join(&def.address);
+                let file = File::open(&path)
+                    .with_context(|| format!("endpoint {id}：無法開啟 {}", path.display()))?;
+                let (tx, rx) = mpsc::channel(queue);
+                (None, rx, Some(tokio::spawn(read_file(id, tokio::fs::File::from_std(file), tx))))
+            }
+            "bus" => bail!("endpoint {id}：bus 只能由 flow manager 轉送，async Host 不支援"),
+            other => bail!("endpoint {id} 的 type 不能作為 source：{other}"),
+        };
+        Ok(AsyncSource { id, rx, closed: false, ingest, reader })
+    }
+
+    /// 讀取 task 已結束且 queue 已取完（file 讀完）；tcp 斷線會重連，永遠不算結束
+    pub fn is_closed(&self) -> bool {
+        self.closed
+    }
+
+    pub fn ingest_stats(&self) -> Option<&IngestStats> {
+        self.ingest.as_ref().map(|p| p.stats.as_ref())
+    }
+
+    /// 非阻塞：取出目前已到達的 frame，最多 max 筆
+    pub fn recv(&mut self, max: usize) -> Vec<Vec<u8>> {
+        let mut frames = Vec::new();
+        while frames.len() < max {
+            match self.rx.try_recv() {
+                Ok(frame) => frames.push(frame),
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => { self.closed = true; break; }
+            }
+        }
+        frames
+    }
+}
+
+impl Drop for AsyncSource {
+    fn drop(&mut self) {
+        if let Some(task) = &self.reader { task.abort(); }
+    }
+}
+
+// 連線 → 持續讀 frame；斷線或讀取失敗後等 RECONNECT 重連，Runtime 結束（queue 關閉）時結束
+async fn read_tcp(id: u32, addr: String, tx: mpsc::Sender<Vec<u8>>) {
+    let mut warned = false;
+    loop {
+        match connect(&addr).await {
+            Ok(mut s) => {
+                warned = false;
+                loop {
+                    match read_frame(&mut s).await {
+                        Ok(Some(frame)) => if tx.send(frame).await.is_err() { return; },
+                        Ok(None) => {
+                            eprintln!("⚠️  endpoint {id} 連線中斷（{addr}），{}s 後重連", RECONNECT.as_secs());
+                            break;
+                        }
+                        Err(e) => {
+                            eprintln!("⚠️  endpoint {id} 讀取失敗（{addr}）：{e:#}");
+                            break;
+                        }
+                    }
+                }
+            }
+            Err(e) => {
+                if !warned { eprintln!("⚠️  endpoint {id} 無法連線 {addr}：{e:#}"); }
+                warned = true;
+            }
+        }
+        if tx.is_closed() { return; }
+        tokio::time::sleep(RECONNECT).await;
+    }
+}
+
+async fn read_file(id: u32, file: tokio::fs::File, tx: mpsc::Sender<Vec<u8>>) {
+    let mut r = BufReader::new(file);
+    loop {
+        match read_frame(&mut r).await {
+            Ok(Some(frame)) => if tx.send(frame).await.is_err() { return; },
+            Ok(None) => return,
+            Err(e) => {
+                eprintln!("⚠️  endpoint {id} 讀取失敗，視為結束：{e:#}");
+                return;
+            }
+        }
+    }
+}
+
+// ════════════════════════════════════════════════════════════════════════════
+// Sink
+// ════════════════════════════════════════════════════════════════════════════
+
+pub struct AsyncSink {
+    pub id:   u32,
+    pub node: Option<String>,
+    tx:       mpsc::Sender<proto::FlowResult>,
+    task:     JoinHandle<()>,
+    stats:    Arc<IoStats>,
+}
+
+impl AsyncSink {
+    /// 必須在 tokio runtime 內呼叫；file 在這裡開啟，路徑錯誤在載入時就回報
+    pub fn open(def: &EndpointDef, base: &Path, stats: Arc<IoStats>) -> Result<Self> {
+        let id = def.id;
+        let (tx, rx) = mpsc::channel(def.queue.max(1));
+        let task = match def.kind.as_str() {
+            "tcp-client" => tokio::spawn(write_tcp(id, def.address.clone(), rx, Arc::clone(&stats))),
+            "file" => {
+                let path = base.join(&def.address);
+                let file = OpenOptions::new().create(true).append(true).open(&path)
+                    .with_context(|| format!("endpoint {id}：無法寫入 {}", path.display()))?;
+                tokio::spawn(write_file(id, tokio::fs::File::from_std(file), rx, Arc::clone(&stats)))
+            }
+            "stdout" => tokio::spawn(write_stdout(id, rx, Arc::clone(&stats))),
+            other => bail!("endpoint {id} 的 type 不能作為 sink：{other}"),
+        };
+        Ok(AsyncSink { id, node: def.node.clone(), tx, task, stats })
+    }
+
+    /// 放進寫入 queue；queue 滿時丟棄並計入 overflow
+    pub fn send(&self, result: proto::FlowResult) {
+        offer(&self.tx, result, &self.stats);
+    }
+
+    /// 關閉 queue，等寫入 task 送完剩下的結果
+    pub async fn close(self) {
+        drop(self.tx);
+        let _ = self.task.await;
+    }
+}
+
+async fn write_tcp(id: u32, addr: String, mut rx: mpsc::Receiver<proto::FlowResult>, stats: Arc<IoStats>) {
+    let mut stream: Option<TcpStream> = None;
+    let mut warned = false;
+    while let Some(result) = rx.recv().await {
+        if stream.is_none() {
+            match connect(&addr).await {
+                Ok(s) => { stream = Some(s); warned = false; }
+                Err(e) => {
+                    if !warned { eprintln!("⚠️  endpoint {id} 無法連線 {addr}：{e:#}"); }
+                    warned = true;
+                    stats.io_errors.fetch_add(1, Ordering::Relaxed);
+                    continue;
+                }
+            }
+        }
+        let Some(s) = &mut stream else { continue; };
+        match write_frame(s, &result).await {
+            Ok(()) => { stats.sent.fetch_add(1, Ordering::Relaxed); }
+            Err(e) => {
+                eprintln!("⚠️  endpoint {id} 寫入失敗（{addr}）：{e:#}");
+                stats.io_errors.fetch_add(1, Ordering::Relaxed);
+                stream = None;
+            }
+        }
+    }
+}
+
+async fn write_file(id: u32, mut file: tokio::fs::File, mut rx: mpsc::Receiver<proto::FlowResult>,
+                    stats: Arc<IoStats>) {
+    while let Some(result) = rx.recv().await {
+        match write_frame(&mut file, &result).await {
+            Ok(()) => { stats.sent.fetch_add(1, Ordering::Relaxed); }
+            Err(e) => {
+                eprintln!("⚠️  endpoint {id} 寫入失敗：{e:#}");
+                stats.io_errors.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+    }
+}
+
+async fn write_stdout(id: u32, mut rx: mpsc::Receiver<proto::FlowResult>, stats: Arc<IoStats>) {
+    let mut out = tokio::io::stdout();
+    while let Some(r) = rx.recv().await {
+        let line = format!("  [endpoint {id}] {} = {:.4}  msg={} q={} ts={}\n",
+            r.tag_name, r.value, r.msg_id, r.quality, r.timestamp);
+        match out.write_all(line.as_bytes()).await {
+            Ok(()) => { stats.sent.fetch_add(1, Ordering::Relaxed); }
+            Err(_) => { stats.io_errors.fetch_add(1, Ordering::Relaxed); }
+        }
+    }
+}
+
+// ════════════════════════════════════════════════════════════════════════════
+// sink-end handler
+// ════════════════════════════════════════════════════════════════════════════
+
+pub struct AsyncHandler {
+    pub id: String,
+    tx:     mpsc::Sender<serde_json::Value>,
+    task:   JoinHandle<()>,
+    stats:  Arc<IoStats>,
+}
+
+impl AsyncHandler {
+    /// 每個 sink-end 一個 task，事件依序交給 handler；必須在 tokio runtime 內呼叫
+    pub fn open(def: &SinkEndDef, manifest: &Manifest, queue: usize, stats: Arc<IoStats>) -> Result<Self> {
+        let (tx, rx) = mpsc::channel(queue.max(1));
+        let handler = match def.handler.as_str() {
+            "log"     => Handler::Log,
+            "file"    => Handler::File(manifest.path(&def.target)),
+            "webhook" => {
+                http_target(&def.target)?;
+                Handler::Webhook { url: def.target.clone(), timeout: Duration::from_millis(def.timeout_ms) }
+            }
+            other => bail!("sink-end {} 的 handler 不支援：{other}", def.id),
+        };
+        let task = tokio::spawn(handle(def.id.clone(), handler, rx, Arc::clone(&stats)));
+        Ok(AsyncHandler { id: def.id.clone(), tx, task, stats })
+    }
+
+    /// 放進 handler 的 queue；queue 滿時丟棄並計入 overflow
+    pub fn send(&self, event: serde_json::Value) {
+        offer(&self.tx, event, &self.stats);
+    }
+
+    /// 關閉 queue，等 handler 處理完剩下的事件
+    pub async fn close(self) {
+        drop(self.tx);
+        let _ = self.task.await;
+    }
+}
+
+enum Handler {
+    Log,
+    File(std::path::PathBuf),
+    Webhook { url: String, timeout: Duration },
+}
+
+async fn handle(id: String, handler: Handler, mut rx: mpsc::Receiver<serde_json::Value>, stats: Arc<IoStats>) {
+    let mut out = tokio::io::stdout();
+    while let Some(event) = rx.recv().await {
+        let r = match &handler {
+            Handler::Log => out.write_all(format!("  🔔 [sink-end {id}] {event}\n").as_bytes()).await
+                .map_err(anyhow::Error::from),
+            Handler::File(path) => append_line(path, &event.to_string()).await,
+            Handler::Webhook { url, timeout } => match tokio::time::timeout(*timeout,
+                post_json(url, &event.to_string())).await {
+                Ok(r)  => r,
+                Err(_) => Err(anyhow::anyhow!("webhook 超過 {}ms 沒有完成：{url}", timeout.as_millis())),
+            },
+        };
+        match r {
+            Ok(()) => { stats.sent.fetch_add(1, Ordering::Relaxed); }
+            Err(e) => {
+                stats.io_errors.fetch_add(1, Ordering::Relaxed);
+                eprintln!("⚠️  sink-end {id} handler 失敗：{e:#}");
+            }
+        }
+    }
+}
+
+async fn append_line(path: &Path, line: &str) -> Result<()> {
+    let mut f = tokio::fs::OpenOptions::new().create(true).append(true).open(path).await
+        .with_context(|| format!("無法寫入 {}", path.display()))?;
+    f.write_all(format!("{line}\n").as_bytes()).await?;
+    Ok(())
+}
+
+// 同 runtime.rs 的 post_json；逾時由呼叫端的 tokio::time::timeout 處理
+async fn post_json(url: &str, body: &str) -> Result<()> {
+    let (authority, addr, path) = http_target(url)?;
+    let mut s = TcpStream::connect(&addr).await.with_context(|| format!("無法連線 {addr}"))?;
+    s.write_all(http_post(authority, &path, body).as_bytes()).await?;
+    let mut head = [0u8; 12];
+    s.read_exact(&mut head).await.context("webhook 沒有回應")?;
+    http_status(url, &head)
+}
+
+// ── 共用 ─────────────────────────────────────────────────────────────────────
+
+fn offer<T>(tx: &mpsc::Sender<T>, item: T, stats: &IoStats) {
+    match tx.try_send(item) {
+        Ok(()) => {}
+        Err(TrySendError::Full(_)) => { stats.overflow.fetch_add(1, Ordering::Relaxed); }
+        Err(TrySendError::Closed(_)) => { stats.io_errors.fetch_add(1, Ordering::Relaxed); }
+    }
+}
+
+async fn connect(addr: &str) -> Result<TcpStream> {
+    let s = tokio::time::timeout(CONNECT_TIMEOUT, TcpStream::connect(addr)).await
+        .map_err(|_| anyhow::anyhow!("連線逾時"))??;
+    s.set_nodelay(true)?;
+    Ok(s)
+}
+
+async fn read_frame(r: &mut (impl AsyncRead + Unpin)) -> Result<Option<Vec<u8>>> {
+    let len = match r.read_u32().await {
+        Ok(len) => len as usize,
+        Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+    if len > MAX_FRAME { bail!("frame 過大：{len} bytes"); }
+    let mut frame = vec![0u8; len];
+    r.read_exact(&mut frame).await.context("frame 不完整")?;
+    Ok(Some(frame))
+}
+
+async fn write_frame(w: &mut (impl AsyncWrite + Unpin), result: &proto::FlowResult) -> Result<()> {
+    let bytes = result.encode_to_vec();
+    w.write_all(&(bytes.len() as u32).to_be_bytes()).await?;
+    w.write_all(&bytes).await?;
+    w.flush().await?;
+    Ok(())
+}
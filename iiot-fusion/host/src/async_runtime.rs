@@ -0,0 +1,396 @@
+// host/src/async_runtime.rs
+// manifest.json 驅動的 Runtime，tokio 版（IIOT_FLOW_ASYNC=1）
+//
+//   manifest / artifact / 排程 / 微批次 / Supervisor / snapshot 與 runtime.rs 相同，差別在執行方式
+//   （結果分派、redeploy 的交換與換回共用 runtime.rs / redeploy.rs，這裡只有需要 await 的部分）：
+//   ① pipeline 是 AsyncPipeline：call_*_async、async WASI 與 host-api，wasm 每個 epoch tick 讓出 worker
+//   ② source endpoint 由各自的 task 讀進 queue（async_endpoint.rs），tick 只取出已到達的 frame
+//   ③ sink endpoint / sink-end handler 在各自的 task 寫出，tick 只把結果放進 queue：
+//      慢的 TCP 對端或 webhook 不會卡住 pipeline（queue 滿時丟棄並計入 overflow）
+//   ④ snapshot 與 crash dump 的檔案寫入以 block_in_place 執行，必須在 multi-thread runtime 內
+//   ⑤ redeploy（SIGHUP）：新版本在 spawn_blocking 內載入，tick 之間以 async 呼叫切換，grace 期間 crash 即換回
+//
+// bus endpoint 只有同步版支援
+
+use anyhow::{anyhow, bail, Context, Result};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+use tokio::task::JoinHandle;
+use wasmtime::Engine;
+
+use crate::aot::CompileCache;
+use crate::async_endpoint::{AsyncHandler, AsyncSink, AsyncSource, IoStats};
+use crate::manifest::{Manifest, SinkEndDef, SourceDef};
+use crate::redeploy::{self, Candidate, Deployment, Standby};
+use crate::runtime::{self, Route, RuntimeStats};
+use crate::scheduler::Scheduler;
+use crate::snapshot::{Snapshot, SnapshotStore};
+use crate::supervisor::{Gate, Health};
+use crate::{next_msg_id, AsyncPipeline, TagRegistry};
+
+// 與 runtime.rs 相同
+const MAX_FRAMES_PER_TICK: usize = 8192;
+// 每個 sink-end handler 的事件 queue
+const HANDLER_QUEUE: usize = 1024;
+// 等待下一次 tick 時每隔多久檢查一次 stop 旗標
+const STOP_POLL: Duration = Duration::from_millis(50);
+
+// supervisor.call 的 async 版：quarantine 中或 trap → None；超過 max-crashes → Err
+macro_rules! supervised {
+    ($self:ident, |$p:ident| $call:expr) => {{
+        if $self.ready().await? {
+            let $p = &mut $self.live.pipeline;
+            let r = $call.await;
+            $self.supervise(r).await
+        } else {
+            Ok(None)
+        }
+    }};
+}
+
+struct Source {
+    def:   SourceDef,
+    input: usize, // inputs 的索引
+}
+
+pub struct AsyncRuntime {
+    pub live:     Deployment<AsyncPipeline, AsyncHandler>, // 目前執行中的版本
+    pub stats:    RuntimeStats,
+    pub schedule: Scheduler,
+    pub restored: Option<Snapshot>,
+    pub io:       Arc<IoStats>,    // sink / sink-end task 的計數
+    pub reload:   Arc<AtomicBool>, // 設定後於下一次 tick 前開始 redeploy（SIGHUP）
+    registry: Arc<RwLock<TagRegistry>>,
+    sources:  Vec<Source>,
+    inputs:   Vec<AsyncSource>,
+    sinks:    Vec<AsyncSink>,
+    engine:   Engine,
+    cache:    CompileCache,
+    incoming: Option<JoinHandle<Result<Candidate<AsyncPipeline>>>>,    // 背景載入中的新版本
+    standby:  Option<Standby<Deployment<AsyncPipeline, AsyncHandler>>>, // grace 期間保留的舊版本
+}
+
+impl AsyncRuntime {
+    /// 必須在 tokio multi-thread runtime 內呼叫（endpoint 的 task 在這裡啟動）
+    pub async fn load(engine: &Engine, cache: &CompileCache,
+                      registry: Arc<RwLock<TagRegistry>>, dir: impl Into<PathBuf>) -> Result<Self> {
+        let manifest = Manifest::load(dir.into())?;
+        let (pipeline, artifact) = load_pipeline(engine, cache, Arc::clone(&registry), &manifest).await?;
+
+        let io = Arc::new(IoStats::default());
+        let (mut inputs, mut sinks) = (Vec::new(), Vec::new());
+        let mut ids = Vec::new(); // inputs 對應的 endpoint id
+        for def in &manifest.host_endpoints {
+            if def.role == "source" {
+                inputs.push(AsyncSource::open(def, &manifest.dir)?);
+                ids.push(def.id);
+            } else {
+                sinks.push(AsyncSink::open(def, &manifest.dir, Arc::clone(&io))?);
+            }
+        }
+        let sources = manifest.sources.iter().zip(manifest.source_endpoints()?)
+            .map(|(def, id)| Source { def: def.clone(), input: ids.iter().position(|&e| e == id).unwrap() })
+            .collect();
+        let schedule = Scheduler::new(manifest.sources.iter()
+            .map(|s| (Duration::from_millis(s.interval_ms), s.overrun)));
+        let handlers = handlers(&manifest, &io)?;
+
+        let mut live = Deployment::new(manifest, artifact, pipeline, handlers)?;
+        let mut restored = None;
+        if let Some(snap) = live.snapshots.as_ref().map(|s| s.load(&live.manifest.flow_id)).transpose()?.flatten() {
+            match live.pipeline.load_states(snap.payload.clone()).await {
+                Ok(()) => restored = Some(snap),
+                Err(e) => {
+                    eprintln!("⚠️  snapshot {} 無法還原，從初始狀態開始：{e:#}", snap.path.display());
+                    live.pipeline = live.pipeline.respawn().await?;
+                }
+            }
+        }
+
+        Ok(AsyncRuntime {
+            live, stats: RuntimeStats::default(), schedule, restored, io, reload: Arc::new(AtomicBool::new(false)),
+            registry, sources, inputs, sinks, engine: engine.clone(), cache: cache.fork(), incoming: None, standby: None,
+        })
+    }
+
+    /// 同 Runtime::run
+    pub async fn run(&mut self, stop: &AtomicBool) -> Result<()> {
+        while let Some(next) = self.step().await? {
+            if !sleep_until(next, stop).await { break; }
+        }
+        self.finish().await
+    }
+
+    /// 同 Runtime::step
+    pub async fn step(&mut self) -> Result<Option<Instant>> {
+        if self.reload.swap(false, Ordering::Relaxed) {
+            if let Err(e) = self.redeploy() { eprintln!("⚠️  {e:#}"); }
+        }
+        self.poll_redeploy().await;
+        if self.live.snapshots.as_ref().is_some_and(SnapshotStore::due) { self.snapshot().await?; }
+        let due = self.schedule.next_due(|i| self.active(i));
+        if let Some((i, _)) = due.filter(|&(_, due)| due <= Instant::now()) {
+            let started = Instant::now();
+            let r = self.tick(i).await.map(drop);
+            self.settle(r)?;
+            self.schedule.complete(i, started);
+        }
+
+        let next = self.schedule.next_due(|i| self.active(i)).map(|(_, due)| due);
+        if self.live.pipeline.linger_deadline().is_some_and(|d| next.is_none_or(|n| d <= n)) {
+            let r = self.flush().await;
+            self.settle(r)?;
+        }
+        Ok(next)
+    }
+
+    /// 送出剩下的微批次、寫入最後一份 snapshot，等 sink / sink-end task 送完 queue 內的結果
+    pub async fn finish(&mut self) -> Result<()> {
+        let r = self.flush().await;
+        self.settle(r)?;
+        // 停止時新版本仍正常執行：視為通過觀察期
+        self.commit();
+        self.snapshot().await?;
+        for sink in self.sinks.drain(..) { sink.close().await; }
+        for (_, (_, handler)) in self.live.sink_ends.drain() { handler.close().await; }
+        Ok(())
+    }
+
+    // ── redeploy ─────────────────────────────────────────────────────────────
+
+    /// 同 Runtime::redeploy
+    pub fn redeploy(&mut self) -> Result<()> {
+        if self.redeploying() { bail!("上一次 redeploy 尚未結束，略過"); }
+        eprintln!("🚚 redeploy：背景載入 {}", self.live.manifest.dir.display());
+        self.incoming = Some(redeploy::prepare_async(&self.engine, self.cache.fork(), Arc::clone(&self.registry),
+                                                     &self.live.manifest));
+        Ok(())
+    }
+
+    /// 同 Runtime::redeploying
+    pub fn redeploying(&self) -> bool {
+        self.incoming.is_some() || self.standby.is_some()
+    }
+
+    // 背景載入完成就切換；載入或切換失敗時繼續使用目前的版本
+    async fn poll_redeploy(&mut self) {
+        if !self.incoming.as_ref().is_some_and(JoinHandle::is_finished) { return; }
+        let candidate = match self.incoming.take().unwrap().await {
+            Ok(r)  => r,
+            Err(e) => Err(anyhow!("背景載入的 task 異常結束：{e}")),
+        };
+        let r = match candidate {
+            Ok(c)  => self.switch(c).await,
+            Err(e) => Err(e),
+        };
+        if let Err(e) = r {
+            redeploy::cancel(&self.live, &mut self.stats, &e);
+        }
+    }
+
+    async fn switch(&mut self, c: Candidate<AsyncPipeline>) -> Result<()> {
+        // 舊版本執行到目前為止的狀態；隔離中無法取得時新版本從初始狀態開始
+        let state = match self.flush().await {
+            Ok(()) => self.save_states().await,
+            Err(e) => Err(e),
+        };
+        let state = state.unwrap_or_else(|e| { eprintln!("⚠️  {e:#}"); None });
+        let mut pipeline = c.pipeline;
+        match state {
+            Some(state) => pipeline.carry_states(state).await.context("新版本無法載入目前的狀態")?,
+            None => eprintln!("⚠️  {} 目前無法取得狀態，新版本從初始狀態開始", self.live.supervisor.name),
+        }
+
+        let handlers = handlers(&c.manifest, &self.io)?;
+        let next = Deployment::new(c.manifest, c.artifact, pipeline, handlers)?;
+        self.standby = Some(redeploy::promote(&mut self.live, next, c.elapsed, &mut self.stats));
+        Ok(())
+    }
+
+    // 同 Runtime::settle；釋放的版本只關閉 sink-end queue，task 送完剩下的事件後自行結束，不等待
+    fn settle(&mut self, r: Result<()>) -> Result<()> {
+        redeploy::settle(&mut self.live, &mut self.standby, &mut self.stats, r)
+    }
+
+    fn commit(&mut self) {
+        redeploy::commit(&self.live, &mut self.standby);
+    }
+
+    fn active(&self, i: usize) -> bool {
+        !self.inputs[self.sources[i].input].is_closed()
+    }
+
+    pub fn inputs(&self) -> &[AsyncSource] {
+        &self.inputs
+    }
+
+    pub fn source_names(&self) -> impl Iterator<Item = &str> {
+        self.sources.iter().map(|s| s.def.tick_export.as_str())
+    }
+
+    /// 同 Runtime::tick
+    pub async fn tick(&mut self, i: usize) -> Result<usize> {
+        self.stats.ticks += 1;
+        if !self.ready().await? { return Ok(0); }
+        let source = self.live.pipeline.source(&self.sources[i].def.tick_export)?;
+        let frames = self.inputs[self.sources[i].input].recv(MAX_FRAMES_PER_TICK);
+        let n = frames.len();
+        let mut frames = frames.into_iter();
+        while let Some(frame) = frames.next() {
+            self.stats.frames += 1;
+            let Some(tag_id) = runtime::assign(&self.registry, &frame) else {
+                self.stats.dropped += 1;
+                continue;
+            };
+            let batch = self.live.pipeline.pending() as u64 + 1;
+            match supervised!(self, |p| p.push(source, tag_id, next_msg_id(), frame))? {
+                Some(outputs) => self.deliver(outputs)?,
+                None => {
+                    self.stats.count_lost(batch, frames.len());
+                    break;
+                }
+            }
+        }
+        Ok(n)
+    }
+
+    /// 同 Runtime::flush
+    pub async fn flush(&mut self) -> Result<()> {
+        let batch = self.live.pipeline.pending() as u64;
+        if batch == 0 { return Ok(()); }
+        match supervised!(self, |p| p.flush())? {
+            Some(outputs) => self.deliver(outputs),
+            None => { self.stats.lost += batch; Ok(()) }
+        }
+    }
+
+    /// 同 Runtime::snapshot
+    pub async fn snapshot(&mut self) -> Result<()> {
+        if self.standby.is_some() { return Ok(()); }
+        let Some(store) = &mut self.live.snapshots else { return Ok(()); };
+        store.mark();
+        self.flush().await?;
+        let Some(state) = self.save_states().await? else { return Ok(()); };
+        if let Some(store) = &self.live.snapshots {
+            let flow_id = &self.live.manifest.flow_id;
+            if let Err(e) = tokio::task::block_in_place(|| store.save(flow_id, &state)) { eprintln!("⚠️  {e:#}"); }
+        }
+        Ok(())
+    }
+
+    async fn save_states(&mut self) -> Result<Option<Vec<u8>>> {
+        supervised!(self, |p| p.save_states())
+    }
+
+    // ── Supervisor ───────────────────────────────────────────────────────────
+
+    // Supervisor::ready 的 async 版
+    async fn ready(&mut self) -> Result<bool> {
+        loop {
+            match self.live.supervisor.gate()? {
+                Gate::Ready => return Ok(true),
+                Gate::Wait  => return Ok(false),
+                Gate::Restart => match self.respawn().await {
+                    Ok(fresh) => {
+                        self.live.pipeline = fresh;
+                        self.live.supervisor.restarted();
+                    }
+                    Err(e) => self.live.supervisor.crashed(&e),
+                },
+            }
+        }
+    }
+
+    async fn respawn(&self) -> Result<AsyncPipeline> {
+        let mut fresh = self.live.pipeline.respawn().await?;
+        if let Some(state) = self.live.supervisor.restore_point() { fresh.load_states(state).await?; }
+        Ok(fresh)
+    }
+
+    // Supervisor::call 呼叫之後的部分
+    async fn supervise<R>(&mut self, r: Result<R>) -> Result<Option<R>> {
+        let live = &mut self.live;
+        let e = match r {
+            Ok(r) => {
+                if live.supervisor.snapshot_due() {
+                    if let Ok(state) = live.pipeline.save_states().await { live.supervisor.keep(state); }
+                }
+                return Ok(Some(r));
+            }
+            Err(e) => e,
+        };
+        live.supervisor.crashed(&e);
+        if let Some(memory) = live.supervisor.dump_memory() {
+            let pm = live.pipeline.post_mortem(memory).await;
+            tokio::task::block_in_place(|| live.supervisor.write_dump(&e, pm));
+        }
+        if live.supervisor.health() == Health::Failed {
+            return Err(e.context(format!("{} 已停止", live.supervisor.name)));
+        }
+        Ok(None)
+    }
+
+    // ── 結果分派 ─────────────────────────────────────────────────────────────
+
+    // 同 Runtime::deliver；只放進 queue，實際寫出由各自的 task 執行
+    fn deliver(&mut self, outputs: Vec<Vec<u8>>) -> Result<()> {
+        for m in runtime::sunk(&outputs)? {
+            match runtime::route(&self.registry, &self.live, m) {
+                Route::SinkEnd((_, handler), event) => {
+                    self.stats.events += 1;
+                    handler.send(event);
+                }
+                Route::Sink(node, result) => {
+                    for sink in &self.sinks {
+                        if sink.node.as_ref().is_some_and(|n| *n != node) { continue; }
+                        self.stats.sunk += 1;
+                        sink.send(result.clone());
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+// 每個 sink-end 一個 handler task
+fn handlers(manifest: &Manifest, io: &Arc<IoStats>) -> Result<HashMap<String, (SinkEndDef, AsyncHandler)>> {
+    manifest.sink_ends.iter()
+        .map(|def| Ok((def.id.clone(), (def.clone(), AsyncHandler::open(def, manifest, HANDLER_QUEUE, Arc::clone(io))?))))
+        .collect()
+}
+
+/// 同 scheduler::sleep_until，以 tokio timer 等待
+async fn sleep_until(due: Instant, stop: &AtomicBool) -> bool {
+    loop {
+        if stop.load(Ordering::Relaxed) { return false; }
+        let now = Instant::now();
+        if now >= due { return true; }
+        tokio::time::sleep((due - now).min(STOP_POLL)).await;
+    }
+}
+
+// 同 runtime::load_pipeline
+pub(crate) async fn load_pipeline(engine: &Engine, cache: &CompileCache, registry: Arc<RwLock<TagRegistry>>,
+                       manifest: &Manifest) -> Result<(AsyncPipeline, PathBuf)> {
+    let mut loaded = None;
+    for path in manifest.artifact_paths() {
+        if !path.exists() {
+            eprintln!("⚠️  artifact 不存在，略過：{}", path.display());
+            continue;
+        }
+        match AsyncPipeline::load(engine, cache, Arc::clone(&registry), &path.to_string_lossy(),
+                                  manifest.limits).await {
+            Ok(p)  => { loaded = Some((p, path)); break; }
+            Err(e) => eprintln!("⚠️  artifact 載入失敗，改用下一個：{}：{e:#}", path.display()),
+        }
+    }
+    let Some((mut pipeline, artifact)) = loaded else {
+        bail!("manifest {} 沒有可載入的 artifact", manifest.flow_id);
+    };
+    runtime::configure(&mut pipeline, manifest)?;
+    Ok((pipeline, artifact))
+}
@@ -0,0 +1,249 @@
+// host/src/cast.rs
+// 數值族自動轉型（IIoTFlowNodeDevelopmentGuidelines §2）
+//
+//   Validate Pass ⑤ 發現 edge 兩端型別不同但都在數值族 → 插入隱形 Cast Node
+//     src(i16) ──▶ math-op(f32)
+//     src(i16) ──▶ [cast: i16→f32] ──▶ math-op(f32)
+//
+//   值域被目標型別完整涵蓋 → lossless（靜默）
+//   其他數值族組合         → lossy（Deploy 輸出 warning，轉換仍執行）
+//   bool / short-str / blob → 不支援，Validate 報錯
+
+use serde_json::Value;
+
+use crate::flow::{EdgeDef, FlowDef, NodeDef};
+use crate::iiot::flow::node_descriptor::{
+    InputPortDef, JoinStrategy, NodeKind, NodeSpec, OutputPortDef, PortRole,
+};
+use crate::iiot::flow::types::{FlowMsg, PortMsgs, TagValue, ValueKind};
+use crate::kind_name;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CastClass {
+    Lossless,
+    Lossy,
+}
+
+/// 需要插入 Cast Node 的 edge
+#[derive(Debug, Clone)]
+pub struct CastEdge {
+    pub edge:  usize, // flow.edges 的 index
+    pub from:  ValueKind,
+    pub to:    ValueKind,
+    pub class: CastClass,
+}
+
+// ════════════════════════════════════════════════════════════════════════════
+// 數值族 Lattice
+// ════════════════════════════════════════════════════════════════════════════
+
+#[derive(Clone, Copy)]
+enum Num {
+    Int { signed: bool, bits: u32 },
+    Float { mantissa: u32 }, // 含隱藏位的有效位數
+}
+
+fn num(k: ValueKind) -> Option<Num> {
+    Some(match k {
+        ValueKind::I8Val  => Num::Int { signed: true,  bits: 8  },
+        ValueKind::U8Val  => Num::Int { signed: false, bits: 8  },
+        ValueKind::I16Val => Num::Int { signed: true,  bits: 16 },
+        ValueKind::U16Val => Num::Int { signed: false, bits: 16 },
+        ValueKind::I32Val => Num::Int { signed: true,  bits: 32 },
+        ValueKind::U32Val => Num::Int { signed: false, bits: 32 },
+        ValueKind::I64Val => Num::Int { signed: true,  bits: 64 },
+        ValueKind::U64Val => Num::Int { signed: false, bits: 64 },
+        ValueKind::F32Val => Num::Float { mantissa: 24 },
+        ValueKind::F64Val => Num::Float { mantissa: 53 },
+        ValueKind::BoolVal | ValueKind::ShortStr | ValueKind::Blob | ValueKind::Any => return None,
+    })
+}
+
+/// from → to 的轉型分類；None 代表不可自動轉換（非數值族）
+/// 呼叫端需先排除 from == to 與 any
+pub fn classify(from: ValueKind, to: ValueKind) -> Option<CastClass> {
+    let lossless = match (num(from)?, num(to)?) {
+        // 同號：目標較寬；無號 → 有號：目標嚴格較寬
+        (Num::Int { signed: s1, bits: b1 }, Num::Int { signed: s2, bits: b2 }) =>
+            (s1 == s2 && b2 >= b1) || (!s1 && s2 && b2 > b1),
+        // 整數的數值位元數不超過浮點有效位數
+        (Num::Int { signed, bits }, Num::Float { mantissa }) =>
+            bits - u32::from(signed) < mantissa,
+        (Num::Float { mantissa: m1 }, Num::Float { mantissa: m2 }) => m2 >= m1,
+        (Num::Float { .. }, Num::Int { .. }) => false,
+    };
+    Some(if lossless { CastClass::Lossless } else { CastClass::Lossy })
+}
+
+// ════════════════════════════════════════════════════════════════════════════
+// 執行期轉換：語意與 Rust `as` 相同（整數截斷、浮點轉整數飽和）
+// ════════════════════════════════════════════════════════════════════════════
+
+// host 與 iiot-flow-fusion 產生的 glue 共用同一份實作
+include!("cast_value.rs");
+
+// ════════════════════════════════════════════════════════════════════════════
+// Host 內建 Cast Node
+// ════════════════════════════════════════════════════════════════════════════
+
+pub struct CastNode {
+    pub name: String,
+    pub spec: NodeSpec,
+    pub to:   ValueKind,
+}
+
+impl CastNode {
+    pub fn new(c: &CastEdge) -> Self {
+        let (from, to) = (c.from, c.to);
+        let class = match c.class { CastClass::Lossless => "lossless", CastClass::Lossy => "lossy" };
+        let name  = format!("cast:{}→{}", kind_name(from), kind_name(to));
+        let spec = NodeSpec {
+            name:          name.clone(),
+            version:       env!("CARGO_PKG_VERSION").to_string(),
+            kind:          NodeKind::Transform,
+            inputs:        vec![InputPortDef {
+                port_id: 0, name: "in".to_string(), kind: from,
+                role: PortRole::Data, initial_value: None,
+            }],
+            outputs:       vec![OutputPortDef { port_id: 0, name: "out".to_string(), kind: to }],
+            join_strategy: JoinStrategy::Any,
+            props:         vec![],
+            label:         format!("{} → {}", kind_name(from), kind_name(to)),
+            description:   format!("Deploy Pipeline 自動插入的數值轉型（{class}）"),
+            icon:          String::new(),
+            category:      "builtin".to_string(),
+            color:         String::new(),
+        };
+        CastNode { name, spec, to }
+    }
+
+    pub fn process(&self, msgs: Vec<FlowMsg>) -> Vec<PortMsgs> {
+        let msgs = msgs.into_iter()
+            .map(|m| FlowMsg { value: cast_value(m.value, self.to), ..m })
+            .collect();
+        vec![PortMsgs { port_id: 0, msgs }]
+    }
+}
+
+/// 把 Cast Node 插入 flow，回傳 (IR flow, IR topo 順序)
+/// Cast Node 排在原始 nodes 之後（index = nodes.len() + i），
+/// topo 順序中緊跟在其上游 node 之後
+pub fn insert(flow: &FlowDef, topo: &[usize], casts: &[CastEdge]) -> (FlowDef, Vec<usize>) {
+    let mut ir = flow.clone();
+    let base = flow.nodes.len();
+    for (i, c) in casts.iter().enumerate() {
+        let e  = &flow.edges[c.edge];
+        let id = format!("cast#{i}:{}:{}→{}:{}", e.from, e.from_port, e.to, e.to_port);
+        ir.nodes.push(NodeDef {
+            id: id.clone(), wasm: String::new(), props: Value::Null, tags: vec![], limits: Default::default(),
+        });
+        ir.edges[c.edge] = EdgeDef { to: id.clone(), to_port: 0, to_role: "data".to_string(), ..e.clone() };
+        ir.edges.push(EdgeDef { from: id, from_port: 0, ..e.clone() });
+    }
+
+    let mut order = Vec::with_capacity(ir.nodes.len());
+    for &n in topo {
+        order.push(n);
+        for (i, c) in casts.iter().enumerate() {
+            if flow.edges[c.edge].from == flow.nodes[n].id { order.push(base + i); }
+        }
+    }
+    (ir, order)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ValueKind::*;
+
+    const NUMERIC: [ValueKind; 10] =
+        [I8Val, U8Val, I16Val, U16Val, I32Val, U32Val, I64Val, U64Val, F32Val, F64Val];
+
+    fn lossless(from: ValueKind, to: ValueKind) -> bool {
+        classify(from, to) == Some(CastClass::Lossless)
+    }
+
+    #[test]
+    fn classify_integer_widening() {
+        assert!(lossless(I8Val, I16Val) && lossless(U8Val, U64Val), "同號加寬");
+        assert!(lossless(U8Val, I16Val) && lossless(U32Val, I64Val), "無號 → 較寬的有號");
+        assert!(!lossless(U8Val, I8Val) && !lossless(U32Val, I32Val), "無號 → 同寬的有號");
+        assert!(!lossless(I8Val, U64Val), "有號 → 無號");
+        assert!(!lossless(I64Val, I32Val) && !lossless(U16Val, U8Val), "截斷");
+    }
+
+    #[test]
+    fn classify_integer_to_float_by_mantissa() {
+        assert!(lossless(I16Val, F32Val) && lossless(U16Val, F32Val));
+        assert!(!lossless(I32Val, F32Val) && !lossless(U32Val, F32Val), "超過 f32 的 24 位有效位數");
+        assert!(lossless(I32Val, F64Val) && lossless(U32Val, F64Val));
+        assert!(!lossless(I64Val, F64Val) && !lossless(U64Val, F64Val), "超過 f64 的 53 位有效位數");
+    }
+
+    #[test]
+    fn classify_float() {
+        assert!(lossless(F32Val, F64Val));
+        assert!(!lossless(F64Val, F32Val));
+        for to in &NUMERIC[..8] {
+            assert_eq!(classify(F32Val, *to), Some(CastClass::Lossy), "f32 → {}", kind_name(*to));
+        }
+    }
+
+    #[test]
+    fn classify_rejects_non_numeric() {
+        for other in [BoolVal, ShortStr, Blob, Any] {
+            for k in NUMERIC {
+                assert_eq!(classify(other, k), None, "{} → {}", kind_name(other), kind_name(k));
+                assert_eq!(classify(k, other), None, "{} → {}", kind_name(k), kind_name(other));
+            }
+        }
+    }
+
+    #[test]
+    fn lossless_is_reflexive_and_transitive() {
+        for a in NUMERIC {
+            assert!(lossless(a, a), "{}", kind_name(a));
+            for b in NUMERIC.into_iter().filter(|&b| lossless(a, b)) {
+                for c in NUMERIC.into_iter().filter(|&c| lossless(b, c)) {
+                    assert!(lossless(a, c), "{} → {} → {}", kind_name(a), kind_name(b), kind_name(c));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn cast_value_truncates_integers() {
+        assert!(matches!(cast_value(TagValue::I64Val(300), U8Val), TagValue::U8Val(44)));
+        assert!(matches!(cast_value(TagValue::I8Val(-1), U16Val), TagValue::U16Val(u16::MAX)));
+        assert!(matches!(cast_value(TagValue::U64Val(u64::MAX), I64Val), TagValue::I64Val(-1)));
+        assert!(matches!(cast_value(TagValue::U8Val(200), I16Val), TagValue::I16Val(200)));
+    }
+
+    #[test]
+    fn cast_value_saturates_floats() {
+        assert!(matches!(cast_value(TagValue::F64Val(1e10), I32Val), TagValue::I32Val(i32::MAX)));
+        assert!(matches!(cast_value(TagValue::F64Val(-1e10), I32Val), TagValue::I32Val(i32::MIN)));
+        assert!(matches!(cast_value(TagValue::F64Val(-1.5), U8Val), TagValue::U8Val(0)));
+        assert!(matches!(cast_value(TagValue::F32Val(f32::INFINITY), U64Val), TagValue::U64Val(u64::MAX)));
+        assert!(matches!(cast_value(TagValue::F64Val(2.9), I8Val), TagValue::I8Val(2)), "小數捨去");
+        let TagValue::F32Val(x) = cast_value(TagValue::F64Val(1e300), F32Val) else { panic!("應為 f32") };
+        assert!(x.is_infinite());
+    }
+
+    #[test]
+    fn cast_value_nan() {
+        assert!(matches!(cast_value(TagValue::F64Val(f64::NAN), I32Val), TagValue::I32Val(0)));
+        assert!(matches!(cast_value(TagValue::F32Val(f32::NAN), U8Val), TagValue::U8Val(0)));
+        let TagValue::F32Val(x) = cast_value(TagValue::F64Val(f64::NAN), F32Val) else { panic!("應為 f32") };
+        assert!(x.is_nan());
+    }
+
+    #[test]
+    fn cast_value_passes_non_numeric_through() {
+        assert!(matches!(cast_value(TagValue::BoolVal(true), F64Val), TagValue::BoolVal(true)));
+        assert!(matches!(cast_value(TagValue::ShortStr("x".into()), I32Val), TagValue::ShortStr(s) if s == "x"));
+        // 目標不是數值族：整數 → i64、浮點 → f64
+        assert!(matches!(cast_value(TagValue::U32Val(7), BoolVal), TagValue::I64Val(7)));
+        assert!(matches!(cast_value(TagValue::F32Val(0.5), Any), TagValue::F64Val(x) if x == 0.5));
+    }
+}
@@ -0,0 +1,41 @@
+// host/src/cast_value.rs
+// 數值族執行期轉換，語意與 Rust `as` 相同（整數截斷、浮點轉整數飽和）
+//
+// 以 include! 嵌入：host 的 cast.rs 與 iiot-flow-fusion 產生的 glue
+// 使用者需在作用域內提供 TagValue / ValueKind（WIT iiot:flow/types@0.2.0）
+
+enum Raw { I(i128), F(f64) }
+
+fn cast_value(v: TagValue, to: ValueKind) -> TagValue {
+    let raw = match v {
+        TagValue::I8Val(x)  => Raw::I(x.into()),
+        TagValue::U8Val(x)  => Raw::I(x.into()),
+        TagValue::I16Val(x) => Raw::I(x.into()),
+        TagValue::U16Val(x) => Raw::I(x.into()),
+        TagValue::I32Val(x) => Raw::I(x.into()),
+        TagValue::U32Val(x) => Raw::I(x.into()),
+        TagValue::I64Val(x) => Raw::I(x.into()),
+        TagValue::U64Val(x) => Raw::I(x.into()),
+        TagValue::F32Val(x) => Raw::F(x.into()),
+        TagValue::F64Val(x) => Raw::F(x),
+        other => return other, // 非數值族原樣通過，由下游自行處理
+    };
+    macro_rules! to {
+        ($variant:ident, $t:ty) => {
+            TagValue::$variant(match raw { Raw::I(i) => i as $t, Raw::F(f) => f as $t })
+        };
+    }
+    match to {
+        ValueKind::I8Val  => to!(I8Val,  i8),
+        ValueKind::U8Val  => to!(U8Val,  u8),
+        ValueKind::I16Val => to!(I16Val, i16),
+        ValueKind::U16Val => to!(U16Val, u16),
+        ValueKind::I32Val => to!(I32Val, i32),
+        ValueKind::U32Val => to!(U32Val, u32),
+        ValueKind::I64Val => to!(I64Val, i64),
+        ValueKind::U64Val => to!(U64Val, u64),
+        ValueKind::F32Val => to!(F32Val, f32),
+        ValueKind::F64Val => to!(F64Val, f64),
+        _ => match raw { Raw::I(i) => TagValue::I64Val(i as i64), Raw::F(f) => TagValue::F64Val(f) },
+    }
+}
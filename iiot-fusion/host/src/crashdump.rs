@@ -0,0 +1,318 @@
+// host/src/crashdump.rs
+// trap 的 post-mortem bundle：在現場重現別人寫的 Node 的 crash
+//
+//   <dir>/<unix-ms>-<名稱>/
+//     crash.json      錯誤鏈、trap 當下的 Node、wasm backtrace（函式名稱 / index / offset）、
+//                     觸發 crash 的輸入（FlowMsg 或 TagUpdate）、state / memory 檔案清單
+//     input-<i>.pb    raw TagUpdate（protobuf，可直接重送給 nng-pull endpoint）
+//     state.bin       save_state() 的輸出；trap 後的 instance 無法再進入時，改用 Supervisor 最近一次的 snapshot
+//     memory-<i>.bin  Store 內每個 linear memory 的完整內容（crash-dump.memory = true 才輸出：
+//                     每份最多 limits.memory-mb，gateway 的 flash 有限，預設關閉）
+//
+// 現場的收集分兩段：
+//   limits::call 失敗時記錄 Scene（backtrace、Node、memory handle；Engine 開啟 coredump_on_trap）
+//   Node / Sink / Fused 包裝記錄觸發 crash 的輸入
+// Supervisor 判定 crash 後呼叫 Restartable::post_mortem 取出，由 CrashDumper 寫入
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::path::{Path, PathBuf};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+use wasmtime::{Memory, Store, WasmBacktrace, WasmCoreDump};
+
+use crate::iiot::flow::types::{FlowMsg, TagValue, ValueKind};
+use crate::{kind_name, HostState, RawUpdate};
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case", default)]
+pub struct CrashDumpConfig {
+    // 輸出目錄（相對於 manifest.json）；空字串 = 不輸出
+    pub dir:    String,
+    // 是否輸出 linear memory（每個 crash 最多 limits.memory-mb，預設關閉）
+    pub memory: bool,
+    // 最多保留幾份，超過時刪除最舊的
+    pub keep:   usize,
+}
+
+impl Default for CrashDumpConfig {
+    fn default() -> Self {
+        CrashDumpConfig { dir: "crash-dumps".to_string(), memory: false, keep: 20 }
+    }
+}
+
+impl CrashDumpConfig {
+    pub fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// base 為相對路徑的基準目錄；dir 為空時回傳 None
+    pub fn dumper(&self, base: &Path) -> Option<CrashDumper> {
+        if self.dir.is_empty() { return None; }
+        Some(CrashDumper { dir: base.join(&self.dir), memory: self.memory, keep: self.keep })
+    }
+}
+
+// ── trap 現場 ────────────────────────────────────────────────────────────────
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct Frame {
+    pub module:        Option<String>,
+    pub func_index:    u32,
+    pub func_name:     Option<String>,
+    pub func_offset:   Option<usize>,
+    pub module_offset: Option<usize>,
+    // DWARF 的 file:line（WASMTIME_BACKTRACE_DETAILS=1 且 Node 帶 debug info 時才有）
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub symbols:       Vec<String>,
+}
+
+/// 觸發 crash 的輸入
+#[derive(Debug, Clone)]
+pub enum CrashInput {
+    Raw(Vec<RawUpdate>),                      // Source / Fused Pipeline
+    Msgs { port: u32, msgs: Vec<FlowMsg> },   // 一般 Node / Sink 的一批訊息
+}
+
+/// limits::call 失敗時記錄的現場（HostState 持有，post_mortem 取出後清空）
+#[derive(Default)]
+pub(crate) struct Scene {
+    pub node:     Option<String>,
+    pub frames:   Vec<Frame>,
+    pub memories: Vec<Memory>,
+    pub input:    Option<CrashInput>,
+}
+
+impl Scene {
+    pub(crate) fn record(&mut self, node: &str, err: &wasmtime::Error) {
+        let core = err.downcast_ref::<WasmCoreDump>();
+        let frames = core.map(|c| c.frames())
+            .or_else(|| err.downcast_ref::<WasmBacktrace>().map(|b| b.frames()))
+            .unwrap_or_default();
+        self.node     = Some(node.to_string());
+        self.frames   = frames.iter().map(|f| Frame {
+            module:        f.module().name().map(str::to_string),
+            func_index:    f.func_index(),
+            func_name:     f.func_name().map(str::to_string),
+            func_offset:   f.func_offset(),
+            module_offset: f.module_offset(),
+            symbols:       f.symbols().iter().map(|s| format!("{} {}:{}",
+                s.name().unwrap_or("?"), s.file().unwrap_or("?"), s.line().unwrap_or(0))).collect(),
+        }).collect();
+        // 沒有 coredump（非 wasm 執行中的錯誤）時 memory 無法取得
+        self.memories = core.map(|c| c.memories().to_vec()).unwrap_or_default();
+    }
+}
+
+/// crash 的完整現場（Restartable::post_mortem）
+#[derive(Default)]
+pub struct PostMortem {
+    pub node:     Option<String>,
+    pub frames:   Vec<Frame>,
+    pub input:    Option<CrashInput>,
+    pub state:    Option<std::result::Result<Vec<u8>, String>>, // None = 不適用（Cast Node）
+    pub memories: Vec<Vec<u8>>,
+}
+
+/// 取出 store 內記錄的現場（memory = 是否複製 linear memory）；save 嘗試從 trap 後的 instance 取得 state
+pub(crate) fn post_mortem(store: &mut Store<HostState>, memory: bool,
+                          save: impl FnOnce(&mut Store<HostState>) -> Result<Vec<u8>>) -> PostMortem {
+    let mut pm = capture(store, memory);
+    let state = save(store);
+    settle(store, &mut pm, state);
+    pm
+}
+
+/// post_mortem 的前半：取出現場，state 由呼叫端另外取得（async 的 save-states 要 await）
+pub(crate) fn capture(store: &mut Store<HostState>, memory: bool) -> PostMortem {
+    let scene = std::mem::take(&mut store.data_mut().scene);
+    let memories = if memory {
+        scene.memories.iter().map(|m| m.data(&*store).to_vec()).collect()
+    } else {
+        Vec::new()
+    };
+    PostMortem { node: scene.node, frames: scene.frames, input: scene.input, state: None, memories }
+}
+
+/// post_mortem 的後半：填入 state
+pub(crate) fn settle(store: &mut Store<HostState>, pm: &mut PostMortem, state: Result<Vec<u8>>) {
+    pm.state = Some(state.map_err(|e| format!("{e:#}")));
+    // save 失敗時 limits::call 又記錄了一次現場，不留給下一次 crash
+    store.data_mut().scene = Scene::default();
+}
+
+// ── 輸出 ─────────────────────────────────────────────────────────────────────
+
+#[derive(Debug, Clone)]
+pub struct CrashDumper {
+    pub dir:    PathBuf,
+    pub memory: bool,
+    pub keep:   usize,
+}
+
+impl CrashDumper {
+    /// 寫入一份 bundle，回傳目錄；snapshot 為 Supervisor 最近一次正常的 state 與取得時間
+    pub fn write(&self, name: &str, err: &anyhow::Error, pm: PostMortem,
+                 snapshot: Option<(&[u8], Instant)>) -> Result<PathBuf> {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_millis();
+        let dir = self.dir.join(format!("{now}-{}", slug(name)));
+        std::fs::create_dir_all(&dir).with_context(|| format!("無法建立 {}", dir.display()))?;
+
+        let input = match &pm.input {
+            None => Value::Null,
+            Some(CrashInput::Raw(updates)) => {
+                let mut files = Vec::new();
+                for (i, u) in updates.iter().enumerate() {
+                    let file = format!("input-{i}.pb");
+                    std::fs::write(dir.join(&file), &u.raw_bytes)?;
+                    files.push(json!({ "source": u.source, "tag-id": u.tag_id, "msg-id": u.msg_id, "file": file }));
+                }
+                json!({ "raw": files })
+            }
+            Some(CrashInput::Msgs { port, msgs }) =>
+                json!({ "port": port, "msgs": msgs.iter().map(msg_json).collect::<Vec<_>>() }),
+        };
+
+        // instance 還能回應 save_state 就用它；否則退回最近一次 snapshot
+        let state = match (pm.state, snapshot) {
+            (None, _) => Value::Null,
+            (Some(Ok(state)), _) => {
+                std::fs::write(dir.join("state.bin"), &state)?;
+                json!({ "file": "state.bin", "source": "instance", "bytes": state.len() })
+            }
+            (Some(Err(e)), Some((snap, at))) => {
+                std::fs::write(dir.join("state.bin"), snap)?;
+                json!({ "file": "state.bin", "source": "snapshot", "bytes": snap.len(),
+                        "age-ms": at.elapsed().as_millis() as u64, "save-state-error": e })
+            }
+            (Some(Err(e)), None) => json!({ "save-state-error": e }),
+        };
+
+        let mut memories = Vec::new();
+        for (i, mem) in pm.memories.iter().enumerate() {
+            let file = format!("memory-{i}.bin");
+            std::fs::write(dir.join(&file), mem)?;
+            memories.push(json!({ "file": file, "bytes": mem.len() }));
+        }
+
+        let report = json!({
+            "name":      name,
+            "node":      pm.node,
+            "time-ms":   now as u64,
+            "error":     format!("{err:#}"),
+            "backtrace": pm.frames,
+            "input":     input,
+            "state":     state,
+            "memories":  memories,
+        });
+        std::fs::write(dir.join("crash.json"), serde_json::to_string_pretty(&report)? + "\n")?;
+
+        self.prune()?;
+        Ok(dir)
+    }
+
+    // 只保留最新的 keep 份（目錄名稱以 unix-ms 開頭，依名稱排序即依時間）
+    fn prune(&self) -> Result<()> {
+        let mut dumps: Vec<PathBuf> = std::fs::read_dir(&self.dir)?
+            .filter_map(|e| e.ok().map(|e| e.path()))
+            .filter(|p| p.join("crash.json").is_file())
+            .collect();
+        if dumps.len() <= self.keep { return Ok(()); }
+        dumps.sort();
+        for old in &dumps[..dumps.len() - self.keep] {
+            std::fs::remove_dir_all(old)?;
+        }
+        Ok(())
+    }
+}
+
+fn msg_json(m: &FlowMsg) -> Value {
+    let (kind, value) = match &m.value {
+        TagValue::BoolVal(v)  => (ValueKind::BoolVal,  json!(v)),
+        TagValue::I8Val(v)    => (ValueKind::I8Val,    json!(v)),
+        TagValue::U8Val(v)    => (ValueKind::U8Val,    json!(v)),
+        TagValue::I16Val(v)   => (ValueKind::I16Val,   json!(v)),
+        TagValue::U16Val(v)   => (ValueKind::U16Val,   json!(v)),
+        TagValue::I32Val(v)   => (ValueKind::I32Val,   json!(v)),
+        TagValue::U32Val(v)   => (ValueKind::U32Val,   json!(v)),
+        TagValue::I64Val(v)   => (ValueKind::I64Val,   json!(v)),
+        TagValue::U64Val(v)   => (ValueKind::U64Val,   json!(v)),
+        TagValue::F32Val(v)   => (ValueKind::F32Val,   json!(v)),
+        TagValue::F64Val(v)   => (ValueKind::F64Val,   json!(v)),
+        TagValue::ShortStr(v) => (ValueKind::ShortStr, json!(v)),
+        TagValue::Blob(v)     => (ValueKind::Blob,     json!(v)),
+    };
+    json!({
+        "tag-id":    m.tag_id,
+        "msg-id":    m.msg_id,
+        "kind":      kind_name(kind),
+        "value":     value,
+        "timestamp": m.timestamp,
+        "quality":   m.quality,
+    })
+}
+
+// 目錄名稱：只留英數、- 與 _
+fn slug(name: &str) -> String {
+    name.chars().map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' { c } else { '_' }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::TempDir;
+
+    fn dump(dir: &Path, name: &str) {
+        std::fs::create_dir_all(dir.join(name)).unwrap();
+        std::fs::write(dir.join(name).join("crash.json"), "{}").unwrap();
+    }
+
+    #[test]
+    fn memory_dumps_are_opt_in() {
+        let cfg = CrashDumpConfig::default();
+        assert!(!cfg.memory, "預設不應輸出 linear memory");
+    }
+
+    #[test]
+    fn prune_keeps_the_newest_n() {
+        let tmp = TempDir::new("crash-prune");
+        let dumper = CrashDumper { dir: tmp.path().to_path_buf(), memory: false, keep: 2 };
+        for ms in [1700000000001u64, 1700000000003, 1700000000002, 1700000000004] {
+            dump(tmp.path(), &format!("{ms}-flow_a"));
+        }
+        // 沒有 crash.json 的目錄不是 bundle，不計數也不刪
+        std::fs::create_dir_all(tmp.path().join("notes")).unwrap();
+
+        dumper.prune().unwrap();
+
+        let mut left: Vec<String> = std::fs::read_dir(tmp.path()).unwrap()
+            .map(|e| e.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        left.sort();
+        assert_eq!(left, ["1700000000003-flow_a", "1700000000004-flow_a", "notes"], "應只保留最新兩份");
+    }
+
+    #[test]
+    fn write_prunes_old_bundles() {
+        let tmp = TempDir::new("crash-write");
+        let dumper = CrashDumper { dir: tmp.path().to_path_buf(), memory: false, keep: 1 };
+        dump(tmp.path(), "1000-flow_a");
+
+        let dir = dumper.write("flow a", &anyhow::anyhow!("boom"), PostMortem::default(), None).unwrap();
+
+        assert!(dir.join("crash.json").is_file(), "應寫出 crash.json");
+        assert!(!tmp.path().join("1000-flow_a").exists(), "舊的 bundle 應被刪除");
+        let report: Value = serde_json::from_str(&std::fs::read_to_string(dir.join("crash.json")).unwrap()).unwrap();
+        assert_eq!(report["error"], "boom");
+        assert_eq!(report["memories"], json!([]), "沒有 memory 時不應有 memory 檔");
+    }
+
+    #[test]
+    fn slug_keeps_only_safe_chars() {
+        assert_eq!(slug("flow plant/1"), "flow_plant_1");
+        assert_eq!(slug("line-2_temp"), "line-2_temp");
+        assert_eq!(slug("../etc"), "___etc");
+        assert_eq!(slug("溫度"), "__");
+    }
+}
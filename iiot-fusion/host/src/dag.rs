@@ -0,0 +1,369 @@
+// host/src/dag.rs
+// 未 Fusion 的 DAG 執行器：依 flow.json 載入 Node，驗證後照 topo 順序推送 FlowMsg
+// 訊息在 Node 間的投遞（fan-out / fan-in）由 router.rs 的 edge table 負責
+//
+//   describe() kind = source   → Source（process_raw 入口）
+//   describe() kind = sink     → Sink  （flow-node-with-host world）
+//   其他                        → 一般 Node（process）；孤立的 Node 由 Validate 回報
+//   Validate 排定的型別轉換     → Host 內建 Cast Node（cast.rs）
+
+use anyhow::{bail, Context, Result};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+use wasmtime::component::{Component, Linker};
+use wasmtime::Engine;
+
+use crate::flow::FlowDef;
+use crate::props;
+use crate::iiot::flow::node_descriptor::{NodeKind, NodeSpec};
+use crate::iiot::flow::types::{FlowMsg, ValueKind};
+use crate::aot::CompileCache;
+use crate::cast::{self, CastNode};
+use crate::router::{Inbox, Router};
+use crate::snapshot::{Snapshot, SnapshotStore};
+use crate::crashdump::CrashDumper;
+use crate::limits::ExecLimits;
+use crate::redeploy::Standby;
+use crate::supervisor::{Supervisor, SupervisorConfig};
+use crate::validate::{self, Issue};
+use crate::{kind_name, HostState, Node, SinkNode, TagRegistry};
+
+pub enum DagNode {
+    Node(Node),
+    Sink(SinkNode),
+    Cast(CastNode),
+}
+
+impl DagNode {
+    /// 依 describe() 的 kind 選擇包裝：sink → SinkNode，其他 → Node
+    /// flow-node-with-host 的 linker 是 flow-node 的超集，先以它實例化讀出 kind
+    pub fn new(engine: &Engine, linker: &Linker<HostState>, component: &Component,
+               registry: Arc<RwLock<TagRegistry>>, limits: ExecLimits) -> Result<Self> {
+        let sink = SinkNode::new(engine, component, Arc::clone(&registry), limits)?;
+        if sink.spec.kind == NodeKind::Sink { return Ok(DagNode::Sink(sink)); }
+        drop(sink);
+        Node::new(engine, linker, component, registry, limits).map(DagNode::Node)
+    }
+
+    pub fn name(&self) -> &str {
+        match self {
+            DagNode::Node(n) => &n.name,
+            DagNode::Sink(s) => &s.name,
+            DagNode::Cast(c) => &c.name,
+        }
+    }
+
+    pub fn init(&mut self, props: &str, wiring: &str) -> Result<()> {
+        match self {
+            DagNode::Node(n) => n.init(props, wiring),
+            DagNode::Sink(s) => s.init(props, wiring),
+            DagNode::Cast(_) => Ok(()),
+        }
+    }
+
+    pub fn spec(&self) -> &NodeSpec {
+        match self {
+            DagNode::Node(n) => &n.spec,
+            DagNode::Sink(s) => &s.spec,
+            DagNode::Cast(c) => &c.spec,
+        }
+    }
+
+    /// Cast Node 沒有狀態，回傳空
+    pub fn save_state(&mut self) -> Result<Vec<u8>> {
+        match self {
+            DagNode::Node(n) => n.save_state(),
+            DagNode::Sink(s) => s.save_state(),
+            DagNode::Cast(_) => Ok(Vec::new()),
+        }
+    }
+
+    pub fn load_state(&mut self, state: Vec<u8>) -> Result<()> {
+        match self {
+            DagNode::Node(n) => n.load_state(state),
+            DagNode::Sink(s) => s.load_state(state),
+            DagNode::Cast(_) => Ok(()),
+        }
+    }
+
+    pub fn set_limits(&mut self, id: &str, limits: ExecLimits) {
+        match self {
+            DagNode::Node(n) => n.set_limits(id, limits),
+            DagNode::Sink(s) => s.set_limits(id, limits),
+            DagNode::Cast(_) => {}
+        }
+    }
+}
+
+pub struct FlowDag {
+    pub ids:            Vec<String>,    // topo 順序（含 Cast Node）
+    pub nodes:          Vec<DagNode>,   // 與 ids 對齊
+    pub warnings:       Vec<Issue>,     // Validate 的 warning（有損轉型）
+    pub resolved_types: Vec<ValueKind>, // 與 flow.edges 對齊
+    pub ir:             FlowDef,        // 插入 Cast Node 後的 flow（Fusion 依此產生 glue）
+    pub init_args:      Vec<Option<(String, String)>>, // 與 ids 對齊，(props, wiring)；Cast Node 為 None
+    pub supervisors:    Vec<Supervisor>, // 與 ids 對齊：每個 Node 各自隔離 / 重啟（supervisor.rs）
+    router:             Router,
+    is_source:          Vec<bool>,
+    standby:            Vec<Option<Standby<(DagNode, Supervisor)>>>, // redeploy_node 後 grace 期間的舊 Node
+}
+
+impl FlowDag {
+    /// 載入流程：① 實例化 + describe() → ② Validate（validate.rs）→ ③ props + init()
+    /// Validate 失敗時回傳 ValidationFailed（§14.1 JSON 報告），不呼叫任何 init()
+    pub fn load(engine: &Engine, linker: &Linker<HostState>, cache: &CompileCache,
+                registry: Arc<RwLock<TagRegistry>>,
+                flow: &FlowDef, wasm_dir: &Path) -> Result<Self> {
+        // 同一個 .wasm 可被多個 node 共用，只編譯一次
+        let mut components: HashMap<&str, Component> = HashMap::new();
+        let mut loaded = Vec::with_capacity(flow.nodes.len());
+        for def in &flow.nodes {
+            if !components.contains_key(def.wasm.as_str()) {
+                let component = cache.load(engine, &wasm_dir.join(&def.wasm))?;
+                components.insert(def.wasm.as_str(), component);
+            }
+            let component = &components[def.wasm.as_str()];
+            let registry  = Arc::clone(&registry);
+            let mut node = DagNode::new(engine, linker, component, registry, def.limits)
+                .with_context(|| format!("node {}", def.id))?;
+            // 錯誤訊息以 flow 內的 node id 標示
+            node.set_limits(&def.id, def.limits);
+            loaded.push(Some(node));
+        }
+
+        let specs: Vec<&NodeSpec> = loaded.iter().flatten().map(DagNode::spec).collect();
+        let validated = validate::validate(flow, &specs, &registry.read().unwrap())?;
+
+        // 插入 Cast Node 後依 topo 順序排列
+        let (ir, order) = cast::insert(flow, &validated.topo_order, &validated.casts);
+        loaded.extend(validated.casts.iter().map(|c| Some(DagNode::Cast(CastNode::new(c)))));
+        let ids: Vec<String> = order.iter().map(|&i| ir.nodes[i].id.clone()).collect();
+        let mut nodes: Vec<DagNode> = order.iter().map(|&i| loaded[i].take().unwrap()).collect();
+        let pos: HashMap<&str, usize> =
+            ids.iter().enumerate().map(|(p, id)| (id.as_str(), p)).collect();
+        let router = Router::new(&ir, &pos);
+
+        // props 驗證 + init(props, wiring)
+        // wiring 以使用者的 flow 為準，Cast Node 對 Node 不可見
+        let mut init_args = vec![None; nodes.len()];
+        for (p, &i) in order.iter().enumerate() {
+            let Some(def) = flow.nodes.get(i) else { continue; };
+            let props  = props::resolve(&def.id, &def.props, &nodes[p].spec().props)?.to_string();
+            let wiring = wiring(flow, &validated.resolved_types, &def.id, nodes[p].spec()).to_string();
+            nodes[p].init(&props, &wiring).with_context(|| format!("node {}", def.id))?;
+            init_args[p] = Some((props, wiring));
+        }
+
+        let is_source = nodes.iter().map(|n| n.spec().kind == NodeKind::Source).collect();
+        let supervisors = ids.iter()
+            .map(|id| Supervisor::new(&format!("node {id}"), SupervisorConfig::default()))
+            .collect();
+        let standby = ids.iter().map(|_| None).collect();
+        Ok(FlowDag {
+            ids, nodes, warnings: validated.warnings, resolved_types: validated.resolved_types,
+            ir, init_args, supervisors, router, is_source, standby,
+        })
+    }
+
+    /// 所有 Node 改用 config（預設為 SupervisorConfig::default()）
+    pub fn supervise(&mut self, config: SupervisorConfig) {
+        for s in &mut self.supervisors { s.config = config; }
+    }
+
+    /// Node crash 時寫出 crash dump（None = 不輸出）
+    pub fn dump_to(&mut self, dumper: Option<CrashDumper>) {
+        for s in &mut self.supervisors { s.dumps = dumper.clone(); }
+    }
+
+    pub fn node(&self, id: &str) -> Option<&DagNode> {
+        let p = self.ids.iter().position(|i| i == id)?;
+        Some(&self.nodes[p])
+    }
+
+    pub fn node_mut(&mut self, id: &str) -> Option<&mut DagNode> {
+        let p = self.ids.iter().position(|i| i == id)?;
+        Some(&mut self.nodes[p])
+    }
+
+    /// 把所有 WASM Node 的狀態寫入 snapshot（key = node id）；回傳寫入的份數
+    pub fn save_snapshots(&mut self, store: &SnapshotStore) -> Result<usize> {
+        let mut n = 0;
+        for (id, node) in self.ids.iter().zip(&mut self.nodes) {
+            if matches!(node, DagNode::Cast(_)) { continue; }
+            let state = node.save_state().with_context(|| format!("node {id} save_state 失敗"))?;
+            store.save(id, &state)?;
+            n += 1;
+        }
+        Ok(n)
+    }
+
+    /// 從 snapshot 還原各 Node 的狀態，回傳 (node id, 還原的 snapshot)
+    pub fn restore_snapshots(&mut self, store: &SnapshotStore) -> Result<Vec<(String, Snapshot)>> {
+        let mut restored = Vec::new();
+        for (id, node) in self.ids.iter().zip(&mut self.nodes) {
+            if matches!(node, DagNode::Cast(_)) { continue; }
+            let Some(snap) = store.load(id)? else { continue; };
+            node.load_state(snap.payload.clone())
+                .with_context(|| format!("node {id} 無法載入 snapshot {}", snap.path.display()))?;
+            restored.push((id.clone(), snap));
+        }
+        Ok(restored)
+    }
+
+    /// 以新的 instance（例如新版本的 .wasm）取代 node id，不中斷其他 Node：
+    /// port 定義必須相同；以原本的 props / wiring init，再轉移狀態（版本不同時經過 migrate-state）
+    /// 失敗時原本的 Node 不受影響；grace 內新 Node crash 即換回原本的 Node
+    pub fn redeploy_node(&mut self, id: &str, mut node: DagNode, grace: Duration) -> Result<()> {
+        let p = self.ids.iter().position(|i| i == id).with_context(|| format!("flow 內沒有 node {id}"))?;
+        if matches!(self.nodes[p], DagNode::Cast(_)) || matches!(node, DagNode::Cast(_)) {
+            bail!("node {id}：Cast Node 由 Validate 產生，不能 redeploy");
+        }
+        if self.standby[p].is_some() { bail!("node {id} 的上一次 redeploy 仍在觀察期間"); }
+        let (old, new) = (self.nodes[p].spec(), node.spec());
+        if !same_ports(old, new) {
+            bail!("node {id}：{} {} 與 {} {} 的 port 定義不同，需要重新 Deploy 整條 flow",
+                old.name, old.version, new.name, new.version);
+        }
+        node.set_limits(id, self.ir.node(id).map(|d| d.limits).unwrap_or_default());
+        if let Some((props, wiring)) = &self.init_args[p] {
+            node.init(props, wiring).with_context(|| format!("node {id} 的新版本 init 失敗"))?;
+        }
+        let state = self.nodes[p].save_state().with_context(|| format!("node {id} save_state 失敗"))?;
+        node.load_state(state).with_context(|| format!("node {id} 的新版本無法載入目前的狀態"))?;
+
+        let mut supervisor = Supervisor::new(&format!("node {id}"), self.supervisors[p].config);
+        supervisor.dumps = self.supervisors[p].dumps.clone();
+        eprintln!("🔀 node {id} redeploy：{} → {}，觀察 {}ms",
+            self.nodes[p].spec().version, node.spec().version, grace.as_millis());
+        let old = (std::mem::replace(&mut self.nodes[p], node),
+                   std::mem::replace(&mut self.supervisors[p], supervisor));
+        self.standby[p] = Some(Standby::new(old, grace));
+        Ok(())
+    }
+
+    // redeploy_node 的 grace 期間：crash 就換回原本的 Node，期滿則釋放
+    fn settle(&mut self, p: usize) {
+        let Some(standby) = &self.standby[p] else { return; };
+        if self.supervisors[p].crashes == 0 {
+            if standby.expired() {
+                eprintln!("✅ node {} redeploy：{} 已穩定執行，釋放 {}", self.ids[p],
+                    self.nodes[p].spec().version, standby.old.0.spec().version);
+                self.standby[p] = None;
+            }
+            return;
+        }
+        let (node, supervisor) = self.standby[p].take().unwrap().old;
+        eprintln!("↩️  node {} redeploy：{} 在觀察期間 crash，已換回 {}", self.ids[p],
+            self.nodes[p].spec().version, node.spec().version);
+        self.nodes[p] = node;
+        self.supervisors[p] = supervisor;
+    }
+
+    /// source node id 在 ids（topo 順序）內的位置，run 的 source
+    pub fn source(&self, id: &str) -> Result<usize> {
+        match self.ids.iter().position(|n| n == id) {
+            Some(p) if self.is_source[p] => Ok(p),
+            Some(_) => bail!("{id} 不是 Source Node"),
+            None    => bail!("flow 內沒有 node {id}"),
+        }
+    }
+
+    /// 一筆 raw TagUpdate 送入收到它的 Source（source = ids 內的位置），跑完整張 DAG；其他 Source 不執行
+    /// 回傳抵達 Sink 的 FlowMsg（空代表途中被 filter 掉）
+    /// Node trap 時由該 Node 的 Supervisor 隔離，送往它的訊息丟棄，其餘 Node 照常執行；
+    /// 只有 crash 次數超過 max-crashes 才回傳錯誤
+    pub fn run(&mut self, source: usize, tag_id: u32, msg_id: u32, raw: &[u8]) -> Result<Vec<FlowMsg>> {
+        if !self.is_source.get(source).copied().unwrap_or(false) { bail!("第 {source} 個 Node 不是 Source"); }
+        let mut inbox = Inbox::new(self.nodes.len());
+        let mut sunk = Vec::new();
+
+        for p in 0..self.nodes.len() {
+            let msgs = inbox.take(p);
+            let is_source = p == source;
+            if msgs.is_empty() && !is_source { continue; }
+            let r = self.supervisors[p].call(&mut self.nodes[p], |node| match node {
+                DagNode::Node(n) if is_source => n.process_raw(tag_id, msg_id, raw),
+                DagNode::Node(n) => {
+                    let mut outputs = Vec::new();
+                    for (port, batch) in by_port(msgs) { outputs.extend(n.process(port, &batch)?); }
+                    Ok(outputs)
+                }
+                DagNode::Cast(c) => Ok(c.process(msgs.into_iter().map(|(_, m)| m).collect())),
+                DagNode::Sink(s) => {
+                    for (port, batch) in by_port(msgs) {
+                        s.process(port, &batch)?;
+                        sunk.extend(batch);
+                    }
+                    Ok(Vec::new())
+                }
+            });
+            // redeploy 後的新 Node crash：換回原本的 Node，這一輪送往它的訊息丟棄
+            let rolled_back = self.standby[p].is_some() && self.supervisors[p].crashes > 0;
+            self.settle(p);
+            let outputs = if rolled_back { None } else { r? };
+            // None：Node crash 或仍在隔離中
+            for out in outputs.into_iter().flatten() {
+                self.router.deliver(p, out.port_id, out.msgs, &mut inbox);
+            }
+        }
+        Ok(sunk)
+    }
+}
+
+// redeploy 的新 Node 必須有相同的 input / output port（edge 與 Validate 的結果沿用）
+fn same_ports(a: &NodeSpec, b: &NodeSpec) -> bool {
+    a.kind == b.kind
+        && a.inputs.len() == b.inputs.len() && a.outputs.len() == b.outputs.len()
+        && a.inputs.iter().zip(&b.inputs).all(|(x, y)| x.port_id == y.port_id && x.kind == y.kind && x.role == y.role)
+        && a.outputs.iter().zip(&b.outputs).all(|(x, y)| x.port_id == y.port_id && x.kind == y.kind)
+}
+
+/// inbox 內連續同一 input port 的訊息合成一批，保持抵達順序
+fn by_port(msgs: Vec<(u32, FlowMsg)>) -> Vec<(u32, Vec<FlowMsg>)> {
+    let mut batches: Vec<(u32, Vec<FlowMsg>)> = Vec::new();
+    for (port, msg) in msgs {
+        match batches.last_mut() {
+            Some((p, batch)) if *p == port => batch.push(msg),
+            _ => batches.push((port, vec![msg])),
+        }
+    }
+    batches
+}
+
+/// init() 的 wiring JSON（IIoTFlowArchitecture §4.4）
+/// resolved-type：port 宣告具體型別時即該型別（必要時已 cast），any 則取推導結果
+fn wiring(flow: &FlowDef, resolved: &[ValueKind], id: &str, spec: &NodeSpec) -> Value {
+    let inputs: Vec<Value> = flow.edges.iter().enumerate().filter(|(_, e)| e.to == id).map(|(j, e)| {
+        let port = spec.inputs.iter().find(|p| p.port_id == e.to_port);
+        let kind = match port {
+            Some(p) if p.kind != ValueKind::Any => p.kind,
+            _ => resolved[j],
+        };
+        json!({
+            "port-id":       e.to_port,
+            "name":          port.map(|p| p.name.as_str()).unwrap_or_default(),
+            "from-node":     e.from,
+            "from-port":     e.from_port,
+            "resolved-type": kind_name(kind),
+        })
+    }).collect();
+
+    let outputs: Vec<Value> = spec.outputs.iter().map(|p| {
+        let edges: Vec<usize> = (0..flow.edges.len())
+            .filter(|&j| flow.edges[j].from == id && flow.edges[j].from_port == p.port_id)
+            .collect();
+        let to_nodes: Vec<&str> = edges.iter().map(|&j| flow.edges[j].to.as_str()).collect();
+        let kind = edges.first().map_or(p.kind, |&j| resolved[j]);
+        json!({
+            "port-id":       p.port_id,
+            "name":          p.name,
+            "to-nodes":      to_nodes,
+            "resolved-type": kind_name(kind),
+        })
+    }).collect();
+
+    json!({ "node-id": id, "inputs": inputs, "outputs": outputs })
+}
@@ -0,0 +1,394 @@
+// host/src/endpoint.rs
+// manifest.json host-endpoints 的實作（IIoTFlowArchitecture §12.2 / §13.2 的 recv / send）
+//
+//   nng-pull    source only：Protocol Driver 的 nng PUSH 連進來（ingest.rs），每筆訊息 = 一個 TagUpdate
+//   tcp-client  source：讀 frame；斷線下次 tick 重連      sink：寫 frame；斷線下次 send 重連
+//               連線失敗後依指數 backoff（RECONNECT_MIN → RECONNECT_MAX）才再試，期間立即返回
+//   file        source：依序讀完 frame 後視為結束         sink：append frame
+//   stdout      sink only：印出一行，開發 / 除錯用
+//   bus         source only：flow manager 依 manifest.subscribe 轉送的 TagUpdate（manager.rs）；
+//               manager 停止轉送（共用 input 都結束）後視為結束
+//
+// frame 格式（nng-pull 以外）：u32 big-endian 長度 + protobuf（source = TagUpdate，sink = FlowResult）
+// 過大或截斷的 frame 不中斷 flow：計入 rejected（Runtime 的 dropped），tcp 關閉並重連、file 視為讀完
+
+use anyhow::{bail, Context, Result};
+use prost::Message as _;
+use std::fs::{File, OpenOptions};
+use std::io::{BufReader, ErrorKind, Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::path::Path;
+use std::sync::mpsc::{Receiver, TryRecvError};
+use std::time::{Duration, Instant};
+
+use crate::ingest::{IngestStats, PullSocket};
+use crate::manifest::EndpointDef;
+use crate::proto;
+
+pub(crate) const MAX_FRAME: usize = 16 * 1024 * 1024;
+pub(crate) const CONNECT_TIMEOUT: Duration = Duration::from_secs(3);
+const RECONNECT_MIN: Duration = Duration::from_millis(200);
+const RECONNECT_MAX: Duration = Duration::from_secs(30);
+
+pub struct Endpoint {
+    pub id:   u32,
+    pub role: String,
+    pub node: Option<String>,
+    io:       Io,
+    rejected: u64, // 尚未被 take_rejected 取走的壞 frame 數
+}
+
+enum Io {
+    NngPull(PullSocket),
+    Tcp(TcpLink),
+    FileIn { reader: Option<BufReader<File>> }, // None = 已讀完
+    FileOut(File),
+    Stdout,
+    Bus    { rx: Receiver<Vec<u8>>, closed: bool },
+}
+
+impl Endpoint {
+    /// file 的相對路徑以 base（manifest 目錄）為準
+    pub fn open(def: &EndpointDef, base: &Path) -> Result<Self> {
+        let io = match (def.kind.as_str(), def.role.as_str()) {
+            ("nng-pull", _) => Io::NngPull(PullSocket::listen(&def.address, def.queue)
+                .with_context(|| format!("endpoint {}", def.id))?),
+            ("tcp-client", _) => Io::Tcp(TcpLink::new(&def.address)),
+            ("file", "source") => {
+                let path = base.join(&def.address);
+                let file = File::open(&path)
+                    .with_context(|| format!("endpoint {}：無法開啟 {}", def.id, path.display()))?;
+                Io::FileIn { reader: Some(BufReader::new(file)) }
+            }
+            ("file", _) => {
+                let path = base.join(&def.address);
+                let file = OpenOptions::new().create(true).append(true).open(&path)
+                    .with_context(|| format!("endpoint {}：無法寫入 {}", def.id, path.display()))?;
+                Io::FileOut(file)
+            }
+            ("stdout", _) => Io::Stdout,
+            ("bus", _) => bail!("endpoint {}：bus 由 flow manager 轉送，請以 flows.json 啟動（manager.rs）", def.id),
+            (other, _) => bail!("endpoint {} 的 type 不支援：{other}", def.id),
+        };
+        Ok(Endpoint { id: def.id, role: def.role.clone(), node: def.node.clone(), io, rejected: 0 })
+    }
+
+    /// type = "bus"：從 flow manager 的 queue 讀取
+    pub fn bus(def: &EndpointDef, rx: Receiver<Vec<u8>>) -> Self {
+        Endpoint {
+            id: def.id, role: def.role.clone(), node: def.node.clone(),
+            io: Io::Bus { rx, closed: false }, rejected: 0,
+        }
+    }
+
+    /// 上次呼叫之後因過大 / 截斷而丟棄的 frame 數
+    pub fn take_rejected(&mut self) -> u64 {
+        std::mem::take(&mut self.rejected)
+    }
+
+    /// source 是否已經不會再有資料（file 讀完、bus 停止轉送）；tcp 斷線會重連，永遠不算結束
+    pub fn is_closed(&self) -> bool {
+        matches!(self.io, Io::FileIn { reader: None } | Io::Bus { closed: true, .. })
+    }
+
+    /// nng-pull 的連線 / 接收統計
+    pub fn ingest_stats(&self) -> Option<&IngestStats> {
+        match &self.io {
+            Io::NngPull(pull) => Some(&pull.stats),
+            _ => None,
+        }
+    }
+
+    /// 非阻塞：取出目前已到達的 frame，最多 max 筆
+    /// 壞 frame 只影響該條連線 / 檔案（計入 take_rejected），不回傳錯誤
+    pub fn recv(&mut self, max: usize) -> Result<Vec<Vec<u8>>> {
+        let mut frames = Vec::new();
+        match &mut self.io {
+            Io::NngPull(pull) => frames = pull.try_recv(max),
+            Io::Tcp(link) => {
+                if link.fill(self.id) { self.rejected += 1; }
+                while frames.len() < max {
+                    match split_frame(&mut link.buf) {
+                        Ok(Some(frame)) => frames.push(frame),
+                        Ok(None) => break,
+                        Err(e) => {
+                            eprintln!("⚠️  endpoint {} {e:#}（{}），關閉連線並重連", self.id, link.addr);
+                            self.rejected += 1;
+                            link.reset();
+                            break;
+                        }
+                    }
+                }
+            }
+            Io::FileIn { reader } => {
+                while frames.len() < max {
+                    let Some(r) = reader else { break; };
+                    match read_frame(r) {
+                        Ok(Some(frame)) => frames.push(frame),
+                        Ok(None) => *reader = None,
+                        Err(e) => {
+                            eprintln!("⚠️  endpoint {} {e:#}，其後的內容不再讀取", self.id);
+                            self.rejected += 1;
+                            *reader = None;
+                        }
+                    }
+                }
+            }
+            Io::Bus { rx, closed } => {
+                while frames.len() < max {
+                    match rx.try_recv() {
+                        Ok(frame) => frames.push(frame),
+                        Err(TryRecvError::Empty) => break,
+                        Err(TryRecvError::Disconnected) => { *closed = true; break; }
+                    }
+                }
+            }
+            Io::FileOut(_) | Io::Stdout => bail!("endpoint {} 不是 source", self.id),
+        }
+        Ok(frames)
+    }
+
+    pub fn send(&mut self, result: &proto::FlowResult) -> Result<()> {
+        match &mut self.io {
+            Io::Tcp(link) => {
+                let Some(s) = link.connect(self.id) else {
+                    bail!("endpoint {} 尚未連線（{}）", self.id, link.addr);
+                };
+                // 非阻塞 socket：寫入時暫時切回阻塞，避免 frame 寫一半
+                let written = s.set_nonblocking(false).map_err(anyhow::Error::from)
+                    .and_then(|_| write_frame(s, result));
+                let _ = s.set_nonblocking(true);
+                if let Err(e) = written {
+                    link.reset();
+                    return Err(e).with_context(|| format!("endpoint {} 寫入失敗（{}）", self.id, link.addr));
+                }
+                Ok(())
+            }
+            Io::FileOut(f) => write_frame(f, result),
+            Io::Stdout => {
+                println!("  [endpoint {}] {} = {:.4}  msg={} q={} ts={}",
+                    self.id, result.tag_name, result.value, result.msg_id, result.quality, result.timestamp);
+                Ok(())
+            }
+            Io::FileIn { .. } | Io::NngPull(_) | Io::Bus { .. } => bail!("endpoint {} 不是 sink", self.id),
+        }
+    }
+}
+
+// tcp-client 的連線狀態
+struct TcpLink {
+    addr:     String,
+    stream:   Option<TcpStream>,
+    buf:      Vec<u8>,      // 尚未湊滿一個 frame 的 bytes
+    warned:   bool,
+    backoff:  Duration,     // 下一次失敗後的等待時間
+    retry_at: Option<Instant>, // 在此之前不嘗試連線
+}
+
+impl TcpLink {
+    fn new(addr: &str) -> Self {
+        TcpLink {
+            addr: addr.to_string(), stream: None, buf: Vec::new(), warned: false,
+            backoff: RECONNECT_MIN, retry_at: None,
+        }
+    }
+
+    // 尚未連線就嘗試連線（backoff 期間直接返回）；失敗只警告一次，等下次呼叫再試
+    fn connect(&mut self, id: u32) -> Option<&mut TcpStream> {
+        if self.stream.is_none() && self.retry_at.is_none_or(|t| Instant::now() >= t) {
+            match open_tcp(&self.addr) {
+                Ok(s) => {
+                    self.stream = Some(s);
+                    self.warned = false;
+                    self.backoff = RECONNECT_MIN;
+                    self.retry_at = None;
+                }
+                Err(e) => {
+                    if !self.warned { eprintln!("⚠️  endpoint {id} 無法連線 {}：{e}", self.addr); }
+                    self.warned = true;
+                    self.retry_at = Some(Instant::now() + self.backoff);
+                    self.backoff = (self.backoff * 2).min(RECONNECT_MAX);
+                }
+            }
+        }
+        self.stream.as_mut()
+    }
+
+    // 讀取目前所有可讀的 bytes 到 buf；連線中斷時丟掉最後不完整的 frame（無法再完成），有丟棄時回傳 true
+    fn fill(&mut self, id: u32) -> bool {
+        if self.connect(id).is_none() { return false; }
+        let (addr, s, buf) = (&self.addr, self.stream.as_mut().unwrap(), &mut self.buf);
+        let mut chunk = [0u8; 64 * 1024];
+        let mut lost = false;
+        loop {
+            match s.read(&mut chunk) {
+                Ok(0) => {
+                    eprintln!("⚠️  endpoint {id} 連線中斷（{addr}），下次 tick 重連");
+                    lost = true;
+                    break;
+                }
+                Ok(n) => buf.extend_from_slice(&chunk[..n]),
+                Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+                Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                Err(e) => {
+                    eprintln!("⚠️  endpoint {id} 讀取失敗（{addr}）：{e}");
+                    lost = true;
+                    break;
+                }
+            }
+        }
+        if !lost { return false; }
+        self.stream = None;
+        // 中斷前完整收到的 frame 仍然交出
+        let complete = complete_frames(&self.buf);
+        let truncated = complete < self.buf.len();
+        self.buf.truncate(complete);
+        truncated
+    }
+
+    // 關閉連線並丟掉緩衝；下次呼叫時重連
+    fn reset(&mut self) {
+        if let Some(s) = self.stream.take() { let _ = s.shutdown(std::net::Shutdown::Both); }
+        self.buf.clear();
+    }
+}
+
+// buf 開頭完整 frame 的總長度
+fn complete_frames(buf: &[u8]) -> usize {
+    let mut at = 0;
+    while buf.len() >= at + 4 {
+        let len = u32::from_be_bytes([buf[at], buf[at + 1], buf[at + 2], buf[at + 3]]) as usize;
+        if len > MAX_FRAME || buf.len() < at + 4 + len { break; }
+        at += 4 + len;
+    }
+    at
+}
+
+fn open_tcp(addr: &str) -> Result<TcpStream> {
+    let sa = addr.to_socket_addrs()?.next().with_context(|| format!("無法解析位址 {addr}"))?;
+    let s  = TcpStream::connect_timeout(&sa, CONNECT_TIMEOUT)?;
+    s.set_nonblocking(true)?;
+    s.set_nodelay(true)?;
+    Ok(s)
+}
+
+fn split_frame(buf: &mut Vec<u8>) -> Result<Option<Vec<u8>>> {
+    if buf.len() < 4 { return Ok(None); }
+    let len = u32::from_be_bytes([buf[0], buf[1], buf[2], buf[3]]) as usize;
+    if len > MAX_FRAME { bail!("frame 過大：{len} bytes"); }
+    if buf.len() < 4 + len { return Ok(None); }
+    let frame = buf[4..4 + len].to_vec();
+    buf.drain(..4 + len);
+    Ok(Some(frame))
+}
+
+fn read_frame(r: &mut impl Read) -> Result<Option<Vec<u8>>> {
+    let mut len = [0u8; 4];
+    match r.read_exact(&mut len) {
+        Ok(()) => {}
+        Err(e) if e.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e.into()),
+    }
+    let len = u32::from_be_bytes(len) as usize;
+    if len > MAX_FRAME { bail!("frame 過大：{len} bytes"); }
+    let mut frame = vec![0u8; len];
+    r.read_exact(&mut frame).context("frame 不完整")?;
+    Ok(Some(frame))
+}
+
+fn write_frame(w: &mut impl Write, result: &proto::FlowResult) -> Result<()> {
+    let bytes = result.encode_to_vec();
+    w.write_all(&(bytes.len() as u32).to_be_bytes())?;
+    w.write_all(&bytes)?;
+    w.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn frame(body: &[u8]) -> Vec<u8> {
+        let mut f = (body.len() as u32).to_be_bytes().to_vec();
+        f.extend_from_slice(body);
+        f
+    }
+
+    fn oversized() -> Vec<u8> {
+        ((MAX_FRAME + 1) as u32).to_be_bytes().to_vec()
+    }
+
+    #[test]
+    fn split_frame_waits_for_complete_frames() {
+        let mut buf = frame(b"abc");
+        buf.extend(frame(b""));
+        buf.extend(&frame(b"xyz")[..5]);
+        assert_eq!(split_frame(&mut buf).unwrap().as_deref(), Some(&b"abc"[..]));
+        assert_eq!(split_frame(&mut buf).unwrap().as_deref(), Some(&b""[..]));
+        // 截斷的 frame 留在 buf，等下一次 recv
+        assert_eq!(split_frame(&mut buf).unwrap(), None);
+        assert_eq!(buf, frame(b"xyz")[..5]);
+
+        let mut header = vec![0, 0];
+        assert_eq!(split_frame(&mut header).unwrap(), None, "長度欄位不足 4 bytes");
+        assert_eq!(header, [0, 0]);
+    }
+
+    #[test]
+    fn split_frame_rejects_oversized_before_buffering() {
+        let mut buf = oversized();
+        let e = split_frame(&mut buf).unwrap_err();
+        assert!(e.to_string().contains("frame 過大"), "{e}");
+
+        // 剛好 MAX_FRAME 不算過大，只是尚未收齊
+        let mut buf = (MAX_FRAME as u32).to_be_bytes().to_vec();
+        assert_eq!(split_frame(&mut buf).unwrap(), None);
+    }
+
+    #[test]
+    fn read_frame_until_eof() {
+        let mut bytes = frame(b"abc");
+        bytes.extend(frame(b"de"));
+        let mut r = Cursor::new(bytes);
+        assert_eq!(read_frame(&mut r).unwrap().as_deref(), Some(&b"abc"[..]));
+        assert_eq!(read_frame(&mut r).unwrap().as_deref(), Some(&b"de"[..]));
+        assert_eq!(read_frame(&mut r).unwrap(), None, "讀完視為結束");
+    }
+
+    #[test]
+    fn read_frame_truncated_and_oversized() {
+        let mut r = Cursor::new(frame(b"abcdef")[..7].to_vec());
+        let e = read_frame(&mut r).unwrap_err();
+        assert!(format!("{e:#}").contains("frame 不完整"), "{e:#}");
+
+        let mut r = Cursor::new(oversized());
+        let e = read_frame(&mut r).unwrap_err();
+        assert!(e.to_string().contains("frame 過大"), "{e}");
+    }
+
+    #[test]
+    fn write_frame_round_trips() {
+        let result = proto::FlowResult { tag_id: 7, tag_name: "t".into(), value: 1.5, ..Default::default() };
+        let mut out = Vec::new();
+        write_frame(&mut out, &result).unwrap();
+        let body = read_frame(&mut Cursor::new(out)).unwrap().unwrap();
+        assert_eq!(body, result.encode_to_vec());
+        let back = proto::FlowResult::decode(body.as_slice()).unwrap();
+        assert_eq!((back.tag_id, back.tag_name.as_str(), back.value), (7, "t", 1.5));
+    }
+
+    #[test]
+    fn complete_frames_stops_at_truncated_or_oversized() {
+        let mut buf = frame(b"ab");
+        buf.extend(frame(b"c"));
+        let whole = buf.len();
+        buf.extend(&frame(b"def")[..4]);
+        assert_eq!(complete_frames(&buf), whole);
+
+        let mut buf = frame(b"ab");
+        buf.extend(oversized());
+        buf.extend([0; 8]);
+        assert_eq!(complete_frames(&buf), 6);
+    }
+}
@@ -0,0 +1,82 @@
+// host/src/flow.rs
+// flow.json 解析（IIoTFlowArchitecture §7.1）
+//
+// 只負責「讀檔 + 結構檢查」，Node 載入與訊息路由交給 dag.rs
+
+use anyhow::{bail, Context, Result};
+use serde::Deserialize;
+use std::collections::HashSet;
+use std::path::Path;
+
+use crate::limits::ExecLimits;
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct FlowDef {
+    #[serde(default)]
+    pub version: String,
+    pub nodes:   Vec<NodeDef>,
+    #[serde(default)]
+    pub edges:   Vec<EdgeDef>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct NodeDef {
+    pub id:    String,
+    pub wasm:  String,
+    #[serde(default)]
+    pub props: serde_json::Value,
+    // Source 接收的 tag 名稱，型別推導用；output 為 any 的 Source 必填
+    #[serde(default)]
+    pub tags:  Vec<String>,
+    // 每次呼叫的執行上限（fuel / deadline-ms）；省略 = 預設值
+    #[serde(default)]
+    pub limits: ExecLimits,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct EdgeDef {
+    pub from:      String,
+    #[serde(default)]
+    pub from_port: u32,
+    pub to:        String,
+    #[serde(default)]
+    pub to_port:   u32,
+    #[serde(default = "default_role")]
+    pub to_role:   String,
+}
+
+fn default_role() -> String { "data".to_string() }
+
+impl FlowDef {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("讀取失敗：{}", path.display()))?;
+        Self::parse(&text).with_context(|| format!("flow.json 格式錯誤：{}", path.display()))
+    }
+
+    pub fn parse(text: &str) -> Result<Self> {
+        let flow: FlowDef = serde_json::from_str(text)?;
+        flow.check()?;
+        Ok(flow)
+    }
+
+    pub fn node(&self, id: &str) -> Option<&NodeDef> {
+        self.nodes.iter().find(|n| n.id == id)
+    }
+
+    // node id 不可重複，edge 兩端都必須指向存在的 node
+    fn check(&self) -> Result<()> {
+        let mut ids = HashSet::new();
+        for n in &self.nodes {
+            if !ids.insert(n.id.as_str()) { bail!("node id 重複：{}", n.id); }
+        }
+        for e in &self.edges {
+            if !ids.contains(e.from.as_str()) { bail!("edge 起點不存在：{}", e.from); }
+            if !ids.contains(e.to.as_str())   { bail!("edge 終點不存在：{}", e.to); }
+        }
+        Ok(())
+    }
+
+}
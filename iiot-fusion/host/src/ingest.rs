@@ -0,0 +1,567 @@
+// host/src/ingest.rs
+// Protocol Driver → Rule Engine 的 TagUpdate 入口（SystemRequirements §1：Drivers ──nng PUSH──▶）
+//
+// nng PULL socket 的 listener 端，直接實作 SP（Scalability Protocols）wire format，
+// 不需要 libnng / cmake，Driver 端用任何 nng / nanomsg 的 PUSH socket dial 進來即可：
+//
+//   握手（雙方各送 8 bytes）：00 'S' 'P' 00 | protocol u16 BE | 00 00
+//       PUSH = 0x50，PULL = 0x51（我們只接受 PUSH）
+//   tcp://  每筆訊息：u64 BE 長度 + payload
+//   ipc://  每筆訊息：0x01 + u64 BE 長度 + payload
+//
+// 支援範圍（nng 的 pull0 listener 的子集）：
+//   - 只 listen，不 dial；transport 只有 tcp://（限 loopback）與 ipc://，沒有 tls+tcp / ws / inproc
+//   - 對端必須是 push0（protocol 0x50）；握手後 PULL 不再送出任何 bytes
+//   - PUSH/PULL v0 的訊息沒有 protocol header（不像 REQ/REP、SURVEYOR 帶 request id / backtrace），
+//     SP 本身也沒有 keepalive 訊息：nng 的 tcp / ipc transport 都只有上面的 framing，
+//     斷線由 OS 回報（read 得到 EOF / error）；ipc 的訊息類型只接受 0x01，其他值視為協定錯誤
+//   - 單筆訊息上限 MAX_MSG；過大或截斷的訊息會中止該條連線（只影響該 Driver）
+//   與真正的 nng 互通的測試：tests 的 nngcat_push（需要 PATH 上有 nngcat，否則略過）
+//
+// Backpressure：每條連線一個 reader thread，訊息放進 bounded queue；
+// queue 滿時 reader 阻塞、不再讀 socket，壓力經由 TCP / unix socket 傳回 Driver 的 PUSH
+// 結束（Drop）時關閉 listener、shutdown 每條連線並 join 所有 reader thread
+//
+// AsyncPullSocket（tokio Host，async_endpoint.rs）：同樣的 wire format，每條連線一個 tokio task，
+// queue 滿時 send().await 暫停該 task，backpressure 相同
+
+use anyhow::{bail, Context, Result};
+use std::io::{ErrorKind, Read, Write};
+use std::net::{Shutdown, TcpListener, TcpStream, ToSocketAddrs};
+use std::os::unix::fs::FileTypeExt;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::mpsc::{sync_channel, Receiver, SyncSender, TryRecvError};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::sync::mpsc;
+use tokio::task::{JoinHandle, JoinSet};
+
+const SP_PUSH: u16 = 0x50;
+const SP_PULL: u16 = 0x51;
+const MAX_MSG: u64 = 16 * 1024 * 1024;
+const ACCEPT_POLL: Duration = Duration::from_millis(20);
+
+#[derive(Debug, Default)]
+pub struct IngestStats {
+    pub connections: AtomicU64,
+    pub received:    AtomicU64,
+    pub rejected:    AtomicU64, // 握手失敗 / 訊息過大的連線
+}
+
+pub struct PullSocket {
+    pub url:   String,
+    pub stats: Arc<IngestStats>,
+    rx:        Receiver<Vec<u8>>,
+    closed:    Arc<AtomicBool>,  // Drop 時通知 accept thread 結束，釋放位址
+    accept:    Option<thread::JoinHandle<()>>,
+    ipc_path:  Option<PathBuf>,  // 結束時刪除 socket 檔
+}
+
+impl PullSocket {
+    /// 在 url 上 listen；tcp:// 只允許 loopback（Driver 與 Rule Engine 同機）
+    pub fn listen(url: &str, capacity: usize) -> Result<Self> {
+        let (tx, rx) = sync_channel(capacity.max(1));
+        let stats  = Arc::new(IngestStats::default());
+        let closed = Arc::new(AtomicBool::new(false));
+        let ctx    = Accept { url: url.to_string(), tx, stats: Arc::clone(&stats), closed: Arc::clone(&closed) };
+
+        let (accept, ipc_path) = if let Some(path) = url.strip_prefix("ipc://") {
+            let path = PathBuf::from(path);
+            remove_stale_socket(&path).with_context(|| format!("nng PULL 無法 listen：{url}"))?;
+            let listener = UnixListener::bind(&path)
+                .with_context(|| format!("nng PULL 無法 listen：{url}"))?;
+            listener.set_nonblocking(true)?;
+            let accept = ctx.spawn(true, move || listener.accept()
+                .and_then(|(s, _)| { s.set_nonblocking(false)?; Ok(s) }))?;
+            (Some(accept), Some(path))
+        } else if let Some(addr) = url.strip_prefix("tcp://") {
+            let sa = addr.to_socket_addrs()?.next().with_context(|| format!("無法解析位址 {addr}"))?;
+            if !sa.ip().is_loopback() { bail!("nng PULL 只允許 listen 在 localhost：{url}"); }
+            let listener = TcpListener::bind(sa)
+                .with_context(|| format!("nng PULL 無法 listen：{url}"))?;
+            listener.set_nonblocking(true)?;
+            let accept = ctx.spawn(false, move || listener.accept()
+                .and_then(|(s, _)| { s.set_nonblocking(false)?; s.set_nodelay(true)?; Ok(s) }))?;
+            (Some(accept), None)
+        } else {
+            bail!("nng PULL 只支援 ipc:// 或 tcp://：{url}");
+        };
+
+        Ok(PullSocket { url: url.to_string(), stats, rx, closed, accept, ipc_path })
+    }
+
+    /// 非阻塞：取出 queue 內已到達的訊息，最多 max 筆
+    pub fn try_recv(&self, max: usize) -> Vec<Vec<u8>> {
+        let mut msgs = Vec::new();
+        while msgs.len() < max {
+            match self.rx.try_recv() {
+                Ok(m) => msgs.push(m),
+                Err(TryRecvError::Empty | TryRecvError::Disconnected) => break,
+            }
+        }
+        msgs
+    }
+}
+
+impl Drop for PullSocket {
+    fn drop(&mut self) {
+        self.closed.store(true, Ordering::Relaxed);
+        // 先放掉 Receiver：queue 滿而阻塞在 send 的 reader 才會結束
+        drop(std::mem::replace(&mut self.rx, sync_channel(1).1));
+        if let Some(accept) = self.accept.take() { let _ = accept.join(); }
+        if let Some(path) = &self.ipc_path { std::fs::remove_file(path).ok(); }
+    }
+}
+
+// ipc:// 的位址上已有檔案：只刪除 socket（上次沒清掉的），其他檔案視為設定錯誤
+fn remove_stale_socket(path: &Path) -> Result<()> {
+    match std::fs::symlink_metadata(path) {
+        Ok(meta) if meta.file_type().is_socket() => Ok(std::fs::remove_file(path)?),
+        Ok(_) => bail!("{} 已存在且不是 socket，不會覆蓋", path.display()),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+// 可以從別的 thread shutdown 的連線（Drop 時中斷阻塞在 read 的 reader）
+trait Conn: Read + Write + Send + Sized + 'static {
+    fn try_clone(&self) -> std::io::Result<Self>;
+    fn shutdown(&self);
+}
+
+impl Conn for TcpStream {
+    fn try_clone(&self) -> std::io::Result<Self> { TcpStream::try_clone(self) }
+    fn shutdown(&self) { let _ = TcpStream::shutdown(self, Shutdown::Both); }
+}
+
+impl Conn for UnixStream {
+    fn try_clone(&self) -> std::io::Result<Self> { UnixStream::try_clone(self) }
+    fn shutdown(&self) { let _ = UnixStream::shutdown(self, Shutdown::Both); }
+}
+
+struct Accept {
+    url:    String,
+    tx:     SyncSender<Vec<u8>>,
+    stats:  Arc<IngestStats>,
+    closed: Arc<AtomicBool>,
+}
+
+impl Accept {
+    // accept 迴圈（非阻塞 listener，定期檢查 closed）：每條 Driver 連線一個 reader thread；
+    // closed 後 shutdown 所有連線並 join reader
+    fn spawn<S, F>(self, ipc: bool, mut accept: F) -> Result<thread::JoinHandle<()>>
+    where
+        S: Conn,
+        F: FnMut() -> std::io::Result<S> + Send + 'static,
+    {
+        let name = format!("nng-pull {}", self.url);
+        let handle = thread::Builder::new().name(name).spawn(move || {
+            let mut readers: Vec<(S, thread::JoinHandle<()>)> = Vec::new();
+            while !self.closed.load(Ordering::Relaxed) {
+                readers.retain(|(_, r)| !r.is_finished());
+                let stream = match accept() {
+                    Ok(s) => s,
+                    Err(e) => {
+                        if e.kind() != ErrorKind::WouldBlock {
+                            eprintln!("⚠️  nng PULL {} accept 失敗：{e}", self.url);
+                        }
+                        thread::sleep(ACCEPT_POLL);
+                        continue;
+                    }
+                };
+                let Ok(peer) = stream.try_clone() else { continue; };
+                self.stats.connections.fetch_add(1, Ordering::Relaxed);
+                let (tx, stats, url) = (self.tx.clone(), Arc::clone(&self.stats), self.url.clone());
+                let reader = thread::spawn(move || {
+                    if let Err(e) = serve(stream, ipc, &tx, &stats) {
+                        stats.rejected.fetch_add(1, Ordering::Relaxed);
+                        eprintln!("⚠️  nng PULL {url} 連線中止：{e:#}");
+                    }
+                });
+                readers.push((peer, reader));
+            }
+            for (peer, _) in &readers { peer.shutdown(); }
+            for (_, reader) in readers { let _ = reader.join(); }
+        })?;
+        Ok(handle)
+    }
+}
+
+// 單條連線：握手後持續讀訊息，直到 Driver 斷線（回傳 Ok）或協定錯誤（回傳 Err）
+fn serve(mut s: impl Read + Write, ipc: bool, tx: &SyncSender<Vec<u8>>, stats: &IngestStats) -> Result<()> {
+    let mut ours = [0u8, b'S', b'P', 0, 0, 0, 0, 0];
+    ours[4..6].copy_from_slice(&SP_PULL.to_be_bytes());
+    s.write_all(&ours)?;
+
+    let mut peer = [0u8; 8];
+    s.read_exact(&mut peer).context("握手未完成")?;
+    if peer[..4] != [0, b'S', b'P', 0] { bail!("不是 SP 協定的連線"); }
+    let proto = u16::from_be_bytes([peer[4], peer[5]]);
+    if proto != SP_PUSH { bail!("對端 protocol 0x{proto:02x} 不是 PUSH"); }
+
+    loop {
+        if ipc {
+            let mut kind = [0u8; 1];
+            match s.read_exact(&mut kind) {
+                Ok(()) => {}
+                Err(e) if e.kind() == ErrorKind::UnexpectedEof => return Ok(()),
+                Err(e) => return Err(e.into()),
+            }
+            if kind[0] != 1 { bail!("未知的 ipc 訊息類型 {}", kind[0]); }
+        }
+        let mut len = [0u8; 8];
+        match s.read_exact(&mut len) {
+            Ok(()) => {}
+            Err(e) if e.kind() == ErrorKind::UnexpectedEof && !ipc => return Ok(()),
+            Err(e) => return Err(e.into()),
+        }
+        let len = u64::from_be_bytes(len);
+        if len > MAX_MSG { bail!("訊息過大：{len} bytes"); }
+        let mut msg = vec![0u8; len as usize];
+        s.read_exact(&mut msg)?;
+        stats.received.fetch_add(1, Ordering::Relaxed);
+        // queue 滿時在這裡阻塞 → 不再讀 socket → Driver 端 PUSH 受到 backpressure
+        if tx.send(msg).is_err() { return Ok(()); }
+    }
+}
+
+// ════════════════════════════════════════════════════════════════════════════
+// async 版（tokio）
+// ════════════════════════════════════════════════════════════════════════════
+
+pub struct AsyncPullSocket {
+    pub url:   String,
+    pub stats: Arc<IngestStats>,
+    accept:    JoinHandle<()>,  // Drop 時 abort：listener 與各連線的 JoinSet 一起 drop，連線 task 全部中止
+    ipc_path:  Option<PathBuf>,
+}
+
+impl AsyncPullSocket {
+    /// 同 PullSocket::listen，訊息從回傳的 Receiver 取出；必須在 tokio runtime 內呼叫
+    pub fn listen(url: &str, capacity: usize) -> Result<(Self, mpsc::Receiver<Vec<u8>>)> {
+        let (tx, rx) = mpsc::channel(capacity.max(1));
+        let stats = Arc::new(IngestStats::default());
+        let ctx   = AsyncAccept { url: url.to_string(), tx, stats: Arc::clone(&stats) };
+
+        let (accept, ipc_path) = if let Some(path) = url.strip_prefix("ipc://") {
+            let path = PathBuf::from(path);
+            remove_stale_socket(&path).with_context(|| format!("nng PULL 無法 listen：{url}"))?;
+            let listener = tokio::net::UnixListener::bind(&path)
+                .with_context(|| format!("nng PULL 無法 listen：{url}"))?;
+            let accept = tokio::spawn(async move {
+                let mut readers = JoinSet::new();
+                loop {
+                    match listener.accept().await {
+                        Ok((s, _)) => ctx.serve(&mut readers, s, true),
+                        Err(e)     => ctx.failed(e).await,
+                    }
+                }
+            });
+            (accept, Some(path))
+        } else if let Some(addr) = url.strip_prefix("tcp://") {
+            let sa = addr.to_socket_addrs()?.next().with_context(|| format!("無法解析位址 {addr}"))?;
+            if !sa.ip().is_loopback() { bail!("nng PULL 只允許 listen 在 localhost：{url}"); }
+            let listener = std::net::TcpListener::bind(sa)
+                .with_context(|| format!("nng PULL 無法 listen：{url}"))?;
+            listener.set_nonblocking(true)?;
+            let listener = tokio::net::TcpListener::from_std(listener)?;
+            let accept = tokio::spawn(async move {
+                let mut readers = JoinSet::new();
+                loop {
+                    match listener.accept().await {
+                        Ok((s, _)) => { s.set_nodelay(true).ok(); ctx.serve(&mut readers, s, false) }
+                        Err(e)     => ctx.failed(e).await,
+                    }
+                }
+            });
+            (accept, None)
+        } else {
+            bail!("nng PULL 只支援 ipc:// 或 tcp://：{url}");
+        };
+
+        Ok((AsyncPullSocket { url: url.to_string(), stats, accept, ipc_path }, rx))
+    }
+}
+
+impl Drop for AsyncPullSocket {
+    fn drop(&mut self) {
+        self.accept.abort();
+        if let Some(path) = &self.ipc_path { std::fs::remove_file(path).ok(); }
+    }
+}
+
+struct AsyncAccept {
+    url:   String,
+    tx:    mpsc::Sender<Vec<u8>>,
+    stats: Arc<IngestStats>,
+}
+
+impl AsyncAccept {
+    // 每條 Driver 連線一個 task（放在 accept task 的 JoinSet，accept 結束時一起 abort）
+    fn serve<S: AsyncRead + AsyncWrite + Unpin + Send + 'static>(&self, readers: &mut JoinSet<()>,
+                                                                 stream: S, ipc: bool) {
+        while readers.try_join_next().is_some() {}
+        self.stats.connections.fetch_add(1, Ordering::Relaxed);
+        let (tx, stats, url) = (self.tx.clone(), Arc::clone(&self.stats), self.url.clone());
+        readers.spawn(async move {
+            if let Err(e) = serve_async(stream, ipc, &tx, &stats).await {
+                stats.rejected.fetch_add(1, Ordering::Relaxed);
+                eprintln!("⚠️  nng PULL {url} 連線中止：{e:#}");
+            }
+        });
+    }
+
+    async fn failed(&self, e: std::io::Error) {
+        eprintln!("⚠️  nng PULL {} accept 失敗：{e}", self.url);
+        tokio::time::sleep(ACCEPT_POLL).await;
+    }
+}
+
+// 同 serve
+async fn serve_async(mut s: impl AsyncRead + AsyncWrite + Unpin, ipc: bool, tx: &mpsc::Sender<Vec<u8>>,
+                     stats: &IngestStats) -> Result<()> {
+    let mut ours = [0u8, b'S', b'P', 0, 0, 0, 0, 0];
+    ours[4..6].copy_from_slice(&SP_PULL.to_be_bytes());
+    s.write_all(&ours).await?;
+
+    let mut peer = [0u8; 8];
+    s.read_exact(&mut peer).await.context("握手未完成")?;
+    if peer[..4] != [0, b'S', b'P', 0] { bail!("不是 SP 協定的連線"); }
+    let proto = u16::from_be_bytes([peer[4], peer[5]]);
+    if proto != SP_PUSH { bail!("對端 protocol 0x{proto:02x} 不是 PUSH"); }
+
+    loop {
+        if ipc {
+            match s.read_u8().await {
+                Ok(1) => {}
+                Ok(kind) => bail!("未知的 ipc 訊息類型 {kind}"),
+                Err(e) if e.kind() == ErrorKind::UnexpectedEof => return Ok(()),
+                Err(e) => return Err(e.into()),
+            }
+        }
+        let len = match s.read_u64().await {
+            Ok(len) => len,
+            Err(e) if e.kind() == ErrorKind::UnexpectedEof && !ipc => return Ok(()),
+            Err(e) => return Err(e.into()),
+        };
+        if len > MAX_MSG { bail!("訊息過大：{len} bytes"); }
+        let mut msg = vec![0u8; len as usize];
+        s.read_exact(&mut msg).await?;
+        stats.received.fetch_add(1, Ordering::Relaxed);
+        // queue 滿時在這裡等待 → 不再讀 socket → Driver 端 PUSH 受到 backpressure
+        if tx.send(msg).await.is_err() { return Ok(()); }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::process::Command;
+
+    // nng 的 push0 dial 進來時送出的握手（nng src/sp/transport/{tcp,ipc}）
+    const NNG_PUSH_HELLO: [u8; 8] = [0, b'S', b'P', 0, 0, 0x50, 0, 0];
+    const NNG_PULL_HELLO: [u8; 8] = [0, b'S', b'P', 0, 0, 0x51, 0, 0];
+
+    fn tcp_frame(payload: &[u8]) -> Vec<u8> {
+        let mut f = (payload.len() as u64).to_be_bytes().to_vec();
+        f.extend_from_slice(payload);
+        f
+    }
+
+    fn ipc_frame(payload: &[u8]) -> Vec<u8> {
+        let mut f = vec![1u8];
+        f.extend(tcp_frame(payload));
+        f
+    }
+
+    // 對端送出 wire 後關閉寫入端；回傳 (serve 結果, 收到的訊息, PULL 送回的 bytes)
+    fn run(ipc: bool, wire: Vec<u8>) -> (Result<()>, Vec<Vec<u8>>, Vec<u8>) {
+        let (ours, mut peer) = UnixStream::pair().unwrap();
+        let (tx, rx) = sync_channel(64);
+        let stats = IngestStats::default();
+        let writer = thread::spawn(move || {
+            peer.write_all(&wire).unwrap();
+            peer.shutdown(Shutdown::Write).unwrap();
+            drain(&mut peer)
+        });
+        let result = serve(ours, ipc, &tx, &stats);
+        let back = writer.join().unwrap();
+        let msgs: Vec<Vec<u8>> = rx.try_iter().collect();
+        assert_eq!(stats.received.load(Ordering::Relaxed), msgs.len() as u64);
+        (result, msgs, back)
+    }
+
+    // 讀到對端關閉；對端關閉時還有未讀的資料會得到 ECONNRESET，也視為關閉
+    fn drain(s: &mut UnixStream) -> Vec<u8> {
+        let mut back = Vec::new();
+        let mut buf = [0u8; 64];
+        loop {
+            match s.read(&mut buf) {
+                Ok(0) => return back,
+                Ok(n) => back.extend_from_slice(&buf[..n]),
+                Err(e) if e.kind() == ErrorKind::ConnectionReset => return back,
+                Err(e) => panic!("讀取失敗：{e}"),
+            }
+        }
+    }
+
+    fn temp_path(name: &str) -> PathBuf {
+        static SEQ: AtomicU64 = AtomicU64::new(0);
+        let n = SEQ.fetch_add(1, Ordering::Relaxed);
+        std::env::temp_dir().join(format!("iiot-ingest-{}-{n}-{name}", std::process::id()))
+    }
+
+    #[test]
+    fn handshake_and_tcp_framing() {
+        let mut wire = NNG_PUSH_HELLO.to_vec();
+        wire.extend(tcp_frame(b"first"));
+        wire.extend(tcp_frame(b""));
+        wire.extend(tcp_frame(b"third"));
+        let (result, msgs, back) = run(false, wire);
+        result.unwrap();
+        assert_eq!(back, NNG_PULL_HELLO, "握手後 PULL 不應再送出任何 bytes");
+        assert_eq!(msgs, vec![b"first".to_vec(), Vec::new(), b"third".to_vec()]);
+    }
+
+    #[test]
+    fn ipc_framing() {
+        let mut wire = NNG_PUSH_HELLO.to_vec();
+        wire.extend(ipc_frame(b"a"));
+        wire.extend(ipc_frame(b"bc"));
+        let (result, msgs, _) = run(true, wire);
+        result.unwrap();
+        assert_eq!(msgs, vec![b"a".to_vec(), b"bc".to_vec()]);
+    }
+
+    #[test]
+    fn rejects_other_protocols() {
+        // REQ（0x30）與 PULL 自己（0x51）都不是 PUSH
+        for proto in [0x30u8, 0x51] {
+            let (result, msgs, _) = run(false, vec![0, b'S', b'P', 0, 0, proto, 0, 0]);
+            assert!(result.unwrap_err().to_string().contains("不是 PUSH"));
+            assert!(msgs.is_empty());
+        }
+        let (result, _, _) = run(false, b"GET / HTTP/1.1\r\n".to_vec());
+        assert!(result.unwrap_err().to_string().contains("不是 SP"));
+    }
+
+    #[test]
+    fn incomplete_handshake() {
+        let (result, _, _) = run(false, NNG_PUSH_HELLO[..5].to_vec());
+        assert!(format!("{:#}", result.unwrap_err()).contains("握手未完成"));
+    }
+
+    #[test]
+    fn oversized_message_aborts_connection() {
+        let mut wire = NNG_PUSH_HELLO.to_vec();
+        wire.extend(tcp_frame(b"ok"));
+        wire.extend((MAX_MSG + 1).to_be_bytes());
+        let (result, msgs, _) = run(false, wire);
+        assert!(result.unwrap_err().to_string().contains("訊息過大"));
+        assert_eq!(msgs, vec![b"ok".to_vec()]);
+    }
+
+    #[test]
+    fn truncated_message_is_an_error() {
+        let mut wire = NNG_PUSH_HELLO.to_vec();
+        let mut frame = tcp_frame(b"0123456789");
+        frame.truncate(12);
+        wire.extend(frame);
+        let (result, msgs, _) = run(false, wire);
+        assert!(result.is_err());
+        assert!(msgs.is_empty());
+
+        // ipc：只有類型 byte、沒有長度
+        let mut wire = NNG_PUSH_HELLO.to_vec();
+        wire.push(1);
+        assert!(run(true, wire).0.is_err());
+    }
+
+    #[test]
+    fn unknown_ipc_message_type() {
+        let mut wire = NNG_PUSH_HELLO.to_vec();
+        wire.push(2);
+        wire.extend(tcp_frame(b"x"));
+        let (result, _, _) = run(true, wire);
+        assert!(result.unwrap_err().to_string().contains("未知的 ipc 訊息類型"));
+    }
+
+    #[test]
+    fn ipc_path_only_replaces_sockets() {
+        let file = temp_path("plain");
+        std::fs::write(&file, b"keep me").unwrap();
+        let err = PullSocket::listen(&format!("ipc://{}", file.display()), 4).err().unwrap();
+        assert!(format!("{err:#}").contains("不是 socket"));
+        assert_eq!(std::fs::read(&file).unwrap(), b"keep me");
+        std::fs::remove_file(&file).unwrap();
+
+        // 上次留下的 socket 檔可以取代
+        let sock = temp_path("sock");
+        drop(UnixListener::bind(&sock).unwrap());
+        assert!(sock.exists());
+        let pull = PullSocket::listen(&format!("ipc://{}", sock.display()), 4).unwrap();
+        drop(pull);
+        assert!(!sock.exists());
+    }
+
+    #[test]
+    fn drop_stops_readers_and_closes_connections() {
+        let sock = temp_path("drop");
+        let url  = format!("ipc://{}", sock.display());
+        let pull = PullSocket::listen(&url, 1).unwrap();
+
+        // 一條閒置的連線 + 一條 queue 滿而阻塞在 send 的連線
+        let mut idle = UnixStream::connect(&sock).unwrap();
+        idle.write_all(&NNG_PUSH_HELLO).unwrap();
+        let mut busy = UnixStream::connect(&sock).unwrap();
+        busy.write_all(&NNG_PUSH_HELLO).unwrap();
+        for _ in 0..3 { busy.write_all(&ipc_frame(b"x")).unwrap(); }
+        let t0 = std::time::Instant::now();
+        while pull.stats.received.load(Ordering::Relaxed) < 2 {
+            assert!(t0.elapsed() < Duration::from_secs(5), "reader 沒有收到訊息");
+            thread::sleep(Duration::from_millis(5));
+        }
+
+        drop(pull);
+        // reader 已 join、連線已 shutdown：對端讀到 EOF（握手之後沒有其他 bytes）
+        for s in [&mut idle, &mut busy] {
+            s.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+            assert_eq!(drain(s), NNG_PULL_HELLO);
+        }
+        assert!(!sock.exists());
+    }
+
+    #[test]
+    fn tcp_listen_is_loopback_only() {
+        assert!(PullSocket::listen("tcp://0.0.0.0:0", 1).is_err());
+        assert!(PullSocket::listen("inproc://x", 1).is_err());
+    }
+
+    // 與真正的 nng 互通：PATH 上有 nngcat（nng 附帶的工具）時，以 push0 dial 進來送一筆
+    #[test]
+    fn nngcat_push() {
+        if Command::new("nngcat").arg("--version").output().is_err() {
+            eprintln!("略過：PATH 上沒有 nngcat");
+            return;
+        }
+        let sock = temp_path("nngcat");
+        let url  = format!("ipc://{}", sock.display());
+        let pull = PullSocket::listen(&url, 4).unwrap();
+        let status = Command::new("nngcat")
+            .args(["--push", "--dial", &url, "--data", "hello-from-nng", "--delay", "0"])
+            .status().unwrap();
+        assert!(status.success());
+        let t0 = std::time::Instant::now();
+        let msgs = loop {
+            let msgs = pull.try_recv(4);
+            if !msgs.is_empty() || t0.elapsed() > Duration::from_secs(5) { break msgs; }
+            thread::sleep(Duration::from_millis(10));
+        };
+        assert_eq!(msgs, vec![b"hello-from-nng".to_vec()]);
+    }
+}
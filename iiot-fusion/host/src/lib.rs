@@ -0,0 +1,1220 @@
+// host/src/lib.rs
+// IIoT Flow Fusion Host（函式庫）
+//
+// 用兩個 bindgen! 分別對應兩個 WIT world（iiot:flow@0.2.0，多 port）：
+//   FlowNode          ← flow-node          (Source / Node A/B/C)
+//   FlowNodeWithHost  ← flow-node-with-host (Sink)
+// fused-pipeline world 有兩個版本，依 artifact 的 export 名稱選擇（FusedApi）：
+//   0.2.0  export iiot:flow/pipeline@0.2.0：run-batch / nodes / trace import（各 Node 的執行上限）
+//   0.1.0  inline 的 pipeline export（凍結）：Host 逐筆呼叫 run 代替 run-batch，執行上限只有整條 flow 一層
+// state-migration（flow-node-migratable）是選擇性 export，instantiate 後才查詢（state.rs）
+//
+// fused-pipeline 兩個版本都另有 async 版的 bindings（AsyncPipeline：call_*_async，tokio Host 用）
+//
+// Host Function (host-api) 透過 Linker::func_wrap 手動掛載（async 版用 func_wrap_async）
+// Deploy Pipeline（flow / validate / cast / props）也放在這裡，
+// 供 iiot-flow-host 與 iiot-flow-fusion 兩個 binary 共用
+
+use anyhow::{bail, Context, Result};
+use prost::Message as _;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::Path;
+use std::sync::{Arc, RwLock};
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::time::{Duration, Instant};
+use wasmtime::component::{bindgen, Component, Instance, Linker, ResourceTable, TypedFunc};
+use wasmtime::{ AsContextMut, Engine, Store };
+use wasmtime_wasi::{WasiCtx, WasiCtxBuilder, WasiView, WasiCtxView};
+use tokio::io::AsyncWriteExt as _;
+
+// ── bindings for flow-node world (Source, Node A/B/C) ────────────────────────
+bindgen!({
+    world: "flow-node",
+    path:  "../wit/0.2.0",
+});
+
+// ── bindings for flow-node-with-host world (Sink) ────────────────────────────
+mod sink_bindings {
+    wasmtime::component::bindgen!({
+        world: "flow-node-with-host",
+        path:  "../wit/0.2.0",
+        with: {
+            "iiot:flow/types@0.2.0":           crate::iiot::flow::types,
+            "iiot:flow/node-descriptor@0.2.0": crate::iiot::flow::node_descriptor,
+        }
+    });
+}
+
+mod fused_bindings {
+    wasmtime::component::bindgen!({
+        world: "fused-pipeline",
+        path:  "../wit/0.2.0",
+    });
+}
+
+mod fused_async_bindings {
+    wasmtime::component::bindgen!({
+        world:   "fused-pipeline",
+        path:    "../wit/0.2.0",
+        exports: { default: async },
+    });
+}
+
+// ── iiot:flow@0.1.0 的 fused-pipeline（舊 artifact）──────────────────────────
+mod legacy_bindings {
+    wasmtime::component::bindgen!({
+        world: "fused-pipeline",
+        path:  "../wit",
+    });
+}
+
+mod legacy_async_bindings {
+    wasmtime::component::bindgen!({
+        world:   "fused-pipeline",
+        path:    "../wit",
+        exports: { default: async },
+    });
+}
+
+pub mod aot;
+pub mod async_endpoint;
+pub mod async_runtime;
+pub mod cast;
+pub mod crashdump;
+pub mod dag;
+pub mod endpoint;
+pub mod flow;
+pub mod ingest;
+pub mod limits;
+pub mod manager;
+pub mod manifest;
+pub mod pool;
+pub mod props;
+pub mod redeploy;
+pub mod router;
+pub mod runtime;
+pub mod scheduler;
+pub mod snapshot;
+pub mod state;
+pub mod supervisor;
+pub mod tags;
+pub mod validate;
+
+use aot::CompileCache;
+use crashdump::{CrashInput, PostMortem, Scene};
+use limits::{Budget, ExecLimits};
+use state::Envelope;
+use tags::TagChanges;
+pub use fused_bindings::exports::iiot::flow::pipeline::RawUpdate;
+use iiot::flow::node_descriptor::NodeSpec;
+use iiot::flow::types::{FlowMsg, PortMsgs, ValueKind};
+
+// ════════════════════════════════════════════════════════════════════════════
+// Tag Registry
+// ════════════════════════════════════════════════════════════════════════════
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TagMeta {
+    pub name:          String,
+    pub unit:          String,
+    pub mqtt_topic:    String,
+    pub historian_tag: String,
+    pub alarm_group:   String,
+    pub eng_low:       f64,
+    pub eng_high:      f64,
+    pub value_kind:    ValueKind, // Source 的 any output 依此推導型別
+    pub extra:         BTreeMap<String, String>, // tag 設定檔的自訂屬性（tags.rs）
+}
+
+impl TagMeta {
+    /// 只有名稱與型別，其餘屬性空白
+    pub fn new(name: &str, value_kind: ValueKind) -> Self {
+        TagMeta {
+            name: name.to_string(), unit: String::new(),
+            mqtt_topic: String::new(), historian_tag: String::new(), alarm_group: String::new(),
+            eng_low: 0.0, eng_high: 0.0, value_kind, extra: BTreeMap::new(),
+        }
+    }
+
+    /// Tag Registry 沒有的 tag：以 TagUpdate 的內容建立
+    pub fn from_update(tu: &proto::TagUpdate) -> Self {
+        let kind = if tu.f64_val.is_some() { ValueKind::F64Val } else { ValueKind::Any };
+        TagMeta { unit: tu.unit.clone(), ..TagMeta::new(&tu.tag_id_str, kind) }
+    }
+}
+
+pub struct TagRegistry {
+    tags:       HashMap<u32, TagMeta>,
+    name_to_id: HashMap<String, u32>,
+    next_id:    u32,
+    configured: HashSet<u32>, // 由 tag 設定檔載入的 tag
+    generation: u64,          // 每次 apply 有變更時 +1（manager.rs：view 依此同步）
+}
+
+impl Default for TagRegistry {
+    fn default() -> Self { Self::new() }
+}
+
+impl TagRegistry {
+    pub fn new() -> Self {
+        TagRegistry {
+            tags: HashMap::new(), name_to_id: HashMap::new(), next_id: 1,
+            configured: HashSet::new(), generation: 0,
+        }
+    }
+
+    /// 套用 tag 設定檔的內容（tags.rs）：已存在的 tag 更新 meta、tag_id 不變；
+    /// 從設定檔移除的 tag 一併移除（tag_id 不再分配）；執行期才出現的 tag 不受影響
+    pub fn apply(&mut self, tags: &[TagMeta]) -> TagChanges {
+        let mut changes = TagChanges::default();
+        let mut configured = HashSet::with_capacity(tags.len());
+        for meta in tags {
+            let id = match self.name_to_id.get(&meta.name) {
+                Some(&id) => {
+                    if self.tags[&id] != *meta {
+                        self.tags.insert(id, meta.clone());
+                        changes.updated += 1;
+                    }
+                    id
+                }
+                None => {
+                    changes.added += 1;
+                    self.get_or_create(&meta.name, meta.clone())
+                }
+            };
+            configured.insert(id);
+        }
+        for id in self.configured.difference(&configured) {
+            if let Some(meta) = self.tags.remove(id) {
+                self.name_to_id.remove(&meta.name);
+                changes.removed += 1;
+            }
+        }
+        self.configured = configured;
+        if !changes.is_empty() { self.generation += 1; }
+        changes
+    }
+
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    /// view 內的 tag 依 from 更新 meta，from 已移除的 tag 一併移除（manager.rs：重新載入後同步）
+    pub fn refresh(&mut self, from: &TagRegistry) {
+        let names: Vec<String> = self.name_to_id.keys().cloned().collect();
+        for name in names {
+            let id = self.name_to_id[&name];
+            match from.entry(&name) {
+                Some((from_id, meta)) if from_id == id => { self.tags.insert(id, meta.clone()); }
+                _ => {
+                    self.name_to_id.remove(&name);
+                    self.tags.remove(&id);
+                }
+            }
+        }
+        self.generation = from.generation;
+    }
+
+    pub fn get_or_create(&mut self, name: &str, meta: TagMeta) -> u32 {
+        if let Some(&id) = self.name_to_id.get(name) { return id; }
+        let id = self.next_id;
+        self.next_id += 1;
+        self.name_to_id.insert(name.to_string(), id);
+        self.tags.insert(id, meta);
+        id
+    }
+
+    pub fn len(&self) -> usize {
+        self.tags.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tags.is_empty()
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.name_to_id.contains_key(name)
+    }
+
+    /// 只含 keep 接受的 tag、tag_id 不變的副本（manager.rs：各 flow 的 registry view）
+    pub fn view(&self, keep: impl Fn(&str) -> bool) -> TagRegistry {
+        let mut view = TagRegistry { generation: self.generation, ..TagRegistry::new() };
+        for (name, &id) in self.name_to_id.iter().filter(|(name, _)| keep(name)) {
+            view.insert(id, name, self.tags[&id].clone());
+        }
+        view
+    }
+
+    /// 加入 tag_id 已在別的 registry 分配好的 tag（view 同步用）
+    pub fn insert(&mut self, id: u32, name: &str, meta: TagMeta) {
+        self.name_to_id.insert(name.to_string(), id);
+        self.tags.insert(id, meta);
+        self.next_id = self.next_id.max(id + 1);
+    }
+
+    /// tag 名稱與 meta（view 同步用）
+    pub fn entry(&self, name: &str) -> Option<(u32, &TagMeta)> {
+        let &id = self.name_to_id.get(name)?;
+        Some((id, &self.tags[&id]))
+    }
+
+    pub fn get_attr(&self, tag_id: u32, key: &str) -> Option<String> {
+        let m = self.tags.get(&tag_id)?;
+        match key {
+            "name"          => Some(m.name.clone()),
+            "unit"          => Some(m.unit.clone()),
+            "mqtt_topic"    => Some(m.mqtt_topic.clone()),
+            "historian_tag" => Some(m.historian_tag.clone()),
+            "alarm_group"   => Some(m.alarm_group.clone()),
+            _               => m.extra.get(key).cloned(),
+        }
+    }
+
+    pub fn get_eng_range(&self, tag_id: u32) -> Option<(f64, f64)> {
+        let m = self.tags.get(&tag_id)?;
+        Some((m.eng_low, m.eng_high))
+    }
+
+    pub fn value_kind(&self, name: &str) -> Option<ValueKind> {
+        let id = self.name_to_id.get(name)?;
+        Some(self.tags[id].value_kind)
+    }
+}
+
+// ════════════════════════════════════════════════════════════════════════════
+// msg_id Allocator
+// ════════════════════════════════════════════════════════════════════════════
+
+static MSG_COUNTER: AtomicU32 = AtomicU32::new(1);
+
+pub fn next_msg_id() -> u32 {
+    loop {
+        let id = MSG_COUNTER.fetch_add(1, Ordering::Relaxed);
+        if id != 0 { return id; }
+    }
+}
+
+// ════════════════════════════════════════════════════════════════════════════
+// Store State
+// ════════════════════════════════════════════════════════════════════════════
+
+pub struct HostState {
+    wasi:     WasiCtx,
+    table:    ResourceTable,
+    registry: Arc<RwLock<TagRegistry>>,
+    budget:   Budget, // 執行上限（limits.rs）
+    scene:    Scene,  // 最近一次失敗的現場（crashdump.rs）
+}
+
+impl WasiView for HostState {
+    fn ctx(&mut self) -> WasiCtxView<'_> { WasiCtxView { ctx: &mut self.wasi, table: &mut self.table } }
+}
+
+/// limits 在 instantiate 前就要生效（instance 數只在 instantiate 時檢查）
+pub fn make_store(engine: &Engine, registry: Arc<RwLock<TagRegistry>>, limits: ExecLimits) -> Store<HostState> {
+    let wasi = WasiCtxBuilder::new().inherit_stdio().max_random_size(u64::MAX).build();
+    let table: ResourceTable = ResourceTable::new();
+    let budget = Budget::new("（載入中）", limits);
+    let mut store = Store::new(engine, HostState { wasi, table, registry, budget, scene: Scene::default() });
+    store.set_hostcall_fuel(usize::MAX);
+    store.limiter(|s| &mut s.budget);
+    // instantiate 時的 start function 也在上限內執行
+    let _ = limits::arm(store.as_context_mut(), None);
+    store
+}
+
+/// make_store 的 async 版（AsyncPipeline）：deadline 改為協作式（limits.rs），之後只能以 *_async 呼叫
+pub fn make_async_store(engine: &Engine, registry: Arc<RwLock<TagRegistry>>, limits: ExecLimits) -> Store<HostState> {
+    let mut store = make_store(engine, registry, limits);
+    limits::cooperative(&mut store);
+    let _ = limits::arm(store.as_context_mut(), None);
+    store
+}
+
+// ════════════════════════════════════════════════════════════════════════════
+// Deploy 型別名稱
+// ════════════════════════════════════════════════════════════════════════════
+
+/// WIT 的 value-kind 名稱（wiring / 錯誤報告用）
+pub fn kind_name(k: ValueKind) -> &'static str {
+    match k {
+        ValueKind::BoolVal  => "bool-val",
+        ValueKind::I8Val    => "i8-val",
+        ValueKind::U8Val    => "u8-val",
+        ValueKind::I16Val   => "i16-val",
+        ValueKind::U16Val   => "u16-val",
+        ValueKind::I32Val   => "i32-val",
+        ValueKind::U32Val   => "u32-val",
+        ValueKind::I64Val   => "i64-val",
+        ValueKind::U64Val   => "u64-val",
+        ValueKind::F32Val   => "f32-val",
+        ValueKind::F64Val   => "f64-val",
+        ValueKind::ShortStr => "short-str",
+        ValueKind::Blob     => "blob",
+        ValueKind::Any      => "any",
+    }
+}
+
+/// kind_name 的反向（CLI / 設定檔用）
+pub fn parse_kind(name: &str) -> Option<ValueKind> {
+    Some(match name {
+        "bool-val"  => ValueKind::BoolVal,
+        "i8-val"    => ValueKind::I8Val,
+        "u8-val"    => ValueKind::U8Val,
+        "i16-val"   => ValueKind::I16Val,
+        "u16-val"   => ValueKind::U16Val,
+        "i32-val"   => ValueKind::I32Val,
+        "u32-val"   => ValueKind::U32Val,
+        "i64-val"   => ValueKind::I64Val,
+        "u64-val"   => ValueKind::U64Val,
+        "f32-val"   => ValueKind::F32Val,
+        "f64-val"   => ValueKind::F64Val,
+        "short-str" => ValueKind::ShortStr,
+        "blob"      => ValueKind::Blob,
+        "any"       => ValueKind::Any,
+        _ => return None,
+    })
+}
+
+// ════════════════════════════════════════════════════════════════════════════
+// 通用 Node 包裝（flow-node world）
+// ════════════════════════════════════════════════════════════════════════════
+
+pub struct Node {
+    store:     Store<HostState>,
+    bindings:  FlowNode,
+    component: Component,        // respawn 用（supervisor.rs）
+    linker:    Linker<HostState>,
+    init_args: Option<(String, String)>, // 最近一次成功的 init(props, wiring)
+    migrate:   Option<MigrateFunc>,      // 選擇性的 migrate-state export（state.rs）
+    pub name:  String,
+    pub spec:  NodeSpec,
+}
+
+impl Node {
+    pub fn load(engine: &Engine, linker: &Linker<HostState>, cache: &CompileCache,
+            registry: Arc<RwLock<TagRegistry>>, path: &str, limits: ExecLimits) -> Result<Self> {
+        let component = cache.load(engine, Path::new(path))?;
+        Self::new(engine, linker, &component, registry, limits)
+    }
+
+    pub fn new(engine: &Engine, linker: &Linker<HostState>, component: &Component,
+           registry: Arc<RwLock<TagRegistry>>, limits: ExecLimits) -> Result<Self> {
+        let mut store = make_store(engine, registry, limits);
+        let instance = limits::call(&mut store, |s| linker.instantiate(s, component))?;
+        let bindings = FlowNode::new(&mut store, &instance)?;
+        let migrate  = migrate_export(&mut store, &instance)?;
+        let spec = limits::call(&mut store, |s| bindings.iiot_flow_meta().call_describe(s))?;
+        store.data_mut().budget.set_owner(&spec.name, limits);
+        Ok(Node {
+            store, bindings, component: component.clone(), linker: linker.clone(), init_args: None, migrate,
+            name: spec.name.clone(), spec,
+        })
+    }
+
+    /// 以同一個 Component 建立新的 instance：相同的上限，並以相同參數重新 init
+    pub fn respawn(&self) -> Result<Self> {
+        let (owner, limits) = self.store.data().budget.owner().clone();
+        let registry = Arc::clone(&self.store.data().registry);
+        let mut node = Self::new(self.store.engine(), &self.linker, &self.component, registry, limits)?;
+        node.set_limits(&owner, limits);
+        if let Some((props, wiring)) = &self.init_args { node.init(props, wiring)?; }
+        Ok(node)
+    }
+
+    /// 變更執行上限（instance 數除外）；node 為錯誤訊息中的名稱（flow 內的 node id）
+    pub fn set_limits(&mut self, node: &str, limits: ExecLimits) {
+        self.store.data_mut().budget.set_owner(node, limits);
+    }
+
+    pub fn init(&mut self, props: &str, wiring: &str) -> Result<()> {
+        limits::call(&mut self.store, |s| self.bindings.iiot_flow_node().call_init(s, props, wiring))?
+            .map_err(|e| anyhow::anyhow!("{} init 失敗：{e}", self.name))?;
+        self.init_args = Some((props.to_string(), wiring.to_string()));
+        Ok(())
+    }
+    pub fn process(&mut self, input_port: u32, msgs: &[FlowMsg]) -> Result<Vec<PortMsgs>> {
+        let r = limits::call(&mut self.store,
+            |s| self.bindings.iiot_flow_node().call_process(s, input_port, msgs));
+        Ok(with_input(&mut self.store, r, || CrashInput::Msgs { port: input_port, msgs: msgs.to_vec() })?.outputs)
+    }
+    pub fn process_raw(&mut self, tag_id: u32, msg_id: u32, raw: &[u8]) -> Result<Vec<PortMsgs>> {
+        let r = limits::call(&mut self.store,
+            |s| self.bindings.iiot_flow_node().call_process_raw(s, tag_id, msg_id, raw));
+        // 單一 Node 的 crash：source 欄位不使用
+        Ok(with_input(&mut self.store, r, || raw_input(0, tag_id, msg_id, raw))?.outputs)
+    }
+    /// save-state 的輸出包上 envelope（node 名稱、版本）
+    pub fn save_state(&mut self) -> Result<Vec<u8>> {
+        let payload = limits::call(&mut self.store, |s| self.bindings.iiot_flow_node().call_save_state(s))?;
+        Ok(Envelope::new(&self.spec.name, &self.spec.version, payload).encode())
+    }
+    /// 檢查 envelope，版本不同時先經過 migrate-state
+    pub fn load_state(&mut self, state: Vec<u8>) -> Result<()> {
+        let store = &mut self.store;
+        let state = state::open(&self.spec.name, &self.spec.version, state, self.migrate.as_ref()
+            .map(|f| |from: &str, s| migrate_state(store, f, from, s)))?;
+        limits::call(&mut self.store, |s| self.bindings.iiot_flow_node().call_load_state(s, &state))
+    }
+    /// 取出最近一次 crash 的現場（crashdump.rs）
+    pub fn post_mortem(&mut self, memory: bool) -> PostMortem {
+        let spec = &self.spec;
+        crashdump::post_mortem(&mut self.store, memory, |s| limits::call(s,
+            |s| self.bindings.iiot_flow_node().call_save_state(s))
+            .map(|p| Envelope::new(&spec.name, &spec.version, p).encode()))
+    }
+}
+
+// ════════════════════════════════════════════════════════════════════════════
+// Sink Node 包裝（flow-node-with-host world）
+// ════════════════════════════════════════════════════════════════════════════
+
+pub struct SinkNode {
+    store:     Store<HostState>,
+    bindings:  sink_bindings::FlowNodeWithHost,
+    component: Component,
+    linker:    Linker<HostState>,
+    init_args: Option<(String, String)>,
+    migrate:   Option<MigrateFunc>,
+    pub name:  String,
+    pub spec:  NodeSpec,
+}
+
+impl SinkNode {
+    pub fn new(engine: &Engine, component: &Component,
+           registry: Arc<RwLock<TagRegistry>>, limits: ExecLimits) -> Result<Self> {
+        // Sink 用自己的 linker（包含 host-api）
+        let mut sink_linker: Linker<HostState> = Linker::new(engine);
+        wasmtime_wasi::p2::add_to_linker_sync(&mut sink_linker)?;
+        add_host_api_to_linker(&mut sink_linker)?;
+        Self::instantiate(engine, &sink_linker, component, registry, limits)
+    }
+
+    fn instantiate(engine: &Engine, linker: &Linker<HostState>, component: &Component,
+                   registry: Arc<RwLock<TagRegistry>>, limits: ExecLimits) -> Result<Self> {
+        let mut store = make_store(engine, registry, limits);
+        let instance = limits::call(&mut store, |s| linker.instantiate(s, component))?;
+        let bindings = sink_bindings::FlowNodeWithHost::new(&mut store, &instance)?;
+        let migrate  = migrate_export(&mut store, &instance)?;
+        let spec = limits::call(&mut store, |s| bindings.iiot_flow_meta().call_describe(s))?;
+        store.data_mut().budget.set_owner(&spec.name, limits);
+        Ok(SinkNode {
+            store, bindings, component: component.clone(), linker: linker.clone(), init_args: None, migrate,
+            name: spec.name.clone(), spec,
+        })
+    }
+
+    /// 同 Node::respawn
+    pub fn respawn(&self) -> Result<Self> {
+        let (owner, limits) = self.store.data().budget.owner().clone();
+        let registry = Arc::clone(&self.store.data().registry);
+        let mut sink = Self::instantiate(self.store.engine(), &self.linker, &self.component, registry, limits)?;
+        sink.set_limits(&owner, limits);
+        if let Some((props, wiring)) = &self.init_args { sink.init(props, wiring)?; }
+        Ok(sink)
+    }
+
+    pub fn set_limits(&mut self, node: &str, limits: ExecLimits) {
+        self.store.data_mut().budget.set_owner(node, limits);
+    }
+
+    pub fn init(&mut self, props: &str, wiring: &str) -> Result<()> {
+        limits::call(&mut self.store, |s| self.bindings.iiot_flow_node().call_init(s, props, wiring))?
+            .map_err(|e| anyhow::anyhow!("{} init 失敗：{e}", self.name))?;
+        self.init_args = Some((props.to_string(), wiring.to_string()));
+        Ok(())
+    }
+    pub fn process(&mut self, input_port: u32, msgs: &[FlowMsg]) -> Result<()> {
+        let r = limits::call(&mut self.store, |s| self.bindings.iiot_flow_node().call_process(s, input_port, msgs));
+        with_input(&mut self.store, r, || CrashInput::Msgs { port: input_port, msgs: msgs.to_vec() })?;
+        Ok(())
+    }
+    /// save-state 的輸出包上 envelope（node 名稱、版本）
+    pub fn save_state(&mut self) -> Result<Vec<u8>> {
+        let payload = limits::call(&mut self.store, |s| self.bindings.iiot_flow_node().call_save_state(s))?;
+        Ok(Envelope::new(&self.spec.name, &self.spec.version, payload).encode())
+    }
+    /// 檢查 envelope，版本不同時先經過 migrate-state
+    pub fn load_state(&mut self, state: Vec<u8>) -> Result<()> {
+        let store = &mut self.store;
+        let state = state::open(&self.spec.name, &self.spec.version, state, self.migrate.as_ref()
+            .map(|f| |from: &str, s| migrate_state(store, f, from, s)))?;
+        limits::call(&mut self.store, |s| self.bindings.iiot_flow_node().call_load_state(s, &state))
+    }
+    pub fn post_mortem(&mut self, memory: bool) -> PostMortem {
+        let spec = &self.spec;
+        crashdump::post_mortem(&mut self.store, memory, |s| limits::call(s,
+            |s| self.bindings.iiot_flow_node().call_save_state(s))
+            .map(|p| Envelope::new(&spec.name, &spec.version, p).encode()))
+    }
+}
+
+// ════════════════════════════════════════════════════════════════
+// Fused Pipeline 包裝（Fusion + AOT 後使用）
+// ════════════════════════════════════════════════════════════════
+
+/// 微批次：累積到 max_size 筆，或最早一筆已等待 max_linger，就以一次 run-batch 送進 Pipeline
+#[derive(Debug, Clone, Copy)]
+pub struct BatchConfig {
+    pub max_size:   usize,
+    pub max_linger: Duration,
+}
+
+impl Default for BatchConfig {
+    fn default() -> Self {
+        BatchConfig { max_size: 256, max_linger: Duration::from_millis(5) }
+    }
+}
+
+// ── fused-pipeline 的 WIT 版本 ───────────────────────────────────────────────
+const PIPELINE_EXPORT: &str = "iiot:flow/pipeline@0.2.0";
+const TRACE_IMPORT:    &str = "iiot:flow/trace@0.2.0";
+
+fn is_legacy(component: &Component) -> bool {
+    component.get_export_index(None, PIPELINE_EXPORT).is_none()
+}
+
+// glue 進入每個 Node 前通知，改用該 Node 的執行上限（0.1.0 的 artifact 沒有這個 import）
+fn add_trace_to_linker(linker: &mut Linker<HostState>) -> Result<()> {
+    linker.instance(TRACE_IMPORT)?.func_wrap("enter-node", |ctx, (index,): (u32,)| {
+        limits::arm(ctx, Some(index as usize))
+    })?;
+    Ok(())
+}
+
+// 0.1.0 的 artifact 沒有 nodes / trace：node-limits 無法套用，只剩整條 flow 的上限
+fn warn_legacy_limits(ids: &[String], nodes: &BTreeMap<String, ExecLimits>) {
+    if ids.is_empty() && !nodes.is_empty() {
+        eprintln!("⚠️  artifact 為 iiot:flow@0.1.0（沒有 trace import），忽略 node-limits，改用整條 flow 的執行上限");
+    }
+}
+
+enum FusedApi {
+    Current(fused_bindings::FusedPipeline),
+    Legacy(legacy_bindings::FusedPipeline),
+}
+
+impl FusedApi {
+    fn instantiate(store: &mut Store<HostState>, component: &Component,
+                   linker: &Linker<HostState>) -> wasmtime::Result<Self> {
+        Ok(if is_legacy(component) {
+            FusedApi::Legacy(legacy_bindings::FusedPipeline::instantiate(store, component, linker)?)
+        } else {
+            FusedApi::Current(fused_bindings::FusedPipeline::instantiate(store, component, linker)?)
+        })
+    }
+
+    fn name(&self, s: &mut Store<HostState>) -> wasmtime::Result<String> {
+        match self {
+            FusedApi::Current(b) => b.iiot_flow_pipeline().call_name(s),
+            FusedApi::Legacy(b)  => b.pipeline().call_name(s),
+        }
+    }
+
+    fn version(&self, s: &mut Store<HostState>) -> wasmtime::Result<String> {
+        match self {
+            FusedApi::Current(b) => b.iiot_flow_pipeline().call_version(s),
+            FusedApi::Legacy(b)  => b.pipeline().call_version(s),
+        }
+    }
+
+    // 0.1.0 沒有 nodes()：不知道 Node 邊界，只有整條 flow 的上限
+    fn nodes(&self, s: &mut Store<HostState>) -> wasmtime::Result<Vec<String>> {
+        match self {
+            FusedApi::Current(b) => b.iiot_flow_pipeline().call_nodes(s),
+            FusedApi::Legacy(_)  => Ok(Vec::new()),
+        }
+    }
+
+    // 0.1.0 的 run 沒有 source：glue 把每筆送進所有 Source（只支援單一 source 的 flow）
+    fn run(&self, s: &mut Store<HostState>, source: u32, tag_id: u32, msg_id: u32, raw: &[u8])
+           -> wasmtime::Result<Vec<u8>> {
+        match self {
+            FusedApi::Current(b) => b.iiot_flow_pipeline().call_run(s, source, tag_id, msg_id, raw),
+            FusedApi::Legacy(b)  => b.pipeline().call_run(s, tag_id, msg_id, raw),
+        }
+    }
+
+    fn run_batch(&self, s: &mut Store<HostState>, updates: &[RawUpdate]) -> wasmtime::Result<Vec<Vec<u8>>> {
+        match self {
+            FusedApi::Current(b) => b.iiot_flow_pipeline().call_run_batch(s, updates),
+            FusedApi::Legacy(b)  => updates.iter()
+                .map(|u| b.pipeline().call_run(&mut *s, u.tag_id, u.msg_id, &u.raw_bytes))
+                .collect(),
+        }
+    }
+
+    fn save_states(&self, s: &mut Store<HostState>) -> wasmtime::Result<Vec<u8>> {
+        match self {
+            FusedApi::Current(b) => b.iiot_flow_pipeline().call_save_states(s),
+            FusedApi::Legacy(b)  => b.pipeline().call_save_states(s),
+        }
+    }
+
+    fn load_states(&self, s: &mut Store<HostState>, states: &[u8]) -> wasmtime::Result<()> {
+        match self {
+            FusedApi::Current(b) => b.iiot_flow_pipeline().call_load_states(s, states),
+            FusedApi::Legacy(b)  => b.pipeline().call_load_states(s, states),
+        }
+    }
+}
+
+pub struct FusedPipeline {
+    store:     Store<HostState>,
+    bindings:  FusedApi,
+    component: Component,
+    linker:    Linker<HostState>,
+    pending:   Vec<RawUpdate>,
+    first_at: Option<Instant>, // pending 第一筆進來的時間
+    pub name:    String,
+    pub version: String,
+    pub nodes:   Vec<String>, // glue 內的 Node id（topo 順序，含 Cast Node）
+    pub batch:   BatchConfig,
+}
+
+impl FusedPipeline {
+    /// limits 為整條 flow 的上限（各 Node 預設相同，之後可用 set_limits 個別覆寫）
+    pub fn load(engine: &Engine, cache: &CompileCache,
+            registry: Arc<RwLock<TagRegistry>>,
+            path: &str, limits: ExecLimits) -> Result<Self> {
+        // .cwasm：AOT 預編譯版本，直接 mmap；.wasm：查 AOT 快取，沒有才 JIT 編譯
+        let component = cache.load(engine, Path::new(path))?;
+
+        let mut fused_linker: Linker<HostState> = Linker::new(engine);
+        wasmtime_wasi::p2::add_to_linker_sync(&mut fused_linker)?;
+        add_host_api_to_linker(&mut fused_linker)?;
+        add_trace_to_linker(&mut fused_linker)?;
+        Self::instantiate(engine, fused_linker, component, registry, limits)
+    }
+
+    fn instantiate(engine: &Engine, linker: Linker<HostState>, component: Component,
+                   registry: Arc<RwLock<TagRegistry>>, limits: ExecLimits) -> Result<Self> {
+        let mut store = make_store(engine, registry, limits);
+        let bindings = limits::call(&mut store, |s| FusedApi::instantiate(s, &component, &linker))?;
+        let name    = limits::call(&mut store, |s| bindings.name(s))?;
+        let version = limits::call(&mut store, |s| bindings.version(s))?;
+        let nodes   = limits::call(&mut store, |s| bindings.nodes(s))?;
+        store.data_mut().budget.set_owner(&name, limits);
+        store.data_mut().budget.set_nodes(nodes.iter().map(|id| (id.clone(), limits)).collect());
+
+        Ok(FusedPipeline {
+            store, bindings, component, linker, pending: Vec::new(), first_at: None,
+            name, version, nodes, batch: BatchConfig::default(),
+        })
+    }
+
+    /// 以同一個 Component 建立新的 instance：沿用 flow / 各 Node 的上限與 batch 設定（pending 不帶過去）
+    pub fn respawn(&self) -> Result<Self> {
+        let budget   = &self.store.data().budget;
+        let registry = Arc::clone(&self.store.data().registry);
+        let mut fresh = Self::instantiate(self.store.engine(), self.linker.clone(), self.component.clone(),
+                                          registry, budget.owner().1)?;
+        fresh.store.data_mut().budget.set_owner(&budget.owner().0, budget.owner().1);
+        fresh.store.data_mut().budget.set_nodes(budget.nodes().to_vec());
+        fresh.batch = self.batch;
+        Ok(fresh)
+    }
+
+    /// 變更執行上限（instance 數除外）：flow 為整條 flow 與未列出的 Node，nodes 依 node id 個別指定
+    pub fn set_limits(&mut self, flow: ExecLimits, nodes: &BTreeMap<String, ExecLimits>) {
+        warn_legacy_limits(&self.nodes, nodes);
+        let budget = &mut self.store.data_mut().budget;
+        budget.set_owner(&self.name, flow);
+        budget.set_nodes(self.nodes.iter()
+            .map(|id| (id.clone(), nodes.get(id).copied().unwrap_or(flow)))
+            .collect());
+    }
+
+    /// source node id 在 nodes() 內的索引（run / push 的 source）；0.1.0 只有單一 run 入口，一律為 0
+    pub fn source(&self, id: &str) -> Result<u32> {
+        source_index(&self.nodes, id)
+    }
+
+    // 核心：一次呼叫跑完整個 Pipeline（source = 收到這筆的 Source，見 source()）
+    pub fn run(&mut self, source: u32, tag_id: u32, msg_id: u32, raw: &[u8]) -> Result<Vec<u8>> {
+        let r = limits::call(&mut self.store, |s| self.bindings.run(s, source, tag_id, msg_id, raw));
+        with_input(&mut self.store, r, || raw_input(source, tag_id, msg_id, raw))
+    }
+
+    /// 一次 component 呼叫跑完多筆，回傳與 updates 對齊（空 = 沒有訊息抵達 sink）
+    pub fn run_batch(&mut self, updates: &[RawUpdate]) -> Result<Vec<Vec<u8>>> {
+        let r = limits::call(&mut self.store, |s| self.bindings.run_batch(s, updates));
+        let out = with_input(&mut self.store, r, || CrashInput::Raw(updates.to_vec()))?;
+        if out.len() != updates.len() {
+            bail!("run-batch 回傳 {} 筆結果，預期 {} 筆", out.len(), updates.len());
+        }
+        Ok(out)
+    }
+
+    // ── 微批次（batch 設定）──────────────────────────────────────────────
+
+    /// 放進 pending；達到 max_size 時立即送出並回傳該批結果，否則回傳空
+    pub fn push(&mut self, source: u32, tag_id: u32, msg_id: u32, raw: Vec<u8>) -> Result<Vec<Vec<u8>>> {
+        if self.pending.is_empty() { self.first_at = Some(Instant::now()); }
+        self.pending.push(RawUpdate { source, tag_id, msg_id, raw_bytes: raw });
+        if self.pending.len() >= self.batch.max_size.max(1) { return self.flush(); }
+        Ok(Vec::new())
+    }
+
+    /// pending 最晚必須送出的時間；沒有 pending 時為 None
+    pub fn linger_deadline(&self) -> Option<Instant> {
+        self.first_at.map(|t| t + self.batch.max_linger)
+    }
+
+    /// max_linger 已到期就送出 pending
+    pub fn poll(&mut self) -> Result<Vec<Vec<u8>>> {
+        match self.linger_deadline() {
+            Some(d) if Instant::now() >= d => self.flush(),
+            _ => Ok(Vec::new()),
+        }
+    }
+
+    /// 立即送出 pending（save_states 前、停止前都要先 flush）
+    pub fn flush(&mut self) -> Result<Vec<Vec<u8>>> {
+        self.first_at = None;
+        if self.pending.is_empty() { return Ok(Vec::new()); }
+        let updates = std::mem::take(&mut self.pending);
+        self.run_batch(&updates)
+    }
+
+    pub fn pending(&self) -> usize {
+        self.pending.len()
+    }
+
+    /// Store 內 linear memory 總量（bytes）
+    pub fn memory(&self) -> usize {
+        self.store.data().budget.memory()
+    }
+
+    /// save-states 的輸出包上 envelope（flow 名稱、版本）
+    pub fn save_states(&mut self) -> Result<Vec<u8>> {
+        let payload = limits::call(&mut self.store, |s| self.bindings.save_states(s))?;
+        Ok(Envelope::new(&self.name, &self.version, payload).encode())
+    }
+
+    /// Pipeline 沒有 migrate-state：flow 版本不同的 state 一律拒絕
+    pub fn load_states(&mut self, states: Vec<u8>) -> Result<()> {
+        let states = state::open(&self.name, &self.version, states, None::<state::Migrate>)?;
+        limits::call(&mut self.store, |s| self.bindings.load_states(s, &states))
+    }
+
+    /// redeploy 用：呼叫端已確認是同一個 flow-id，接受任何 flow 名稱與版本的 state，
+    /// glue 依 node id 交給各 Node 的 load-state；新版本已移除的 Node 先在這裡捨棄
+    pub fn carry_states(&mut self, states: Vec<u8>) -> Result<()> {
+        let states = carried(&self.nodes, states)?;
+        limits::call(&mut self.store, |s| self.bindings.load_states(s, &states))
+    }
+
+    /// 取出最近一次 crash 的現場；node 為 trap 當下執行中的 Node（enter-node）
+    pub fn post_mortem(&mut self, memory: bool) -> PostMortem {
+        let (name, version) = (&self.name, &self.version);
+        crashdump::post_mortem(&mut self.store, memory, |s| limits::call(s,
+            |s| self.bindings.save_states(s))
+            .map(|p| Envelope::new(name, version, p).encode()))
+    }
+}
+
+// ════════════════════════════════════════════════════════════════
+// Fused Pipeline 包裝（async，tokio Host 使用）
+// ════════════════════════════════════════════════════════════════
+
+type AsyncRawUpdate = fused_async_bindings::exports::iiot::flow::pipeline::RawUpdate;
+
+// limits::call 的 async 版：先 arm，await 完交給 limits::outcome
+macro_rules! call_async {
+    ($store:expr, |$s:ident| $call:expr) => {{
+        limits::arm($store.as_context_mut(), None)?;
+        let $s = &mut $store;
+        let r = $call.await;
+        limits::outcome(&mut $store, r)
+    }};
+}
+
+/// FusedPipeline 的 async 版（async_runtime.rs）：同一個 artifact、同樣的微批次與執行上限，
+/// wasm 在 fiber 上執行，host-api / WASI 是 async，每個 epoch tick 讓出 tokio worker 一次
+// FusedApi 的 async 版
+enum AsyncFusedApi {
+    Current(fused_async_bindings::FusedPipeline),
+    Legacy(legacy_async_bindings::FusedPipeline),
+}
+
+impl AsyncFusedApi {
+    async fn instantiate(store: &mut Store<HostState>, component: &Component,
+                         linker: &Linker<HostState>) -> wasmtime::Result<Self> {
+        Ok(if is_legacy(component) {
+            AsyncFusedApi::Legacy(legacy_async_bindings::FusedPipeline::instantiate_async(store, component, linker).await?)
+        } else {
+            AsyncFusedApi::Current(fused_async_bindings::FusedPipeline::instantiate_async(store, component, linker).await?)
+        })
+    }
+
+    async fn name(&self, s: &mut Store<HostState>) -> wasmtime::Result<String> {
+        match self {
+            AsyncFusedApi::Current(b) => b.iiot_flow_pipeline().call_name(s).await,
+            AsyncFusedApi::Legacy(b)  => b.pipeline().call_name(s).await,
+        }
+    }
+
+    async fn version(&self, s: &mut Store<HostState>) -> wasmtime::Result<String> {
+        match self {
+            AsyncFusedApi::Current(b) => b.iiot_flow_pipeline().call_version(s).await,
+            AsyncFusedApi::Legacy(b)  => b.pipeline().call_version(s).await,
+        }
+    }
+
+    async fn nodes(&self, s: &mut Store<HostState>) -> wasmtime::Result<Vec<String>> {
+        match self {
+            AsyncFusedApi::Current(b) => b.iiot_flow_pipeline().call_nodes(s).await,
+            AsyncFusedApi::Legacy(_)  => Ok(Vec::new()),
+        }
+    }
+
+    async fn run_batch(&self, s: &mut Store<HostState>, updates: &[AsyncRawUpdate]) -> wasmtime::Result<Vec<Vec<u8>>> {
+        match self {
+            AsyncFusedApi::Current(b) => b.iiot_flow_pipeline().call_run_batch(s, updates).await,
+            AsyncFusedApi::Legacy(b)  => {
+                let mut out = Vec::with_capacity(updates.len());
+                for u in updates {
+                    out.push(b.pipeline().call_run(&mut *s, u.tag_id, u.msg_id, &u.raw_bytes).await?);
+                }
+                Ok(out)
+            }
+        }
+    }
+
+    async fn save_states(&self, s: &mut Store<HostState>) -> wasmtime::Result<Vec<u8>> {
+        match self {
+            AsyncFusedApi::Current(b) => b.iiot_flow_pipeline().call_save_states(s).await,
+            AsyncFusedApi::Legacy(b)  => b.pipeline().call_save_states(s).await,
+        }
+    }
+
+    async fn load_states(&self, s: &mut Store<HostState>, states: &[u8]) -> wasmtime::Result<()> {
+        match self {
+            AsyncFusedApi::Current(b) => b.iiot_flow_pipeline().call_load_states(s, states).await,
+            AsyncFusedApi::Legacy(b)  => b.pipeline().call_load_states(s, states).await,
+        }
+    }
+}
+
+pub struct AsyncPipeline {
+    store:     Store<HostState>,
+    bindings:  AsyncFusedApi,
+    component: Component,
+    linker:    Linker<HostState>,
+    pending:   Vec<AsyncRawUpdate>,
+    first_at:  Option<Instant>,
+    pub name:    String,
+    pub version: String,
+    pub nodes:   Vec<String>,
+    pub batch:   BatchConfig,
+}
+
+impl AsyncPipeline {
+    /// 同 FusedPipeline::load
+    pub async fn load(engine: &Engine, cache: &CompileCache,
+                      registry: Arc<RwLock<TagRegistry>>,
+                      path: &str, limits: ExecLimits) -> Result<Self> {
+        let component = cache.load(engine, Path::new(path))?;
+
+        let mut linker: Linker<HostState> = Linker::new(engine);
+        wasmtime_wasi::p2::add_to_linker_async(&mut linker)?;
+        add_host_api_to_linker_async(&mut linker)?;
+        add_trace_to_linker(&mut linker)?;
+        Self::instantiate(engine, linker, component, registry, limits).await
+    }
+
+    async fn instantiate(engine: &Engine, linker: Linker<HostState>, component: Component,
+                         registry: Arc<RwLock<TagRegistry>>, limits: ExecLimits) -> Result<Self> {
+        let mut store = make_async_store(engine, registry, limits);
+        let bindings = call_async!(store, |s| AsyncFusedApi::instantiate(s, &component, &linker))?;
+        let name    = call_async!(store, |s| bindings.name(s))?;
+        let version = call_async!(store, |s| bindings.version(s))?;
+        let nodes   = call_async!(store, |s| bindings.nodes(s))?;
+        store.data_mut().budget.set_owner(&name, limits);
+        store.data_mut().budget.set_nodes(nodes.iter().map(|id| (id.clone(), limits)).collect());
+
+        Ok(AsyncPipeline {
+            store, bindings, component, linker, pending: Vec::new(), first_at: None,
+            name, version, nodes, batch: BatchConfig::default(),
+        })
+    }
+
+    /// 同 FusedPipeline::respawn
+    pub async fn respawn(&self) -> Result<Self> {
+        let budget   = &self.store.data().budget;
+        let registry = Arc::clone(&self.store.data().registry);
+        let mut fresh = Self::instantiate(self.store.engine(), self.linker.clone(), self.component.clone(),
+                                          registry, budget.owner().1).await?;
+        fresh.store.data_mut().budget.set_owner(&budget.owner().0, budget.owner().1);
+        fresh.store.data_mut().budget.set_nodes(budget.nodes().to_vec());
+        fresh.batch = self.batch;
+        Ok(fresh)
+    }
+
+    /// 同 FusedPipeline::set_limits
+    pub fn set_limits(&mut self, flow: ExecLimits, nodes: &BTreeMap<String, ExecLimits>) {
+        warn_legacy_limits(&self.nodes, nodes);
+        let budget = &mut self.store.data_mut().budget;
+        budget.set_owner(&self.name, flow);
+        budget.set_nodes(self.nodes.iter()
+            .map(|id| (id.clone(), nodes.get(id).copied().unwrap_or(flow)))
+            .collect());
+    }
+
+    /// 同 FusedPipeline::source
+    pub fn source(&self, id: &str) -> Result<u32> {
+        source_index(&self.nodes, id)
+    }
+
+    async fn run_batch(&mut self, updates: &[AsyncRawUpdate]) -> Result<Vec<Vec<u8>>> {
+        let r = call_async!(self.store, |s| self.bindings.run_batch(s, updates));
+        let out = with_input(&mut self.store, r, || CrashInput::Raw(updates.iter().map(|u| RawUpdate {
+            source: u.source, tag_id: u.tag_id, msg_id: u.msg_id, raw_bytes: u.raw_bytes.clone(),
+        }).collect()))?;
+        if out.len() != updates.len() {
+            bail!("run-batch 回傳 {} 筆結果，預期 {} 筆", out.len(), updates.len());
+        }
+        Ok(out)
+    }
+
+    /// 同 FusedPipeline::push
+    pub async fn push(&mut self, source: u32, tag_id: u32, msg_id: u32, raw: Vec<u8>) -> Result<Vec<Vec<u8>>> {
+        if self.pending.is_empty() { self.first_at = Some(Instant::now()); }
+        self.pending.push(AsyncRawUpdate { source, tag_id, msg_id, raw_bytes: raw });
+        if self.pending.len() >= self.batch.max_size.max(1) { return self.flush().await; }
+        Ok(Vec::new())
+    }
+
+    pub fn linger_deadline(&self) -> Option<Instant> {
+        self.first_at.map(|t| t + self.batch.max_linger)
+    }
+
+    /// 同 FusedPipeline::flush
+    pub async fn flush(&mut self) -> Result<Vec<Vec<u8>>> {
+        self.first_at = None;
+        if self.pending.is_empty() { return Ok(Vec::new()); }
+        let updates = std::mem::take(&mut self.pending);
+        self.run_batch(&updates).await
+    }
+
+    pub fn pending(&self) -> usize {
+        self.pending.len()
+    }
+
+    pub fn memory(&self) -> usize {
+        self.store.data().budget.memory()
+    }
+
+    /// 同 FusedPipeline::save_states
+    pub async fn save_states(&mut self) -> Result<Vec<u8>> {
+        let payload = call_async!(self.store, |s| self.bindings.save_states(s))?;
+        Ok(Envelope::new(&self.name, &self.version, payload).encode())
+    }
+
+    /// 同 FusedPipeline::load_states
+    pub async fn load_states(&mut self, states: Vec<u8>) -> Result<()> {
+        let states = state::open(&self.name, &self.version, states, None::<state::Migrate>)?;
+        call_async!(self.store, |s| self.bindings.load_states(s, &states))
+    }
+
+    /// 同 FusedPipeline::carry_states
+    pub async fn carry_states(&mut self, states: Vec<u8>) -> Result<()> {
+        let states = carried(&self.nodes, states)?;
+        call_async!(self.store, |s| self.bindings.load_states(s, &states))
+    }
+
+    /// 同 FusedPipeline::post_mortem（save-states 在取出現場之後才 await）
+    pub async fn post_mortem(&mut self, memory: bool) -> PostMortem {
+        let mut pm = crashdump::capture(&mut self.store, memory);
+        let state = self.save_states().await;
+        crashdump::settle(&mut self.store, &mut pm, state);
+        pm
+    }
+}
+
+// 失敗時記錄觸發的輸入，crash dump 用（成功時不複製）
+fn with_input<R>(store: &mut Store<HostState>, r: Result<R>, input: impl FnOnce() -> CrashInput) -> Result<R> {
+    if r.is_err() { store.data_mut().scene.input = Some(input()); }
+    r
+}
+
+fn raw_input(source: u32, tag_id: u32, msg_id: u32, raw: &[u8]) -> CrashInput {
+    CrashInput::Raw(vec![RawUpdate { source, tag_id, msg_id, raw_bytes: raw.to_vec() }])
+}
+
+// Fused Pipeline 的 source 索引；nodes 為空 = 0.1.0
+fn source_index(nodes: &[String], id: &str) -> Result<u32> {
+    if nodes.is_empty() { return Ok(0); }
+    match nodes.iter().position(|n| n == id) {
+        Some(i) => Ok(i as u32),
+        None    => bail!("flow 內沒有 source node {id}"),
+    }
+}
+
+// redeploy 轉移給新版本的 save-states：拆掉 envelope，捨棄新版本已移除的 Node
+// （glue 的 load-states 遇到未知 node id 會 trap）
+fn carried(nodes: &[String], states: Vec<u8>) -> Result<Vec<u8>> {
+    let states = match Envelope::decode(&states)? { Some(env) => env.payload, None => states };
+    let states = proto::NodeStates::decode(states.as_slice()).context("state 格式錯誤")?;
+    let (kept, removed): (Vec<_>, Vec<_>) = states.states.into_iter().partition(|s| nodes.contains(&s.node));
+    for s in removed { eprintln!("⚠️  新版本沒有 {}，捨棄其 state", s.node); }
+    Ok(proto::NodeStates { states: kept }.encode_to_vec())
+}
+
+// ── 選擇性的 state-migration export（wit/0.2.0 flow-node-migratable）──────────
+const MIGRATION_EXPORT: &str = "iiot:flow/state-migration@0.2.0";
+
+type MigrateFunc = TypedFunc<(String, Vec<u8>), (std::result::Result<Vec<u8>, String>,)>;
+
+fn migrate_export(store: &mut Store<HostState>, instance: &Instance) -> Result<Option<MigrateFunc>> {
+    let Some(iface) = instance.get_export_index(&mut *store, None, MIGRATION_EXPORT) else { return Ok(None); };
+    let Some(func) = instance.get_export_index(&mut *store, Some(&iface), "migrate-state") else {
+        bail!("{MIGRATION_EXPORT} 缺少 migrate-state");
+    };
+    let f = instance.get_typed_func(store, func)
+        .map_err(|e| anyhow::anyhow!("{MIGRATION_EXPORT} 的 migrate-state 型別不符：{e}"))?;
+    Ok(Some(f))
+}
+
+fn migrate_state(store: &mut Store<HostState>, f: &MigrateFunc, from: &str, state: Vec<u8>) -> Result<Vec<u8>> {
+    let (r,) = limits::call(store, |s| f.call(s, (from.to_string(), state)))?;
+    r.map_err(anyhow::Error::msg)
+}
+
+// ── 掛載 host-api Host Functions ─────────────────────────────────────────────
+pub fn add_host_api_to_linker(linker: &mut Linker<HostState>) -> Result<()> {
+    // 0.1.0：fused-pipeline，0.2.0：多 port Node，實作相同
+    for name in ["iiot:flow/host-api@0.1.0", "iiot:flow/host-api@0.2.0"] {
+        let mut root = linker.instance(name)?;
+
+        root.func_wrap("get-tag-attr", |ctx, (tag_id, key): (u32, String)| {
+            let reg = ctx.data().registry.read().unwrap();
+            Ok((reg.get_attr(tag_id, &key),))
+        })?;
+
+        root.func_wrap("get-eng-range", |ctx, (tag_id,): (u32,)| {
+            let reg = ctx.data().registry.read().unwrap();
+            Ok((reg.get_eng_range(tag_id),))
+        })?;
+
+        root.func_wrap("log-debug", |_ctx, (node_name, msg): (String, String)| {
+            eprintln!("[WASM:{}] {}", node_name, msg);
+            Ok(())
+        })?;
+    }
+
+    Ok(())
+}
+
+/// add_host_api_to_linker 的 async 版（AsyncPipeline）：host function 內的 I/O 以 await 等待，
+/// 只暫停呼叫它的 wasm fiber，不佔住 tokio worker
+pub fn add_host_api_to_linker_async(linker: &mut Linker<HostState>) -> Result<()> {
+    for name in ["iiot:flow/host-api@0.1.0", "iiot:flow/host-api@0.2.0"] {
+        let mut root = linker.instance(name)?;
+
+        root.func_wrap_async("get-tag-attr", |ctx, (tag_id, key): (u32, String)| Box::new(async move {
+            let reg = ctx.data().registry.read().unwrap();
+            Ok((reg.get_attr(tag_id, &key),))
+        }))?;
+
+        root.func_wrap_async("get-eng-range", |ctx, (tag_id,): (u32,)| Box::new(async move {
+            let reg = ctx.data().registry.read().unwrap();
+            Ok((reg.get_eng_range(tag_id),))
+        }))?;
+
+        // stderr 導向 pipe、對方讀得慢時 eprintln! 會阻塞：改由 tokio 的 stderr 寫出
+        root.func_wrap_async("log-debug", |_ctx, (node_name, msg): (String, String)| Box::new(async move {
+            let line = format!("[WASM:{node_name}] {msg}\n");
+            tokio::io::stderr().write_all(line.as_bytes()).await?;
+            Ok(())
+        }))?;
+    }
+
+    Ok(())
+}
+
+// ════════════════════════════════════════════════════════════════════════════
+// Protobuf（Host 端）
+// ════════════════════════════════════════════════════════════════════════════
+
+pub mod proto {
+    #[derive(prost::Message, Clone)]
+    pub struct TagUpdate {
+        #[prost(string,  tag = "1")] pub tag_id_str: String,
+        #[prost(uint64,  tag = "2")] pub timestamp:  u64,
+        #[prost(uint32,  tag = "3")] pub quality:    u32,
+        #[prost(string,  tag = "4")] pub unit:       String,
+        #[prost(double, optional, tag = "14")] pub f64_val: Option<f64>,
+    }
+
+    #[allow(dead_code)] // Sink 端 encode 的格式，Host 解碼時使用
+    #[derive(prost::Message, Clone)]
+    pub struct FlowResult {
+        #[prost(uint32, tag = "1")] pub tag_id:     u32,
+        #[prost(string, tag = "2")] pub tag_name:   String,
+        #[prost(string, tag = "3")] pub mqtt_topic: String,
+        #[prost(uint32, tag = "4")] pub msg_id:     u32,
+        #[prost(double, tag = "5")] pub value:      f64,
+        #[prost(uint64, tag = "6")] pub timestamp:  u64,
+        #[prost(uint32, tag = "7")] pub quality:    u32,
+    }
+
+    // Fused pipeline run() 的回傳：抵達 Sink 的訊息（與 fusion/template/glue.rs 相同）
+    #[derive(prost::Message)]
+    pub struct SunkMsg {
+        #[prost(string, tag = "1")]           pub sink:      String,
+        #[prost(uint32, tag = "2")]           pub tag_id:    u32,
+        #[prost(uint32, tag = "3")]           pub msg_id:    u32,
+        #[prost(double, optional, tag = "4")] pub value:     Option<f64>,
+        #[prost(uint64, tag = "5")]           pub timestamp: u64,
+        #[prost(uint32, tag = "6")]           pub quality:   u32,
+    }
+
+    #[derive(prost::Message)]
+    pub struct FusedOutput {
+        #[prost(message, repeated, tag = "1")] pub sunk: Vec<SunkMsg>,
+    }
+
+    // Fused pipeline save-states / load-states 的 payload（與 fusion/template/glue.rs 相同）
+    #[derive(prost::Message)]
+    pub struct NodeState {
+        #[prost(string, tag = "1")] pub node:  String,
+        #[prost(bytes,  tag = "2")] pub state: Vec<u8>,
+    }
+
+    #[derive(prost::Message)]
+    pub struct NodeStates {
+        #[prost(message, repeated, tag = "1")] pub states: Vec<NodeState>,
+    }
+}
+
+// ════════════════════════════════════════════════════════════════════════════
+// 測試用暫存目錄（drop 時刪除）
+// ════════════════════════════════════════════════════════════════════════════
+
+#[cfg(test)]
+pub(crate) struct TempDir(std::path::PathBuf);
+
+#[cfg(test)]
+impl TempDir {
+    pub fn new(name: &str) -> Self {
+        use std::sync::atomic::{AtomicU64, Ordering};
+        static SEQ: AtomicU64 = AtomicU64::new(0);
+        let n   = SEQ.fetch_add(1, Ordering::Relaxed);
+        let dir = std::env::temp_dir().join(format!("iiot-test-{}-{n}-{name}", std::process::id()));
+        std::fs::create_dir_all(&dir).expect("無法建立暫存目錄");
+        TempDir(dir)
+    }
+
+    pub fn path(&self) -> &std::path::Path {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
@@ -0,0 +1,449 @@
+// host/src/limits.rs
+// 每個 Store 的執行 / 資源上限（SystemRequirements 驗收：惡意 wasm 只能影響自己的 flow）
+//
+//   fuel      wasm 指令消耗 fuel，用完即 trap；與機器快慢無關、可重現
+//   deadline  epoch interruption：背景 thread 每 EPOCH_TICK 遞增 engine epoch，超過 deadline 即 trap
+//             （牆鐘上限，fuel 量不到的 host call / 大量 memory.copy 也涵蓋）
+//   memory    ResourceLimiter：Store 內 linear memory 總量（DAG = 單一 Node，Fused = 整條 flow）；
+//             Fused 另外依 enter-node 把執行期間的成長算到該 Node，套用 node-limits 的上限
+//   tables / instances  單一 table 的元素數、Store 內的 instance 數（instantiate 時檢查）
+//   engine    所有 Store 合計的 linear memory（MemoryPool，SystemRequirements：< 180MB），
+//             IIOT_FLOW_ENGINE_MEMORY_MB=<MB>（預設 180），=off 不限；Store drop 時歸還
+//
+// 超過上限一律 trap 並回傳 LimitExceeded（指出是哪個 Node），不會拖垮整個 gateway
+//
+//   DAG    每個 Node 一個 Store，每次呼叫 Node export 前重新設定
+//   Fused  單一 Store；glue 進入每個 Node 前呼叫 trace.enter-node，Host 依該 Node 的上限重新設定，
+//          trap 時也靠它指出是哪個 Node
+//
+// 兩者都會改變編譯結果：Engine 一律用 new_engine() 建立（Host / Fusion 相同，AOT 產物才相容）
+//
+// async Store（AsyncPipeline）的 deadline 改為協作式：每個 epoch tick 讓出 executor 一次，
+// 累計 deadline 的 tick 數後才 trap（等待 executor 的時間不計入），同一個 tokio worker 上的 I/O task 不會被卡住
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
+use wasmtime::{AsContextMut, Config, Engine, ResourceLimiter, Store, StoreContextMut, Trap, UpdateDeadline,
+               WasmCoreDump};
+
+use crate::HostState;
+
+pub const EPOCH_TICK: Duration = Duration::from_millis(10);
+
+// 不限 deadline：current epoch + NO_DEADLINE 不會溢位，也不可能到達
+const NO_DEADLINE: u64 = u64::MAX / 2;
+const MB: usize = 1024 * 1024;
+
+/// 所有 Store 合計的 linear memory 預設上限（MB）
+pub const ENGINE_MEMORY_MB: u64 = 180;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case", default)]
+pub struct ExecLimits {
+    // 每次呼叫可用的 fuel（約等於執行的 wasm 指令數）；null = 不限
+    pub fuel:        Option<u64>,
+    // 每次呼叫的牆鐘上限；null = 不限
+    pub deadline_ms: Option<u64>,
+    // linear memory 總量上限（MB）；null = 不限
+    pub memory_mb:      Option<u64>,
+    // 單一 table 的元素數上限；null = 不限
+    pub table_elements: Option<usize>,
+    // Store 內的 instance 數上限（component 內每個 core module 各算一個）；null = wasmtime 預設
+    pub instances:      Option<usize>,
+}
+
+impl Default for ExecLimits {
+    fn default() -> Self {
+        ExecLimits {
+            fuel: None, deadline_ms: Some(1000),
+            memory_mb: Some(128), table_elements: None, instances: None,
+        }
+    }
+}
+
+impl ExecLimits {
+    pub fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+
+    fn memory_bytes(&self) -> usize {
+        self.memory_mb.map_or(usize::MAX, |mb| (mb as usize).saturating_mul(MB))
+    }
+
+    fn deadline_ticks(&self) -> u64 {
+        match self.deadline_ms {
+            Some(ms) => ms.div_ceil(EPOCH_TICK.as_millis() as u64).max(1),
+            None     => NO_DEADLINE,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Limit {
+    Fuel(u64),
+    Deadline(Duration),
+    Memory { cap_mb: u64, size: usize }, // size = 成長後的 bytes
+    Engine { cap_mb: u64, size: usize }, // size = 所有 Store 合計成長後的 bytes
+    Table  { cap: usize, desired: usize },
+}
+
+/// Node 超過執行上限；instance 已 trap，不可再呼叫
+#[derive(Debug, Clone)]
+pub struct LimitExceeded {
+    pub node:  String,
+    pub limit: Limit,
+}
+
+impl fmt::Display for LimitExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.limit {
+            Limit::Fuel(n)     => write!(f, "node {} 超過執行上限：fuel {n} 已用完（疑似無窮迴圈）", self.node),
+            Limit::Deadline(d) => write!(f, "node {} 超過執行上限：執行超過 {}ms（疑似無窮迴圈）",
+                self.node, d.as_millis()),
+            Limit::Memory { cap_mb, size } => write!(f, "node {} 超過記憶體上限 {cap_mb}MB（成長後 {:.1}MB）",
+                self.node, size as f64 / MB as f64),
+            Limit::Engine { cap_mb, size } => write!(f,
+                "node {} 超過 engine 記憶體上限 {cap_mb}MB（所有 flow 合計成長後 {:.1}MB）",
+                self.node, size as f64 / MB as f64),
+            Limit::Table { cap, desired } => write!(f, "node {} 的 table 超過上限 {cap} 個元素（要求 {desired}）",
+                self.node),
+        }
+    }
+}
+
+impl std::error::Error for LimitExceeded {}
+
+// ── 所有 Store 共用的 linear memory 額度 ─────────────────────────────────────
+
+#[derive(Debug)]
+pub struct MemoryPool {
+    used: AtomicUsize,
+    cap:  usize,
+}
+
+impl MemoryPool {
+    /// cap_mb：None = 不限
+    pub fn new(cap_mb: Option<u64>) -> Arc<Self> {
+        let cap = cap_mb.map_or(usize::MAX, |mb| (mb as usize).saturating_mul(MB));
+        Arc::new(MemoryPool { used: AtomicUsize::new(0), cap })
+    }
+
+    /// 目前所有 Store 合計的 linear memory（bytes）
+    pub fn used(&self) -> usize {
+        self.used.load(Ordering::Relaxed)
+    }
+
+    fn cap_mb(&self) -> u64 {
+        (self.cap / MB) as u64
+    }
+
+    // 超過上限時不預留，回傳 false
+    fn reserve(&self, bytes: usize) -> bool {
+        self.used.fetch_update(Ordering::AcqRel, Ordering::Acquire,
+            |used| used.checked_add(bytes).filter(|&n| n <= self.cap)).is_ok()
+    }
+
+    fn release(&self, bytes: usize) {
+        self.used.fetch_sub(bytes, Ordering::AcqRel);
+    }
+}
+
+/// Host 的 MemoryPool：IIOT_FLOW_ENGINE_MEMORY_MB=<MB>（預設 ENGINE_MEMORY_MB），=off 不限
+pub fn engine_memory() -> &'static Arc<MemoryPool> {
+    static POOL: OnceLock<Arc<MemoryPool>> = OnceLock::new();
+    POOL.get_or_init(|| {
+        let cap = match std::env::var("IIOT_FLOW_ENGINE_MEMORY_MB") {
+            Ok(v) if v == "off" => None,
+            Ok(v) => Some(v.parse().unwrap_or_else(|_| {
+                eprintln!("⚠️  IIOT_FLOW_ENGINE_MEMORY_MB「{v}」不是數字，改用 {ENGINE_MEMORY_MB}MB");
+                ENGINE_MEMORY_MB
+            })),
+            Err(_) => Some(ENGINE_MEMORY_MB),
+        };
+        MemoryPool::new(cap)
+    })
+}
+
+// ── Store 內的上限狀態（HostState 持有）────────────────────────────────────
+
+#[derive(Debug)]
+pub struct Budget {
+    owner:   (String, ExecLimits),      // DAG：Node 本身；Fused：glue（尚未進入任何 Node）
+    nodes:   Vec<(String, ExecLimits)>, // Fused：glue 的 Node 索引 → (node id, 上限)
+    current: Option<usize>,
+    memory:  usize,                     // Store 內所有 linear memory（bytes）
+    grown:   Vec<usize>,                // 與 nodes 對齊：各 Node 執行期間成長的 bytes
+    slices:  Option<u64>,               // async：deadline 前剩下的 epoch tick 數；None = sync
+    pool:    Arc<MemoryPool>,           // 所有 Store 共用；memory 於 drop 時歸還
+}
+
+impl Budget {
+    pub fn new(name: &str, limits: ExecLimits) -> Self {
+        Self::with_pool(name, limits, Arc::clone(engine_memory()))
+    }
+
+    pub fn with_pool(name: &str, limits: ExecLimits, pool: Arc<MemoryPool>) -> Self {
+        Budget {
+            owner: (name.to_string(), limits), nodes: Vec::new(), current: None,
+            memory: 0, grown: Vec::new(), slices: None, pool,
+        }
+    }
+
+    /// Store 內 linear memory 總量（bytes）
+    pub fn memory(&self) -> usize {
+        self.memory
+    }
+
+    pub fn owner(&self) -> &(String, ExecLimits) {
+        &self.owner
+    }
+
+    pub fn nodes(&self) -> &[(String, ExecLimits)] {
+        &self.nodes
+    }
+
+    pub fn set_owner(&mut self, name: &str, limits: ExecLimits) {
+        self.owner = (name.to_string(), limits);
+    }
+
+    pub fn set_nodes(&mut self, nodes: Vec<(String, ExecLimits)>) {
+        self.grown = vec![0; nodes.len()];
+        self.nodes = nodes;
+    }
+
+    fn exceeded(&self, limit: Limit) -> wasmtime::Error {
+        wasmtime::Error::new(LimitExceeded { node: self.active().0.clone(), limit })
+    }
+
+    // 目前執行中的 (node, 上限)
+    fn active(&self) -> &(String, ExecLimits) {
+        self.current.and_then(|i| self.nodes.get(i)).unwrap_or(&self.owner)
+    }
+}
+
+impl Drop for Budget {
+    fn drop(&mut self) {
+        self.pool.release(self.memory);
+    }
+}
+
+impl ResourceLimiter for Budget {
+    fn memory_growing(&mut self, current: usize, desired: usize,
+                      _maximum: Option<usize>) -> wasmtime::Result<bool> {
+        let delta = desired.saturating_sub(current);
+        let flow  = self.owner.1;
+        if self.memory + delta > flow.memory_bytes() {
+            let cap_mb = flow.memory_mb.unwrap_or(0);
+            return Err(self.exceeded(Limit::Memory { cap_mb, size: self.memory + delta }));
+        }
+        let node = self.current.filter(|&i| i < self.nodes.len());
+        if let Some(i) = node {
+            let node = self.nodes[i].1;
+            if self.grown[i] + delta > node.memory_bytes() {
+                let cap_mb = node.memory_mb.unwrap_or(0);
+                return Err(self.exceeded(Limit::Memory { cap_mb, size: self.grown[i] + delta }));
+            }
+        }
+        if !self.pool.reserve(delta) {
+            let (cap_mb, size) = (self.pool.cap_mb(), self.pool.used() + delta);
+            return Err(self.exceeded(Limit::Engine { cap_mb, size }));
+        }
+        if let Some(i) = node { self.grown[i] += delta; }
+        self.memory += delta;
+        Ok(true)
+    }
+
+    fn table_growing(&mut self, _current: usize, desired: usize,
+                     _maximum: Option<usize>) -> wasmtime::Result<bool> {
+        match self.active().1.table_elements {
+            Some(cap) if desired > cap => Err(self.exceeded(Limit::Table { cap, desired })),
+            _ => Ok(true),
+        }
+    }
+
+    fn instances(&self) -> usize {
+        self.owner.1.instances.unwrap_or(wasmtime::DEFAULT_INSTANCE_LIMIT)
+    }
+}
+
+/// 建立 Host / Fusion 共用的 Engine（component model + fuel + epoch），並啟動 epoch thread
+pub fn new_engine() -> Result<Engine> {
+    let mut config = Config::new();
+    config.wasm_component_model(true);
+    config.consume_fuel(true);
+    config.epoch_interruption(true);
+    // trap 時附上 coredump（crashdump.rs 取 backtrace 與 linear memory）；不影響編譯結果
+    config.coredump_on_trap(true);
+    let engine = Engine::new(&config)?;
+
+    // Engine 與 process 同生命週期，thread 不需要結束
+    let ticker = engine.clone();
+    std::thread::Builder::new().name("epoch-ticker".to_string()).spawn(move || loop {
+        std::thread::sleep(EPOCH_TICK);
+        ticker.increment_epoch();
+    })?;
+    Ok(engine)
+}
+
+/// 依 node（None = owner）的上限重新加滿 fuel、設定 epoch deadline
+pub(crate) fn arm(mut store: StoreContextMut<'_, HostState>, node: Option<usize>) -> wasmtime::Result<()> {
+    store.data_mut().budget.current = node;
+    let limits = store.data().budget.active().1;
+    store.set_fuel(limits.fuel.unwrap_or(u64::MAX))?;
+    if store.data().budget.slices.is_some() {
+        store.data_mut().budget.slices = Some(limits.deadline_ticks());
+        store.set_epoch_deadline(1);
+    } else {
+        store.set_epoch_deadline(limits.deadline_ticks());
+    }
+    Ok(())
+}
+
+/// 改為協作式 deadline（async Store 用，之後只能以 *_async 呼叫）：
+/// 每個 epoch tick 讓出一次，累計到 deadline 的 tick 數時 trap（與 sync 相同的 Trap::Interrupt）
+pub(crate) fn cooperative(store: &mut Store<HostState>) {
+    store.data_mut().budget.slices = Some(0);
+    store.epoch_deadline_callback(|mut ctx| {
+        let budget = &mut ctx.data_mut().budget;
+        match budget.slices {
+            Some(n) if n > 1 => {
+                budget.slices = Some(n - 1);
+                Ok(UpdateDeadline::Yield(1))
+            }
+            _ => Ok(UpdateDeadline::Interrupt),
+        }
+    });
+}
+
+/// 在上限內呼叫一次 export；fuel / deadline 造成的 trap 轉成 LimitExceeded
+pub(crate) fn call<R>(store: &mut Store<HostState>,
+                      f: impl FnOnce(&mut Store<HostState>) -> wasmtime::Result<R>) -> Result<R> {
+    arm(store.as_context_mut(), None)?;
+    let r = f(store);
+    outcome(store, r)
+}
+
+/// call 的後半：錯誤時記錄現場、轉成 LimitExceeded（async 呼叫先 arm，await 完交給這裡）
+pub(crate) fn outcome<R>(store: &mut Store<HostState>, r: wasmtime::Result<R>) -> Result<R> {
+    r.map_err(|e| {
+        let node = store.data().budget.active().0.clone();
+        store.data_mut().scene.record(&node, &e);
+        diagnose(store.data(), e)
+    })
+}
+
+pub(crate) fn diagnose(state: &HostState, err: wasmtime::Error) -> anyhow::Error {
+    // ResourceLimiter 回傳的 LimitExceeded 在 err 內層，同樣提到最外層
+    if let Some(e) = err.downcast_ref::<LimitExceeded>().cloned() {
+        return without_coredump(err).context(e);
+    }
+    let (node, limits) = state.budget.active().clone();
+    let limit = match err.downcast_ref::<Trap>() {
+        Some(Trap::OutOfFuel) => Limit::Fuel(limits.fuel.unwrap_or(u64::MAX)),
+        Some(Trap::Interrupt) => Limit::Deadline(Duration::from_millis(limits.deadline_ms.unwrap_or(0))),
+        _ => return without_coredump(err),
+    };
+    // 最外層是 LimitExceeded（可 downcast），trap 與 wasm backtrace 留在 cause
+    without_coredump(err).context(LimitExceeded { node, limit })
+}
+
+// coredump 已由 Scene 取走；錯誤訊息只保留 backtrace 與 trap（Trap 型別保留，其餘層改為文字）
+fn without_coredump(err: wasmtime::Error) -> anyhow::Error {
+    let Some(dump) = err.downcast_ref::<WasmCoreDump>().map(ToString::to_string) else { return err.into(); };
+    let trap   = err.downcast_ref::<Trap>().copied();
+    let layers: Vec<String> = err.chain().map(|e| e.to_string()).filter(|m| *m != dump).collect();
+    let Some((root, rest)) = layers.split_last() else { return err.into(); };
+    let mut out = match trap {
+        Some(t) => anyhow::Error::new(t),
+        None    => anyhow::anyhow!("{root}"),
+    };
+    for m in rest.iter().rev() { out = out.context(m.clone()); }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limits(memory_mb: Option<u64>) -> ExecLimits {
+        ExecLimits { memory_mb, ..ExecLimits::default() }
+    }
+
+    // memory_growing 失敗時的上限種類
+    fn grow(b: &mut Budget, bytes: usize) -> Result<(), (String, Limit)> {
+        let current = b.memory;
+        b.memory_growing(current, current + bytes, None).map(|_| ()).map_err(|e| {
+            let e = e.downcast_ref::<LimitExceeded>().expect("應為 LimitExceeded").clone();
+            (e.node, e.limit)
+        })
+    }
+
+    #[test]
+    fn flow_limit_caps_the_whole_store() {
+        let pool = MemoryPool::new(None);
+        let mut b = Budget::with_pool("flow", limits(Some(1)), Arc::clone(&pool));
+        grow(&mut b, MB / 2).unwrap();
+        grow(&mut b, MB / 2).unwrap();
+        assert_eq!(grow(&mut b, 1), Err(("flow".to_string(), Limit::Memory { cap_mb: 1, size: MB + 1 })));
+        assert_eq!((b.memory(), pool.used()), (MB, MB), "失敗的成長不計入");
+    }
+
+    #[test]
+    fn node_limit_counts_growth_while_the_node_runs() {
+        let pool = MemoryPool::new(None);
+        let mut b = Budget::with_pool("flow", limits(Some(8)), Arc::clone(&pool));
+        b.set_nodes(vec![("a".to_string(), limits(Some(1))), ("b".to_string(), limits(Some(8)))]);
+
+        b.current = Some(0);
+        grow(&mut b, MB).unwrap();
+        assert_eq!(grow(&mut b, 1), Err(("a".to_string(), Limit::Memory { cap_mb: 1, size: MB + 1 })));
+
+        // 其他 Node 與 glue 本身不受 a 的上限影響，仍受整條 flow 的上限
+        b.current = Some(1);
+        grow(&mut b, MB).unwrap();
+        b.current = None;
+        grow(&mut b, 6 * MB).unwrap();
+        assert_eq!(grow(&mut b, 1).unwrap_err().1, Limit::Memory { cap_mb: 8, size: 8 * MB + 1 });
+        assert_eq!(b.grown, vec![MB, MB]);
+        assert_eq!(pool.used(), 8 * MB);
+    }
+
+    #[test]
+    fn engine_limit_is_shared_and_released_on_drop() {
+        let pool = MemoryPool::new(Some(2));
+        let mut a = Budget::with_pool("flow-a", limits(Some(2)), Arc::clone(&pool));
+        let mut b = Budget::with_pool("flow-b", limits(Some(2)), Arc::clone(&pool));
+        grow(&mut a, MB).unwrap();
+        grow(&mut b, MB).unwrap();
+        // 各自都在 flow 上限內，但合計超過 engine 上限
+        assert_eq!(grow(&mut b, 1), Err(("flow-b".to_string(), Limit::Engine { cap_mb: 2, size: 2 * MB + 1 })));
+        assert_eq!(pool.used(), 2 * MB);
+
+        drop(a);
+        assert_eq!(pool.used(), MB, "Store drop 時歸還");
+        grow(&mut b, MB).unwrap();
+        assert_eq!(pool.used(), 2 * MB);
+    }
+
+    #[test]
+    fn node_limit_failure_does_not_reserve_engine_memory() {
+        let pool = MemoryPool::new(Some(4));
+        let mut b = Budget::with_pool("flow", limits(None), Arc::clone(&pool));
+        b.set_nodes(vec![("a".to_string(), limits(Some(1)))]);
+        b.current = Some(0);
+        assert!(grow(&mut b, 2 * MB).is_err());
+        assert_eq!(pool.used(), 0);
+    }
+
+    #[test]
+    fn limit_messages_name_the_node() {
+        let e = LimitExceeded { node: "node-a".to_string(), limit: Limit::Engine { cap_mb: 180, size: 181 * MB } };
+        assert_eq!(e.to_string(), "node node-a 超過 engine 記憶體上限 180MB（所有 flow 合計成長後 181.0MB）");
+        assert_eq!(ExecLimits::default().memory_bytes(), 128 * MB);
+        assert_eq!(limits(None).memory_bytes(), usize::MAX);
+    }
+}
@@ -1,337 +1,124 @@
 // host/src/main.rs
-// IIoT Flow Fusion Host
+// IIoT Flow Fusion Host：demo 主程式
 //
-// 用兩個 bindgen! 分別對應兩個 WIT world：
-//   FlowNode          ← flow-node          (Source / Node A/B/C)
-//   FlowNodeWithHost  ← flow-node-with-host (Sink)
+// 依 flow.json 載入 Node 逐一執行（DAG），
+// 或載入 iiot-flow-fusion 產出的 fused-pipeline component（單一 instance），
+// 或依 flow-deploy/manifest.json 啟動 Runtime（endpoint + 排程），
+// 或依 flows.json 在同一個 process 執行多條 flow（manager.rs）
 //
-// Host Function (host-api) 透過 Linker::func_wrap 手動掛載
-// use wasmtime::error::{ Context, Result };
-
-use anyhow::{ bail, Context, Result };
-use wasmtime::error::Context as _;
-use std::collections::HashMap;
+// 用法：iiot-flow-host <wasm_dir> [flow.json | flow.wasm | flow.cwasm]
+//       iiot-flow-host <flow-deploy_dir>
+//       iiot-flow-host <flows_dir>          ← 內含 flows.json；stdin 指令 list / workers / start / stop / redeploy
+//
+// Node trap 時的 crash dump：IIOT_FLOW_CRASH_DIR=<dir>（預設 <wasm_dir>/crash-dumps），=off 停用；
+// 狀態持久化：IIOT_FLOW_STATE_DIR=<dir> 時啟動後還原、Step 3 結束時寫入（預設不使用）；
+// Fused 的 Step 6 平行 benchmark：IIOT_FLOW_BENCH_FLOWS=<flow 數>、IIOT_FLOW_WORKERS=<thread 數>（預設 CPU core 數）；
+// Runtime 模式改用 manifest 的 crash-dump / snapshot；SIGHUP 重新讀取 flow-deploy 目錄並不停機切換；
+// IIOT_FLOW_ASYNC=1 時 Runtime 改用 tokio 版（async_runtime.rs：async component 呼叫與 endpoint I/O）；
+// 所有 flow 合計的 linear memory 上限：IIOT_FLOW_ENGINE_MEMORY_MB=<MB>（預設 180），=off 不限；
+// Tag Registry 由 IIOT_FLOW_TAGS=<file>（預設 <dir>/tags.toml / tags.json / tags.csv）載入，檔案變更時自動重新載入
+
+use anyhow::{bail, Result};
+use std::io::BufRead;
+use std::path::Path;
+use signal_hook::consts::{SIGHUP, SIGINT, SIGTERM};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, TryRecvError};
 use std::sync::{Arc, RwLock};
-use std::sync::atomic::{AtomicU32, Ordering};
-use std::time::Instant;
-use wasmtime::component::{bindgen, Component, Linker, ResourceTable };
-use wasmtime::{ Config, Engine, Store };
-use wasmtime_wasi::{WasiCtx, WasiCtxBuilder, WasiView, WasiCtxView};
-
-// ── bindings for flow-node world (Source, Node A/B/C) ────────────────────────
-bindgen!({
-    world: "flow-node",
-    path:  "../wit",
-});
-
-// ── bindings for flow-node-with-host world (Sink) ────────────────────────────
-mod sink_bindings {
-    wasmtime::component::bindgen!({
-        world: "flow-node-with-host",
-        path:  "../wit",
-        with: {
-            "iiot:flow/types@0.1.0": crate::iiot::flow::types,
-        }
-    });
-}
-
-mod fused_bindings {
-    wasmtime::component::bindgen!({
-        world: "fused-pipeline",
-        path:  "../wit",
-        // with: {
-        //     "iiot:flow/types@0.1.0": crate::iiot::flow::types,
-        // }
-    });
-}
-
-use iiot::flow::types::{FlowMsg, TagValue, ValueKind};
-
-// ════════════════════════════════════════════════════════════════════════════
-// Tag Registry
-// ════════════════════════════════════════════════════════════════════════════
-
-#[derive(Debug, Clone)]
-pub struct TagMeta {
-    pub name:          String,
-    pub unit:          String,
-    pub mqtt_topic:    String,
-    pub historian_tag: String,
-    pub alarm_group:   String,
-    pub eng_low:       f64,
-    pub eng_high:      f64,
-}
-
-pub struct TagRegistry {
-    tags:       HashMap<u32, TagMeta>,
-    name_to_id: HashMap<String, u32>,
-    next_id:    u32,
-}
-
-impl TagRegistry {
-    pub fn new() -> Self {
-        TagRegistry { tags: HashMap::new(), name_to_id: HashMap::new(), next_id: 1 }
-    }
-
-    pub fn get_or_create(&mut self, name: &str, meta: TagMeta) -> u32 {
-        if let Some(&id) = self.name_to_id.get(name) { return id; }
-        let id = self.next_id;
-        self.next_id += 1;
-        self.name_to_id.insert(name.to_string(), id);
-        self.tags.insert(id, meta);
-        id
-    }
-
-    pub fn get_attr(&self, tag_id: u32, key: &str) -> Option<String> {
-        let m = self.tags.get(&tag_id)?;
-        match key {
-            "name"          => Some(m.name.clone()),
-            "unit"          => Some(m.unit.clone()),
-            "mqtt_topic"    => Some(m.mqtt_topic.clone()),
-            "historian_tag" => Some(m.historian_tag.clone()),
-            "alarm_group"   => Some(m.alarm_group.clone()),
-            _               => None,
-        }
-    }
-
-    pub fn get_eng_range(&self, tag_id: u32) -> Option<(f64, f64)> {
-        let m = self.tags.get(&tag_id)?;
-        Some((m.eng_low, m.eng_high))
-    }
-}
-
-// ════════════════════════════════════════════════════════════════════════════
-// msg_id Allocator
-// ════════════════════════════════════════════════════════════════════════════
-
-static MSG_COUNTER: AtomicU32 = AtomicU32::new(1);
-
-fn next_msg_id() -> u32 {
-    loop {
-        let id = MSG_COUNTER.fetch_add(1, Ordering::Relaxed);
-        if id != 0 { return id; }
-    }
-}
+use std::time::{Duration, Instant};
+use wasmtime::component::Linker;
+use wasmtime::Engine;
+
+use iiot_flow_host::aot::CompileCache;
+use iiot_flow_host::async_runtime::AsyncRuntime;
+use iiot_flow_host::crashdump::{CrashDumpConfig, CrashDumper};
+use iiot_flow_host::dag::{DagNode, FlowDag};
+use iiot_flow_host::flow::FlowDef;
+use iiot_flow_host::limits::{self, ExecLimits};
+use iiot_flow_host::manager::FlowManager;
+use iiot_flow_host::pool::{Task, WorkerPool};
+use iiot_flow_host::runtime::Runtime;
+use iiot_flow_host::snapshot::{Snapshot, SnapshotStore};
+use iiot_flow_host::supervisor::{Supervisor, SupervisorConfig};
+use iiot_flow_host::tags::{self, TagTable, TagWatcher};
+use iiot_flow_host::iiot::flow::types::{FlowMsg, PortMsgs, TagValue, ValueKind};
+use iiot_flow_host::{kind_name, next_msg_id, proto, FusedPipeline, HostState, Node, TagMeta, TagRegistry};
 
 // ════════════════════════════════════════════════════════════════════════════
-// Store State
+// 執行模式
 // ════════════════════════════════════════════════════════════════════════════
 
-pub struct HostState {
-    wasi:     WasiCtx,
-    table:    ResourceTable,
-    registry: Arc<RwLock<TagRegistry>>,
-}
+// demo 的 TagUpdate 都由第一個 Source 接收：topo 順序的第一個 Node 沒有 input edge，一定是 Source
+const DEMO_SOURCE: usize = 0;
 
-impl WasiView for HostState {
-    fn ctx(&mut self) -> WasiCtxView<'_> { WasiCtxView { ctx: &mut self.wasi, table: &mut self.table } }
+enum Pipeline {
+    Dag(Box<FlowDag>),                          // flow.json：每個 Node 各自一個 Store，由 Host 路由
+    Fused(Box<FusedPipeline>, Box<Supervisor>), // flow-<name>.wasm / .cwasm：單一 component，routing 在 glue 內
 }
 
-fn make_store(engine: &Engine, registry: Arc<RwLock<TagRegistry>>) -> Store<HostState> {
-    let wasi = WasiCtxBuilder::new().inherit_stdio().max_random_size(u64::MAX).build();
-    let table: ResourceTable = ResourceTable::new();
-    // Store::new(engine, HostState { wasi, table, registry })
-    let mut store = Store::new(engine, HostState { wasi, table, registry });
-    store.set_hostcall_fuel(usize::MAX);
-    store
-}
-
-// ════════════════════════════════════════════════════════════════════════════
-// Deploy 型別檢查
-// ════════════════════════════════════════════════════════════════════════════
-
-fn type_check(
-    from: &str, from_out: ValueKind,
-    to:   &str, to_in:    &[ValueKind],
-) -> Result<()> {
-    let ok = to_in.iter().any(|k| *k == ValueKind::Any || *k == from_out)
-          || from_out == ValueKind::Any;
-    if !ok {
-        bail!("❌ 型別不符  {} ({:?}) → {} ({:?})", from, from_out, to, to_in);
+impl Pipeline {
+    fn fused(pipe: FusedPipeline) -> Self {
+        let supervisor = Supervisor::new(&format!("flow {}", pipe.name), SupervisorConfig::default());
+        Pipeline::Fused(Box::new(pipe), Box::new(supervisor))
     }
-    println!("  ✅ {:>30} → {:<30} ({:?})", from, to, from_out);
-    Ok(())
-}
 
-// ════════════════════════════════════════════════════════════════════════════
-// 通用 Node 包裝（flow-node world）
-// ════════════════════════════════════════════════════════════════════════════
-
-struct Node {
-    store:    Store<HostState>,
-    bindings: FlowNode,
-    pub name: String,
-}
-
-impl Node {
-    fn load(engine: &Engine, linker: &Linker<HostState>,
-            registry: Arc<RwLock<TagRegistry>>, path: &str) -> Result<Self> {
-        let component = Component::from_file(engine, path)
-            .with_context(|| format!("載入失敗：{path}"))?;
-        let mut store = make_store(engine, registry);
-        let bindings = FlowNode::instantiate(&mut store, &component, linker)?;
-        let name = bindings.iiot_flow_meta().call_name(&mut store)?;
-        Ok(Node { store, bindings, name })
+    /// 跑一筆 TagUpdate，回傳抵達 Sink 的值（fan-in 時每條分支各一筆；空 = 被 filter 掉或 crash）
+    fn run(&mut self, tag_id: u32, msg_id: u32, raw: &[u8]) -> Result<Vec<f64>> {
+        use prost::Message as _;
+        match self {
+            Pipeline::Dag(dag) => Ok(dag.run(DEMO_SOURCE, tag_id, msg_id, raw)?.iter()
+                .map(|m| match m.value { TagValue::F64Val(v) => v, _ => 0.0 })
+                .collect()),
+            Pipeline::Fused(fused, supervisor) => {
+                let Some(out) = supervisor.call(fused.as_mut(), |p| p.run(DEMO_SOURCE as u32, tag_id, msg_id, raw))? else {
+                    return Ok(Vec::new());
+                };
+                let out = proto::FusedOutput::decode(out.as_slice())?;
+                Ok(out.sunk.iter().map(|m| m.value.unwrap_or(0.0)).collect())
+            }
+        }
     }
 
-    fn meta_input_types(&mut self) -> Result<Vec<ValueKind>> {
-        Ok(self.bindings.iiot_flow_meta().call_accepted_input_types(&mut self.store)?)
-    }
-    fn meta_output_type(&mut self) -> Result<ValueKind> {
-        Ok(self.bindings.iiot_flow_meta().call_output_type(&mut self.store)?)
-    }
-    fn process(&mut self, msg: &FlowMsg) -> Result<Vec<FlowMsg>> {
-        Ok(self.bindings.iiot_flow_node().call_process(&mut self.store, msg)?.msgs)
-    }
-    fn process_raw(&mut self, tag_id: u32, msg_id: u32, raw: &[u8]) -> Result<Vec<FlowMsg>> {
-        Ok(self.bindings.iiot_flow_node()
-            .call_process_raw(&mut self.store, tag_id, msg_id, raw)?.msgs)
-    }
-    fn save_state(&mut self) -> Result<Vec<u8>> {
-        Ok(self.bindings.iiot_flow_node().call_save_state(&mut self.store)?)
-    }
-    fn load_state(&mut self, s: Vec<u8>) -> Result<()> {
-        Ok(self.bindings.iiot_flow_node().call_load_state(&mut self.store, &s)?)
+    fn dump_to(&mut self, dumper: Option<CrashDumper>) {
+        match self {
+            Pipeline::Dag(dag)    => dag.dump_to(dumper),
+            Pipeline::Fused(_, s) => s.dumps = dumper,
+        }
     }
-}
-
-// ════════════════════════════════════════════════════════════════════════════
-// Sink Node 包裝（flow-node-with-host world）
-// ════════════════════════════════════════════════════════════════════════════
 
-struct SinkNode {
-    store:    Store<HostState>,
-    bindings: sink_bindings::FlowNodeWithHost,
-    pub name: String,
-}
-
-impl SinkNode {
-    fn load(engine: &Engine, _linker: &Linker<HostState>,
-            registry: Arc<RwLock<TagRegistry>>, path: &str) -> Result<Self> {
-        let component = Component::from_file(engine, path)
-            .with_context(|| format!("載入失敗：{path}"))?;
-        let mut store = make_store(engine, registry);
-
-        // Sink 用自己的 linker（包含 host-api）
-        let mut sink_linker: Linker<HostState> = Linker::new(engine);
-        wasmtime_wasi::p2::add_to_linker_sync(&mut sink_linker)?;
-        add_host_api_to_linker(&mut sink_linker)?;
-
-        let bindings = sink_bindings::FlowNodeWithHost::instantiate(
-            &mut store, &component, &sink_linker)?;
-        let name = bindings.iiot_flow_meta().call_name(&mut store)?;
-        Ok(SinkNode { store, bindings, name })
+    /// 從 snapshot 還原狀態：(key, snapshot)
+    fn restore(&mut self, store: &SnapshotStore) -> Result<Vec<(String, Snapshot)>> {
+        match self {
+            Pipeline::Dag(dag) => dag.restore_snapshots(store),
+            Pipeline::Fused(fused, _) => {
+                let Some(snap) = store.load(&fused.name)? else { return Ok(Vec::new()); };
+                fused.load_states(snap.payload.clone())?;
+                Ok(vec![(fused.name.clone(), snap)])
+            }
+        }
     }
 
-    fn meta_input_types(&mut self) -> Result<Vec<ValueKind>> {
-        Ok(self.bindings.iiot_flow_meta().call_accepted_input_types(&mut self.store)?)
-    }
-    fn meta_output_type(&mut self) -> Result<ValueKind> {
-        Ok(self.bindings.iiot_flow_meta().call_output_type(&mut self.store)?)
-    }
-    fn process(&mut self, msg: &FlowMsg) -> Result<()> {
-        self.bindings.iiot_flow_node().call_process(&mut self.store, msg)?;
-        Ok(())
+    /// 寫入 snapshot，回傳份數
+    fn save(&mut self, store: &SnapshotStore) -> Result<usize> {
+        match self {
+            Pipeline::Dag(dag) => dag.save_snapshots(store),
+            Pipeline::Fused(fused, _) => {
+                let state = fused.save_states()?;
+                store.save(&fused.name, &state)?;
+                Ok(1)
+            }
+        }
     }
-}
-
-// ════════════════════════════════════════════════════════════════
-// Fused Pipeline 包裝（Fusion + AOT 後使用）
-// ════════════════════════════════════════════════════════════════
 
-struct FusedPipeline {
-    store:    Store<HostState>,
-    bindings: fused_bindings::FusedPipeline,
-    pub name: String,
-}
-
-impl FusedPipeline {
-    fn load(engine: &Engine,
-            registry: Arc<RwLock<TagRegistry>>,
-            path: &str) -> Result<Self> {
-        let component = if path.ends_with(".cwasm") {
-            // AOT 預編譯版本：直接 mmap，無 JIT 開銷
-            unsafe { Component::deserialize_file(engine, path) }
-                .with_context(|| format!("載入 AOT 失敗：{path}"))?
-        } else {
-            // 一般 .wasm：JIT 編譯
-            Component::from_file(engine, path)
-                .with_context(|| format!("載入失敗：{path}"))?
+    /// crash 過的 Node / flow：(名稱, crash 次數, 重啟次數, 最後一次的 crash dump)
+    fn crashes(&self) -> Vec<(&str, u64, u64, Option<&Path>)> {
+        let supervisors: Vec<&Supervisor> = match self {
+            Pipeline::Dag(dag)    => dag.supervisors.iter().collect(),
+            Pipeline::Fused(_, s) => vec![s.as_ref()],
         };
-
-        let mut store = make_store(engine, Arc::clone(&registry));
-
-        let mut fused_linker: Linker<HostState> = Linker::new(engine);
-        wasmtime_wasi::p2::add_to_linker_sync(&mut fused_linker)?;
-        add_host_api_to_linker(&mut fused_linker)?;
-
-        let bindings = fused_bindings::FusedPipeline::instantiate(
-            &mut store, &component, &fused_linker)?;
-        let name = bindings.pipeline().call_name(&mut store)?;
-
-        Ok(FusedPipeline { store, bindings, name })
-    }
-
-    // 核心：一次呼叫跑完整個 Pipeline
-    fn run(&mut self, tag_id: u32, msg_id: u32, raw: &[u8]) -> Result<Vec<u8>> {
-        Ok(self.bindings.pipeline()
-            .call_run(&mut self.store, tag_id, msg_id, raw)?)
-    }
-
-    fn save_states(&mut self) -> Result<Vec<u8>> {
-        Ok(self.bindings.pipeline().call_save_states(&mut self.store)?)
-    }
-
-    fn load_states(&mut self, states: Vec<u8>) -> Result<()> {
-        Ok(self.bindings.pipeline().call_load_states(&mut self.store, &states)?)
-    }
-}
-
-// ── 掛載 host-api Host Functions ─────────────────────────────────────────────
-fn add_host_api_to_linker(linker: &mut Linker<HostState>) -> Result<()> {
-    let mut root = linker.instance("iiot:flow/host-api@0.1.0")?;
-
-    root.func_wrap("get-tag-attr", |ctx, (tag_id, key): (u32, String)| {
-        let reg = ctx.data().registry.read().unwrap();
-        Ok((reg.get_attr(tag_id, &key),))
-    })?;
-
-    root.func_wrap("get-eng-range", |ctx, (tag_id,): (u32,)| {
-        let reg = ctx.data().registry.read().unwrap();
-        Ok((reg.get_eng_range(tag_id),))
-    })?;
-
-    root.func_wrap("log-debug", |_ctx, (node_name, msg): (String, String)| {
-        eprintln!("[WASM:{}] {}", node_name, msg);
-        Ok(())
-    })?;
-
-    Ok(())
-}
-
-// ════════════════════════════════════════════════════════════════════════════
-// Protobuf（Host 端）
-// ════════════════════════════════════════════════════════════════════════════
-
-mod proto {
-    #[derive(prost::Message, Clone)]
-    pub struct TagUpdate {
-        #[prost(string,  tag = "1")] pub tag_id_str: String,
-        #[prost(uint64,  tag = "2")] pub timestamp:  u64,
-        #[prost(uint32,  tag = "3")] pub quality:    u32,
-        #[prost(string,  tag = "4")] pub unit:       String,
-        #[prost(double, optional, tag = "14")] pub f64_val: Option<f64>,
-    }
-
-    #[derive(prost::Message)]
-    pub struct FlowResult {
-        #[prost(uint32, tag = "1")] pub tag_id:     u32,
-        #[prost(string, tag = "2")] pub tag_name:   String,
-        #[prost(string, tag = "3")] pub mqtt_topic: String,
-        #[prost(uint32, tag = "4")] pub msg_id:     u32,
-        #[prost(double, tag = "5")] pub value:      f64,
-        #[prost(uint64, tag = "6")] pub timestamp:  u64,
-        #[prost(uint32, tag = "7")] pub quality:    u32,
+        supervisors.into_iter().filter(|s| s.crashes > 0)
+            .map(|s| (s.name.as_str(), s.crashes, s.restarts, s.last.as_ref().and_then(|c| c.dump.as_deref())))
+            .collect()
     }
 }
 
@@ -342,71 +129,104 @@ mod proto {
 fn main() -> Result<()> {
     let args: Vec<String> = std::env::args().collect();
     let dir = if args.len() > 1 { args[1].as_str() } else { "." };
+    let flow_path = if args.len() > 2 { args[2].clone() } else { format!("{dir}/flow.json") };
+    let fused = flow_path.ends_with(".wasm") || flow_path.ends_with(".cwasm");
+    // 只給一個目錄且內含 manifest.json → flow-deploy Runtime
+    let deploy = args.len() <= 2 && Path::new(dir).join("manifest.json").is_file();
+    // tokio 版 Runtime（只影響 flow-deploy 模式）
+    let use_async = deploy && std::env::var("IIOT_FLOW_ASYNC").is_ok_and(|v| v != "0");
+    // 只給一個目錄且內含 flows.json → 多條 flow
+    let flows = args.len() <= 2 && !deploy && Path::new(dir).join("flows.json").is_file();
 
     println!("╔══════════════════════════════════════════════════════════════╗");
     println!("║  IIoT Flow Fusion Host                                       ║");
-    println!("║  Source → NodeA → NodeB → NodeC → Sink                      ║");
+    if use_async {
+        println!("║  flow-deploy/manifest.json → Runtime（tokio async）          ║");
+    } else if deploy {
+        println!("║  flow-deploy/manifest.json → Runtime                         ║");
+    } else if flows {
+        println!("║  flows.json → Flow Manager（多條 flow 各自獨立執行）         ║");
+    } else if fused {
+        println!("║  fused-pipeline component（單一 instance 執行）              ║");
+    } else {
+        println!("║  flow.json → DAG（未 Fusion，逐 Node 執行）                  ║");
+    }
     println!("╚══════════════════════════════════════════════════════════════╝\n");
 
     // ── Engine ───────────────────────────────────────────────────────────────
-    let mut config = Config::new();
-    config.wasm_component_model(true);
-    let engine = Engine::new(&config)?;
+    // component model + fuel / epoch 執行上限（limits.rs）
+    let engine = limits::new_engine()?;
 
     // 通用 linker（給 Source / Node A/B/C）
     let mut linker: Linker<HostState> = Linker::new(&engine);
     wasmtime_wasi::p2::add_to_linker_sync(&mut linker)?;
 
+    // AOT 快取：IIOT_FLOW_CACHE=<dir>（預設 <wasm_dir>/.cwasm-cache），=off 停用
+    let cache = match std::env::var("IIOT_FLOW_CACHE") {
+        Ok(v) if v == "off" => CompileCache::disabled(&engine),
+        Ok(v)               => CompileCache::new(&engine, v)?,
+        Err(_)              => CompileCache::new(&engine, Path::new(dir).join(".cwasm-cache"))?,
+    };
+
     // ── Tag Registry ─────────────────────────────────────────────────────────
+    // 設定檔以外的 tag 在第一次收到 TagUpdate 時建立（TagMeta::from_update）
     let registry = Arc::new(RwLock::new(TagRegistry::new()));
-    {
-        let mut reg = registry.write().unwrap();
-        reg.get_or_create("plant1.motor3.temp", TagMeta {
-            name:          "Motor 3 Temperature".to_string(),
-            unit:          "°C".to_string(),
-            mqtt_topic:    "factory/plant1/motor3/temperature".to_string(),
-            historian_tag: "PI:plant1_motor3_temp".to_string(),
-            alarm_group:   "critical".to_string(),
-            eng_low: 0.0, eng_high: 150.0,
-        });
-    }
-
-    // ── Step 1：載入 Nodes ───────────────────────────────────────────────────
-    println!("▶ Step 1：載入 WASM Nodes...");
-    let t0 = Instant::now();
-
-    let mk = |wasm: &str| -> Result<Node> {
-        Node::load(&engine, &linker, Arc::clone(&registry), &format!("{dir}/{wasm}"))
+    let _watcher = match TagTable::locate(dir) {
+        Some(path) => {
+            let t0 = Instant::now();
+            let table = TagTable::load(&path)?;
+            table.apply(&registry);
+            println!("🏷  Tag Registry：{} 個 tag（{}，{:.1}ms），檔案變更時自動重新載入\n",
+                table.tags.len(), path.display(), t0.elapsed().as_secs_f64() * 1000.0);
+            Some(TagWatcher::spawn(table, Arc::clone(&registry), tags::WATCH_INTERVAL)?)
+        }
+        None => {
+            println!("🏷  Tag Registry：找不到 tag 設定檔（IIOT_FLOW_TAGS 或 {dir}/tags.*），tag 依收到的 TagUpdate 建立\n");
+            None
+        }
     };
 
-    let mut source = mk("source_node.wasm")?;
-    let mut node_a = mk("node_a.wasm")?;
-    let mut node_b = mk("node_b.wasm")?;
-    let mut node_c = mk("node_c.wasm")?;
-    let mut sink   = SinkNode::load(
-        &engine, &linker, Arc::clone(&registry), &format!("{dir}/sink_node.wasm"))?;
-
-    println!("  {} │ {} │ {} │ {} │ {}",
-        source.name, node_a.name, node_b.name, node_c.name, sink.name);
-    println!("  ✅ 完成 ({:.1}ms)\n", t0.elapsed().as_secs_f64() * 1000.0);
-
-    // ── Step 2：Deploy 型別檢查 ──────────────────────────────────────────────
-    println!("▶ Step 2：Deploy 型別檢查...");
-
-    let src_out  = source.meta_output_type()?;
-    let a_in     = node_a.meta_input_types()?;
-    let a_out    = node_a.meta_output_type()?;
-    let b_in     = node_b.meta_input_types()?;
-    let b_out    = node_b.meta_output_type()?;
-    let c_in     = node_c.meta_input_types()?;
-    let c_out    = node_c.meta_output_type()?;
-    let sink_in  = sink.meta_input_types()?;
-
-    type_check(&source.name, src_out, &node_a.name, &a_in)?;
-    type_check(&node_a.name, a_out,   &node_b.name, &b_in)?;
-    type_check(&node_b.name, b_out,   &node_c.name, &c_in)?;
-    type_check(&node_c.name, c_out,   &sink.name,   &sink_in)?;
-    println!("  ✅ 型別全部相容，允許 Deploy\n");
+    if use_async {
+        return run_deploy_async(&engine, &cache, registry, dir);
+    }
+    if deploy {
+        return run_deploy(&engine, &cache, registry, dir);
+    }
+    if flows {
+        return run_flows(&engine, &cache, registry, dir);
+    }
+
+    let mut pipeline = if fused {
+        // ── Step 1：載入 Fused Pipeline ─────────────────────────────────────
+        println!("▶ Step 1：載入 Fused Pipeline {flow_path}...");
+        let t0 = Instant::now();
+        let pipe = FusedPipeline::load(&engine, &cache, Arc::clone(&registry), &flow_path,
+                                       ExecLimits::default())?;
+        let (hits, misses) = cache.stats();
+        println!("  ✅ 完成 {} v{} ({:.1}ms，AOT 快取命中 {hits} / 編譯 {misses}，linear memory {:.1}MB)\n",
+            pipe.name, pipe.version, t0.elapsed().as_secs_f64() * 1000.0, pipe.memory() as f64 / 1048576.0);
+
+        // ── Step 2：Deploy 驗證 ─────────────────────────────────────────────
+        println!("▶ Step 2：Deploy 驗證已在 iiot-flow-fusion 產生時完成，略過\n");
+        Pipeline::fused(pipe)
+    } else {
+        Pipeline::Dag(Box::new(load_dag(&engine, &linker, &cache, &registry, dir, &flow_path)?))
+    };
+    pipeline.dump_to(match std::env::var("IIOT_FLOW_CRASH_DIR") {
+        Ok(v) if v == "off" => None,
+        Ok(v)               => CrashDumpConfig { dir: v, ..Default::default() }.dumper(Path::new("")),
+        Err(_)              => CrashDumpConfig::default().dumper(Path::new(dir)),
+    });
+    let snapshots = match std::env::var("IIOT_FLOW_STATE_DIR") {
+        Ok(v) => Some(SnapshotStore::open(v, Duration::ZERO, 3)?),
+        Err(_) => None,
+    };
+    if let Some(store) = &snapshots {
+        for (key, snap) in pipeline.restore(store)? {
+            println!("  ♻️  {key} 已還原狀態：{}（#{}，{} bytes）", snap.path.display(), snap.seq, snap.payload.len());
+        }
+        println!();
+    }
 
     // ── Step 3：模擬 TagUpdate 流 ────────────────────────────────────────────
     println!("▶ Step 3：模擬 Protocol Driver TagUpdate 流...\n");
@@ -426,13 +246,6 @@ fn main() -> Result<()> {
     for (tag_name, raw_val, quality) in inputs {
         use prost::Message as _;
 
-        // Host 職責：分配 tag_id + msg_id
-        let tag_id = registry.write().unwrap().get_or_create(tag_name, TagMeta {
-            name: tag_name.to_string(), unit: "°C".to_string(),
-            mqtt_topic: format!("iiot/{}", tag_name.replace('.', "/")),
-            historian_tag: String::new(), alarm_group: "default".to_string(),
-            eng_low: 0.0, eng_high: 150.0,
-        });
         let msg_id = next_msg_id();
 
         // 組 Protobuf bytes（模擬 nng 進來的資料）
@@ -446,53 +259,57 @@ fn main() -> Result<()> {
         let mut bytes = Vec::new();
         tu.encode(&mut bytes)?;
 
+        // Host 職責：分配 tag_id（Tag Registry 沒有的 tag 依 TagUpdate 建立）
+        let tag_id = registry.write().unwrap().get_or_create(tag_name, TagMeta::from_update(&tu));
+
         print!("  IN  {:<25} raw={:>5.1}°C  q={}  id={:>3} │ ",
                tag_name, raw_val, quality, msg_id);
 
-        // ── Pipeline ─────────────────────────────────────────────────────
-        let msgs = source.process_raw(tag_id, msg_id, &bytes)?;
-        if msgs.is_empty() { println!("DROPPED (source)"); continue; }
-
-        let msgs = run_node(&mut node_a, msgs)?;
-        if msgs.is_empty() { println!("DROPPED (node-a)"); continue; }
-
-        let msgs = run_node(&mut node_b, msgs)?;
-        if msgs.is_empty() { println!("DROPPED (bad quality)"); continue; }
-
-        let msgs = run_node(&mut node_c, msgs)?;
-        if msgs.is_empty() { println!("DROPPED (node-c)"); continue; }
-
-        // 取 Node C 的最終 avg 值顯示
-        let avg = match msgs[0].value { TagValue::F64Val(v) => v, _ => 0.0 };
+        let sunk = pipeline.run(tag_id, msg_id, &bytes)?;
+        if sunk.is_empty() { println!("DROPPED"); continue; }
 
-        // Sink：查 Registry + encode（副作用，不回傳 FlowMsg）
-        sink.process(&msgs[0])?;
+        let avgs: Vec<String> = sunk.iter().map(|v| format!("{v:>8.4}")).collect();
 
         let mqtt = registry.read().unwrap()
             .get_attr(tag_id, "mqtt_topic").unwrap_or_default();
-        println!("OUT avg={:>8.4}°F  → {}", avg, mqtt);
+        println!("OUT avg={}°F  → {}", avgs.join(" /"), mqtt);
+    }
+    if let Some(store) = &snapshots {
+        let n = pipeline.save(store)?;
+        println!("  💾 已寫入 {n} 份 snapshot 到 {}", store.dir.display());
+    }
+    for (name, crashes, restarts, dump) in pipeline.crashes() {
+        println!("  ⚠️  {name} crash {crashes} 次 / 重啟 {restarts} 次");
+        if let Some(dir) = dump { println!("     crash dump：{}", dir.display()); }
     }
 
     // ── Step 4：Snapshot / Restore ──────────────────────────────────────────
-    println!("\n▶ Step 4：Node C Snapshot / Restore...");
-    let snap = node_c.save_state()?;
-    println!("  Snapshot {} bytes", snap.len());
-
-    let mut node_c2 = mk("node_c.wasm")?;
-    node_c2.load_state(snap)?;
-
-    let test_msg = FlowMsg {
-        tag_id: 1, msg_id: 9999,
-        value: TagValue::F64Val(200.0),
-        timestamp: 0, quality: 0,
-    };
-    let r1 = node_c.process(&test_msg)?;
-    let r2 = node_c2.process(&test_msg)?;
-    let v1 = match r1[0].value { TagValue::F64Val(v) => v, _ => 0.0 };
-    let v2 = match r2[0].value { TagValue::F64Val(v) => v, _ => 0.0 };
-    println!("  原始={:.6}  還原={:.6}  差={:.2e}  {}",
-        v1, v2, (v1-v2).abs(),
-        if (v1-v2).abs() < 1e-10 { "✅" } else { "❌" });
+    match &mut pipeline {
+        Pipeline::Dag(dag) => snapshot_node_c(dag, &engine, &linker, &cache, &registry, dir)?,
+        Pipeline::Fused(pipe, _) => {
+            println!("\n▶ Step 4：Fused Pipeline Snapshot / Restore...");
+            let snap = pipe.save_states()?;
+            println!("  Snapshot {} bytes（全部 Node）", snap.len());
+
+            let mut pipe2 = FusedPipeline::load(&engine, &cache, Arc::clone(&registry), &flow_path,
+                                                ExecLimits::default())?;
+            pipe2.load_states(snap)?;
+            let mut pipe2 = Pipeline::fused(pipe2);
+
+            use prost::Message as _;
+            let test = proto::TagUpdate {
+                tag_id_str: "plant1.motor3.temp".to_string(),
+                timestamp: 0, quality: 0, unit: "°C".to_string(),
+                f64_val: Some(93.0),
+            }.encode_to_vec();
+            let r1 = pipeline.run(1, 9999, &test)?;
+            let r2 = pipe2.run(1, 9999, &test)?;
+            let (v1, v2) = (r1.first().copied().unwrap_or(0.0), r2.first().copied().unwrap_or(0.0));
+            println!("  原始={:.6}  還原={:.6}  差={:.2e}  {}",
+                v1, v2, (v1-v2).abs(),
+                if (v1-v2).abs() < 1e-10 { "✅" } else { "❌" });
+        }
+    }
 
     // ── Step 5：Benchmark ───────────────────────────────────────────────────
     println!("\n▶ Step 5：吞吐量 Benchmark (10萬筆)...");
@@ -508,12 +325,8 @@ fn main() -> Result<()> {
     const N: u64 = 100_000;
     let t = Instant::now();
     for _ in 0..N {
-        let mid  = next_msg_id();
-        let msgs = source.process_raw(1, mid, &bench_bytes)?;
-        let msgs = run_node(&mut node_a, msgs)?;
-        let msgs = run_node(&mut node_b, msgs)?;
-        let msgs = run_node(&mut node_c, msgs)?;
-        sink.process(msgs.first().unwrap())?;
+        let mid = next_msg_id();
+        pipeline.run(1, mid, &bench_bytes)?;
     }
     let el = t.elapsed();
     println!("  吞吐量   = {:.0} msgs/sec", (N as f64) / el.as_secs_f64());
@@ -522,11 +335,408 @@ fn main() -> Result<()> {
         if (el.as_micros() as f64) / (N as f64) < 500.0 { "✅ < 500µs 目標達成" }
         else { "⚠️  超過 500µs（請用 release build）" });
 
+    // Fused：同樣 10 萬筆改走微批次（run-batch），一次 component 呼叫跑多筆
+    if let Pipeline::Fused(pipe, _) = &mut pipeline {
+        for size in [16, 256] {
+            pipe.batch.max_size = size;
+            let t = Instant::now();
+            for _ in 0..N {
+                pipe.push(DEMO_SOURCE as u32, 1, next_msg_id(), bench_bytes.clone())?;
+            }
+            pipe.flush()?;
+            let el = t.elapsed();
+            println!("  run-batch {size:>3} 筆/批 = {:.0} msgs/sec（{:.3}µs/msg）",
+                (N as f64) / el.as_secs_f64(), (el.as_micros() as f64) / (N as f64));
+        }
+        bench_parallel(&engine, &cache, &registry, &flow_path, N, &bench_bytes)?;
+    }
+
+    Ok(())
+}
+
+// ── Fused：Step 6 多條 flow 平行 benchmark（pool.rs）─────────────────────────
+// 同一個 artifact 載入多個 instance（各自一個 Store），main thread 當 Router 把 frame 放進每條 flow 的 queue，
+// 比較全部 flow 擠在 1 條 worker thread 與分散到多條 worker thread 的總吞吐量
+
+// 每次 step 最多從 queue 取出的 frame 數
+const BENCH_STEP: usize = 4096;
+
+struct BenchFlow {
+    pipeline: FusedPipeline,
+    rx:       Receiver<Vec<u8>>,
+}
+
+impl Task for BenchFlow {
+    fn step(&mut self) -> Result<Option<Instant>> {
+        for _ in 0..BENCH_STEP {
+            match self.rx.try_recv() {
+                Ok(frame) => { self.pipeline.push(DEMO_SOURCE as u32, 1, next_msg_id(), frame)?; }
+                Err(TryRecvError::Empty) => return Ok(Some(Instant::now() + Duration::from_micros(100))),
+                Err(TryRecvError::Disconnected) => return Ok(None),
+            }
+        }
+        Ok(Some(Instant::now()))
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        self.pipeline.flush().map(drop)
+    }
+}
+
+fn bench_parallel(engine: &Engine, cache: &CompileCache, registry: &Arc<RwLock<TagRegistry>>, flow_path: &str,
+                  total: u64, frame: &[u8]) -> Result<()> {
+    let cores = std::thread::available_parallelism().map_or(1, |n| n.get());
+    let env = |key: &str, default: usize| std::env::var(key).ok().and_then(|v| v.parse().ok()).unwrap_or(default);
+    let flows   = env("IIOT_FLOW_BENCH_FLOWS", cores.max(2)).max(1);
+    let workers = env("IIOT_FLOW_WORKERS", cores).max(1);
+    let per_flow = total / flows as u64;
+    println!("\n▶ Step 6：多條 flow 平行 Benchmark（{flows} 條 flow × {per_flow} 筆，{cores} 個 CPU core）...");
+
+    let mut pipelines = (0..flows)
+        .map(|_| FusedPipeline::load(engine, cache, Arc::clone(registry), flow_path, ExecLimits::default()))
+        .collect::<Result<Vec<_>>>()?;
+    let mut best = 0.0f64;
+    let mut single = 0.0f64;
+    for n in if workers > 1 { vec![1, workers] } else { vec![1] } {
+        let (back, el) = run_sharded(pipelines, n, per_flow, frame)?;
+        pipelines = back;
+        let rate = (per_flow * flows as u64) as f64 / el.as_secs_f64();
+        if n == 1 { single = rate; }
+        best = best.max(rate);
+        println!("  {n:>2} worker thread{} = {rate:.0} msgs/sec{}", if n == 1 { " " } else { "s" },
+            if n == 1 { "（單一 thread）".to_string() } else { format!("（shard，{:.2}x）", rate / single) });
+    }
+    println!("  {}",
+        if best >= 50_000.0 { "✅ ≥ 50,000 tags/sec 目標達成" }
+        else { "⚠️  低於 50,000 tags/sec（請用 release build 或增加 IIOT_FLOW_WORKERS）" });
+    Ok(())
+}
+
+// 在 workers 條 thread 上跑完每條 flow 各 per_flow 筆，交回 pipeline 與花費時間
+fn run_sharded(pipelines: Vec<FusedPipeline>, workers: usize, per_flow: u64, frame: &[u8])
+    -> Result<(Vec<FusedPipeline>, Duration)> {
+    let pool = WorkerPool::new("bench", workers)?;
+    let stop = Arc::new(AtomicBool::new(false));
+    let t = Instant::now();
+    let (mut queues, mut dones) = (Vec::new(), Vec::new());
+    for (i, pipeline) in pipelines.into_iter().enumerate() {
+        let (tx, rx) = mpsc::sync_channel(BENCH_STEP);
+        dones.push(pool.spawn(&i.to_string(), BenchFlow { pipeline, rx }, Arc::clone(&stop))?);
+        queues.push(tx);
+    }
+    for _ in 0..per_flow {
+        for q in &queues { q.send(frame.to_vec())?; }
+    }
+    drop(queues);
+    let mut back = Vec::new();
+    for done in dones {
+        let done = done.recv()?;
+        done.result?;
+        back.push(done.task.pipeline);
+    }
+    let el = t.elapsed();
+    pool.shutdown();
+    Ok((back, el))
+}
+
+// ── Deploy 模式：manifest.json 驅動，直到所有 source 結束 ─────────────────────
+fn run_deploy(engine: &Engine, cache: &CompileCache, registry: Arc<RwLock<TagRegistry>>,
+              dir: &str) -> Result<()> {
+    println!("▶ 載入 {dir}/manifest.json...");
+    let t0 = Instant::now();
+    let mut rt = Runtime::load(engine, cache, registry, dir)?;
+    let (hits, misses) = cache.stats();
+    println!("  ✅ {} v{}：{} ({:.1}ms，AOT 快取命中 {hits} / 編譯 {misses}，linear memory {:.1}MB)",
+        rt.live.manifest.flow_id, rt.live.pipeline.version, rt.live.artifact.display(),
+        t0.elapsed().as_secs_f64() * 1000.0, rt.live.pipeline.memory() as f64 / 1048576.0);
+    for s in &rt.live.manifest.sources {
+        println!("  ⏱  source   {:<16} 每 {}ms（overrun：{:?}）  {}",
+            s.tick_export, s.interval_ms, s.overrun, s.description);
+    }
+    for e in &rt.live.manifest.host_endpoints {
+        println!("  🔌 endpoint {:<16} {:<10} {:<6} {}", e.id, e.kind, e.role, e.address);
+    }
+    for s in &rt.live.manifest.sink_ends {
+        println!("  🔔 sink-end {:<16} {:<10} {}", s.id, s.handler, s.target);
+    }
+    if let Some(store) = &rt.live.snapshots {
+        println!("  💾 snapshot {} 每 {}ms", store.dir.display(), store.interval.as_millis());
+    }
+    if let Some(snap) = &rt.restored {
+        println!("  ♻️  已還原狀態：{}（#{}，{} bytes）", snap.path.display(), snap.seq, snap.payload.len());
+    }
+
+    let stop = stop_signals()?;
+    // SIGHUP：重新讀取 manifest.json / artifact，不停機切換（redeploy.rs）
+    signal_hook::flag::register(SIGHUP, Arc::clone(&rt.reload))?;
+
+    println!("\n▶ 執行中（Ctrl-C / SIGTERM 停止；SIGHUP 重新部署；所有 source 結束後也會停止）...\n");
+    rt.run(&stop)?;
+    println!("\n  {}", if stop.load(Ordering::Relaxed) { "🛑 收到停止訊號" } else { "✅ 所有 source 已結束" });
+    report(&rt);
+    Ok(())
+}
+
+// ── tokio 版 Runtime（IIOT_FLOW_ASYNC=1）─────────────────────────────────────
+fn run_deploy_async(engine: &Engine, cache: &CompileCache, registry: Arc<RwLock<TagRegistry>>,
+                    dir: &str) -> Result<()> {
+    // snapshot / crash dump 以 block_in_place 寫檔，需要 multi-thread runtime
+    let tokio = tokio::runtime::Builder::new_multi_thread().enable_all().build()?;
+    tokio.block_on(async {
+        println!("▶ 載入 {dir}/manifest.json（async）...");
+        let t0 = Instant::now();
+        let mut rt = AsyncRuntime::load(engine, cache, registry, dir).await?;
+        let (hits, misses) = cache.stats();
+        println!("  ✅ {} v{}：{} ({:.1}ms，AOT 快取命中 {hits} / 編譯 {misses}，linear memory {:.1}MB)",
+            rt.live.manifest.flow_id, rt.live.pipeline.version, rt.live.artifact.display(),
+            t0.elapsed().as_secs_f64() * 1000.0, rt.live.pipeline.memory() as f64 / 1048576.0);
+        for s in &rt.live.manifest.sources {
+            println!("  ⏱  source   {:<16} 每 {}ms（overrun：{:?}）  {}",
+                s.tick_export, s.interval_ms, s.overrun, s.description);
+        }
+        for e in &rt.live.manifest.host_endpoints {
+            println!("  🔌 endpoint {:<16} {:<10} {:<6} {}", e.id, e.kind, e.role, e.address);
+        }
+        for s in &rt.live.manifest.sink_ends {
+            println!("  🔔 sink-end {:<16} {:<10} {}", s.id, s.handler, s.target);
+        }
+        if let Some(store) = &rt.live.snapshots {
+            println!("  💾 snapshot {} 每 {}ms", store.dir.display(), store.interval.as_millis());
+        }
+        if let Some(snap) = &rt.restored {
+            println!("  ♻️  已還原狀態：{}（#{}，{} bytes）", snap.path.display(), snap.seq, snap.payload.len());
+        }
+
+        let stop = stop_signals()?;
+        signal_hook::flag::register(SIGHUP, Arc::clone(&rt.reload))?;
+
+        println!("\n▶ 執行中（tokio，Ctrl-C / SIGTERM 停止；SIGHUP 重新部署；所有 source 結束後也會停止）...\n");
+        rt.run(&stop).await?;
+        println!("\n  {}", if stop.load(Ordering::Relaxed) { "🛑 收到停止訊號" } else { "✅ 所有 source 已結束" });
+        report_async(&rt);
+        Ok(())
+    })
+}
+
+// 同 report；sink / sink-end 的寫出結果來自各自的 task
+fn report_async(rt: &AsyncRuntime) {
+    for (i, name) in rt.source_names().enumerate() {
+        let t = rt.schedule.stats(i);
+        println!("  ⏱  {name:<16} tick {:>6}  jitter 平均 {:>8.3}ms / 最大 {:>8.3}ms  overrun {} / 跳過 {}",
+            t.ticks, t.jitter_mean().as_secs_f64() * 1000.0, t.jitter_max.as_secs_f64() * 1000.0,
+            t.overruns, t.skipped);
+    }
+    for input in rt.inputs() {
+        let Some(s) = input.ingest_stats() else { continue; };
+        println!("  📥 endpoint {:<4} nng PULL  連線 {} / 接收 {} / 拒絕 {}", input.id,
+            s.connections.load(Ordering::Relaxed), s.received.load(Ordering::Relaxed),
+            s.rejected.load(Ordering::Relaxed));
+    }
+    let (st, io) = (&rt.stats, &rt.io);
+    println!("  📊 frame {} / sink {} / sink-end {} / 丟棄 {} / 遺失 {}",
+        st.frames, st.sunk, st.events, st.dropped, st.lost);
+    println!("  📤 寫出 {} / I/O 錯誤 {} / queue 滿丟棄 {}", io.sent.load(Ordering::Relaxed),
+        io.io_errors.load(Ordering::Relaxed), io.overflow.load(Ordering::Relaxed));
+    if st.redeploys + st.rollbacks > 0 {
+        println!("  🔀 redeploy 切換 {} 次 / 取消或換回 {} 次，目前 v{}（{}）",
+            st.redeploys, st.rollbacks, rt.live.pipeline.version, rt.live.artifact.display());
+    }
+    let sv = &rt.live.supervisor;
+    if sv.crashes > 0 {
+        println!("  🧯 {} crash {} 次 / 重啟 {} 次 / 狀態 {:?}", sv.name, sv.crashes, sv.restarts, sv.health());
+        if let Some(c) = &sv.last {
+            println!("     最後一次：{}", c.error);
+            if let Some(dir) = &c.dump { println!("     crash dump：{}", dir.display()); }
+        }
+    }
+}
+
+// SIGINT / SIGTERM：做完當下的 tick 後結束；第二次訊號直接終止
+fn stop_signals() -> Result<Arc<AtomicBool>> {
+    let stop = Arc::new(AtomicBool::new(false));
+    for sig in [SIGINT, SIGTERM] {
+        signal_hook::flag::register_conditional_shutdown(sig, 1, Arc::clone(&stop))?;
+        signal_hook::flag::register(sig, Arc::clone(&stop))?;
+    }
+    Ok(stop)
+}
+
+// Runtime 結束時的統計
+fn report(rt: &Runtime) {
+    for (i, name) in rt.source_names().enumerate() {
+        let t = rt.schedule.stats(i);
+        println!("  ⏱  {name:<16} tick {:>6}  jitter 平均 {:>8.3}ms / 最大 {:>8.3}ms  overrun {} / 跳過 {}",
+            t.ticks, t.jitter_mean().as_secs_f64() * 1000.0, t.jitter_max.as_secs_f64() * 1000.0,
+            t.overruns, t.skipped);
+    }
+    for ep in rt.endpoints() {
+        let Some(s) = ep.ingest_stats() else { continue; };
+        println!("  📥 endpoint {:<4} nng PULL  連線 {} / 接收 {} / 拒絕 {}", ep.id,
+            s.connections.load(Ordering::Relaxed), s.received.load(Ordering::Relaxed),
+            s.rejected.load(Ordering::Relaxed));
+    }
+    let st = &rt.stats;
+    println!("  📊 frame {} / sink {} / sink-end {} / 丟棄 {} / 遺失 {} / I/O 錯誤 {} / webhook queue 滿丟棄 {}",
+        st.frames, st.sunk, st.events, st.dropped, st.lost, st.io_errors, st.overflow);
+    if st.redeploys + st.rollbacks > 0 {
+        println!("  🔀 redeploy 切換 {} 次 / 取消或換回 {} 次，目前 v{}（{}）",
+            st.redeploys, st.rollbacks, rt.live.pipeline.version, rt.live.artifact.display());
+    }
+    let sv = &rt.live.supervisor;
+    if sv.crashes > 0 {
+        println!("  🧯 {} crash {} 次 / 重啟 {} 次 / 狀態 {:?}", sv.name, sv.crashes, sv.restarts, sv.health());
+        if let Some(c) = &sv.last {
+            println!("     最後一次：{}", c.error);
+            if let Some(dir) = &c.dump { println!("     crash dump：{}", dir.display()); }
+        }
+    }
+}
+
+// ── Flow Manager 模式：flows.json 的多條 flow，stdin 逐行讀取指令 ─────────────
+fn run_flows(engine: &Engine, cache: &CompileCache, registry: Arc<RwLock<TagRegistry>>,
+             dir: &str) -> Result<()> {
+    println!("▶ 載入 {dir}/flows.json...");
+    let mut mgr = FlowManager::load(engine, cache, registry, dir)?;
+    for e in &mgr.config.inputs {
+        println!("  🔌 input    {:<16} {:<10} {}", e.id, e.kind, e.address);
+    }
+    for (f, entry) in mgr.list().iter().zip(&mgr.config.flows) {
+        println!("  🧩 flow     {:<16} {:<10} 訂閱 {}", f.id, if entry.autostart { "autostart" } else { "手動啟動" },
+            f.subscribe.join(", "));
+    }
+    match mgr.worker_loads().len() {
+        0 => println!("  🧵 worker   每條 flow 一條專屬 thread"),
+        n => println!("  🧵 worker   {n} 條 thread，flow 依負載分散"),
+    }
+    mgr.start_all();
+
+    let stop = stop_signals()?;
+    // SIGHUP：所有執行中的 flow 重新部署
+    let reload = Arc::new(AtomicBool::new(false));
+    signal_hook::flag::register(SIGHUP, Arc::clone(&reload))?;
+    let (tx, commands) = mpsc::channel();
+    std::thread::spawn(move || {
+        for line in std::io::stdin().lock().lines().map_while(Result::ok) {
+            if tx.send(line).is_err() { break; }
+        }
+    });
+
+    println!("\n▶ 執行中（指令：list / workers / start <flow> / stop <flow> / redeploy <flow>；Ctrl-C / SIGTERM 停止；\
+              SIGHUP 重新部署全部 flow）...\n");
+    mgr.run(&stop, |mgr| {
+        if reload.swap(false, Ordering::Relaxed) {
+            for f in mgr.list().into_iter().filter(|f| f.uptime.is_some()) {
+                if let Err(e) = mgr.redeploy(&f.id) { eprintln!("⚠️  {e:#}"); }
+            }
+        }
+        while let Ok(line) = commands.try_recv() {
+            if let Err(e) = command(mgr, &line) { eprintln!("⚠️  {e:#}"); }
+        }
+    })?;
+    println!("\n  {}", if stop.load(Ordering::Relaxed) { "🛑 收到停止訊號" } else { "✅ 所有 input 與 flow 已結束" });
+
+    let st = &mgr.stats;
+    println!("  🧭 router frame {} / 轉送 {} / 無人訂閱 {} / 丟棄 {} / queue 滿 {}",
+        st.frames, st.routed, st.unrouted, st.dropped, st.overflow);
+    for (id, exit) in mgr.exits() {
+        println!("\n  ── flow {id} ──");
+        if let Err(e) = &exit.result { println!("  ❌ {e:#}"); }
+        if let Some(rt) = &exit.runtime { report(rt); }
+    }
+    Ok(())
+}
+
+fn command(mgr: &mut FlowManager, line: &str) -> Result<()> {
+    let words: Vec<&str> = line.split_whitespace().collect();
+    match words.as_slice() {
+        [] => {}
+        ["list"] => for f in mgr.list() {
+            let uptime = f.uptime.map_or(String::new(), |d| format!("{:.1}s", d.as_secs_f64()));
+            println!("  🧩 {:<16} {:<8} {:>8}  tag {:>3}  轉送 {:>8}  queue 滿 {:>6}  訂閱 {}",
+                f.id, format!("{:?}", f.state), uptime, f.tags, f.routed, f.overflow, f.subscribe.join(", "));
+        }
+        ["workers"] => match mgr.worker_loads() {
+            loads if loads.is_empty() => println!("  🧵 每條 flow 一條專屬 thread"),
+            loads => println!("  🧵 各 worker 的 flow 數：{loads:?}"),
+        },
+        ["start", id]    => mgr.start(id)?,
+        ["stop", id]     => mgr.stop(id)?,
+        ["redeploy", id] => mgr.redeploy(id)?,
+        _ => bail!("無法辨識的指令：{line}（list / workers / start <flow> / stop <flow> / redeploy <flow>）"),
+    }
     Ok(())
 }
 
-fn run_node(node: &mut Node, msgs: Vec<FlowMsg>) -> Result<Vec<FlowMsg>> {
-    let mut out = Vec::new();
-    for msg in msgs { out.extend(node.process(&msg)?); }
-    Ok(out)
+// ── DAG 模式：Step 1 載入 + Step 2 驗證結果 ──────────────────────────────────
+fn load_dag(engine: &Engine, linker: &Linker<HostState>, cache: &CompileCache, registry: &Arc<RwLock<TagRegistry>>,
+            dir: &str, flow_path: &str) -> Result<FlowDag> {
+    // ── Step 1：載入 flow.json 與 Nodes ─────────────────────────────────────
+    println!("▶ Step 1：載入 {flow_path} 與 WASM Nodes...");
+    let t0 = Instant::now();
+
+    let flow = FlowDef::load(flow_path)?;
+    let dag = FlowDag::load(engine, linker, cache, Arc::clone(registry), &flow, Path::new(dir))?;
+
+    let names: Vec<&str> = dag.nodes.iter().map(|n| n.name()).collect();
+    println!("  {}", names.join(" │ "));
+    let (hits, misses) = cache.stats();
+    println!("  ✅ 完成 flow v{}：{} nodes / {} edges ({:.1}ms，AOT 快取命中 {hits} / 編譯 {misses})\n",
+        flow.version, dag.nodes.len(), flow.edges.len(), t0.elapsed().as_secs_f64() * 1000.0);
+
+    // ── Step 2：Deploy 驗證結果 ──────────────────────────────────────────────
+    // 六個 validate pass 已在 FlowDag::load 內完成，失敗時輸出 §14.1 JSON 報告
+    println!("▶ Step 2：Deploy 驗證結果...");
+
+    for (e, kind) in flow.edges.iter().zip(&dag.resolved_types) {
+        let to_kind = dag.node(&e.to)
+            .and_then(|n| n.spec().inputs.iter().find(|i| i.port_id == e.to_port))
+            .map_or(ValueKind::Any, |i| i.kind);
+        let (a, b) = (format!("{}:{}", e.from, e.from_port), format!("{}:{}", e.to, e.to_port));
+        println!("  ✅ {a:>24} → {b:<24} ({} → {})", kind_name(*kind), kind_name(to_kind));
+    }
+    for w in &dag.warnings {
+        println!("  ⚠️  [{}] {}", w.code, w.message);
+    }
+    println!("  ✅ 六項驗證全部通過，允許 Deploy\n");
+
+    Ok(dag)
+}
+
+// ── DAG 模式：Step 4 Node C 單獨 snapshot 後還原到新 instance ─────────────────
+fn snapshot_node_c(dag: &mut FlowDag, engine: &Engine, linker: &Linker<HostState>, cache: &CompileCache,
+                   registry: &Arc<RwLock<TagRegistry>>, dir: &str) -> Result<()> {
+    let def = dag.ir.node("node-c").map(|def| (def.wasm.clone(), def.limits));
+    // 新 instance 以 DAG 建立時相同的 props / wiring init 之後才能 load_state
+    let init_args = dag.ids.iter().position(|id| id == "node-c").and_then(|p| dag.init_args[p].clone());
+    if let (Some((wasm, limits)), Some(DagNode::Node(node_c))) = (def, dag.node_mut("node-c")) {
+        println!("\n▶ Step 4：Node C Snapshot / Restore...");
+        let snap = node_c.save_state()?;
+        println!("  Snapshot {} bytes", snap.len());
+
+        let mut node_c2 = Node::load(engine, linker, cache, Arc::clone(registry),
+                                     &format!("{dir}/{wasm}"), limits)?;
+        if let Some((props, wiring)) = &init_args { node_c2.init(props, wiring)?; }
+        node_c2.load_state(snap)?;
+
+        let test_msg = FlowMsg {
+            tag_id: 1, msg_id: 9999,
+            value: TagValue::F64Val(200.0),
+            timestamp: 0, quality: 0,
+        };
+        let r1 = node_c.process(0, std::slice::from_ref(&test_msg))?;
+        let r2 = node_c2.process(0, std::slice::from_ref(&test_msg))?;
+        // node-c 沒有輸出（非 f64 值、props 變更等）時只回報，不中斷 demo
+        let value = |out: &[PortMsgs]| match out.first().and_then(|o| o.msgs.first()).map(|m| &m.value) {
+            Some(TagValue::F64Val(v)) => Some(*v),
+            _ => None,
+        };
+        match (value(&r1), value(&r2)) {
+            (Some(v1), Some(v2)) => println!("  原始={:.6}  還原={:.6}  差={:.2e}  {}",
+                v1, v2, (v1-v2).abs(),
+                if (v1-v2).abs() < 1e-10 { "✅" } else { "❌" }),
+            (v1, v2) => println!("  ❌ node-c 沒有 f64 輸出，無法比對（原始 {v1:?} / 還原 {v2:?}）"),
+        }
+    }
+
+    Ok(())
 }
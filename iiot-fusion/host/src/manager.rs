@@ -0,0 +1,560 @@
+// host/src/manager.rs
+// 同一個 Host process 內執行多條互不影響的 flow（flows.json）
+//
+//   flows.json
+//     inputs   共用的 source endpoint（nng-pull / tcp-client / file），Driver 只需要連到這裡
+//     flows    各 flow-deploy 目錄；autostart = false 的 flow 只檢查 manifest，等 start 才啟動
+//
+//   每條 flow 是獨立的 Runtime（runtime.rs）：自己的 Store<HostState>、limits、supervisor、snapshot、
+//   sink endpoint；在背景 thread 載入後交給 worker pool（pool.rs）執行：
+//     workers = 0（預設）每條 flow 一條專屬 thread；workers = N 所有 flow 分散到 N 條 thread
+//   載入失敗或 crash 超過 max-crashes 只停止該 flow
+//
+//   Router（FlowManager::run）
+//     ① 從 inputs 取出 TagUpdate frame，在共用的 Tag Registry 分配 tag_id
+//     ② 依各 flow manifest 的 subscribe（* 萬用字元）轉送到該 flow 的 bus endpoint queue
+//     ③ queue 滿了只丟棄該 flow 的 frame（overflow），Router 不等待，其他 flow 照常收到
+//     ④ 所有 input 都結束（file 讀完）後停止轉送，各 flow 的 bus 隨之結束
+//
+//   Tag Registry view：每條 flow 的 HostState 只看得到自己訂閱的 tag（tag_id 與共用 registry 相同），
+//   第一次轉送某個 tag 前同步進 view；tag 設定檔重新載入（tags.rs）後，轉送前先更新各 view
+
+use anyhow::{anyhow, bail, Context, Result};
+use prost::Message as _;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{sync_channel, Receiver, SyncSender, TryRecvError, TrySendError};
+use std::sync::{Arc, RwLock};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+use wasmtime::Engine;
+
+use crate::aot::CompileCache;
+use crate::endpoint::Endpoint;
+use crate::manifest::{EndpointDef, Manifest};
+use crate::pool::{Done, Task, WorkerPool};
+use crate::runtime::Runtime;
+use crate::{proto, TagMeta, TagRegistry};
+
+// 每次輪詢單一 input 最多取出的 frame 數（與 runtime.rs 的 MAX_FRAMES_PER_TICK 相同）
+const MAX_FRAMES_PER_POLL: usize = 8192;
+// 載入中的 flow 多久檢查一次是否完成
+const LOAD_POLL: Duration = Duration::from_millis(20);
+
+pub const INPUT_TYPES: &[&str] = &["nng-pull", "tcp-client", "file"];
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct ManagerConfig {
+    #[serde(default)]
+    pub inputs:  Vec<EndpointDef>,
+    // 沒有 frame 時 Router 的等待間隔
+    #[serde(default = "default_poll")]
+    pub poll_ms: u64,
+    // 執行 flow 的 thread 數；0 = 每條 flow 一條專屬 thread（pool.rs）
+    #[serde(default)]
+    pub workers: usize,
+    pub flows:   Vec<FlowEntry>,
+    #[serde(skip)]
+    pub dir:     PathBuf, // flows.json 所在目錄，flow 目錄與 file input 的相對路徑以此為準
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct FlowEntry {
+    pub dir:       String,
+    #[serde(default = "default_autostart")]
+    pub autostart: bool,
+}
+
+fn default_poll() -> u64 { 5 }
+fn default_autostart() -> bool { true }
+
+impl ManagerConfig {
+    /// 讀取 <dir>/flows.json
+    pub fn load(dir: impl AsRef<Path>) -> Result<Self> {
+        let dir  = dir.as_ref();
+        let path = dir.join("flows.json");
+        let text = std::fs::read_to_string(&path)
+            .with_context(|| format!("讀取失敗：{}", path.display()))?;
+        let config: ManagerConfig = serde_json::from_str(&text)
+            .with_context(|| format!("flows.json 格式錯誤：{}", path.display()))?;
+        config.check().with_context(|| format!("flows.json 格式錯誤：{}", path.display()))?;
+        Ok(ManagerConfig { dir: dir.to_path_buf(), ..config })
+    }
+
+    fn check(&self) -> Result<()> {
+        if self.flows.is_empty() { bail!("至少需要一條 flow"); }
+        if self.poll_ms == 0     { bail!("poll-ms 必須 > 0"); }
+        let mut ids = HashSet::new();
+        for e in &self.inputs {
+            if !ids.insert(e.id) { bail!("input id 重複：{}", e.id); }
+            if !INPUT_TYPES.contains(&e.kind.as_str()) {
+                bail!("input {} 的 type 不支援：{}（可用：{}）", e.id, e.kind, INPUT_TYPES.join(" / "));
+            }
+            if e.role != "source" { bail!("input {} 的 role 必須是 source：{}", e.id, e.role); }
+            if e.address.is_empty() { bail!("input {} 缺少 address", e.id); }
+            if e.queue == 0 { bail!("input {} 的 queue 必須 > 0", e.id); }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct RouterStats {
+    pub frames:   u64,
+    pub routed:   u64, // 送進 flow queue 的 frame（一個 frame 送給 N 條 flow 算 N 次）
+    pub unrouted: u64, // 沒有任何執行中的 flow 訂閱
+    pub dropped:  u64, // 無法解碼、過大或截斷的 frame
+    pub overflow: u64, // flow queue 滿了而丟棄
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FlowState {
+    Stopped, // 尚未啟動或已由 stop 停止
+    Running, // 載入中或執行中
+    Exited,  // source 全部結束
+    Failed,  // 載入失敗或 crash 超過 max-crashes
+}
+
+/// list 的一列
+#[derive(Debug, Clone)]
+pub struct FlowStatus {
+    pub id:        String,
+    pub dir:       PathBuf,
+    pub state:     FlowState,
+    pub subscribe: Vec<String>,
+    pub tags:      usize, // registry view 內的 tag 數
+    pub routed:    u64,
+    pub overflow:  u64,
+    pub uptime:    Option<Duration>,
+}
+
+/// flow 結束時交回的 Runtime（載入失敗時為 None）與結果
+pub struct FlowExit {
+    pub runtime: Option<Runtime>,
+    pub result:  Result<()>,
+    pub stopped: bool, // 由 stop 停止（而不是 source 結束或失敗）
+}
+
+struct Flow {
+    manifest: Manifest, // 最近一次 start（或 load）時讀取
+    registry: Arc<RwLock<TagRegistry>>,
+    routed:   u64,
+    overflow: u64,
+    running:  Option<Running>,
+    exit:     Option<FlowExit>, // 最近一次結束
+}
+
+struct Running {
+    tx:     Option<SyncSender<Vec<u8>>>, // None = 已停止轉送
+    stop:   Arc<AtomicBool>,
+    reload: Arc<AtomicBool>,
+    since:  Instant,
+    done:   Receiver<Done<FlowTask>>,
+}
+
+// worker pool 上的一條 flow：先在背景 thread 載入（不佔用 worker），完成後才開始 step
+struct FlowTask {
+    loading: Option<JoinHandle<Result<Runtime>>>,
+    runtime: Option<Runtime>,
+    reload:  Arc<AtomicBool>,
+    started: Instant,
+}
+
+impl Task for FlowTask {
+    fn step(&mut self) -> Result<Option<Instant>> {
+        if let Some(rt) = &mut self.runtime { return rt.step(); }
+        if !self.loading.as_ref().is_some_and(JoinHandle::is_finished) {
+            return Ok(Some(Instant::now() + LOAD_POLL));
+        }
+        let mut rt = self.loading.take().unwrap().join().map_err(|_| anyhow!("載入 flow 的 thread panic"))??;
+        println!("  ✅ flow {} v{}：{}（{:.1}ms）", rt.live.manifest.flow_id, rt.live.pipeline.version,
+            rt.live.artifact.display(), self.started.elapsed().as_secs_f64() * 1000.0);
+        rt.reload = Arc::clone(&self.reload);
+        self.runtime = Some(rt);
+        Ok(Some(Instant::now()))
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        // 載入中就停止：等載入結束再釋放，避免 endpoint 還佔著位址時又重新 start
+        if let Some(loading) = self.loading.take() { let _ = loading.join(); }
+        self.runtime.as_mut().map_or(Ok(()), Runtime::finish)
+    }
+}
+
+impl Flow {
+    fn id(&self) -> &str {
+        &self.manifest.flow_id
+    }
+
+    fn subscribed(&self, tag: &str) -> bool {
+        self.manifest.subscribe.iter().any(|p| matches(p, tag))
+    }
+}
+
+pub struct FlowManager {
+    pub config: ManagerConfig,
+    pub stats:  RouterStats,
+    engine:     Engine,
+    cache:      CompileCache,
+    registry:   Arc<RwLock<TagRegistry>>,
+    inputs:     Vec<Endpoint>,
+    flows:      Vec<Flow>, // flows.json 的順序
+    pool:       WorkerPool<FlowTask>,
+}
+
+impl FlowManager {
+    /// 讀取 flows.json 與各 flow 的 manifest、開啟共用 input；flow 由 start / start_all 啟動
+    pub fn load(engine: &Engine, cache: &CompileCache, registry: Arc<RwLock<TagRegistry>>,
+                dir: impl AsRef<Path>) -> Result<Self> {
+        let config = ManagerConfig::load(dir)?;
+        let mut flows: Vec<Flow> = Vec::new();
+        for entry in &config.flows {
+            let manifest = Manifest::load(config.dir.join(&entry.dir))?;
+            if flows.iter().any(|f| f.id() == manifest.flow_id) {
+                bail!("flow-id 重複：{}（{}）", manifest.flow_id, entry.dir);
+            }
+            flows.push(Flow {
+                manifest, registry: Arc::new(RwLock::new(TagRegistry::new())),
+                routed: 0, overflow: 0, running: None, exit: None,
+            });
+        }
+        let inputs = config.inputs.iter()
+            .map(|def| Endpoint::open(def, &config.dir).with_context(|| format!("input {}", def.id)))
+            .collect::<Result<Vec<_>>>()?;
+        let pool = WorkerPool::new("flow", config.workers)?;
+        Ok(FlowManager {
+            config, stats: RouterStats::default(), engine: engine.clone(), cache: cache.fork(),
+            registry, inputs, flows, pool,
+        })
+    }
+
+    /// 啟動所有 autostart 的 flow；個別失敗只警告
+    pub fn start_all(&mut self) {
+        let ids: Vec<String> = self.config.flows.iter().zip(&self.flows)
+            .filter(|(e, _)| e.autostart).map(|(_, f)| f.id().to_string()).collect();
+        for id in ids {
+            if let Err(e) = self.start(&id) { eprintln!("⚠️  flow {id} 無法啟動：{e:#}"); }
+        }
+    }
+
+    /// 重新讀取 manifest 後在背景載入，交給 worker pool 執行（載入不佔用 Router 與 worker）
+    pub fn start(&mut self, id: &str) -> Result<()> {
+        let i = self.index(id)?;
+        if self.flows[i].running.is_some() { bail!("flow {id} 已在執行中"); }
+        let manifest = Manifest::load(&self.flows[i].manifest.dir)?;
+        if manifest.flow_id != id { bail!("{} 的 flow-id 已變為 {}", manifest.dir.display(), manifest.flow_id); }
+
+        let flow = &mut self.flows[i];
+        flow.manifest = manifest;
+        let registry = self.registry.read().unwrap().view(|tag| flow.subscribed(tag));
+        flow.registry = Arc::new(RwLock::new(registry));
+        let queue = flow.manifest.host_endpoints.iter().find(|e| e.kind == "bus").map_or(1, |e| e.queue);
+        let (tx, rx) = sync_channel(queue);
+        let (stop, reload) = (Arc::new(AtomicBool::new(false)), Arc::new(AtomicBool::new(false)));
+
+        let (engine, cache, registry) = (self.engine.clone(), self.cache.fork(), Arc::clone(&flow.registry));
+        let manifest = flow.manifest.clone();
+        let loading = std::thread::Builder::new().name(format!("load {id}"))
+            .spawn(move || Runtime::load_on_bus(&engine, &cache, registry, manifest, rx))?;
+        let task = FlowTask { loading: Some(loading), runtime: None, reload: Arc::clone(&reload), started: Instant::now() };
+        let done = self.pool.spawn(id, task, Arc::clone(&stop))?;
+        flow.running = Some(Running { tx: Some(tx), stop, reload, since: Instant::now(), done });
+        flow.exit = None;
+        Ok(())
+    }
+
+    /// 做完當下的 tick 後停止（載入中則等載入完成）
+    pub fn stop(&mut self, id: &str) -> Result<()> {
+        let i = self.index(id)?;
+        let Some(running) = self.flows[i].running.take() else { bail!("flow {id} 沒有在執行"); };
+        running.stop.store(true, Ordering::Relaxed);
+        self.join(i, running);
+        println!("  🛑 flow {id} 已停止");
+        Ok(())
+    }
+
+    /// 要求 flow 在背景載入 manifest 目錄內的新版本並不停機切換（redeploy.rs）
+    pub fn redeploy(&mut self, id: &str) -> Result<()> {
+        let i = self.index(id)?;
+        let Some(running) = &self.flows[i].running else { bail!("flow {id} 沒有在執行"); };
+        running.reload.store(true, Ordering::Relaxed);
+        Ok(())
+    }
+
+    pub fn list(&self) -> Vec<FlowStatus> {
+        self.flows.iter().map(|f| FlowStatus {
+            id:        f.id().to_string(),
+            dir:       f.manifest.dir.clone(),
+            state:     state(f),
+            subscribe: f.manifest.subscribe.clone(),
+            tags:      f.registry.read().unwrap().len(),
+            routed:    f.routed,
+            overflow:  f.overflow,
+            uptime:    f.running.as_ref().map(|r| r.since.elapsed()),
+        }).collect()
+    }
+
+    /// worker pool 的 thread 數與各 thread 的 flow 數（workers = 0 時為空）
+    pub fn worker_loads(&self) -> Vec<usize> {
+        self.pool.loads()
+    }
+
+    /// 已結束的 flow 最近一次的結果
+    pub fn exits(&self) -> impl Iterator<Item = (&str, &FlowExit)> {
+        self.flows.iter().filter_map(|f| Some((f.id(), f.exit.as_ref()?)))
+    }
+
+    /// 轉送 input 的 frame，直到 stop 被設定，或所有 input 結束且所有 flow 都已停止
+    /// 每輪先呼叫 control（start / stop / list 等指令由呼叫端在這裡執行）；結束時停止所有 flow
+    pub fn run(&mut self, stop: &AtomicBool, mut control: impl FnMut(&mut Self)) -> Result<()> {
+        let poll = Duration::from_millis(self.config.poll_ms);
+        let result = loop {
+            control(self);
+            self.reap();
+            if stop.load(Ordering::Relaxed) { break Ok(()); }
+            if self.inputs.iter().all(Endpoint::is_closed) {
+                // 不會再有 frame：關閉所有 bus，flow 讀完 queue 後自行結束
+                for r in self.flows.iter_mut().filter_map(|f| f.running.as_mut()) { r.tx = None; }
+                if self.flows.iter().all(|f| f.running.is_none()) { break Ok(()); }
+            }
+            match self.route() {
+                Ok(0)  => std::thread::sleep(poll),
+                Ok(_)  => {}
+                Err(e) => break Err(e),
+            }
+        };
+        for i in 0..self.flows.len() {
+            let Some(running) = self.flows[i].running.take() else { continue; };
+            running.stop.store(true, Ordering::Relaxed);
+            self.join(i, running);
+        }
+        result
+    }
+
+    // 從每個 input 取出一輪 frame 並轉送，回傳 frame 數
+    fn route(&mut self) -> Result<usize> {
+        {
+            let reg = self.registry.read().unwrap();
+            for flow in &self.flows {
+                if flow.registry.read().unwrap().generation() == reg.generation() { continue; }
+                flow.registry.write().unwrap().refresh(&reg);
+            }
+        }
+        let mut n = 0;
+        for ep in self.inputs.iter_mut().filter(|e| !e.is_closed()) {
+            let frames = ep.recv(MAX_FRAMES_PER_POLL).with_context(|| format!("input {}", ep.id))?;
+            self.stats.dropped += ep.take_rejected();
+            n += frames.len();
+            for frame in frames { forward(&self.registry, &mut self.flows, &mut self.stats, frame); }
+        }
+        Ok(n)
+    }
+
+    // 收回已自行結束（source 結束、載入失敗、crash 超過上限）的 flow
+    fn reap(&mut self) {
+        for flow in &mut self.flows {
+            let Some(running) = &flow.running else { continue; };
+            let exit = match running.done.try_recv() {
+                Ok(done) => exit(done),
+                Err(TryRecvError::Empty) => continue,
+                Err(TryRecvError::Disconnected) => lost(),
+            };
+            match &exit.result {
+                Err(e) => eprintln!("⚠️  flow {} 結束：{e:#}", flow.id()),
+                Ok(()) => println!("  ✅ flow {} 的 source 已全部結束", flow.id()),
+            }
+            flow.running = None;
+            flow.exit = Some(exit);
+        }
+    }
+
+    fn join(&mut self, i: usize, running: Running) {
+        drop(running.tx);
+        self.flows[i].exit = Some(running.done.recv().map_or_else(|_| lost(), exit));
+    }
+
+    fn index(&self, id: &str) -> Result<usize> {
+        self.flows.iter().position(|f| f.id() == id)
+            .with_context(|| format!("flow {id} 不存在（flows.json 內的 flow-id）"))
+    }
+}
+
+// 一個 frame：在共用的 Tag Registry 分配 tag_id，送進每條訂閱該 tag 的執行中 flow 的 queue
+fn forward(registry: &RwLock<TagRegistry>, flows: &mut [Flow], stats: &mut RouterStats, frame: Vec<u8>) {
+    stats.frames += 1;
+    let Ok(tu) = proto::TagUpdate::decode(frame.as_slice()) else {
+        stats.dropped += 1;
+        return;
+    };
+    let name = tu.tag_id_str.as_str();
+    let (id, meta) = {
+        let mut reg = registry.write().unwrap();
+        let id = reg.get_or_create(name, TagMeta::from_update(&tu));
+        (id, reg.entry(name).map(|(_, m)| m.clone()).unwrap())
+    };
+    let mut delivered = false;
+    for flow in flows.iter_mut().filter(|f| f.subscribed(name)) {
+        let Some(tx) = flow.running.as_ref().and_then(|r| r.tx.as_ref()) else { continue; };
+        if !flow.registry.read().unwrap().contains(name) {
+            flow.registry.write().unwrap().insert(id, name, meta.clone());
+        }
+        delivered = true;
+        match tx.try_send(frame.clone()) {
+            Ok(()) => { flow.routed += 1; stats.routed += 1; }
+            Err(TrySendError::Full(_)) => { flow.overflow += 1; stats.overflow += 1; }
+            // 正在結束，reap 時處理
+            Err(TrySendError::Disconnected(_)) => {}
+        }
+    }
+    if !delivered { stats.unrouted += 1; }
+}
+
+fn exit(done: Done<FlowTask>) -> FlowExit {
+    FlowExit { runtime: done.task.runtime, result: done.result, stopped: done.stopped }
+}
+
+// worker thread panic：task 沒有交回
+fn lost() -> FlowExit {
+    FlowExit { runtime: None, result: Err(anyhow!("執行 flow 的 worker thread 異常結束")), stopped: false }
+}
+
+fn state(f: &Flow) -> FlowState {
+    match (&f.running, &f.exit) {
+        (Some(_), _)                         => FlowState::Running,
+        (None, Some(e)) if e.result.is_err() => FlowState::Failed,
+        (None, Some(e)) if !e.stopped        => FlowState::Exited,
+        (None, _)                            => FlowState::Stopped,
+    }
+}
+
+// tag 名稱是否符合 subscribe 的 pattern（* 比對任意長度，包含 .）
+fn matches(pattern: &str, tag: &str) -> bool {
+    let mut parts = pattern.split('*');
+    let Some(mut rest) = tag.strip_prefix(parts.next().unwrap_or_default()) else { return false; };
+    let parts: Vec<&str> = parts.collect();
+    let Some((last, middle)) = parts.split_last() else { return rest.is_empty(); };
+    for part in middle {
+        let Some(at) = rest.find(part) else { return false; };
+        rest = &rest[at + part.len()..];
+    }
+    rest.ends_with(last)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc::{self, Sender};
+
+    #[test]
+    fn subscribe_glob() {
+        let cases = [
+            ("plant1.motor3.temp", "plant1.motor3.temp", true),
+            ("plant1.motor3.temp", "plant1.motor3.temp2", false),
+            ("plant1.*",           "plant1.motor3.temp", true),
+            ("plant1.*",           "plant1.",            true),
+            ("plant1.*",           "plant2.motor3.temp", false),
+            ("*.temp",             "plant1.motor3.temp", true),
+            ("*.temp",             "plant1.motor3.rpm",  false),
+            ("plant1.*.temp",      "plant1.motor3.temp", true),
+            ("plant1.*.temp",      "plant1.a.b.temp",    true),
+            ("plant1.*.temp",      "plant1.temp",        false),
+            ("*motor*",            "plant1.motor3.temp", true),
+            ("*",                  "",                   true),
+            // 重疊：中段與結尾不可共用同一段字元
+            ("a*ab*ab",            "aabab",              true),
+            ("a*ab*ab",            "aab",                false),
+            ("*aa",                "aa",                 true),
+            ("aa*aa",              "aaa",                false),
+        ];
+        for (pattern, tag, want) in cases {
+            assert_eq!(matches(pattern, tag), want, "{pattern} vs {tag}");
+        }
+    }
+
+    fn config(json: &str) -> Result<()> {
+        serde_json::from_str::<ManagerConfig>(json).unwrap().check()
+    }
+
+    #[test]
+    fn config_check() {
+        config(r#"{ "inputs": [{ "id": 0, "type": "nng-pull", "address": "tcp://127.0.0.1:5555", "role": "source" }],
+                    "flows": [{ "dir": "a" }] }"#).unwrap();
+        let err = |json: &str| config(json).unwrap_err().to_string();
+        let input = |def: &str| format!(r#"{{ "inputs": [{def}], "flows": [{{ "dir": "a" }}] }}"#);
+        assert_eq!(err(r#"{ "flows": [] }"#), "至少需要一條 flow");
+        assert_eq!(err(r#"{ "poll-ms": 0, "flows": [{ "dir": "a" }] }"#), "poll-ms 必須 > 0");
+        assert_eq!(err(&input(r#"{ "id": 1, "type": "file", "address": "a", "role": "source" },
+                                 { "id": 1, "type": "file", "address": "b", "role": "source" }"#)), "input id 重複：1");
+        assert!(err(&input(r#"{ "id": 0, "type": "bus", "role": "source" }"#)).starts_with("input 0 的 type 不支援：bus"));
+        assert_eq!(err(&input(r#"{ "id": 0, "type": "file", "address": "a", "role": "sink" }"#)),
+                   "input 0 的 role 必須是 source：sink");
+        assert_eq!(err(&input(r#"{ "id": 0, "type": "file", "role": "source" }"#)), "input 0 缺少 address");
+        assert_eq!(err(&input(r#"{ "id": 0, "type": "file", "address": "a", "role": "source", "queue": 0 }"#)),
+                   "input 0 的 queue 必須 > 0");
+    }
+
+    // 只有 bus queue 的 flow；回傳 queue 的接收端（done 的 Sender 一併保留，避免被當成 lost）
+    fn flow(id: &str, subscribe: &str, queue: usize) -> (Flow, Receiver<Vec<u8>>, Sender<Done<FlowTask>>) {
+        let manifest = Manifest::parse(&format!(r#"{{
+            "flow-id": "{id}", "artifact": {{ "wasm": "flow.wasm" }},
+            "sources": [{{ "tick-export": "src", "interval-ms": 100 }}],
+            "host-endpoints": [{{ "id": 0, "type": "bus", "role": "source", "queue": {queue} }}],
+            "subscribe": ["{subscribe}"]
+        }}"#)).unwrap();
+        let (tx, rx) = sync_channel(queue);
+        let (done_tx, done) = mpsc::channel();
+        let running = Running {
+            tx: Some(tx), stop: Arc::default(), reload: Arc::default(), since: Instant::now(), done,
+        };
+        let flow = Flow {
+            manifest, registry: Arc::new(RwLock::new(TagRegistry::new())),
+            routed: 0, overflow: 0, running: Some(running), exit: None,
+        };
+        (flow, rx, done_tx)
+    }
+
+    fn frame(tag: &str) -> Vec<u8> {
+        proto::TagUpdate { tag_id_str: tag.to_string(), f64_val: Some(1.0), ..Default::default() }.encode_to_vec()
+    }
+
+    #[test]
+    fn forward_only_to_subscribed_flows() {
+        let registry = RwLock::new(TagRegistry::new());
+        let (a, rx_a, _da) = flow("a", "plant1.*", 8);
+        let (b, rx_b, _db) = flow("b", "*.rpm", 8);
+        let mut flows = vec![a, b];
+        let mut stats = RouterStats::default();
+
+        forward(&registry, &mut flows, &mut stats, frame("plant1.motor3.temp"));
+        forward(&registry, &mut flows, &mut stats, frame("plant1.motor3.rpm"));
+        forward(&registry, &mut flows, &mut stats, frame("plant2.pump.flow"));
+        forward(&registry, &mut flows, &mut stats, b"\xff\xff".to_vec());
+
+        assert_eq!(rx_a.try_iter().count(), 2);
+        assert_eq!(rx_b.try_iter().collect::<Vec<_>>(), vec![frame("plant1.motor3.rpm")]);
+        assert_eq!((stats.frames, stats.routed, stats.unrouted, stats.dropped), (4, 3, 1, 1));
+        // 各 flow 的 registry view 只有自己訂閱的 tag，tag_id 與共用 registry 相同
+        assert_eq!(flows[0].registry.read().unwrap().len(), 2);
+        assert!(!flows[1].registry.read().unwrap().contains("plant1.motor3.temp"));
+        assert_eq!(registry.read().unwrap().len(), 3);
+    }
+
+    #[test]
+    fn full_queue_counts_overflow_for_that_flow_only() {
+        let registry = RwLock::new(TagRegistry::new());
+        let (slow, _rx_slow, _ds) = flow("slow", "*", 1);
+        let (fast, rx_fast, _df)  = flow("fast", "*", 8);
+        let mut flows = vec![slow, fast];
+        let mut stats = RouterStats::default();
+        for _ in 0..3 { forward(&registry, &mut flows, &mut stats, frame("plant1.motor3.temp")); }
+
+        assert_eq!((flows[0].routed, flows[0].overflow), (1, 2));
+        assert_eq!((flows[1].routed, flows[1].overflow), (3, 0));
+        assert_eq!(rx_fast.try_iter().count(), 3);
+        assert_eq!((stats.routed, stats.overflow), (4, 2));
+    }
+}
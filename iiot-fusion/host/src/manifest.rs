@@ -0,0 +1,272 @@
+// host/src/manifest.rs
+// flow-deploy/manifest.json 解析（IIoTFlowArchitecture §12）
+//
+//   flow-deploy/
+//   ├── manifest.json    ← Runtime 的唯一入口
+//   ├── flow.wasm        ← iiot-flow-fusion 產出的 fused pipeline
+//   └── flow.cwasm       ← Native AOT（選擇性）
+//
+// 只負責「讀檔 + 結構檢查」，endpoint 連線與排程交給 runtime.rs
+
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::crashdump::CrashDumpConfig;
+use crate::limits::ExecLimits;
+use crate::redeploy::RedeployConfig;
+use crate::scheduler::OverrunPolicy;
+use crate::snapshot::SnapshotConfig;
+use crate::supervisor::SupervisorConfig;
+use crate::BatchConfig;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct Manifest {
+    #[serde(default)]
+    pub version:        String,
+    pub flow_id:        String,
+    pub artifact:       ArtifactDef,
+    #[serde(default)]
+    pub sources:        Vec<SourceDef>,
+    #[serde(default)]
+    pub host_endpoints: Vec<EndpointDef>,
+    // type = "bus" 的 endpoint 要接收的 tag（* 萬用字元）；flow manager 依此轉送（manager.rs）
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub subscribe:      Vec<String>,
+    #[serde(default)]
+    pub sink_ends:      Vec<SinkEndDef>,
+    #[serde(default, skip_serializing_if = "BatchDef::is_default")]
+    pub batch:          BatchDef,
+    // 每次呼叫的執行上限：limits 為整條 flow 的預設，node-limits 依 node id 覆寫
+    #[serde(default, skip_serializing_if = "ExecLimits::is_default")]
+    pub limits:         ExecLimits,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub node_limits:    BTreeMap<String, ExecLimits>,
+    // trap 後的隔離 / 重啟策略（supervisor.rs）
+    #[serde(default, skip_serializing_if = "SupervisorConfig::is_default")]
+    pub supervisor:     SupervisorConfig,
+    // trap 的 post-mortem bundle 輸出（crashdump.rs）
+    #[serde(default, skip_serializing_if = "CrashDumpConfig::is_default")]
+    pub crash_dump:     CrashDumpConfig,
+    // 狀態的持久化 snapshot：定期與停止時寫入，啟動時還原（snapshot.rs）
+    #[serde(default, skip_serializing_if = "SnapshotConfig::is_default")]
+    pub snapshot:       SnapshotConfig,
+    // 不停機 redeploy 的觀察期間（redeploy.rs）
+    #[serde(default, skip_serializing_if = "RedeployConfig::is_default")]
+    pub redeploy:       RedeployConfig,
+    #[serde(skip)]
+    pub dir:            PathBuf, // manifest.json 所在目錄，artifact / file endpoint 的相對路徑以此為準
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct ArtifactDef {
+    pub wasm:          String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub native_aot:    Option<String>,
+    #[serde(default)]
+    pub prefer_native: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct SourceDef {
+    // flow 的 source node id：這個 source 的 frame 只交給該 Source Node（run / run-batch 的 source）
+    pub tick_export: String,
+    pub interval_ms: u64,
+    // tick 超時的處理方式：skip（預設）/ catch-up
+    #[serde(default)]
+    pub overrun:     OverrunPolicy,
+    // 每次 tick 讀取的 endpoint；省略時依序對應 role = "source" 的 endpoint
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub endpoint_id: Option<u32>,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub description: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct EndpointDef {
+    pub id:      u32,
+    #[serde(rename = "type")]
+    pub kind:    String, // nng-pull / tcp-client / file / stdout / bus
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub address: String,
+    pub role:    String, // source / sink
+    // role = "sink" 時接收哪個 sink node 的輸出；省略 = 全部 sink
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub node:    Option<String>,
+    // nng-pull 的 bounded queue 容量（滿了就對 Driver 施加 backpressure）；
+    // bus 的 queue 滿了由 flow manager 丟棄該 flow 的 frame
+    #[serde(default = "default_queue", skip_serializing_if = "is_default_queue")]
+    pub queue:   usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct SinkEndDef {
+    pub id:         String, // flow 內 sink-end node 的 id
+    pub handler:    String, // log / file / webhook
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub target:     String,
+    #[serde(default = "default_timeout")]
+    pub timeout_ms: u64,
+}
+
+// Fused Pipeline 的微批次（run-batch）；省略時用 BatchConfig 的預設值
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case", default)]
+pub struct BatchDef {
+    pub max_size:      usize,
+    pub max_linger_ms: u64,
+}
+
+impl Default for BatchDef {
+    fn default() -> Self {
+        let c = BatchConfig::default();
+        BatchDef { max_size: c.max_size, max_linger_ms: c.max_linger.as_millis() as u64 }
+    }
+}
+
+impl BatchDef {
+    pub fn config(&self) -> BatchConfig {
+        BatchConfig { max_size: self.max_size, max_linger: Duration::from_millis(self.max_linger_ms) }
+    }
+
+    fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+fn default_timeout() -> u64 { 3000 }
+fn default_queue() -> usize { 4096 }
+fn is_default_queue(q: &usize) -> bool { *q == default_queue() }
+
+pub const ENDPOINT_TYPES:    &[&str] = &["nng-pull", "tcp-client", "file", "stdout", "bus"];
+pub const SINK_END_HANDLERS: &[&str] = &["log", "file", "webhook"];
+
+impl Manifest {
+    /// 讀取 <dir>/manifest.json
+    pub fn load(dir: impl AsRef<Path>) -> Result<Self> {
+        let dir  = dir.as_ref();
+        let path = dir.join("manifest.json");
+        let text = std::fs::read_to_string(&path)
+            .with_context(|| format!("讀取失敗：{}", path.display()))?;
+        let manifest = Self::parse(&text)
+            .with_context(|| format!("manifest.json 格式錯誤：{}", path.display()))?;
+        Ok(Manifest { dir: dir.to_path_buf(), ..manifest })
+    }
+
+    pub fn parse(text: &str) -> Result<Self> {
+        let manifest: Manifest = serde_json::from_str(text)?;
+        manifest.check()?;
+        Ok(manifest)
+    }
+
+    /// 依 prefer-native 排出要嘗試的 artifact 順序（第一個載入失敗時 Runtime 會退回下一個）
+    pub fn artifact_paths(&self) -> Vec<PathBuf> {
+        let wasm = self.path(&self.artifact.wasm);
+        match &self.artifact.native_aot {
+            Some(aot) if self.artifact.prefer_native => vec![self.path(aot), wasm],
+            _ => vec![wasm],
+        }
+    }
+
+    /// 相對於 manifest 目錄的路徑
+    pub fn path(&self, rel: &str) -> PathBuf {
+        self.dir.join(rel)
+    }
+
+    pub fn endpoint(&self, id: u32) -> Option<&EndpointDef> {
+        self.host_endpoints.iter().find(|e| e.id == id)
+    }
+
+    /// 每個 source 實際讀取的 endpoint id（與 sources 對齊）
+    pub fn source_endpoints(&self) -> Result<Vec<u32>> {
+        let mut implicit = self.host_endpoints.iter().filter(|e| e.role == "source");
+        self.sources.iter().map(|s| match s.endpoint_id {
+            Some(id) => Ok(id),
+            None => implicit.next().map(|e| e.id)
+                .with_context(|| format!("source {} 沒有可用的 source endpoint", s.tick_export)),
+        }).collect()
+    }
+
+    fn check(&self) -> Result<()> {
+        if self.flow_id.is_empty()       { bail!("flow-id 不可為空"); }
+        if self.artifact.wasm.is_empty() { bail!("artifact.wasm 不可為空"); }
+        if self.sources.is_empty()       { bail!("至少需要一個 source"); }
+        if self.batch.max_size == 0      { bail!("batch.max-size 必須 > 0"); }
+        for (node, l) in std::iter::once(("（flow）", &self.limits))
+            .chain(self.node_limits.iter().map(|(id, l)| (id.as_str(), l))) {
+            if l.fuel == Some(0)        { bail!("{node} 的 limits.fuel 必須 > 0"); }
+            if l.deadline_ms == Some(0) { bail!("{node} 的 limits.deadline-ms 必須 > 0"); }
+            if l.memory_mb == Some(0)   { bail!("{node} 的 limits.memory-mb 必須 > 0"); }
+            if l.instances == Some(0)   { bail!("{node} 的 limits.instances 必須 > 0"); }
+        }
+        if !self.snapshot.dir.is_empty() && self.snapshot.interval_ms == 0 {
+            bail!("snapshot.interval-ms 必須 > 0");
+        }
+        let sv = &self.supervisor;
+        if sv.backoff_ms > sv.backoff_max_ms {
+            bail!("supervisor.backoff-ms（{}）不可大於 backoff-max-ms（{}）", sv.backoff_ms, sv.backoff_max_ms);
+        }
+
+        let mut ids = HashSet::new();
+        for e in &self.host_endpoints {
+            if !ids.insert(e.id) { bail!("endpoint id 重複：{}", e.id); }
+            if !ENDPOINT_TYPES.contains(&e.kind.as_str()) {
+                bail!("endpoint {} 的 type 不支援：{}（可用：{}）", e.id, e.kind, ENDPOINT_TYPES.join(" / "));
+            }
+            match e.role.as_str() {
+                "source" if e.kind == "stdout"   => bail!("endpoint {}：stdout 只能當 sink", e.id),
+                "sink"   if e.kind == "nng-pull" => bail!("endpoint {}：nng-pull 只能當 source", e.id),
+                "sink"   if e.kind == "bus"      => bail!("endpoint {}：bus 只能當 source", e.id),
+                "source" | "sink" => {}
+                other => bail!("endpoint {} 的 role 必須是 source 或 sink：{other}", e.id),
+            }
+            if !matches!(e.kind.as_str(), "stdout" | "bus") && e.address.is_empty() {
+                bail!("endpoint {} 缺少 address", e.id);
+            }
+            if e.kind == "nng-pull" && !(e.address.starts_with("ipc://") || e.address.starts_with("tcp://")) {
+                bail!("endpoint {}：nng-pull 的 address 必須是 ipc:// 或 tcp://（{}）", e.id, e.address);
+            }
+            if e.queue == 0 { bail!("endpoint {} 的 queue 必須 > 0", e.id); }
+        }
+        match self.host_endpoints.iter().filter(|e| e.kind == "bus").count() {
+            0 if !self.subscribe.is_empty() => bail!("subscribe 只對 type = bus 的 endpoint 有效"),
+            1 if self.subscribe.is_empty()  => bail!("bus endpoint 需要 subscribe（要接收的 tag）"),
+            0 | 1 => {}
+            _ => bail!("最多只能有一個 bus endpoint"),
+        }
+        if self.subscribe.iter().any(String::is_empty) { bail!("subscribe 不可包含空字串"); }
+
+        for (s, id) in self.sources.iter().zip(self.source_endpoints()?) {
+            if s.interval_ms == 0 { bail!("source {} 的 interval-ms 必須 > 0", s.tick_export); }
+            match self.endpoint(id) {
+                Some(e) if e.role == "source" => {}
+                Some(_) => bail!("source {} 指定的 endpoint {id} 不是 role = source", s.tick_export),
+                None    => bail!("source {} 指定的 endpoint {id} 不存在", s.tick_export),
+            }
+        }
+
+        let mut ends = HashSet::new();
+        for s in &self.sink_ends {
+            if !ends.insert(s.id.as_str()) { bail!("sink-end id 重複：{}", s.id); }
+            if !SINK_END_HANDLERS.contains(&s.handler.as_str()) {
+                bail!("sink-end {} 的 handler 不支援：{}（可用：{}）",
+                    s.id, s.handler, SINK_END_HANDLERS.join(" / "));
+            }
+            match s.handler.as_str() {
+                "file" if s.target.is_empty() => bail!("sink-end {} 缺少 target 檔案路徑", s.id),
+                "webhook" if !s.target.starts_with("http://") => {
+                    bail!("sink-end {}：webhook 目前只支援 http://（{}）", s.id, s.target)
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+}
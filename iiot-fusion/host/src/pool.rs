@@ -0,0 +1,242 @@
+// host/src/pool.rs
+// 多條 flow 的 worker pool（SystemRequirements §6：多核心 ARM gateway 上 50k tags/sec）
+//
+//   每條 flow 是獨立的 Store，不同 flow 可以在不同 thread 同時執行；同一條 flow 永遠只在一個 worker 上
+//   workers = 0  每個 task 一條專屬 thread（pin），task 結束時 thread 跟著結束
+//   workers = N  固定 N 條 thread，新的 task 指派給目前 task 最少的 worker（shard）；
+//                同一個 worker 上的 flow 共用時間，一條 flow 的長時間呼叫（含 deadline 前的無窮迴圈）
+//                會延後其他 flow 的 tick，需要嚴格隔離延遲時用 workers = 0
+//
+//   worker 輪流呼叫手上每個 task 的 step()，依回傳的下一次時間等待（最多 STOP_POLL 檢查一次 stop）；
+//   step() 回傳 None（source 全部結束）或 stop 被設定 → finish()；回傳錯誤 → 直接結束
+//   step() / finish() 的 panic 只結束該 task（以錯誤交回），同一個 worker 上的其他 task 繼續執行
+//   結束的 task 連同結果從 spawn 回傳的 Receiver 交回
+//
+//   task 的輸入 queue 由 task 自己持有（Runtime 的 bus endpoint / benchmark 的 frame queue），
+//   都是 std mpsc（Rust 1.67 起為 crossbeam 的 lock-free 實作），生產端與 worker 之間不共用鎖
+
+use anyhow::{anyhow, Result};
+use std::any::Any;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use crate::runtime::Runtime;
+
+// 等待下一次 step 時每隔多久檢查一次 stop 旗標
+const STOP_POLL: Duration = Duration::from_millis(50);
+
+/// 可以交給 worker 輪流執行的工作單位（一條 flow）
+pub trait Task: Send + 'static {
+    /// 執行一輪，回傳下一次要呼叫的時間；None = 已沒有工作，接著呼叫 finish
+    fn step(&mut self) -> Result<Option<Instant>>;
+    /// 停止前的收尾（送出剩下的批次、寫入 snapshot 等）
+    fn finish(&mut self) -> Result<()>;
+}
+
+impl Task for Runtime {
+    fn step(&mut self) -> Result<Option<Instant>> {
+        Runtime::step(self)
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        Runtime::finish(self)
+    }
+}
+
+/// 結束的 task 與結果
+pub struct Done<T> {
+    pub task:    T,
+    pub result:  Result<()>,
+    pub stopped: bool, // 因 stop 結束
+}
+
+struct Assign<T> {
+    task: T,
+    stop: Arc<AtomicBool>,
+    done: Sender<Done<T>>,
+}
+
+struct Worker<T> {
+    tx:     Sender<Assign<T>>,
+    load:   Arc<AtomicUsize>, // 目前的 task 數
+    thread: JoinHandle<()>,
+}
+
+pub struct WorkerPool<T: Task> {
+    name:    String,
+    workers: Vec<Worker<T>>, // workers = 0 時為空
+}
+
+impl<T: Task> WorkerPool<T> {
+    /// workers = 0：每個 task 一條專屬 thread；name 為 thread 名稱的前綴
+    pub fn new(name: &str, workers: usize) -> Result<Self> {
+        let workers = (0..workers)
+            .map(|i| spawn_worker(format!("{name}-{i}")))
+            .collect::<Result<Vec<_>>>()?;
+        Ok(WorkerPool { name: name.to_string(), workers })
+    }
+
+    /// worker thread 數；0 = 每個 task 一條
+    pub fn workers(&self) -> usize {
+        self.workers.len()
+    }
+
+    /// 各 worker 目前的 task 數
+    pub fn loads(&self) -> Vec<usize> {
+        self.workers.iter().map(|w| w.load.load(Ordering::Relaxed)).collect()
+    }
+
+    /// 指派 task；label 只用於專屬 thread 的名稱。stop 被設定後 task 在下一輪 finish 並交回
+    pub fn spawn(&self, label: &str, task: T, stop: Arc<AtomicBool>) -> Result<Receiver<Done<T>>> {
+        let (done, rx) = mpsc::channel();
+        let assign = Assign { task, stop, done };
+        let Some(w) = self.workers.iter().min_by_key(|w| w.load.load(Ordering::Relaxed)) else {
+            // 專屬 thread：送出後放掉 Sender，task 結束時 thread 跟著結束
+            let w = spawn_worker(format!("{} {label}", self.name))?;
+            w.load.fetch_add(1, Ordering::Relaxed);
+            let _ = w.tx.send(assign);
+            return Ok(rx);
+        };
+        w.load.fetch_add(1, Ordering::Relaxed);
+        let _ = w.tx.send(assign);
+        Ok(rx)
+    }
+
+    /// 等所有 worker 把手上的 task 做完後結束
+    pub fn shutdown(self) {
+        for w in self.workers {
+            drop(w.tx);
+            let _ = w.thread.join();
+        }
+    }
+}
+
+fn spawn_worker<T: Task>(name: String) -> Result<Worker<T>> {
+    let (tx, rx) = mpsc::channel();
+    let load = Arc::new(AtomicUsize::new(0));
+    let counter = Arc::clone(&load);
+    let thread = std::thread::Builder::new().name(name).spawn(move || work(rx, &counter))?;
+    Ok(Worker { tx, load, thread })
+}
+
+// worker 迴圈：輪流 step 手上的 task，沒有 task 且不會再有指派時結束
+fn work<T: Task>(rx: Receiver<Assign<T>>, load: &AtomicUsize) {
+    let mut tasks: Vec<(Assign<T>, Instant)> = Vec::new();
+    let mut open = true;
+    loop {
+        let wake = tasks.iter().map(|&(_, at)| at).min();
+        let wait = wake.map_or(STOP_POLL, |at| at.saturating_duration_since(Instant::now()).min(STOP_POLL));
+        let assigned = match (open, tasks.is_empty()) {
+            (true, true)  => rx.recv().map_err(|_| RecvTimeoutError::Disconnected),
+            (true, false) => rx.recv_timeout(wait),
+            (false, true) => return,
+            (false, false) => { std::thread::sleep(wait); Err(RecvTimeoutError::Timeout) }
+        };
+        match assigned {
+            Ok(a) => tasks.push((a, Instant::now())),
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => open = false,
+        }
+
+        let now = Instant::now();
+        let mut i = 0;
+        while i < tasks.len() {
+            let (a, at) = &mut tasks[i];
+            let stopped = a.stop.load(Ordering::Relaxed);
+            let r = if stopped { Ok(None) } else if *at <= now { guard(|| a.task.step()) } else { Ok(Some(*at)) };
+            if let Ok(Some(next)) = r {
+                *at = next;
+                i += 1;
+                continue;
+            }
+            let (mut a, _) = tasks.swap_remove(i);
+            let result = r.and_then(|_| guard(|| a.task.finish()));
+            load.fetch_sub(1, Ordering::Relaxed);
+            let _ = a.done.send(Done { task: a.task, result, stopped });
+        }
+    }
+}
+
+// task 的 panic 轉成錯誤；panic 後的 task 不再呼叫 finish
+fn guard<R>(f: impl FnOnce() -> Result<R>) -> Result<R> {
+    catch_unwind(AssertUnwindSafe(f)).unwrap_or_else(|p| Err(anyhow!("task panic：{}", panic_message(&*p))))
+}
+
+fn panic_message(p: &(dyn Any + Send)) -> &str {
+    p.downcast_ref::<&str>().copied().or_else(|| p.downcast_ref::<String>().map(String::as_str)).unwrap_or("（非字串的 panic）")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 執行 steps 輪後結束；panic_at 那一輪 panic
+    struct Counter {
+        steps:    u32,
+        panic_at: Option<u32>,
+        finished: bool,
+    }
+
+    impl Counter {
+        fn new(panic_at: Option<u32>) -> Self {
+            Counter { steps: 0, panic_at, finished: false }
+        }
+    }
+
+    impl Task for Counter {
+        fn step(&mut self) -> Result<Option<Instant>> {
+            self.steps += 1;
+            if self.panic_at == Some(self.steps) { panic!("boom at {}", self.steps); }
+            Ok((self.steps < 3).then(Instant::now))
+        }
+
+        fn finish(&mut self) -> Result<()> {
+            self.finished = true;
+            Ok(())
+        }
+    }
+
+    fn run(pool: &WorkerPool<Counter>, task: Counter) -> Receiver<Done<Counter>> {
+        pool.spawn("counter", task, Arc::new(AtomicBool::new(false))).unwrap()
+    }
+
+    #[test]
+    fn panic_ends_only_that_task() {
+        let pool = WorkerPool::new("test", 1).unwrap();
+        let bad  = run(&pool, Counter::new(Some(2)));
+        let good = run(&pool, Counter::new(None));
+
+        let bad = bad.recv().expect("panic 的 task 仍應交回");
+        assert_eq!(bad.result.unwrap_err().to_string(), "task panic：boom at 2");
+        assert!(!bad.task.finished, "panic 後不呼叫 finish");
+
+        let good = good.recv().expect("同一個 worker 上的其他 task 不受影響");
+        good.result.unwrap();
+        assert_eq!((good.task.steps, good.task.finished), (3, true));
+
+        // worker thread 仍在，之後指派的 task 照常執行
+        run(&pool, Counter::new(None)).recv().unwrap().result.unwrap();
+        assert_eq!(pool.loads(), vec![0]);
+        pool.shutdown();
+    }
+
+    #[test]
+    fn dedicated_thread_reports_panic() {
+        let pool = WorkerPool::new("test", 0).unwrap();
+        let done = run(&pool, Counter::new(Some(1))).recv().unwrap();
+        assert_eq!(done.result.unwrap_err().to_string(), "task panic：boom at 1");
+    }
+
+    #[test]
+    fn stop_finishes_without_stepping() {
+        let pool = WorkerPool::new("test", 1).unwrap();
+        let done = pool.spawn("counter", Counter::new(Some(1)), Arc::new(AtomicBool::new(true))).unwrap().recv().unwrap();
+        done.result.unwrap();
+        assert!(done.stopped && done.task.finished);
+        assert_eq!(done.task.steps, 0);
+    }
+}
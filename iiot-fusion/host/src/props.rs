@@ -0,0 +1,176 @@
+// host/src/props.rs
+// flow.json props 依 Node 的 prop-def 驗證（IIoTFlowArchitecture §4.3）
+//
+//   未知 key / 型別不符 / select 不在 choices / required 未填 → 錯誤
+//   未填且非 required → 以 default-value 補上
+// 驗證後的完整 props 以 JSON 傳入 Node 的 init()
+
+use anyhow::{bail, Result};
+use serde_json::{Map, Value};
+
+use crate::iiot::flow::node_descriptor::{PropDef, PropType};
+
+pub fn resolve(node_id: &str, props: &Value, defs: &[PropDef]) -> Result<Value> {
+    let empty = Map::new();
+    let given = match props {
+        Value::Object(m) => m,
+        Value::Null      => &empty,
+        _ => bail!("{node_id}：props 必須是 JSON object"),
+    };
+
+    let mut errors = Vec::new();
+    for key in given.keys() {
+        if !defs.iter().any(|d| &d.key == key) {
+            errors.push(format!("未知的 prop「{key}」"));
+        }
+    }
+
+    let mut out = Map::new();
+    for def in defs {
+        let value = match given.get(&def.key) {
+            Some(v) => v.clone(),
+            None if def.required => {
+                errors.push(format!("缺少必填 prop「{}」", def.key));
+                continue;
+            }
+            None => match serde_json::from_str(&def.default_value) {
+                Ok(v)  => v,
+                Err(e) => {
+                    errors.push(format!("prop「{}」的 default-value 不是合法 JSON：{e}", def.key));
+                    continue;
+                }
+            },
+        };
+        if let Err(e) = check_type(def, &value) {
+            errors.push(format!("prop「{}」{e}", def.key));
+            continue;
+        }
+        out.insert(def.key.clone(), value);
+    }
+
+    if !errors.is_empty() {
+        bail!("{node_id} props 驗證失敗：\n    {}", errors.join("\n    "));
+    }
+    Ok(Value::Object(out))
+}
+
+fn check_type(def: &PropDef, v: &Value) -> std::result::Result<(), String> {
+    let ok = match def.prop_type {
+        PropType::PropBool   => v.is_boolean(),
+        PropType::PropI32    => v.as_i64().is_some_and(|n| i32::try_from(n).is_ok()),
+        PropType::PropU32    => v.as_u64().is_some_and(|n| u32::try_from(n).is_ok()),
+        PropType::PropF32 |
+        PropType::PropF64    => v.is_number(),
+        PropType::PropString => v.is_string(),
+        PropType::PropSelect => {
+            let Some(s) = v.as_str() else { return Err("必須是字串".to_string()); };
+            if !def.choices.iter().any(|c| c == s) {
+                return Err(format!("「{s}」不在選項 {:?} 內", def.choices));
+            }
+            true
+        }
+        PropType::PropJson   => true,
+    };
+    if ok { Ok(()) } else { Err(format!("型別應為 {:?}，實際為 {v}", def.prop_type)) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn def(key: &str, prop_type: PropType, default_value: &str, required: bool) -> PropDef {
+        PropDef {
+            key: key.to_string(), label: key.to_string(), prop_type,
+            default_value: default_value.to_string(), required,
+            choices: Vec::new(), description: String::new(),
+        }
+    }
+
+    fn defs() -> Vec<PropDef> {
+        let mut mode = def("mode", PropType::PropSelect, "\"avg\"", false);
+        mode.choices = vec!["avg".to_string(), "max".to_string()];
+        vec![
+            def("window",  PropType::PropU32, "10", false),
+            def("gain",    PropType::PropF64, "1.0", false),
+            def("enabled", PropType::PropBool, "true", false),
+            def("unit",    PropType::PropString, "", true),
+            mode,
+        ]
+    }
+
+    fn error(props: Value, defs: &[PropDef]) -> String {
+        format!("{:#}", resolve("node-x", &props, defs).unwrap_err())
+    }
+
+    #[test]
+    fn fills_defaults_and_keeps_given_values() {
+        let out = resolve("node-x", &json!({ "unit": "°C", "window": 5, "mode": "max" }), &defs()).unwrap();
+        assert_eq!(out, json!({ "window": 5, "gain": 1.0, "enabled": true, "unit": "°C", "mode": "max" }));
+    }
+
+    #[test]
+    fn null_props_means_all_defaults() {
+        let defs = [def("window", PropType::PropU32, "10", false)];
+        assert_eq!(resolve("node-x", &Value::Null, &defs).unwrap(), json!({ "window": 10 }));
+    }
+
+    #[test]
+    fn props_must_be_an_object() {
+        assert!(error(json!([1, 2]), &defs()).contains("JSON object"));
+    }
+
+    #[test]
+    fn unknown_key_is_rejected() {
+        let e = error(json!({ "unit": "°C", "windw": 5 }), &defs());
+        assert!(e.contains("未知的 prop「windw」"), "{e}");
+    }
+
+    #[test]
+    fn missing_required_prop_is_rejected() {
+        let e = error(json!({}), &defs());
+        assert!(e.contains("缺少必填 prop「unit」"), "{e}");
+    }
+
+    #[test]
+    fn select_outside_choices_is_rejected() {
+        let e = error(json!({ "unit": "°C", "mode": "median" }), &defs());
+        assert!(e.contains("「median」不在選項"), "{e}");
+        let e = error(json!({ "unit": "°C", "mode": 1 }), &defs());
+        assert!(e.contains("prop「mode」必須是字串"), "{e}");
+    }
+
+    #[test]
+    fn type_mismatch_is_rejected() {
+        for (props, key) in [
+            (json!({ "unit": "°C", "window": -1 }),         "window"),
+            (json!({ "unit": "°C", "window": 1u64 << 32 }), "window"),
+            (json!({ "unit": "°C", "gain": "1.5" }),        "gain"),
+            (json!({ "unit": "°C", "enabled": 1 }),         "enabled"),
+            (json!({ "unit": 42 }),                         "unit"),
+        ] {
+            let e = error(props, &defs());
+            assert!(e.contains(&format!("prop「{key}」型別應為")), "{e}");
+        }
+    }
+
+    #[test]
+    fn all_errors_are_reported_together() {
+        let e = error(json!({ "windw": 5, "mode": "median" }), &defs());
+        assert!(e.contains("未知的 prop「windw」") && e.contains("缺少必填 prop「unit」") && e.contains("median"), "{e}");
+    }
+
+    #[test]
+    fn invalid_default_value_is_reported() {
+        let defs = [def("window", PropType::PropU32, "ten", false)];
+        let e = error(json!({}), &defs);
+        assert!(e.contains("default-value 不是合法 JSON"), "{e}");
+    }
+
+    #[test]
+    fn json_prop_accepts_anything() {
+        let defs = [def("map", PropType::PropJson, "{}", false)];
+        let out = resolve("node-x", &json!({ "map": [1, { "a": null }] }), &defs).unwrap();
+        assert_eq!(out, json!({ "map": [1, { "a": null }] }));
+    }
+}
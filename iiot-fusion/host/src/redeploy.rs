@@ -0,0 +1,351 @@
+// host/src/redeploy.rs
+// 不停機的 redeploy（SystemRequirements：重新 Fusion + 零停機切換）
+//
+//   Runtime（Fused）：SIGHUP 或 Runtime::redeploy()
+//     ① 背景 thread 重新讀取 manifest.json、載入 artifact（編譯不佔用排程），
+//        並先呼叫一次 save-states，讓 glue 執行各 Node 的 init；init 失敗即取消
+//     ② Runtime 在兩個 tick 之間切換：flush 舊 pipeline → save-states → 新 pipeline load-states → 交換
+//        （load-states 失敗也取消，舊 pipeline 不受影響）
+//     ③ redeploy.grace-ms 內新 pipeline crash → 換回舊 instance（狀態停在切換當下）；期滿才釋放舊 instance
+//        grace 期間不寫持久化 snapshot，換回時不會留下新版本的 state
+//     endpoint / source / subscribe 在使用中，無法熱切換：有變更時取消，需要重新啟動 Runtime
+//
+//   AsyncRuntime（IIOT_FLOW_ASYNC=1）：流程相同；背景載入在 spawn_blocking 內完成（編譯不佔用 tokio worker），
+//     save-states / load-states 以 async 呼叫執行
+//   交換、換回與釋放（Deployment / promote / settle / commit）兩者共用，Runtime 只負責取得與載入狀態
+//
+//   DAG：FlowDag::redeploy_node 以新的 instance 取代單一 Node，同樣轉移狀態
+//        （save_state / load_state，版本不同時經過 migrate-state）並保留舊 Node 到 grace 期滿
+
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+use wasmtime::Engine;
+
+use crate::aot::CompileCache;
+use crate::manifest::{Manifest, SinkEndDef};
+use crate::runtime::{self, load_pipeline, Pipeline, RuntimeStats};
+use crate::snapshot::SnapshotStore;
+use crate::supervisor::{Health, Supervisor};
+use crate::{async_runtime, AsyncPipeline, FusedPipeline, TagRegistry};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case", default)]
+pub struct RedeployConfig {
+    // 切換後的觀察期間，期間內 crash 即換回舊版本；0 = 切換後立即釋放舊版本
+    pub grace_ms: u64,
+}
+
+impl Default for RedeployConfig {
+    fn default() -> Self {
+        RedeployConfig { grace_ms: 10_000 }
+    }
+}
+
+impl RedeployConfig {
+    pub fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// 背景載入完成、已 init 的新版本
+pub struct Candidate<P = FusedPipeline> {
+    pub manifest: Manifest,
+    pub artifact: PathBuf,
+    pub pipeline: P,
+    pub elapsed:  Duration, // 載入 + init 花費的時間
+}
+
+/// 在背景 thread 載入 current 所在目錄的 manifest 與 artifact，完成後由 Receiver 取得
+pub fn prepare(engine: &Engine, cache: CompileCache, registry: Arc<RwLock<TagRegistry>>,
+               current: &Manifest) -> Result<Receiver<Result<Candidate>>> {
+    let (tx, rx) = mpsc::channel();
+    let engine  = engine.clone();
+    let current = current.clone();
+    std::thread::Builder::new().name("redeploy".to_string()).spawn(move || {
+        let _ = tx.send(load(&engine, &cache, registry, &current));
+    })?;
+    Ok(rx)
+}
+
+/// prepare 的 tokio 版：必須在 tokio runtime 內呼叫，完成後 await JoinHandle 取得
+pub fn prepare_async(engine: &Engine, cache: CompileCache, registry: Arc<RwLock<TagRegistry>>,
+                     current: &Manifest) -> tokio::task::JoinHandle<Result<Candidate<AsyncPipeline>>> {
+    let engine  = engine.clone();
+    let current = current.clone();
+    tokio::task::spawn_blocking(move || {
+        tokio::runtime::Handle::current().block_on(load_async(&engine, &cache, registry, &current))
+    })
+}
+
+fn load(engine: &Engine, cache: &CompileCache, registry: Arc<RwLock<TagRegistry>>,
+        current: &Manifest) -> Result<Candidate> {
+    let started  = Instant::now();
+    let manifest = reload(current)?;
+    let (mut pipeline, artifact) = load_pipeline(engine, cache, registry, &manifest)?;
+    // glue 第一次被呼叫時 init 所有 Node
+    pipeline.save_states().with_context(|| format!("{} 的 init 失敗", artifact.display()))?;
+    Ok(Candidate { manifest, artifact, pipeline, elapsed: started.elapsed() })
+}
+
+async fn load_async(engine: &Engine, cache: &CompileCache, registry: Arc<RwLock<TagRegistry>>,
+                    current: &Manifest) -> Result<Candidate<AsyncPipeline>> {
+    let started  = Instant::now();
+    let manifest = reload(current)?;
+    let (mut pipeline, artifact) = async_runtime::load_pipeline(engine, cache, registry, &manifest).await?;
+    pipeline.save_states().await.with_context(|| format!("{} 的 init 失敗", artifact.display()))?;
+    Ok(Candidate { manifest, artifact, pipeline, elapsed: started.elapsed() })
+}
+
+// 重新讀取 manifest.json，只允許可以熱切換的變更
+fn reload(current: &Manifest) -> Result<Manifest> {
+    let manifest = Manifest::load(&current.dir)?;
+    if manifest.flow_id != current.flow_id {
+        bail!("flow-id 由 {} 變為 {}，請以新的 Runtime 部署", current.flow_id, manifest.flow_id);
+    }
+    if json(&manifest.host_endpoints) != json(&current.host_endpoints) || json(&manifest.sources) != json(&current.sources)
+        || manifest.subscribe != current.subscribe {
+        bail!("host-endpoints / sources / subscribe 有變更，無法熱切換，請重新啟動 Runtime");
+    }
+    Ok(manifest)
+}
+
+fn json<T: Serialize>(v: &T) -> serde_json::Value {
+    serde_json::to_value(v).unwrap_or_default()
+}
+
+/// grace 期間保留的舊版本
+pub(crate) struct Standby<T> {
+    pub old:   T,
+    pub since: Instant,
+    pub grace: Duration,
+}
+
+impl<T> Standby<T> {
+    pub fn new(old: T, grace: Duration) -> Self {
+        Standby { old, since: Instant::now(), grace }
+    }
+
+    pub fn expired(&self) -> bool {
+        self.since.elapsed() >= self.grace
+    }
+}
+
+/// redeploy 時整組交換的部分（endpoint / source / 排程沿用）；H = sink-end 的 handler
+pub struct Deployment<P, H> {
+    pub manifest:   Manifest,
+    pub artifact:   PathBuf, // 實際載入的 artifact
+    pub pipeline:   P,
+    pub supervisor: Supervisor,
+    pub snapshots:  Option<SnapshotStore>,
+    pub(crate) sink_ends: HashMap<String, (SinkEndDef, H)>,
+}
+
+impl<P, H> Deployment<P, H> {
+    /// Supervisor 與 snapshot 依 manifest 建立
+    pub(crate) fn new(manifest: Manifest, artifact: PathBuf, pipeline: P,
+                      sink_ends: HashMap<String, (SinkEndDef, H)>) -> Result<Self> {
+        Ok(Deployment {
+            supervisor: runtime::supervisor(&manifest),
+            snapshots:  manifest.snapshot.open(&manifest.dir)?,
+            manifest, artifact, pipeline, sink_ends,
+        })
+    }
+}
+
+// ── 切換（Runtime / AsyncRuntime 共用）────────────────────────────────────────
+
+/// 新版本上線：next 換成 live，舊版本在 grace 期間保留
+pub(crate) fn promote<P: Pipeline, H>(live: &mut Deployment<P, H>, mut next: Deployment<P, H>, elapsed: Duration,
+                                      stats: &mut RuntimeStats) -> Standby<Deployment<P, H>> {
+    std::mem::swap(live, &mut next);
+    stats.redeploys += 1;
+    let grace = Duration::from_millis(live.manifest.redeploy.grace_ms);
+    eprintln!("🔀 redeploy：v{} → v{}（{}，背景載入 {:.1}ms），觀察 {}ms",
+        next.pipeline.version(), live.pipeline.version(), file_name(&live.artifact),
+        elapsed.as_secs_f64() * 1000.0, grace.as_millis());
+    Standby::new(next, grace)
+}
+
+/// 背景載入或切換失敗：繼續使用目前的版本
+pub(crate) fn cancel<P: Pipeline, H>(live: &Deployment<P, H>, stats: &mut RuntimeStats, e: &anyhow::Error) {
+    stats.rollbacks += 1;
+    eprintln!("⚠️  redeploy 取消，繼續使用 v{}（{}）：{e:#}", live.pipeline.version(), file_name(&live.artifact));
+}
+
+/// grace 期間：新版本 crash 就換回舊版本，期滿則釋放舊版本
+/// 換回時吞掉 crash 的錯誤（含超過 max-crashes），由舊版本繼續執行；其他錯誤照常回傳
+pub(crate) fn settle<P: Pipeline, H>(live: &mut Deployment<P, H>, standby: &mut Option<Standby<Deployment<P, H>>>,
+                                     stats: &mut RuntimeStats, r: Result<()>) -> Result<()> {
+    let Some(s) = standby else { return r; };
+    if live.supervisor.crashes == 0 {
+        if s.expired() { commit(live, standby); }
+        return r;
+    }
+    // 只有超過 max-crashes 時 crash 才會以錯誤回傳（Supervisor::call）
+    let failed = live.supervisor.health() == Health::Failed;
+    let mut s = standby.take().unwrap();
+    std::mem::swap(live, &mut s.old);
+    stats.rollbacks += 1;
+    eprintln!("↩️  redeploy：v{} 在觀察期間 crash，已換回 v{}（{}）",
+        s.old.pipeline.version(), live.pipeline.version(), file_name(&live.artifact));
+    if failed { Ok(()) } else { r }
+}
+
+/// 通過觀察期：釋放舊版本
+pub(crate) fn commit<P: Pipeline, H>(live: &Deployment<P, H>, standby: &mut Option<Standby<Deployment<P, H>>>) {
+    let Some(s) = standby.take() else { return; };
+    eprintln!("✅ redeploy：v{} 已穩定執行 {}ms，釋放 v{}", live.pipeline.version(),
+        s.since.elapsed().as_millis(), s.old.pipeline.version());
+}
+
+/// artifact 的顯示名稱（log 用）
+pub(crate) fn file_name(path: &Path) -> String {
+    path.file_name().map_or_else(|| path.display().to_string(), |n| n.to_string_lossy().into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::TempDir;
+
+    const MANIFEST: &str = r#"{
+        "version": "0.1.0",
+        "flow-id": "flow",
+        "artifact": { "wasm": "flow.wasm" },
+        "sources": [{ "tick-export": "src-temp", "interval-ms": 100, "endpoint-id": 0 }],
+        "host-endpoints": [
+            { "id": 0, "type": "nng-pull", "address": "tcp://127.0.0.1:5555", "role": "source" },
+            { "id": 1, "type": "stdout", "role": "sink", "node": "sink" }
+        ]
+    }"#;
+
+    // 寫入 MANIFEST 並以 edit 修改後，對原本的 manifest 呼叫 reload
+    fn reload_with(edit: impl FnOnce(&str) -> String) -> Result<Manifest> {
+        let dir = TempDir::new("redeploy");
+        std::fs::write(dir.path().join("manifest.json"), MANIFEST).unwrap();
+        let current = Manifest::load(dir.path()).unwrap();
+        std::fs::write(dir.path().join("manifest.json"), edit(MANIFEST)).unwrap();
+        reload(&current)
+    }
+
+    #[test]
+    fn reload_accepts_artifact_and_limit_changes() {
+        let m = reload_with(|s| s.replace(r#""flow.wasm" }"#, r#""flow-v2.wasm" }, "limits": { "fuel": 1000 }"#))
+            .expect("artifact / limits 可以熱切換");
+        assert_eq!(m.artifact.wasm, "flow-v2.wasm");
+        assert_eq!(m.limits.fuel, Some(1000));
+    }
+
+    #[test]
+    fn reload_rejects_flow_id_change() {
+        let e = reload_with(|s| s.replace(r#""flow-id": "flow""#, r#""flow-id": "flow-2""#)).unwrap_err();
+        assert!(e.to_string().contains("flow-id 由 flow 變為 flow-2"), "{e}");
+    }
+
+    #[test]
+    fn reload_rejects_endpoint_change() {
+        let e = reload_with(|s| s.replace("5555", "5556")).unwrap_err();
+        assert!(e.to_string().contains("無法熱切換"), "{e}");
+    }
+
+    #[test]
+    fn reload_rejects_source_change() {
+        let e = reload_with(|s| s.replace(r#""interval-ms": 100"#, r#""interval-ms": 500"#)).unwrap_err();
+        assert!(e.to_string().contains("無法熱切換"), "{e}");
+    }
+
+    #[test]
+    fn standby_expires_after_grace() {
+        assert!(Standby::new((), Duration::ZERO).expired());
+        assert!(!Standby::new((), Duration::from_secs(60)).expired());
+    }
+
+    // ── 切換 ──
+
+    struct Fake(&'static str);
+
+    impl Pipeline for Fake {
+        fn version(&self) -> &str { self.0 }
+        fn nodes(&self) -> &[String] { &[] }
+        fn configure(&mut self, _: crate::BatchConfig, _: crate::limits::ExecLimits,
+                     _: &std::collections::BTreeMap<String, crate::limits::ExecLimits>) {}
+    }
+
+    fn deployment(version: &'static str, max_crashes: u32) -> Live {
+        let config = crate::supervisor::SupervisorConfig { max_crashes, ..Default::default() };
+        Deployment {
+            manifest:   Manifest::parse(MANIFEST).unwrap(),
+            artifact:   PathBuf::from(format!("flow-{version}.wasm")),
+            pipeline:   Fake(version),
+            supervisor: Supervisor::new("flow", config),
+            snapshots:  None,
+            sink_ends:  HashMap::new(),
+        }
+    }
+
+    type Live = Deployment<Fake, ()>;
+
+    // v1 執行中，v2 上線進入觀察期
+    fn promoted(max_crashes: u32, grace: Duration) -> (Live, Option<Standby<Live>>, RuntimeStats) {
+        let mut live = deployment("1", 3);
+        let mut stats = RuntimeStats::default();
+        let standby = promote(&mut live, deployment("2", max_crashes), Duration::ZERO, &mut stats);
+        (live, Some(Standby { grace, ..standby }), stats)
+    }
+
+    #[test]
+    fn promote_keeps_the_old_version_on_standby() {
+        let (live, standby, stats) = promoted(3, Duration::from_secs(60));
+        assert_eq!(live.pipeline.version(), "2");
+        assert_eq!(standby.unwrap().old.pipeline.version(), "1");
+        assert_eq!(stats.redeploys, 1);
+    }
+
+    #[test]
+    fn crash_during_grace_rolls_back() {
+        let (mut live, mut standby, mut stats) = promoted(3, Duration::from_secs(60));
+        settle(&mut live, &mut standby, &mut stats, Ok(())).unwrap();
+        assert!(standby.is_some(), "沒有 crash 時應繼續觀察");
+
+        live.supervisor.crashed(&anyhow::anyhow!("trap"));
+        settle(&mut live, &mut standby, &mut stats, Ok(())).unwrap();
+        assert_eq!(live.pipeline.version(), "1", "crash 後應換回舊版本");
+        assert!(standby.is_none());
+        assert_eq!(stats.rollbacks, 1);
+    }
+
+    #[test]
+    fn rollback_swallows_the_max_crashes_error() {
+        let (mut live, mut standby, mut stats) = promoted(0, Duration::from_secs(60));
+        live.supervisor.crashed(&anyhow::anyhow!("trap"));
+        assert_eq!(live.supervisor.health(), Health::Failed);
+
+        settle(&mut live, &mut standby, &mut stats, Err(anyhow::anyhow!("flow 已停止")))
+            .expect("換回舊版本後不應回傳新版本的 max-crashes 錯誤");
+        assert_eq!(live.pipeline.version(), "1");
+        assert_eq!(live.supervisor.health(), Health::Running, "舊版本的 Supervisor 不受影響");
+    }
+
+    #[test]
+    fn other_errors_are_returned_after_rollback() {
+        let (mut live, mut standby, mut stats) = promoted(3, Duration::from_secs(60));
+        live.supervisor.crashed(&anyhow::anyhow!("trap"));
+        let e = settle(&mut live, &mut standby, &mut stats, Err(anyhow::anyhow!("endpoint 失敗"))).unwrap_err();
+        assert_eq!(e.to_string(), "endpoint 失敗");
+        assert_eq!(live.pipeline.version(), "1");
+    }
+
+    #[test]
+    fn grace_expiry_commits_the_new_version() {
+        let (mut live, mut standby, mut stats) = promoted(3, Duration::ZERO);
+        settle(&mut live, &mut standby, &mut stats, Ok(())).unwrap();
+        assert!(standby.is_none(), "觀察期滿應釋放舊版本");
+        assert_eq!(live.pipeline.version(), "2");
+        assert_eq!(stats.rollbacks, 0);
+    }
+}
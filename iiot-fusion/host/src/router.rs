@@ -0,0 +1,154 @@
+// host/src/router.rs
+// 未 Fusion 時的動態路由：edge table 查表（IIoTFlowArchitecture §10.4）
+//
+//   output = node_A.process(msgs)
+//   for edge in edge_table[node_A][port]:
+//       edge.to_node.process(edge.to_port, msgs)
+//
+// 投遞順序固定，同一份輸入永遠得到相同的處理順序：
+//   ① Node 依 topo 順序執行，上游先執行的訊息先進下游 inbox（fan-in）
+//   ② 同一個 output port 的多條 edge 依 flow.json 宣告順序投遞（fan-out）
+
+use std::collections::HashMap;
+
+use crate::flow::FlowDef;
+use crate::iiot::flow::types::FlowMsg;
+
+#[derive(Debug, Clone, Copy)]
+pub struct Route {
+    pub to:      usize, // 下游 topo 位置
+    pub to_port: u32,
+}
+
+pub struct Router {
+    table: HashMap<(usize, u32), Vec<Route>>, // (from topo 位置, from-port) → 下游
+}
+
+impl Router {
+    /// pos：node id → topo 位置
+    pub fn new(flow: &FlowDef, pos: &HashMap<&str, usize>) -> Self {
+        let mut table: HashMap<(usize, u32), Vec<Route>> = HashMap::new();
+        for e in &flow.edges {
+            table.entry((pos[e.from.as_str()], e.from_port))
+                 .or_default()
+                 .push(Route { to: pos[e.to.as_str()], to_port: e.to_port });
+        }
+        Router { table }
+    }
+
+    pub fn routes(&self, from: usize, port: u32) -> &[Route] {
+        self.table.get(&(from, port)).map(Vec::as_slice).unwrap_or(&[])
+    }
+
+    /// 把 from 的某個 output port 的輸出投遞到所有下游 inbox
+    /// fan-out 時前 N-1 條 edge 複製，最後一條直接 move
+    pub fn deliver(&self, from: usize, port: u32, msgs: Vec<FlowMsg>, inbox: &mut Inbox) {
+        if msgs.is_empty() { return; }
+        let Some((last, rest)) = self.routes(from, port).split_last() else { return; };
+        for r in rest {
+            inbox.push(r.to, r.to_port, msgs.iter().cloned());
+        }
+        inbox.push(last.to, last.to_port, msgs);
+    }
+}
+
+/// 每個 Node 一個 FIFO，記錄訊息從哪個 input port 進來
+pub struct Inbox {
+    queues: Vec<Vec<(u32, FlowMsg)>>,
+}
+
+impl Inbox {
+    pub fn new(n: usize) -> Self {
+        Inbox { queues: vec![Vec::new(); n] }
+    }
+
+    fn push(&mut self, to: usize, port: u32, msgs: impl IntoIterator<Item = FlowMsg>) {
+        self.queues[to].extend(msgs.into_iter().map(|m| (port, m)));
+    }
+
+    pub fn take(&mut self, node: usize) -> Vec<(u32, FlowMsg)> {
+        std::mem::take(&mut self.queues[node])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::iiot::flow::types::TagValue;
+
+    // §7.1 範例 flow 的 edge（節錄）
+    const FLOW: &str = r#"{
+        "nodes": [
+            { "id": "src-temp",   "wasm": "x" }, { "id": "src-ctrl",   "wasm": "x" },
+            { "id": "math-f2c",   "wasm": "x" }, { "id": "mux-1",      "wasm": "x" },
+            { "id": "router-1",   "wasm": "x" }, { "id": "sink-alarm", "wasm": "x" },
+            { "id": "end-alert",  "wasm": "x" }
+        ],
+        "edges": [
+            { "from": "src-temp", "to": "math-f2c" },
+            { "from": "src-temp", "to": "mux-1" },
+            { "from": "src-ctrl", "to": "mux-1",      "to-port": 2, "to-role": "condition" },
+            { "from": "router-1", "to": "sink-alarm" },
+            { "from": "router-1", "to": "end-alert" },
+            { "from": "mux-1",    "to": "sink-alarm" }
+        ]
+    }"#;
+
+    fn router() -> (Router, HashMap<String, usize>) {
+        let flow = FlowDef::parse(FLOW).unwrap();
+        let pos: HashMap<String, usize> = flow.nodes.iter().enumerate().map(|(i, n)| (n.id.clone(), i)).collect();
+        let by_str = pos.iter().map(|(k, &v)| (k.as_str(), v)).collect();
+        (Router::new(&flow, &by_str), pos)
+    }
+
+    fn msgs(ids: &[u32]) -> Vec<FlowMsg> {
+        ids.iter().map(|&msg_id| FlowMsg {
+            tag_id: 1, msg_id, value: TagValue::F64Val(0.0), timestamp: 0, quality: 0,
+        }).collect()
+    }
+
+    fn ids(q: &[(u32, FlowMsg)]) -> Vec<(u32, u32)> {
+        q.iter().map(|(port, m)| (*port, m.msg_id)).collect()
+    }
+
+    #[test]
+    fn fan_out_follows_edge_declaration_order() {
+        let (r, pos) = router();
+        let routes: Vec<usize> = r.routes(pos["src-temp"], 0).iter().map(|r| r.to).collect();
+        assert_eq!(routes, vec![pos["math-f2c"], pos["mux-1"]]);
+
+        let mut inbox = Inbox::new(pos.len());
+        r.deliver(pos["src-temp"], 0, msgs(&[1, 2]), &mut inbox);
+        assert_eq!(ids(&inbox.take(pos["math-f2c"])), vec![(0, 1), (0, 2)]);
+        assert_eq!(ids(&inbox.take(pos["mux-1"])),    vec![(0, 1), (0, 2)], "每條 edge 都收到完整複本");
+        assert!(inbox.take(pos["math-f2c"]).is_empty(), "take 後清空");
+    }
+
+    #[test]
+    fn fan_in_keeps_delivery_order_and_ports() {
+        let (r, pos) = router();
+        let mut inbox = Inbox::new(pos.len());
+        // 依 topo 順序：src-temp 先於 src-ctrl 投遞
+        r.deliver(pos["src-temp"], 0, msgs(&[1]), &mut inbox);
+        r.deliver(pos["src-ctrl"], 0, msgs(&[2]), &mut inbox);
+        assert_eq!(ids(&inbox.take(pos["mux-1"])), vec![(0, 1), (2, 2)]);
+
+        r.deliver(pos["router-1"], 0, msgs(&[3]), &mut inbox);
+        r.deliver(pos["mux-1"],    0, msgs(&[4]), &mut inbox);
+        assert_eq!(ids(&inbox.take(pos["sink-alarm"])), vec![(0, 3), (0, 4)]);
+        assert_eq!(ids(&inbox.take(pos["end-alert"])),  vec![(0, 3)]);
+    }
+
+    #[test]
+    fn deliver_without_routes_is_a_no_op() {
+        let (r, pos) = router();
+        let mut inbox = Inbox::new(pos.len());
+        r.deliver(pos["sink-alarm"], 0, msgs(&[1]), &mut inbox); // sink 沒有下游
+        r.deliver(pos["src-temp"],   1, msgs(&[2]), &mut inbox); // port 1 沒有 edge
+        r.deliver(pos["src-temp"],   0, Vec::new(), &mut inbox); // 沒有訊息
+        assert!(r.routes(pos["sink-alarm"], 0).is_empty());
+        for n in 0..pos.len() {
+            assert!(inbox.take(n).is_empty(), "node {n} 不應收到訊息");
+        }
+    }
+}
@@ -0,0 +1,633 @@
+// host/src/runtime.rs
+// manifest.json 驅動的 Runtime（IIoTFlowArchitecture §13）
+//
+//   ① 讀取 manifest.json
+//   ② 載入 flow.cwasm / flow.wasm（依 prefer-native；native 載入失敗退回 wasm）
+//   ③ 開啟 host-endpoints，建立單一 Fused Pipeline instance
+//   ④ 依 sources 的 interval-ms 排程（scheduler.rs）：每次 tick 從 source endpoint 取出已到達的
+//      TagUpdate frame → pipeline 微批次（run-batch）→ 抵達 sink 的訊息送往 sink endpoint，
+//      抵達 sink-end 的訊息交給 handler（webhook 由背景 thread 經 bounded queue 送出，queue 滿時丟棄）
+//      batch.max-linger-ms 到期前若沒有下一次 tick，tick 結束時就送出，不讓訊息等到下一個週期
+//   ⑤ stop 旗標（SIGINT / SIGTERM）設定後，做完當下的 tick 就結束
+//   ⑥ pipeline trap 時由 Supervisor 隔離並依 backoff 重啟（manifest supervisor）；
+//      隔離期間不讀取 source，frame 留在 endpoint（nng-pull 的 queue 滿了就對 Driver 形成 backpressure）；
+//      每次 crash 寫出 crash dump 到 manifest crash-dump.dir
+//   ⑦ snapshot.dir：載入時還原 pipeline 狀態，之後每 interval-ms 與停止時寫入（snapshot.rs）
+//   ⑧ reload 旗標（SIGHUP）：背景載入新版本，在 tick 之間不停機切換，grace 期間 crash 即換回（redeploy.rs）
+
+use anyhow::{anyhow, bail, Context, Result};
+use prost::Message as _;
+use std::collections::{BTreeMap, HashMap};
+use std::fs::OpenOptions;
+use std::io::{Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::mpsc::{sync_channel, Receiver, SyncSender, TryRecvError};
+use std::sync::{Arc, RwLock};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+use wasmtime::Engine;
+
+use crate::aot::CompileCache;
+use crate::endpoint::Endpoint;
+use crate::limits::ExecLimits;
+use crate::manifest::{Manifest, SinkEndDef, SourceDef};
+use crate::redeploy::{self, Candidate, Deployment, Standby};
+use crate::scheduler::{self, Scheduler};
+use crate::snapshot::{Snapshot, SnapshotStore};
+use crate::supervisor::Supervisor;
+use crate::{next_msg_id, proto, AsyncPipeline, BatchConfig, FusedPipeline, TagMeta, TagRegistry};
+
+// 單次 tick 最多處理的 frame 數，避免單一 source 長時間佔住 pipeline
+// （100ms tick 約 80k msgs/sec 上限，高於 SystemRequirements §6 的 40,000 tags/sec）
+const MAX_FRAMES_PER_TICK: usize = 8192;
+
+// 每個 webhook sink-end 等待送出的事件上限（同 async_runtime.rs 的 HANDLER_QUEUE）
+const WEBHOOK_QUEUE: usize = 1024;
+
+#[derive(Debug, Default, Clone)]
+pub struct RuntimeStats {
+    pub ticks:     u64,
+    pub frames:    u64,
+    pub sunk:      u64, // 送往 sink endpoint 的訊息
+    pub events:    u64, // 觸發 sink-end handler 的訊息
+    pub dropped:   u64, // 無法解碼、過大或截斷的 frame
+    pub io_errors: u64, // sink endpoint / sink-end handler 失敗
+    pub overflow:  u64, // webhook queue 滿而丟棄的 sink-end 事件
+    pub lost:      u64, // pipeline crash 時尚未產出結果的 frame
+    pub redeploys: u64, // 完成切換的 redeploy
+    pub rollbacks: u64, // 取消或換回舊版本的 redeploy
+}
+
+struct Source {
+    def:      SourceDef,
+    endpoint: usize, // endpoints 的索引
+}
+
+pub struct Runtime {
+    pub live:     Deployment<FusedPipeline, Option<Webhook>>, // 目前執行中的版本（None = 本機寫入的 handler）
+    pub stats:    RuntimeStats,
+    pub schedule: Scheduler,         // 與 manifest.sources 對齊
+    pub restored: Option<Snapshot>,  // 啟動時還原的 snapshot
+    pub reload:   Arc<AtomicBool>,   // 設定後於下一次 tick 前開始 redeploy（SIGHUP）
+    registry:     Arc<RwLock<TagRegistry>>,
+    sources:      Vec<Source>,
+    endpoints:    Vec<Endpoint>,
+    hook_errors:  Arc<AtomicU64>, // webhook worker 的失敗次數，step 時併入 stats.io_errors
+    engine:       Engine,
+    cache:        CompileCache,
+    incoming:     Option<Receiver<Result<Candidate>>>,                        // 背景載入中的新版本
+    standby:      Option<Standby<Deployment<FusedPipeline, Option<Webhook>>>>, // grace 期間保留的舊版本
+}
+
+impl Runtime {
+    pub fn load(engine: &Engine, cache: &CompileCache,
+                registry: Arc<RwLock<TagRegistry>>, dir: impl Into<PathBuf>) -> Result<Self> {
+        Self::open(engine, cache, registry, Manifest::load(dir.into())?, None)
+    }
+
+    /// flow manager 用（manager.rs）：type = "bus" 的 endpoint 從 bus 讀取
+    pub fn load_on_bus(engine: &Engine, cache: &CompileCache, registry: Arc<RwLock<TagRegistry>>,
+                       manifest: Manifest, bus: Receiver<Vec<u8>>) -> Result<Self> {
+        Self::open(engine, cache, registry, manifest, Some(bus))
+    }
+
+    fn open(engine: &Engine, cache: &CompileCache, registry: Arc<RwLock<TagRegistry>>,
+            manifest: Manifest, mut bus: Option<Receiver<Vec<u8>>>) -> Result<Self> {
+        let (pipeline, artifact) = load_pipeline(engine, cache, Arc::clone(&registry), &manifest)?;
+
+        let mut endpoints = Vec::new();
+        for def in &manifest.host_endpoints {
+            endpoints.push(match bus.take_if(|_| def.kind == "bus") {
+                Some(rx) => Endpoint::bus(def, rx),
+                None     => Endpoint::open(def, &manifest.dir)?,
+            });
+        }
+        let sources = manifest.sources.iter().zip(manifest.source_endpoints()?)
+            .map(|(def, id)| Source {
+                def:      def.clone(),
+                endpoint: endpoints.iter().position(|e| e.id == id).unwrap(),
+            })
+            .collect();
+        let schedule = Scheduler::new(manifest.sources.iter()
+            .map(|s| (Duration::from_millis(s.interval_ms), s.overrun)));
+        let hook_errors = Arc::new(AtomicU64::new(0));
+        let sink_ends = sink_ends(&manifest, &hook_errors)?;
+
+        let mut live = Deployment::new(manifest, artifact, pipeline, sink_ends)?;
+        let mut restored = None;
+        if let Some(snap) = live.snapshots.as_ref().map(|s| s.load(&live.manifest.flow_id)).transpose()?.flatten() {
+            match live.pipeline.load_states(snap.payload.clone()) {
+                Ok(()) => restored = Some(snap),
+                Err(e) => {
+                    // 版本不符時拒絕還原；load-states trap 後 instance 已無法使用，一律重新建立並從初始狀態開始
+                    eprintln!("⚠️  snapshot {} 無法還原，從初始狀態開始：{e:#}", snap.path.display());
+                    live.pipeline = live.pipeline.respawn()?;
+                }
+            }
+        }
+
+        Ok(Runtime {
+            live, stats: RuntimeStats::default(), schedule, restored, reload: Arc::new(AtomicBool::new(false)),
+            registry, sources, endpoints, hook_errors, engine: engine.clone(), cache: cache.fork(),
+            incoming: None, standby: None,
+        })
+    }
+
+    /// 依 interval-ms 排程各 source，直到 stop 被設定或所有 source endpoint 都結束（file 讀完）
+    pub fn run(&mut self, stop: &AtomicBool) -> Result<()> {
+        while let Some(next) = self.step()? {
+            if !scheduler::sleep_until(next, stop) { break; }
+        }
+        self.finish()
+    }
+
+    /// 執行一輪：redeploy / snapshot 等例行工作、到期的 source tick 一次；
+    /// 回傳下一次要呼叫的時間，所有 source 都結束時回傳 None（之後呼叫 finish）
+    /// run 與 worker pool（pool.rs）共用，同一個 thread 可以輪流執行多個 Runtime
+    pub fn step(&mut self) -> Result<Option<Instant>> {
+        self.stats.io_errors += self.hook_errors.swap(0, Ordering::Relaxed);
+        if self.reload.swap(false, Ordering::Relaxed) {
+            if let Err(e) = self.redeploy() { eprintln!("⚠️  {e:#}"); }
+        }
+        self.poll_redeploy();
+        if self.live.snapshots.as_ref().is_some_and(SnapshotStore::due) { self.snapshot()?; }
+        let due = self.schedule.next_due(|i| self.active(i));
+        if let Some((i, _)) = due.filter(|&(_, due)| due <= Instant::now()) {
+            let started = Instant::now();
+            let r = self.tick(i).map(drop);
+            self.settle(r)?;
+            self.schedule.complete(i, started);
+        }
+
+        let next = self.schedule.next_due(|i| self.active(i)).map(|(_, due)| due);
+        // 下一次 tick 之前 linger 就會到期（或不會再有 tick）：現在送出
+        if self.live.pipeline.linger_deadline().is_some_and(|d| next.is_none_or(|n| d <= n)) {
+            let r = self.flush();
+            self.settle(r)?;
+        }
+        Ok(next)
+    }
+
+    /// 停止或所有 source 結束後：送出剩下的微批次、寫入最後一份 snapshot
+    pub fn finish(&mut self) -> Result<()> {
+        let r = self.flush();
+        self.settle(r)?;
+        // 停止時新版本仍正常執行：視為通過觀察期
+        self.commit();
+        let r = self.snapshot();
+        // 等 webhook worker 送完 queue 內剩下的事件
+        for hook in self.live.sink_ends.values_mut().filter_map(|(_, hook)| hook.as_mut()) { hook.close(); }
+        self.stats.io_errors += self.hook_errors.swap(0, Ordering::Relaxed);
+        r
+    }
+
+    // ── redeploy ─────────────────────────────────────────────────────────────
+
+    /// 在背景載入 manifest 目錄內目前的 manifest.json 與 artifact，就緒後於 tick 之間切換
+    pub fn redeploy(&mut self) -> Result<()> {
+        if self.redeploying() { bail!("上一次 redeploy 尚未結束，略過"); }
+        eprintln!("🚚 redeploy：背景載入 {}", self.live.manifest.dir.display());
+        self.incoming = Some(redeploy::prepare(&self.engine, self.cache.fork(), Arc::clone(&self.registry),
+                                               &self.live.manifest)?);
+        Ok(())
+    }
+
+    /// 新版本的 redeploy 進行中（背景載入或 grace 期間）
+    pub fn redeploying(&self) -> bool {
+        self.incoming.is_some() || self.standby.is_some()
+    }
+
+    // 背景載入完成就切換；載入或切換失敗時繼續使用目前的版本
+    fn poll_redeploy(&mut self) {
+        let Some(rx) = &self.incoming else { return; };
+        let candidate = match rx.try_recv() {
+            Ok(r) => r,
+            Err(TryRecvError::Empty) => return,
+            Err(TryRecvError::Disconnected) => Err(anyhow!("背景載入的 thread 異常結束")),
+        };
+        self.incoming = None;
+        if let Err(e) = candidate.and_then(|c| self.switch(c)) {
+            redeploy::cancel(&self.live, &mut self.stats, &e);
+        }
+    }
+
+    fn switch(&mut self, c: Candidate) -> Result<()> {
+        // 舊版本執行到目前為止的狀態；隔離中無法取得時新版本從初始狀態開始
+        let state = self.flush()
+            .and_then(|()| self.live.supervisor.call(&mut self.live.pipeline, FusedPipeline::save_states));
+        let state = match state {
+            Ok(state) => state,
+            Err(e) => { eprintln!("⚠️  {e:#}"); None }
+        };
+        let mut pipeline = c.pipeline;
+        match state {
+            Some(state) => pipeline.carry_states(state).context("新版本無法載入目前的狀態")?,
+            None => eprintln!("⚠️  {} 目前無法取得狀態，新版本從初始狀態開始", self.live.supervisor.name),
+        }
+
+        let sink_ends = sink_ends(&c.manifest, &self.hook_errors)?;
+        let next = Deployment::new(c.manifest, c.artifact, pipeline, sink_ends)?;
+        self.standby = Some(redeploy::promote(&mut self.live, next, c.elapsed, &mut self.stats));
+        Ok(())
+    }
+
+    fn settle(&mut self, r: Result<()>) -> Result<()> {
+        redeploy::settle(&mut self.live, &mut self.standby, &mut self.stats, r)
+    }
+
+    fn commit(&mut self) {
+        redeploy::commit(&self.live, &mut self.standby);
+    }
+
+    // source i 的 endpoint 是否還會有資料
+    fn active(&self, i: usize) -> bool {
+        !self.endpoints[self.sources[i].endpoint].is_closed()
+    }
+
+    pub fn endpoints(&self) -> &[Endpoint] {
+        &self.endpoints
+    }
+
+    /// source 名稱（tick-export），與 schedule 的索引對齊
+    pub fn source_names(&self) -> impl Iterator<Item = &str> {
+        self.sources.iter().map(|s| s.def.tick_export.as_str())
+    }
+
+    /// 執行 source i 一次：取出 endpoint 上已到達的 frame 放進 pipeline 的微批次，回傳處理筆數
+    /// （達到 batch.max-size 的部分會立即執行，其餘由 run / flush 送出）
+    /// pipeline 隔離中不讀取 frame；crash 超過 max-crashes 回傳錯誤
+    pub fn tick(&mut self, i: usize) -> Result<usize> {
+        self.stats.ticks += 1;
+        let live = &mut self.live;
+        if !live.supervisor.ready(&mut live.pipeline)? { return Ok(0); }
+        // 只有這個 source 的 Source Node 處理 frame（redeploy 後 nodes() 可能不同，每次 tick 重新查）
+        let source = live.pipeline.source(&self.sources[i].def.tick_export)?;
+        let ep = self.sources[i].endpoint;
+        let frames = self.endpoints[ep].recv(MAX_FRAMES_PER_TICK)
+            .with_context(|| format!("source {}", self.sources[i].def.tick_export))?;
+        // 過大 / 截斷的 frame：endpoint 已關閉該連線，這裡只計數
+        self.stats.dropped += self.endpoints[ep].take_rejected();
+        let n = frames.len();
+        let mut frames = frames.into_iter();
+        while let Some(frame) = frames.next() {
+            self.stats.frames += 1;
+            let Some(tag_id) = assign(&self.registry, &frame) else {
+                self.stats.dropped += 1;
+                continue;
+            };
+            let batch = self.live.pipeline.pending() as u64 + 1;
+            let live = &mut self.live;
+            match live.supervisor.call(&mut live.pipeline, |p| p.push(source, tag_id, next_msg_id(), frame))? {
+                Some(outputs) => self.deliver(outputs)?,
+                None => {
+                    // crash：這一批與本次 tick 剩下的 frame 都不會有結果
+                    self.stats.count_lost(batch, frames.len());
+                    break;
+                }
+            }
+        }
+        Ok(n)
+    }
+
+    /// 送出 pipeline 內尚未執行的微批次
+    pub fn flush(&mut self) -> Result<()> {
+        let batch = self.live.pipeline.pending() as u64;
+        if batch == 0 { return Ok(()); }
+        let live = &mut self.live;
+        match live.supervisor.call(&mut live.pipeline, FusedPipeline::flush)? {
+            Some(outputs) => self.deliver(outputs),
+            None => { self.stats.lost += batch; Ok(()) }
+        }
+    }
+
+    /// 寫入一份 snapshot（先 flush，save-states 只反映已執行的訊息）
+    /// pipeline 隔離中與 redeploy 的 grace 期間略過；磁碟錯誤只警告，不中斷 Runtime
+    pub fn snapshot(&mut self) -> Result<()> {
+        if self.standby.is_some() { return Ok(()); }
+        let Some(store) = &mut self.live.snapshots else { return Ok(()); };
+        store.mark();
+        self.flush()?;
+        let live = &mut self.live;
+        let Some(state) = live.supervisor.call(&mut live.pipeline, FusedPipeline::save_states)? else {
+            return Ok(());
+        };
+        if let Some(store) = &live.snapshots {
+            if let Err(e) = store.save(&live.manifest.flow_id, &state) { eprintln!("⚠️  {e:#}"); }
+        }
+        Ok(())
+    }
+
+    // 抵達 sink-end → handler；其餘 sink → role = sink 且 node 相符（或未指定）的 endpoint
+    fn deliver(&mut self, outputs: Vec<Vec<u8>>) -> Result<()> {
+        for m in sunk(&outputs)? {
+            match route(&self.registry, &self.live, m) {
+                Route::SinkEnd((end, hook), event) => {
+                    self.stats.events += 1;
+                    if let Some(hook) = hook {
+                        if !hook.offer(event.to_string()) { self.stats.overflow += 1; }
+                    } else if let Err(e) = fire(end, &self.live.manifest, &event) {
+                        self.stats.io_errors += 1;
+                        eprintln!("⚠️  sink-end {} handler 失敗：{e:#}", end.id);
+                    }
+                }
+                Route::Sink(sink, result) => {
+                    for ep in &mut self.endpoints {
+                        if ep.role != "sink" || ep.node.as_ref().is_some_and(|n| *n != sink) { continue; }
+                        self.stats.sunk += 1;
+                        if let Err(e) = ep.send(&result) {
+                            self.stats.io_errors += 1;
+                            eprintln!("⚠️  {e:#}");
+                        }
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl RuntimeStats {
+    // crash 時 pipeline 內的 batch 筆與本次 tick 還沒處理的 rest 個 frame 都不會有結果
+    pub(crate) fn count_lost(&mut self, batch: u64, rest: usize) {
+        self.frames += rest as u64;
+        self.lost   += batch + rest as u64;
+    }
+}
+
+// ── Runtime / AsyncRuntime 共用 ──────────────────────────────────────────────
+
+/// 共用邏輯需要的 pipeline 介面（FusedPipeline / AsyncPipeline 的非 async 部分）
+pub(crate) trait Pipeline {
+    fn version(&self) -> &str;
+    fn nodes(&self) -> &[String];
+    fn configure(&mut self, batch: BatchConfig, flow: ExecLimits, nodes: &BTreeMap<String, ExecLimits>);
+}
+
+impl Pipeline for FusedPipeline {
+    fn version(&self) -> &str { &self.version }
+    fn nodes(&self) -> &[String] { &self.nodes }
+    fn configure(&mut self, batch: BatchConfig, flow: ExecLimits, nodes: &BTreeMap<String, ExecLimits>) {
+        self.batch = batch;
+        self.set_limits(flow, nodes);
+    }
+}
+
+impl Pipeline for AsyncPipeline {
+    fn version(&self) -> &str { &self.version }
+    fn nodes(&self) -> &[String] { &self.nodes }
+    fn configure(&mut self, batch: BatchConfig, flow: ExecLimits, nodes: &BTreeMap<String, ExecLimits>) {
+        self.batch = batch;
+        self.set_limits(flow, nodes);
+    }
+}
+
+/// Host 職責：分配 tag_id（Tag Registry 沒有的 tag 以 TagUpdate 內容建立）；無法解碼的 frame 回傳 None
+pub(crate) fn assign(registry: &RwLock<TagRegistry>, frame: &[u8]) -> Option<u32> {
+    let tu = proto::TagUpdate::decode(frame).ok()?;
+    Some(registry.write().unwrap().get_or_create(&tu.tag_id_str, TagMeta::from_update(&tu)))
+}
+
+/// pipeline 的輸出中抵達 sink / sink-end 的訊息
+pub(crate) fn sunk(outputs: &[Vec<u8>]) -> Result<Vec<proto::SunkMsg>> {
+    let mut sunk = Vec::new();
+    for out in outputs.iter().filter(|o| !o.is_empty()) {
+        sunk.extend(proto::FusedOutput::decode(out.as_slice())?.sunk);
+    }
+    Ok(sunk)
+}
+
+/// 抵達 sink 的訊息要送往哪裡；實際送出由各自的 Runtime 處理
+pub(crate) enum Route<'a, H> {
+    SinkEnd(&'a (SinkEndDef, H), serde_json::Value), // handler 與事件內容
+    Sink(String, proto::FlowResult),                 // sink Node id 與送往 sink endpoint 的結果
+}
+
+pub(crate) fn route<'a, P, H>(registry: &RwLock<TagRegistry>, live: &'a Deployment<P, H>,
+                              m: proto::SunkMsg) -> Route<'a, H> {
+    let (tag_name, mqtt_topic) = {
+        let reg = registry.read().unwrap();
+        (reg.get_attr(m.tag_id, "name").unwrap_or_default(),
+         reg.get_attr(m.tag_id, "mqtt_topic").unwrap_or_default())
+    };
+    if let Some(end) = live.sink_ends.get(&m.sink) {
+        return Route::SinkEnd(end, event(&end.0, &live.manifest, &m, &tag_name));
+    }
+    Route::Sink(m.sink, proto::FlowResult {
+        tag_id: m.tag_id, tag_name, mqtt_topic, msg_id: m.msg_id,
+        value: m.value.unwrap_or(f64::NAN), timestamp: m.timestamp, quality: m.quality,
+    })
+}
+
+// ── 載入 ─────────────────────────────────────────────────────────────────────
+
+/// 依 manifest 載入 artifact（依 prefer-native 順序，載入失敗退回下一個），套用 batch 與執行上限
+pub(crate) fn load_pipeline(engine: &Engine, cache: &CompileCache, registry: Arc<RwLock<TagRegistry>>,
+                            manifest: &Manifest) -> Result<(FusedPipeline, PathBuf)> {
+    let mut loaded = None;
+    for path in manifest.artifact_paths() {
+        if !path.exists() {
+            eprintln!("⚠️  artifact 不存在，略過：{}", path.display());
+            continue;
+        }
+        match FusedPipeline::load(engine, cache, Arc::clone(&registry), &path.to_string_lossy(),
+                                  manifest.limits) {
+            Ok(p)  => { loaded = Some((p, path)); break; }
+            Err(e) => eprintln!("⚠️  artifact 載入失敗，改用下一個：{}：{e:#}", path.display()),
+        }
+    }
+    let Some((mut pipeline, artifact)) = loaded else {
+        bail!("manifest {} 沒有可載入的 artifact", manifest.flow_id);
+    };
+    configure(&mut pipeline, manifest)?;
+    Ok((pipeline, artifact))
+}
+
+/// load_pipeline 載入 artifact 之後（async_runtime.rs 共用）：檢查 source，套用 batch 與執行上限
+pub(crate) fn configure(pipeline: &mut impl Pipeline, manifest: &Manifest) -> Result<()> {
+    check_sources(manifest, pipeline.nodes())?;
+    pipeline.configure(manifest.batch.config(), manifest.limits, &manifest.node_limits);
+    for id in manifest.node_limits.keys().filter(|id| !pipeline.nodes().contains(id)) {
+        eprintln!("⚠️  node-limits 的 {id} 不在 flow 內，略過");
+    }
+    if !manifest.version.is_empty() && manifest.version != pipeline.version() {
+        eprintln!("⚠️  manifest version {} 與 artifact version {} 不一致",
+            manifest.version, pipeline.version());
+    }
+    Ok(())
+}
+
+// 每個 source 都要對應到 artifact 內的 Source Node；0.1.0（nodes 為空）的 run 會執行所有 Source，只能有一個 source
+pub(crate) fn check_sources(manifest: &Manifest, nodes: &[String]) -> Result<()> {
+    if nodes.is_empty() {
+        if manifest.sources.len() > 1 {
+            bail!("0.1.0 的 fused pipeline 無法分別執行 {} 個 source，請以新版 iiot-flow-fusion 重新產生",
+                manifest.sources.len());
+        }
+        return Ok(());
+    }
+    match manifest.sources.iter().find(|s| !nodes.contains(&s.tick_export)) {
+        Some(s) => bail!("source {} 不在 artifact 的 Node 內", s.tick_export),
+        None    => Ok(()),
+    }
+}
+
+pub(crate) fn supervisor(manifest: &Manifest) -> Supervisor {
+    let mut supervisor = Supervisor::new(&format!("flow {}", manifest.flow_id), manifest.supervisor);
+    supervisor.dumps = manifest.crash_dump.dumper(&manifest.dir);
+    supervisor
+}
+
+// 每個 sink-end 的設定；webhook 另有背景 worker
+fn sink_ends(manifest: &Manifest, errors: &Arc<AtomicU64>) -> Result<HashMap<String, (SinkEndDef, Option<Webhook>)>> {
+    manifest.sink_ends.iter().map(|def| {
+        let webhook = match def.handler.as_str() {
+            "webhook" => Some(Webhook::spawn(def, Arc::clone(errors))?),
+            _ => None,
+        };
+        Ok((def.id.clone(), (def.clone(), webhook)))
+    }).collect()
+}
+
+// ── sink-end handler ────────────────────────────────────────────────────────
+
+/// webhook 在背景 thread 依序 POST，慢或沒有回應的對端不會卡住 pipeline thread；
+/// drop 時只關閉 queue（worker 送完剩下的事件後自行結束，redeploy 換下的版本不等待），close 則等 worker 結束
+pub struct Webhook {
+    tx:     Option<SyncSender<String>>,
+    worker: Option<JoinHandle<()>>,
+}
+
+impl Webhook {
+    fn spawn(def: &SinkEndDef, errors: Arc<AtomicU64>) -> Result<Self> {
+        http_target(&def.target)?;
+        let (tx, rx) = sync_channel::<String>(WEBHOOK_QUEUE);
+        let (id, url, timeout) = (def.id.clone(), def.target.clone(), Duration::from_millis(def.timeout_ms));
+        let worker = std::thread::Builder::new().name(format!("webhook {id}")).spawn(move || {
+            for body in rx {
+                if let Err(e) = post_json(&url, &body, timeout) {
+                    errors.fetch_add(1, Ordering::Relaxed);
+                    eprintln!("⚠️  sink-end {id} handler 失敗：{e:#}");
+                }
+            }
+        })?;
+        Ok(Webhook { tx: Some(tx), worker: Some(worker) })
+    }
+
+    // 放進 queue；queue 滿時回傳 false（事件丟棄）
+    fn offer(&self, body: String) -> bool {
+        self.tx.as_ref().is_some_and(|tx| tx.try_send(body).is_ok())
+    }
+
+    fn close(&mut self) {
+        drop(self.tx.take());
+        if let Some(w) = self.worker.take() { let _ = w.join(); }
+    }
+}
+
+impl Drop for Webhook {
+    fn drop(&mut self) {
+        drop(self.tx.take());
+    }
+}
+
+// log / file：在 pipeline thread 上直接處理（本機寫入）
+fn fire(end: &SinkEndDef, manifest: &Manifest, event: &serde_json::Value) -> Result<()> {
+    match end.handler.as_str() {
+        "log" => {
+            println!("  🔔 [sink-end {}] {event}", end.id);
+            Ok(())
+        }
+        "file" => {
+            let path = manifest.path(&end.target);
+            let mut f = OpenOptions::new().create(true).append(true).open(&path)
+                .with_context(|| format!("無法寫入 {}", path.display()))?;
+            writeln!(f, "{event}")?;
+            Ok(())
+        }
+        other => bail!("handler 不支援：{other}"),
+    }
+}
+
+/// 交給 handler 的事件內容（async_endpoint.rs 共用）
+pub(crate) fn event(end: &SinkEndDef, manifest: &Manifest, m: &proto::SunkMsg, tag_name: &str) -> serde_json::Value {
+    serde_json::json!({
+        "flow-id":   manifest.flow_id,
+        "sink-end":  end.id,
+        "tag":       tag_name,
+        "tag-id":    m.tag_id,
+        "msg-id":    m.msg_id,
+        "value":     m.value,
+        "timestamp": m.timestamp,
+        "quality":   m.quality,
+    })
+}
+
+// 最小的 HTTP/1.1 POST（只支援 http://，manifest 載入時已檢查）
+fn post_json(url: &str, body: &str, timeout: Duration) -> Result<()> {
+    let (authority, addr, path) = http_target(url)?;
+    let sa = addr.to_socket_addrs()?.next().with_context(|| format!("無法解析位址 {addr}"))?;
+
+    let mut s = TcpStream::connect_timeout(&sa, timeout)?;
+    s.set_read_timeout(Some(timeout))?;
+    s.set_write_timeout(Some(timeout))?;
+    s.write_all(http_post(authority, &path, body).as_bytes())?;
+
+    let mut head = [0u8; 12];
+    s.read_exact(&mut head).context("webhook 沒有回應")?;
+    http_status(url, &head)
+}
+
+/// http://host[:port]/path → (Host 標頭, 連線位址, path)（async_endpoint.rs 共用）
+pub(crate) fn http_target(url: &str) -> Result<(&str, String, String)> {
+    let rest = url.strip_prefix("http://").with_context(|| format!("webhook 只支援 http://：{url}"))?;
+    let (authority, path) = rest.split_once('/').map_or((rest, "/".to_string()), |(a, p)| (a, format!("/{p}")));
+    let addr = if authority.contains(':') { authority.to_string() } else { format!("{authority}:80") };
+    Ok((authority, addr, path))
+}
+
+pub(crate) fn http_post(authority: &str, path: &str, body: &str) -> String {
+    format!("POST {path} HTTP/1.1\r\nHost: {authority}\r\nContent-Type: application/json\r\n\
+             Content-Length: {}\r\nConnection: close\r\n\r\n{body}", body.len())
+}
+
+/// 回應的前 12 bytes（HTTP/1.1 NNN）：非 2xx 回傳錯誤
+pub(crate) fn http_status(url: &str, head: &[u8; 12]) -> Result<()> {
+    let status = std::str::from_utf8(&head[9..12]).unwrap_or("");
+    if !status.starts_with('2') { bail!("webhook 回應 HTTP {status}：{url}"); }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn manifest(sources: &[&str]) -> Manifest {
+        let endpoints: Vec<String> = (0..sources.len()).map(|i| format!(
+            r#"{{ "id": {i}, "type": "nng-pull", "address": "tcp://127.0.0.1:{}", "role": "source" }}"#, 5555 + i)).collect();
+        let sources: Vec<String> = sources.iter().map(|id| format!(
+            r#"{{ "tick-export": "{id}", "interval-ms": 100 }}"#)).collect();
+        Manifest::parse(&format!(r#"{{ "flow-id": "flow", "artifact": {{ "wasm": "flow.wasm" }},
+            "sources": [{}], "host-endpoints": [{}] }}"#, sources.join(","), endpoints.join(","))).unwrap()
+    }
+
+    fn nodes(ids: &[&str]) -> Vec<String> {
+        ids.iter().map(|id| id.to_string()).collect()
+    }
+
+    #[test]
+    fn every_source_must_be_a_node_of_the_artifact() {
+        let flow = nodes(&["src-temp", "src-ctrl", "math-f2c", "sink"]);
+        check_sources(&manifest(&["src-temp", "src-ctrl"]), &flow).unwrap();
+        let e = check_sources(&manifest(&["src-temp", "src-pressure"]), &flow).unwrap_err();
+        assert_eq!(e.to_string(), "source src-pressure 不在 artifact 的 Node 內");
+    }
+
+    #[test]
+    fn legacy_artifact_allows_a_single_source_only() {
+        check_sources(&manifest(&["src-temp"]), &[]).unwrap();
+        let e = check_sources(&manifest(&["src-temp", "src-ctrl"]), &[]).unwrap_err();
+        assert!(e.to_string().starts_with("0.1.0 的 fused pipeline 無法分別執行 2 個 source"), "{e}");
+    }
+}
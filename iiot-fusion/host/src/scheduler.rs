@@ -0,0 +1,176 @@
+// host/src/scheduler.rs
+// 多 Source 週期排程（IIoTFlowArchitecture §8.3 / §13.1 ⑤）
+//
+//   每個 source 各自的 interval-ms，共用同一條執行緒（同一個 WASM instance 不可並行呼叫）
+//   tick 超時（overrun）時依 policy 處理：
+//     skip      錯過的週期直接跳過，下一次對齊到原本的時間格
+//     catch-up  錯過的週期立即補跑（連續呼叫），直到追上時間格
+//   jitter = 實際開始時間 - 排定時間，每個 source 各自統計
+
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+// 等待下一次 tick 時每隔多久檢查一次停止旗標
+const STOP_POLL: Duration = Duration::from_millis(50);
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum OverrunPolicy {
+    #[default]
+    Skip,
+    CatchUp,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct TickStats {
+    pub ticks:      u64,
+    pub overruns:   u64,      // tick 結束時已超過下一個時間格
+    pub skipped:    u64,      // skip policy 丟掉的週期數
+    pub jitter_max: Duration,
+    jitter_sum:     Duration,
+}
+
+impl TickStats {
+    pub fn jitter_mean(&self) -> Duration {
+        if self.ticks == 0 { return Duration::ZERO; }
+        self.jitter_sum / self.ticks as u32
+    }
+}
+
+struct Slot {
+    interval: Duration,
+    policy:   OverrunPolicy,
+    next:     Instant, // 排定的下一次開始時間（時間格，不受 jitter 影響）
+    stats:    TickStats,
+}
+
+pub struct Scheduler {
+    slots: Vec<Slot>,
+}
+
+/// 等到 due；stop 被設定時提早回傳 false
+pub fn sleep_until(due: Instant, stop: &AtomicBool) -> bool {
+    loop {
+        if stop.load(Ordering::Relaxed) { return false; }
+        let now = Instant::now();
+        if now >= due { return true; }
+        std::thread::sleep((due - now).min(STOP_POLL));
+    }
+}
+
+impl Scheduler {
+    /// 每個 source 一個 (interval, policy)；所有 source 從現在開始第一次 tick
+    pub fn new(sources: impl IntoIterator<Item = (Duration, OverrunPolicy)>) -> Self {
+        let now = Instant::now();
+        let slots = sources.into_iter()
+            .map(|(interval, policy)| Slot { interval, policy, next: now, stats: TickStats::default() })
+            .collect();
+        Scheduler { slots }
+    }
+
+    pub fn stats(&self, i: usize) -> &TickStats {
+        &self.slots[i].stats
+    }
+
+    /// 最早到期的 active source 與其排定時間
+    pub fn next_due(&self, active: impl Fn(usize) -> bool) -> Option<(usize, Instant)> {
+        (0..self.slots.len()).filter(|&i| active(i))
+            .map(|i| (i, self.slots[i].next))
+            .min_by_key(|&(_, next)| next)
+    }
+
+    /// source i 的 tick 已於 started 開始、現在結束：記錄 jitter / overrun 並排定下一次
+    pub fn complete(&mut self, i: usize, started: Instant) {
+        let slot = &mut self.slots[i];
+        let jitter = started.saturating_duration_since(slot.next);
+        slot.stats.ticks += 1;
+        slot.stats.jitter_sum += jitter;
+        slot.stats.jitter_max = slot.stats.jitter_max.max(jitter);
+
+        let now  = Instant::now();
+        let next = slot.next + slot.interval;
+        if now <= next {
+            slot.next = next;
+            return;
+        }
+        slot.stats.overruns += 1;
+        slot.next = match slot.policy {
+            OverrunPolicy::CatchUp => next,
+            OverrunPolicy::Skip => {
+                // 跳到 now 之後的第一個時間格
+                let missed = ((now - next).as_nanos() / slot.interval.as_nanos()) as u32 + 1;
+                slot.stats.skipped += missed as u64;
+                next + slot.interval * missed
+            }
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SEC: Duration = Duration::from_secs(1);
+
+    /// 單一 source，排定時間往回移 behind（模擬已落後多少）
+    fn behind(policy: OverrunPolicy, behind: Duration) -> (Scheduler, Instant) {
+        let mut s = Scheduler::new([(SEC, policy)]);
+        let base = Instant::now() - behind;
+        s.slots[0].next = base;
+        (s, base)
+    }
+
+    #[test]
+    fn on_time_tick_advances_one_interval() {
+        let mut s = Scheduler::new([(SEC, OverrunPolicy::Skip)]);
+        let (_, due) = s.next_due(|_| true).unwrap();
+        s.complete(0, due + Duration::from_millis(5));
+        assert_eq!(s.slots[0].next, due + SEC, "對齊原本的時間格，不受 jitter 影響");
+        let st = s.stats(0);
+        assert_eq!((st.ticks, st.overruns, st.skipped), (1, 0, 0));
+        assert_eq!(st.jitter_max, Duration::from_millis(5));
+        assert_eq!(st.jitter_mean(), Duration::from_millis(5));
+    }
+
+    #[test]
+    fn skip_jumps_to_the_first_slot_after_now() {
+        let (mut s, base) = behind(OverrunPolicy::Skip, Duration::from_millis(10_500));
+        s.complete(0, base);
+        // base+1s … base+10s 都已過去：跳過 10 格，下一次為 base+11s
+        assert_eq!(s.slots[0].next, base + SEC * 11);
+        assert!(s.slots[0].next > Instant::now());
+        let st = s.stats(0);
+        assert_eq!((st.ticks, st.overruns, st.skipped), (1, 1, 10));
+    }
+
+    #[test]
+    fn catch_up_runs_every_missed_slot() {
+        let (mut s, base) = behind(OverrunPolicy::CatchUp, Duration::from_millis(3_500));
+        for k in 1..=3 {
+            s.complete(0, Instant::now());
+            assert_eq!(s.slots[0].next, base + SEC * k, "第 {k} 次補跑");
+        }
+        assert_eq!(s.stats(0).overruns, 3);
+        // 補到 base+3s 後，下一格 base+4s 尚未到期
+        s.complete(0, Instant::now());
+        assert_eq!(s.slots[0].next, base + SEC * 4);
+        let st = s.stats(0);
+        assert_eq!((st.ticks, st.overruns, st.skipped), (4, 3, 0));
+    }
+
+    #[test]
+    fn next_due_picks_the_earliest_active_source() {
+        let mut s = Scheduler::new([(SEC, OverrunPolicy::Skip), (SEC * 2, OverrunPolicy::Skip)]);
+        let (_, due) = s.next_due(|_| true).unwrap();
+        s.complete(0, due);
+        assert_eq!(s.next_due(|_| true).map(|(i, _)| i), Some(1));
+        assert_eq!(s.next_due(|i| i == 0), Some((0, due + SEC)));
+        assert_eq!(s.next_due(|_| false), None);
+    }
+
+    #[test]
+    fn jitter_mean_without_ticks_is_zero() {
+        assert_eq!(TickStats::default().jitter_mean(), Duration::ZERO);
+    }
+}
@@ -0,0 +1,309 @@
+// host/src/snapshot.rs
+// Node 狀態的持久化 snapshot：gateway 重開機後還原 sliding average、計數器等狀態
+//
+//   <dir>/<key>/<seq>.snap   key = Fused：flow-id（save-states）；DAG：node id（save_state）
+//   每個 key 保留最新 keep 份；還原時由新到舊取第一份完整且 checksum 正確的
+//
+// 檔案格式（big-endian）：
+//   magic       8   b"IIOTSNAP"
+//   format      u16 FORMAT_VERSION
+//   seq         u64 同一個 key 內遞增
+//   time-ms     u64 unix ms
+//   key         u16 長度 + UTF-8
+//   payload     u64 長度 + save_state 的輸出
+//   sha256      32  以上所有 bytes
+//
+// 寫入：同目錄的暫存檔 → fsync → rename → fsync 目錄，斷電時只會看到舊檔或完整的新檔
+
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs::File;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+const MAGIC: &[u8; 8] = b"IIOTSNAP";
+pub const FORMAT_VERSION: u16 = 1;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case", default)]
+pub struct SnapshotConfig {
+    // 輸出目錄（相對於 manifest.json）；空字串 = 不做 snapshot
+    pub dir:         String,
+    pub interval_ms: u64,
+    // 每個 key 保留的份數
+    pub keep:        usize,
+}
+
+impl Default for SnapshotConfig {
+    fn default() -> Self {
+        SnapshotConfig { dir: "state".to_string(), interval_ms: 5000, keep: 3 }
+    }
+}
+
+impl SnapshotConfig {
+    pub fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// base 為相對路徑的基準目錄；dir 為空時回傳 None
+    pub fn open(&self, base: &Path) -> Result<Option<SnapshotStore>> {
+        if self.dir.is_empty() { return Ok(None); }
+        SnapshotStore::open(base.join(&self.dir), Duration::from_millis(self.interval_ms), self.keep).map(Some)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Snapshot {
+    pub key:     String,
+    pub seq:     u64,
+    pub time_ms: u64,
+    pub path:    PathBuf,
+    pub payload: Vec<u8>,
+}
+
+pub struct SnapshotStore {
+    pub dir:      PathBuf,
+    pub interval: Duration,
+    pub keep:     usize,
+    last:         Instant,
+}
+
+impl SnapshotStore {
+    pub fn open(dir: impl Into<PathBuf>, interval: Duration, keep: usize) -> Result<Self> {
+        let dir = dir.into();
+        std::fs::create_dir_all(&dir).with_context(|| format!("無法建立 snapshot 目錄 {}", dir.display()))?;
+        Ok(SnapshotStore { dir, interval, keep: keep.max(1), last: Instant::now() })
+    }
+
+    /// 距離上一次 snapshot 已超過 interval
+    pub fn due(&self) -> bool {
+        self.last.elapsed() >= self.interval
+    }
+
+    /// 重新計算 interval（不論這一輪是否成功，都等下一個 interval 再試）
+    pub fn mark(&mut self) {
+        self.last = Instant::now();
+    }
+
+    /// 寫入 key 的下一份 snapshot，回傳檔案路徑
+    pub fn save(&self, key: &str, payload: &[u8]) -> Result<PathBuf> {
+        let dir = self.key_dir(key);
+        std::fs::create_dir_all(&dir)?;
+        let seq = self.list(key)?.first().map_or(1, |(seq, _)| seq + 1);
+        let time_ms = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_millis() as u64;
+        let path = dir.join(format!("{seq:010}.snap"));
+        write_atomic(&path, &encode(key, seq, time_ms, payload))
+            .with_context(|| format!("snapshot 寫入失敗：{}", path.display()))?;
+        self.prune(key)?;
+        Ok(path)
+    }
+
+    /// key 最新的有效 snapshot；損毀的檔案警告後改用較舊的一份
+    pub fn load(&self, key: &str) -> Result<Option<Snapshot>> {
+        for (_, path) in self.list(key)? {
+            match std::fs::read(&path).map_err(anyhow::Error::from).and_then(|b| decode(&b)) {
+                Ok(mut snap) if snap.key == key => {
+                    snap.path = path;
+                    return Ok(Some(snap));
+                }
+                Ok(snap) => eprintln!("⚠️  snapshot {} 屬於 {}，不是 {key}，略過", path.display(), snap.key),
+                Err(e)   => eprintln!("⚠️  snapshot {} 無法使用，改用較舊的一份：{e:#}", path.display()),
+            }
+        }
+        Ok(None)
+    }
+
+    fn key_dir(&self, key: &str) -> PathBuf {
+        self.dir.join(slug(key))
+    }
+
+    // (seq, path)，新到舊
+    fn list(&self, key: &str) -> Result<Vec<(u64, PathBuf)>> {
+        let dir = self.key_dir(key);
+        if !dir.is_dir() { return Ok(Vec::new()); }
+        let mut snaps: Vec<(u64, PathBuf)> = std::fs::read_dir(&dir)?
+            .filter_map(|e| e.ok().map(|e| e.path()))
+            .filter(|p| p.extension().is_some_and(|e| e == "snap"))
+            .filter_map(|p| Some((p.file_stem()?.to_str()?.parse().ok()?, p)))
+            .collect();
+        snaps.sort_by_key(|s| std::cmp::Reverse(s.0));
+        Ok(snaps)
+    }
+
+    fn prune(&self, key: &str) -> Result<()> {
+        for (_, old) in self.list(key)?.iter().skip(self.keep) {
+            std::fs::remove_file(old)?;
+        }
+        Ok(())
+    }
+}
+
+fn encode(key: &str, seq: u64, time_ms: u64, payload: &[u8]) -> Vec<u8> {
+    let mut b = Vec::with_capacity(64 + key.len() + payload.len());
+    b.extend_from_slice(MAGIC);
+    b.extend_from_slice(&FORMAT_VERSION.to_be_bytes());
+    b.extend_from_slice(&seq.to_be_bytes());
+    b.extend_from_slice(&time_ms.to_be_bytes());
+    b.extend_from_slice(&(key.len() as u16).to_be_bytes());
+    b.extend_from_slice(key.as_bytes());
+    b.extend_from_slice(&(payload.len() as u64).to_be_bytes());
+    b.extend_from_slice(payload);
+    let sum = Sha256::digest(&b);
+    b.extend_from_slice(&sum);
+    b
+}
+
+fn decode(bytes: &[u8]) -> Result<Snapshot> {
+    if bytes.len() < MAGIC.len() + 32 || &bytes[..MAGIC.len()] != MAGIC { bail!("不是 snapshot 檔案"); }
+    let (body, sum) = bytes.split_at(bytes.len() - 32);
+    if Sha256::digest(body).as_slice() != sum { bail!("checksum 不符（檔案不完整或損毀）"); }
+
+    let mut r = Reader(&body[MAGIC.len()..]);
+    let format = u16::from_be_bytes(r.take()?);
+    if format != FORMAT_VERSION { bail!("snapshot 格式版本 {format} 不支援（目前 {FORMAT_VERSION}）"); }
+    let seq     = u64::from_be_bytes(r.take()?);
+    let time_ms = u64::from_be_bytes(r.take()?);
+    let key_len = u16::from_be_bytes(r.take()?) as usize;
+    let key     = String::from_utf8(r.bytes(key_len)?.to_vec()).context("key 不是 UTF-8")?;
+    let len     = u64::from_be_bytes(r.take()?) as usize;
+    let payload = r.bytes(len)?.to_vec();
+    if !r.0.is_empty() { bail!("payload 之後有多餘的 {} bytes", r.0.len()); }
+    Ok(Snapshot { key, seq, time_ms, path: PathBuf::new(), payload })
+}
+
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn bytes(&mut self, n: usize) -> Result<&'a [u8]> {
+        if self.0.len() < n { bail!("檔案長度不足"); }
+        let (head, rest) = self.0.split_at(n);
+        self.0 = rest;
+        Ok(head)
+    }
+
+    fn take<const N: usize>(&mut self) -> Result<[u8; N]> {
+        Ok(self.bytes(N)?.try_into().unwrap())
+    }
+}
+
+fn write_atomic(path: &Path, bytes: &[u8]) -> Result<()> {
+    let tmp = path.with_extension("snap.tmp");
+    let mut f = File::create(&tmp)?;
+    f.write_all(bytes)?;
+    f.sync_all()?;
+    std::fs::rename(&tmp, path)?;
+    // rename 本身也要落盤
+    if let Some(dir) = path.parent() { File::open(dir)?.sync_all()?; }
+    Ok(())
+}
+
+// 目錄名稱：只留英數、- 與 _
+fn slug(key: &str) -> String {
+    key.chars().map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' { c } else { '_' }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::TempDir;
+
+    fn store(dir: &TempDir, keep: usize) -> SnapshotStore {
+        SnapshotStore::open(dir.path().join("state"), Duration::from_secs(60), keep).unwrap()
+    }
+
+    #[test]
+    fn save_and_load_round_trip() {
+        let dir = TempDir::new("snapshot");
+        let s = store(&dir, 3);
+        assert!(s.load("flow").unwrap().is_none(), "沒有 snapshot 時回傳 None");
+
+        let path = s.save("flow", b"state-1").unwrap();
+        assert_eq!(path, s.dir.join("flow").join("0000000001.snap"));
+        let snap = s.load("flow").unwrap().expect("應讀到剛寫入的 snapshot");
+        assert_eq!((snap.key.as_str(), snap.seq, snap.payload.as_slice(), snap.path), ("flow", 1, &b"state-1"[..], path));
+        assert!(snap.time_ms > 0);
+        assert!(!s.dir.join("flow").join("0000000001.snap.tmp").exists(), "暫存檔應已 rename");
+    }
+
+    #[test]
+    fn keys_are_kept_apart() {
+        let dir = TempDir::new("snapshot");
+        let s = store(&dir, 3);
+        s.save("node-a", b"a").unwrap();
+        s.save("plant/1", b"b").unwrap();
+        assert_eq!(s.load("node-a").unwrap().unwrap().payload, b"a");
+        assert_eq!(s.load("plant/1").unwrap().unwrap().payload, b"b");
+        assert!(s.dir.join("plant_1").is_dir(), "key 以 slug 作為目錄名稱");
+    }
+
+    #[test]
+    fn seq_increases_and_newest_wins() {
+        let dir = TempDir::new("snapshot");
+        let s = store(&dir, 20);
+        for i in 1..=12u8 { s.save("flow", &[i]).unwrap(); }
+        let snap = s.load("flow").unwrap().unwrap();
+        assert_eq!((snap.seq, snap.payload), (12, vec![12]));
+        // 依數值而非檔名排序：超出補零寬度的 seq 仍是最新的
+        std::fs::write(s.dir.join("flow").join("12345678901.snap"), encode("flow", 12_345_678_901, 0, b"big")).unwrap();
+        assert_eq!(s.load("flow").unwrap().unwrap().payload, b"big");
+        assert_eq!(s.save("flow", b"next").unwrap().file_name().unwrap(), "12345678902.snap");
+    }
+
+    #[test]
+    fn prune_keeps_the_newest_n() {
+        let dir = TempDir::new("snapshot");
+        let s = store(&dir, 3);
+        for i in 0..5u8 { s.save("flow", &[i]).unwrap(); }
+        let seqs: Vec<u64> = s.list("flow").unwrap().into_iter().map(|(seq, _)| seq).collect();
+        assert_eq!(seqs, vec![5, 4, 3]);
+        assert_eq!(store(&dir, 0).keep, 1, "keep 至少 1");
+    }
+
+    #[test]
+    fn bad_checksum_falls_back_to_previous() {
+        let dir = TempDir::new("snapshot");
+        let s = store(&dir, 3);
+        s.save("flow", b"good").unwrap();
+        let newest = s.save("flow", b"newer").unwrap();
+        let mut bytes = std::fs::read(&newest).unwrap();
+        let at = bytes.len() - 40;
+        bytes[at] ^= 0xff;
+        std::fs::write(&newest, bytes).unwrap();
+
+        let snap = s.load("flow").unwrap().unwrap();
+        assert_eq!((snap.seq, snap.payload.as_slice()), (1, &b"good"[..]));
+    }
+
+    #[test]
+    fn truncated_file_falls_back_to_previous() {
+        let dir = TempDir::new("snapshot");
+        let s = store(&dir, 3);
+        s.save("flow", b"good").unwrap();
+        let newest = s.save("flow", b"newer").unwrap();
+        let bytes = std::fs::read(&newest).unwrap();
+        std::fs::write(&newest, &bytes[..bytes.len() / 2]).unwrap();
+
+        assert_eq!(s.load("flow").unwrap().unwrap().payload, b"good");
+        std::fs::write(s.dir.join("flow").join("0000000001.snap"), b"").unwrap();
+        assert!(s.load("flow").unwrap().is_none(), "全部損毀時回傳 None");
+    }
+
+    #[test]
+    fn decode_rejects_wrong_key_and_format() {
+        let dir = TempDir::new("snapshot");
+        let s = store(&dir, 3);
+        let path = s.dir.join("flow").join("0000000001.snap");
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(&path, encode("other", 1, 0, b"x")).unwrap();
+        assert!(s.load("flow").unwrap().is_none(), "屬於其他 key 的 snapshot 不能使用");
+
+        let mut bytes = encode("flow", 1, 0, b"x");
+        bytes[MAGIC.len() + 1] = 9;
+        let body = bytes.len() - 32;
+        let sum = Sha256::digest(&bytes[..body]);
+        bytes[body..].copy_from_slice(&sum);
+        assert!(decode(&bytes).unwrap_err().to_string().contains("格式版本 9 不支援"));
+    }
+}
@@ -0,0 +1,223 @@
+// host/src/state.rs
+// Node 狀態的 envelope：Host 包在 save-state 輸出外面，load-state 前檢查是誰、哪個版本存的
+//
+//   save   Node::save_state → Envelope { node, version = describe().version, schema, payload }
+//   load   node 名稱不同          → 拒絕（state 屬於別的 Node）
+//          version 相同          → payload 直接交給 load-state
+//          version 不同          → 有 export state-migration 就先 migrate-state(from-version, payload)，
+//                                   沒有就拒絕（不讓新版 Node 默默讀錯或丟掉舊 state）
+//          沒有 envelope 的舊資料 → 視為 LEGACY_VERSION 存的：版本不同且有 migrate-state 就先遷移，
+//                                   否則警告後原樣交給 load-state
+//
+// Fused Pipeline 以整條 flow 為單位（node = flow 名稱，version = flow 版本），沒有 migrate-state
+//
+// 格式（big-endian）：
+//   magic    8   b"IIOTSTAT"
+//   schema   u16 SCHEMA_VERSION（envelope 本身的格式版本）
+//   node     u16 長度 + UTF-8
+//   version  u16 長度 + UTF-8
+//   payload  u64 長度 + save-state 的輸出
+
+use anyhow::{bail, Context, Result};
+
+const MAGIC: &[u8; 8] = b"IIOTSTAT";
+pub const SCHEMA_VERSION: u16 = 1;
+/// envelope 出現前（Node 介面 0.1.0）存下的 state 一律當作此版本
+pub const LEGACY_VERSION: &str = "0.1.0";
+
+/// migrate-state(from-version, state) 的函式指標型別（open 不需要遷移時傳 None::<Migrate>）
+pub type Migrate = fn(&str, Vec<u8>) -> Result<Vec<u8>>;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Envelope {
+    pub node:    String,
+    pub version: String,
+    pub schema:  u16,
+    pub payload: Vec<u8>,
+}
+
+impl Envelope {
+    pub fn new(node: &str, version: &str, payload: Vec<u8>) -> Self {
+        Envelope { node: node.to_string(), version: version.to_string(), schema: SCHEMA_VERSION, payload }
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut b = Vec::with_capacity(32 + self.node.len() + self.version.len() + self.payload.len());
+        b.extend_from_slice(MAGIC);
+        b.extend_from_slice(&self.schema.to_be_bytes());
+        for s in [&self.node, &self.version] {
+            b.extend_from_slice(&(s.len() as u16).to_be_bytes());
+            b.extend_from_slice(s.as_bytes());
+        }
+        b.extend_from_slice(&(self.payload.len() as u64).to_be_bytes());
+        b.extend_from_slice(&self.payload);
+        b
+    }
+
+    /// 不是 envelope（沒有 magic）時回傳 None；有 magic 但內容不完整則是錯誤
+    pub fn decode(bytes: &[u8]) -> Result<Option<Self>> {
+        let Some(mut r) = bytes.strip_prefix(MAGIC.as_slice()) else { return Ok(None); };
+        let schema = u16::from_be_bytes(take(&mut r)?);
+        if schema != SCHEMA_VERSION { bail!("state envelope 版本 {schema} 不支援（目前 {SCHEMA_VERSION}）"); }
+        let node    = text(&mut r).context("node 名稱")?;
+        let version = text(&mut r).context("node 版本")?;
+        let len     = u64::from_be_bytes(take(&mut r)?) as usize;
+        if r.len() != len { bail!("state envelope 的 payload 長度 {} 與標示的 {len} 不符", r.len()); }
+        Ok(Some(Envelope { node, version, schema, payload: r.to_vec() }))
+    }
+}
+
+/// 把 save-state 的輸出交給 (name, version) 的 Node 之前的檢查與遷移；回傳 load-state 要讀的 bytes
+/// migrate 為 Node 的 migrate-state（沒有 export 時為 None）
+pub fn open(name: &str, version: &str, state: Vec<u8>,
+            migrate: Option<impl FnOnce(&str, Vec<u8>) -> Result<Vec<u8>>>) -> Result<Vec<u8>> {
+    let Some(env) = Envelope::decode(&state).with_context(|| format!("{name} 的 state 無法解析"))? else {
+        let Some(migrate) = migrate.filter(|_| version != LEGACY_VERSION) else {
+            eprintln!("⚠️  {name} 的 state 沒有 envelope（舊版 snapshot），不檢查版本直接還原");
+            return Ok(state);
+        };
+        let payload = migrate(LEGACY_VERSION, state)
+            .with_context(|| format!("{name} 無法將沒有 envelope 的 state 從 {LEGACY_VERSION} 版遷移到 {version} 版"))?;
+        eprintln!("🔀 {name} 的 state 沒有 envelope（舊版 snapshot），已從 {LEGACY_VERSION} 版遷移到 {version} 版");
+        return Ok(payload);
+    };
+    if env.node != name { bail!("state 屬於 {} {}，不是 {name}", env.node, env.version); }
+    if env.version == version { return Ok(env.payload); }
+    let Some(migrate) = migrate else {
+        bail!("{name} 的 state 由 {} 版儲存，目前是 {version} 版，且 Node 沒有 export migrate-state", env.version);
+    };
+    let payload = migrate(&env.version, env.payload)
+        .with_context(|| format!("{name} 無法將 state 從 {} 版遷移到 {version} 版", env.version))?;
+    eprintln!("🔀 {name} 的 state 已從 {} 版遷移到 {version} 版", env.version);
+    Ok(payload)
+}
+
+fn take<const N: usize>(r: &mut &[u8]) -> Result<[u8; N]> {
+    if r.len() < N { bail!("state envelope 長度不足"); }
+    let (head, rest) = r.split_at(N);
+    *r = rest;
+    Ok(head.try_into().unwrap())
+}
+
+fn text(r: &mut &[u8]) -> Result<String> {
+    let len = u16::from_be_bytes(take(r)?) as usize;
+    if r.len() < len { bail!("state envelope 長度不足"); }
+    let (head, rest) = r.split_at(len);
+    *r = rest;
+    Ok(String::from_utf8(head.to_vec())?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+
+    // 記錄 migrate-state 被呼叫時的 from-version，並在 payload 前加上 "m:"
+    fn recording(calls: &RefCell<Vec<String>>) -> impl FnOnce(&str, Vec<u8>) -> Result<Vec<u8>> + '_ {
+        move |from, state| {
+            calls.borrow_mut().push(from.to_string());
+            Ok([b"m:".as_slice(), &state].concat())
+        }
+    }
+
+    #[test]
+    fn envelope_round_trip() {
+        let env = Envelope::new("moving-avg", "0.2.0", vec![1, 2, 3, 0, 255]);
+        let bytes = env.encode();
+        assert!(bytes.starts_with(MAGIC));
+        assert_eq!(Envelope::decode(&bytes).unwrap(), Some(env));
+
+        let empty = Envelope::new("", "", Vec::new());
+        assert_eq!(Envelope::decode(&empty.encode()).unwrap(), Some(empty));
+    }
+
+    #[test]
+    fn bytes_without_magic_are_not_an_envelope() {
+        assert_eq!(Envelope::decode(b"").unwrap(), None);
+        assert_eq!(Envelope::decode(b"IIOTSTA").unwrap(), None);
+        assert_eq!(Envelope::decode(&[0x0a, 0x03, 1, 2, 3]).unwrap(), None);
+    }
+
+    #[test]
+    fn truncated_envelope_is_an_error() {
+        let bytes = Envelope::new("moving-avg", "0.2.0", vec![9; 16]).encode();
+        // magic 之後的每一個截斷點都必須報錯，不能當成「沒有 envelope」
+        for len in MAGIC.len()..bytes.len() {
+            assert!(Envelope::decode(&bytes[..len]).is_err(), "截斷在 {len} bytes 時應報錯");
+        }
+        let mut longer = bytes.clone();
+        longer.push(0);
+        assert!(Envelope::decode(&longer).is_err(), "payload 之後多出的 bytes 應報錯");
+    }
+
+    #[test]
+    fn unsupported_schema_is_an_error() {
+        let mut env = Envelope::new("moving-avg", "0.2.0", vec![1]);
+        env.schema = SCHEMA_VERSION + 1;
+        let err = Envelope::decode(&env.encode()).unwrap_err();
+        assert!(err.to_string().contains("不支援"), "{err:#}");
+    }
+
+    #[test]
+    fn open_same_version_returns_payload() {
+        let calls = RefCell::new(Vec::new());
+        let state = Envelope::new("avg", "0.2.0", b"payload".to_vec()).encode();
+        assert_eq!(open("avg", "0.2.0", state, Some(recording(&calls))).unwrap(), b"payload");
+        assert!(calls.borrow().is_empty(), "版本相同不應呼叫 migrate-state");
+    }
+
+    #[test]
+    fn open_rejects_state_of_another_node() {
+        let state = Envelope::new("counter", "0.2.0", b"payload".to_vec()).encode();
+        let err = open("avg", "0.2.0", state, None::<Migrate>).unwrap_err();
+        assert!(err.to_string().contains("counter"), "{err:#}");
+    }
+
+    #[test]
+    fn open_other_version_migrates() {
+        let calls = RefCell::new(Vec::new());
+        let state = Envelope::new("avg", "0.1.5", b"old".to_vec()).encode();
+        assert_eq!(open("avg", "0.2.0", state, Some(recording(&calls))).unwrap(), b"m:old");
+        assert_eq!(*calls.borrow(), ["0.1.5"]);
+    }
+
+    #[test]
+    fn open_other_version_without_migrate_is_rejected() {
+        let state = Envelope::new("avg", "0.1.5", b"old".to_vec()).encode();
+        let err = open("avg", "0.2.0", state, None::<Migrate>).unwrap_err();
+        assert!(err.to_string().contains("migrate-state"), "{err:#}");
+    }
+
+    #[test]
+    fn open_propagates_migrate_failure() {
+        let state = Envelope::new("avg", "0.1.5", b"old".to_vec()).encode();
+        let failing = |_: &str, _: Vec<u8>| -> Result<Vec<u8>> { bail!("schema 不相容") };
+        let err = open("avg", "0.2.0", state, Some(failing)).unwrap_err();
+        assert!(format!("{err:#}").contains("schema 不相容"), "{err:#}");
+    }
+
+    #[test]
+    fn open_legacy_state_migrates_from_legacy_version() {
+        let calls = RefCell::new(Vec::new());
+        assert_eq!(open("avg", "0.2.0", b"raw".to_vec(), Some(recording(&calls))).unwrap(), b"m:raw");
+        assert_eq!(*calls.borrow(), [LEGACY_VERSION]);
+    }
+
+    #[test]
+    fn open_legacy_state_without_migrate_is_passed_through() {
+        assert_eq!(open("avg", "0.2.0", b"raw".to_vec(), None::<Migrate>).unwrap(), b"raw");
+
+        // 目前就是 LEGACY_VERSION 的 Node 不需要遷移
+        let calls = RefCell::new(Vec::new());
+        assert_eq!(open("avg", LEGACY_VERSION, b"raw".to_vec(), Some(recording(&calls))).unwrap(), b"raw");
+        assert!(calls.borrow().is_empty());
+    }
+
+    #[test]
+    fn open_corrupt_envelope_is_an_error() {
+        let mut state = Envelope::new("avg", "0.2.0", b"payload".to_vec()).encode();
+        state.truncate(state.len() - 1);
+        let err = open("avg", "0.2.0", state, None::<Migrate>).unwrap_err();
+        assert!(err.to_string().contains("無法解析"), "{err:#}");
+    }
+}
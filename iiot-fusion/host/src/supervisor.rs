@@ -0,0 +1,381 @@
+// host/src/supervisor.rs
+// WASM trap 隔離與自動重啟（IIoTFlowArchitecture §14.2）
+//
+//   trap（含 limits.rs 的 LimitExceeded）→ instance 標記 poisoned、進入 quarantine，記錄診斷
+//   quarantine 期間不呼叫該 instance（DAG：送往該 Node 的訊息丟棄；Runtime：暫停讀取 source）
+//   backoff 到期 → 從記憶體內的 Component 重新 instantiate（不重新編譯），
+//                  restore-state 時還原最近一次正常的 save_state snapshot
+//   backoff 每次 crash 加倍（上限 backoff-max-ms）；正常執行 reset-after-ms 後 crash 計數歸零
+//   reset 期間內 crash 超過 max-crashes 次 → Failed，不再重啟，呼叫端收到錯誤
+//
+// DAG 每個 Node 各一個 Supervisor；Fused 整條 flow 一個 Store，以 flow 為單位
+// 設定 dumps 時，每次 crash 在重啟前寫出 post-mortem bundle（crashdump.rs）
+// async Runtime（async_runtime.rs）的 instance 呼叫要 await，改用 gate / restarted / keep 等步驟自行組合
+
+use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::time::{Duration, Instant, SystemTime};
+
+use crate::crashdump::{CrashDumper, PostMortem};
+use crate::dag::DagNode;
+use crate::{FusedPipeline, Node, SinkNode};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case", default)]
+pub struct SupervisorConfig {
+    pub max_crashes:          u32,
+    pub reset_after_ms:       u64,
+    pub backoff_ms:           u64,
+    pub backoff_max_ms:       u64,
+    // 重啟後還原最近一次正常的狀態；false = 從初始狀態重新開始
+    pub restore_state:        bool,
+    // 正常執行時每隔多久做一次 snapshot（restore-state 用）
+    pub snapshot_interval_ms: u64,
+}
+
+impl Default for SupervisorConfig {
+    fn default() -> Self {
+        SupervisorConfig {
+            max_crashes: 5, reset_after_ms: 60_000, backoff_ms: 100, backoff_max_ms: 30_000,
+            restore_state: true, snapshot_interval_ms: 1000,
+        }
+    }
+}
+
+impl SupervisorConfig {
+    pub fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Health {
+    Running,
+    Quarantined { until: Instant },
+    Failed,
+}
+
+/// 呼叫 instance 前的判斷（gate）
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Gate {
+    Ready,   // 可以呼叫
+    Wait,    // quarantine 中
+    Restart, // backoff 到期：重新建立 instance 後呼叫 restarted（失敗時呼叫 crashed）
+}
+
+#[derive(Debug, Clone)]
+pub struct Crash {
+    pub at:    SystemTime,
+    pub error: String,          // 完整的錯誤鏈（{:#}）
+    pub dump:  Option<PathBuf>, // crash dump 目錄
+}
+
+/// 可被 Supervisor 重新建立的 instance
+pub trait Restartable: Sized {
+    /// 從同一個 Component 建立新的 instance（同樣的上限、init 參數）
+    fn respawn(&self) -> Result<Self>;
+    fn save_state(&mut self) -> Result<Vec<u8>>;
+    fn load_state(&mut self, state: Vec<u8>) -> Result<()>;
+    /// crash 後、重啟前取出現場（memory = 是否複製 linear memory）
+    fn post_mortem(&mut self, memory: bool) -> PostMortem;
+}
+
+pub struct Supervisor {
+    pub name:     String,
+    pub config:   SupervisorConfig,
+    pub crashes:  u64, // 累計
+    pub restarts: u64,
+    pub last:     Option<Crash>,
+    pub dumps:    Option<CrashDumper>,
+    health:       Health,
+    recent:       u32,      // reset-after-ms 內的 crash 次數
+    backoff:      Duration, // 下一次 quarantine 的長度
+    started:      Instant,  // 最近一次啟動 / 重啟
+    snapshot:     Option<Vec<u8>>,
+    snapshot_at:  Instant,
+}
+
+impl Supervisor {
+    pub fn new(name: &str, config: SupervisorConfig) -> Self {
+        let now = Instant::now();
+        Supervisor {
+            name: name.to_string(), config, crashes: 0, restarts: 0, last: None, dumps: None,
+            health: Health::Running, recent: 0, backoff: Duration::from_millis(config.backoff_ms),
+            started: now, snapshot: None, snapshot_at: now,
+        }
+    }
+
+    pub fn health(&self) -> Health {
+        self.health
+    }
+
+    /// 目前能否呼叫 instance；Failed 回傳錯誤
+    pub fn gate(&self) -> Result<Gate> {
+        match self.health {
+            Health::Running => Ok(Gate::Ready),
+            Health::Failed  => bail!("{} 已停止：{} 次 crash 後不再重啟", self.name, self.recent),
+            Health::Quarantined { until } if Instant::now() < until => Ok(Gate::Wait),
+            Health::Quarantined { .. } => Ok(Gate::Restart),
+        }
+    }
+
+    /// 可以呼叫 instance 了嗎：quarantine 的 backoff 到期就在這裡重啟；Failed 回傳錯誤
+    pub fn ready<T: Restartable>(&mut self, inst: &mut T) -> Result<bool> {
+        match self.gate()? {
+            Gate::Ready => Ok(true),
+            Gate::Wait  => Ok(false),
+            Gate::Restart => {
+                match self.restart(inst) {
+                    Ok(()) => Ok(true),
+                    Err(e) => { self.crashed(&e); self.ready(inst) }
+                }
+            }
+        }
+    }
+
+    /// 在 supervisor 下呼叫 instance：
+    /// 正常 → Some(結果)；trap 或 quarantine 中 → None；超過 max-crashes → Err
+    pub fn call<T: Restartable, R>(&mut self, inst: &mut T,
+                                   f: impl FnOnce(&mut T) -> Result<R>) -> Result<Option<R>> {
+        if !self.ready(inst)? { return Ok(None); }
+        match f(inst) {
+            Ok(r) => {
+                self.maybe_snapshot(inst);
+                Ok(Some(r))
+            }
+            Err(e) => {
+                self.crashed(&e);
+                self.dump(inst, &e);
+                if self.health == Health::Failed { return Err(e.context(format!("{} 已停止", self.name))); }
+                Ok(None)
+            }
+        }
+    }
+
+    /// 記錄 crash，進入 quarantine（或 Failed）
+    pub fn crashed(&mut self, err: &anyhow::Error) {
+        let now = Instant::now();
+        if now.duration_since(self.started) >= Duration::from_millis(self.config.reset_after_ms) {
+            self.recent  = 0;
+            self.backoff = Duration::from_millis(self.config.backoff_ms);
+        }
+        self.crashes += 1;
+        self.recent  += 1;
+        self.last = Some(Crash { at: SystemTime::now(), error: format!("{err:#}"), dump: None });
+
+        if self.recent > self.config.max_crashes {
+            self.health = Health::Failed;
+            eprintln!("❌ {} crash #{}，超過 max-crashes {}，停止重啟：{err}",
+                self.name, self.recent, self.config.max_crashes);
+            return;
+        }
+        self.health = Health::Quarantined { until: now + self.backoff };
+        eprintln!("⚠️  {} crash #{}，隔離 {}ms 後重啟：{err}",
+            self.name, self.recent, self.backoff.as_millis());
+        self.backoff = (self.backoff * 2).min(Duration::from_millis(self.config.backoff_max_ms));
+    }
+
+    // instance 還沒被換掉之前寫出 crash dump；寫入失敗只警告
+    fn dump<T: Restartable>(&mut self, inst: &mut T, err: &anyhow::Error) {
+        let Some(memory) = self.dump_memory() else { return; };
+        let pm = inst.post_mortem(memory);
+        self.write_dump(err, pm);
+    }
+
+    /// 有設定 dumps 時回傳是否要複製 linear memory（post_mortem 的參數）；None = 不寫 crash dump
+    pub fn dump_memory(&self) -> Option<bool> {
+        self.dumps.as_ref().map(|d| d.memory)
+    }
+
+    /// 寫出 crash dump（crashed 之後、instance 換掉之前）；寫入失敗只警告
+    pub fn write_dump(&mut self, err: &anyhow::Error, pm: PostMortem) {
+        let Some(dumper) = &self.dumps else { return; };
+        let snapshot = self.snapshot.as_deref().map(|s| (s, self.snapshot_at));
+        match dumper.write(&self.name, err, pm, snapshot) {
+            Ok(dir) => {
+                eprintln!("📦 {} crash dump：{}", self.name, dir.display());
+                if let Some(last) = &mut self.last { last.dump = Some(dir); }
+            }
+            Err(e) => eprintln!("⚠️  {} crash dump 寫入失敗：{e:#}", self.name),
+        }
+    }
+
+    fn restart<T: Restartable>(&mut self, inst: &mut T) -> Result<()> {
+        let mut fresh = inst.respawn()?;
+        if let Some(state) = self.restore_point() { fresh.load_state(state)?; }
+        *inst = fresh;
+        self.restarted();
+        Ok(())
+    }
+
+    /// 重啟時要載入的狀態（restore-state 關閉或還沒有 snapshot 時為 None）
+    pub fn restore_point(&self) -> Option<Vec<u8>> {
+        self.snapshot.clone().filter(|_| self.config.restore_state)
+    }
+
+    /// Gate::Restart 後新的 instance 已就緒（restore_point 已載入）
+    pub fn restarted(&mut self) {
+        self.health   = Health::Running;
+        self.started  = Instant::now();
+        self.restarts += 1;
+        eprintln!("🔄 {} 已重啟（第 {} 次{}）", self.name, self.restarts,
+            if self.snapshot.is_some() && self.config.restore_state { "，已還原 snapshot" } else { "" });
+    }
+
+    // snapshot 失敗不算 crash：下一次呼叫若真的 trap 會再被抓到
+    fn maybe_snapshot<T: Restartable>(&mut self, inst: &mut T) {
+        if !self.snapshot_due() { return; }
+        if let Ok(state) = inst.save_state() { self.keep(state); }
+    }
+
+    /// 呼叫成功後是否該更新 restore-state 用的 snapshot（snapshot-interval-ms）
+    pub fn snapshot_due(&self) -> bool {
+        let interval = Duration::from_millis(self.config.snapshot_interval_ms);
+        self.config.restore_state && (self.snapshot.is_none() || self.snapshot_at.elapsed() >= interval)
+    }
+
+    /// 保留最近一次正常的狀態
+    pub fn keep(&mut self, state: Vec<u8>) {
+        self.snapshot    = Some(state);
+        self.snapshot_at = Instant::now();
+    }
+}
+
+// ── Restartable 實作 ─────────────────────────────────────────────────────────
+
+impl Restartable for Node {
+    fn respawn(&self) -> Result<Self> { Node::respawn(self) }
+    fn save_state(&mut self) -> Result<Vec<u8>> { Node::save_state(self) }
+    fn load_state(&mut self, state: Vec<u8>) -> Result<()> { Node::load_state(self, state) }
+    fn post_mortem(&mut self, memory: bool) -> PostMortem { Node::post_mortem(self, memory) }
+}
+
+impl Restartable for SinkNode {
+    fn respawn(&self) -> Result<Self> { SinkNode::respawn(self) }
+    fn save_state(&mut self) -> Result<Vec<u8>> { SinkNode::save_state(self) }
+    fn load_state(&mut self, state: Vec<u8>) -> Result<()> { SinkNode::load_state(self, state) }
+    fn post_mortem(&mut self, memory: bool) -> PostMortem { SinkNode::post_mortem(self, memory) }
+}
+
+impl Restartable for FusedPipeline {
+    fn respawn(&self) -> Result<Self> { FusedPipeline::respawn(self) }
+    fn save_state(&mut self) -> Result<Vec<u8>> { self.save_states() }
+    fn load_state(&mut self, state: Vec<u8>) -> Result<()> { self.load_states(state) }
+    fn post_mortem(&mut self, memory: bool) -> PostMortem { FusedPipeline::post_mortem(self, memory) }
+}
+
+impl Restartable for DagNode {
+    fn respawn(&self) -> Result<Self> {
+        match self {
+            DagNode::Node(n) => Ok(DagNode::Node(n.respawn()?)),
+            DagNode::Sink(s) => Ok(DagNode::Sink(s.respawn()?)),
+            DagNode::Cast(c) => bail!("{} 是 Host 內建的 Cast Node，不會 crash", c.name),
+        }
+    }
+
+    fn save_state(&mut self) -> Result<Vec<u8>> { DagNode::save_state(self) }
+    fn load_state(&mut self, state: Vec<u8>) -> Result<()> { DagNode::load_state(self, state) }
+
+    fn post_mortem(&mut self, memory: bool) -> PostMortem {
+        match self {
+            DagNode::Node(n) => n.post_mortem(memory),
+            DagNode::Sink(s) => s.post_mortem(memory),
+            DagNode::Cast(_) => PostMortem::default(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::anyhow;
+
+    // 不需要 wasm 的 instance：state 只在 load_state 時還原，gen 每次 respawn 加一
+    #[derive(Default)]
+    struct Fake {
+        state: Vec<u8>,
+        gen:   u32,
+    }
+
+    impl Restartable for Fake {
+        fn respawn(&self) -> Result<Self> { Ok(Fake { state: Vec::new(), gen: self.gen + 1 }) }
+        fn save_state(&mut self) -> Result<Vec<u8>> { Ok(self.state.clone()) }
+        fn load_state(&mut self, state: Vec<u8>) -> Result<()> { self.state = state; Ok(()) }
+        fn post_mortem(&mut self, _memory: bool) -> PostMortem { PostMortem::default() }
+    }
+
+    fn config(max_crashes: u32, backoff_ms: u64, backoff_max_ms: u64) -> SupervisorConfig {
+        SupervisorConfig { max_crashes, backoff_ms, backoff_max_ms, ..SupervisorConfig::default() }
+    }
+
+    fn trap(sup: &mut Supervisor, inst: &mut Fake) -> Result<Option<()>> {
+        sup.call(inst, |_| Err(anyhow!("wasm trap")))
+    }
+
+    #[test]
+    fn backoff_doubles_up_to_backoff_max() {
+        let mut sup = Supervisor::new("node-a", config(10, 100, 250));
+        let mut quarantine = Vec::new();
+        for _ in 0..4 {
+            let before = Instant::now();
+            sup.crashed(&anyhow!("wasm trap"));
+            let Health::Quarantined { until } = sup.health() else { panic!("crash 後應進入 quarantine") };
+            quarantine.push((until - before).as_millis() / 50 * 50);
+        }
+        assert_eq!(quarantine, vec![100, 200, 250, 250]);
+        assert_eq!(sup.gate().unwrap(), Gate::Wait, "backoff 期間不呼叫 instance");
+        assert_eq!((sup.crashes, sup.last.as_ref().unwrap().error.as_str()), (4, "wasm trap"));
+    }
+
+    #[test]
+    fn fails_after_max_crashes() {
+        let mut sup = Supervisor::new("node-a", config(2, 0, 0));
+        let mut inst = Fake::default();
+        assert!(trap(&mut sup, &mut inst).unwrap().is_none(), "crash #1 只進入 quarantine");
+        assert!(trap(&mut sup, &mut inst).unwrap().is_none(), "crash #2 只進入 quarantine");
+        let e = trap(&mut sup, &mut inst).unwrap_err();
+        assert_eq!(format!("{e:#}"), "node-a 已停止: wasm trap");
+        assert_eq!(sup.health(), Health::Failed);
+        assert_eq!((sup.crashes, sup.restarts, inst.gen), (3, 2, 2));
+        assert!(sup.gate().unwrap_err().to_string().contains("3 次 crash 後不再重啟"));
+        assert!(sup.call(&mut inst, |_| Ok(())).is_err(), "Failed 後不再呼叫 instance");
+    }
+
+    #[test]
+    fn crash_count_resets_after_reset_window() {
+        let mut sup = Supervisor::new("node-a", SupervisorConfig { reset_after_ms: 1000, ..config(1, 100, 1000) });
+        sup.crashed(&anyhow!("wasm trap"));
+        assert_eq!(sup.backoff, Duration::from_millis(200));
+        // 重啟後正常執行超過 reset-after-ms：計數與 backoff 歸零
+        sup.restarted();
+        sup.started -= Duration::from_millis(1500);
+        sup.crashed(&anyhow!("wasm trap"));
+        assert!(matches!(sup.health(), Health::Quarantined { .. }), "reset 後的 crash 不應直接 Failed");
+        assert_eq!((sup.recent, sup.backoff), (1, Duration::from_millis(200)));
+        // 重啟後 reset 期間內再 crash 就超過 max-crashes
+        sup.restarted();
+        sup.crashed(&anyhow!("wasm trap"));
+        assert_eq!(sup.health(), Health::Failed);
+    }
+
+    #[test]
+    fn restart_restores_kept_state() {
+        let mut sup = Supervisor::new("node-a", config(5, 0, 0));
+        let mut inst = Fake { state: b"avg=21.5".to_vec(), gen: 0 };
+        // 第一次成功的呼叫就保留 snapshot
+        assert_eq!(sup.call(&mut inst, |i| Ok(i.gen)).unwrap(), Some(0));
+        inst.state = b"poisoned".to_vec();
+        assert!(trap(&mut sup, &mut inst).unwrap().is_none());
+
+        assert_eq!(sup.call(&mut inst, |i| Ok(i.gen)).unwrap(), Some(1), "backoff 到期後重新建立 instance");
+        assert_eq!(inst.state, b"avg=21.5", "應還原最近一次正常的狀態");
+        assert_eq!((sup.health(), sup.restarts), (Health::Running, 1));
+
+        sup.keep(b"avg=30".to_vec());
+        assert_eq!(sup.restore_point().as_deref(), Some(&b"avg=30"[..]));
+        sup.config.restore_state = false;
+        assert!(sup.restore_point().is_none(), "restore-state 關閉時從初始狀態開始");
+        assert!(!sup.snapshot_due());
+    }
+}
@@ -0,0 +1,423 @@
+// host/src/tags.rs
+// Tag 設定檔：工廠的 tag 清單由檔案載入 Tag Registry，而不是寫在程式裡
+//
+//   tags.toml   [[tags]] 陣列，每個 table 一個 tag
+//   tags.json   { "tags": [ { ... }, ... ] }
+//   tags.csv    第一行為欄位名稱，每行一個 tag；空白欄位 = 預設值
+//
+// 欄位：name（必填，TagUpdate 的 tag_id_str）、unit、mqtt_topic、historian_tag、alarm_group、
+//       eng_low / eng_high（兩者皆 0 = 未設定）、value_kind（預設 f64-val）
+//       其餘欄位都是自訂屬性（get-attr 查得到，值一律轉成字串）
+//
+// 驗證：name 空白 / 重複、工程範圍（必須有限且 eng_low < eng_high）、value_kind、自訂屬性須為純量；
+//       所有錯誤一次列出，任何一個錯誤都不套用
+//
+// 重新載入：TagWatcher 在背景定期檢查檔案（mtime + 長度，再以 sha256 排除內容未變的寫入），
+//           變更時重新驗證並套用到共用的 Tag Registry（tag_id 不變）；驗證失敗時保留目前內容
+
+use anyhow::{bail, Context, Result};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, RwLock};
+use std::thread::JoinHandle;
+use std::time::{Duration, SystemTime};
+
+use crate::{parse_kind, TagMeta, TagRegistry};
+use crate::iiot::flow::types::ValueKind;
+
+// 未指定 IIOT_FLOW_TAGS 時，依序在目錄內尋找
+const DEFAULT_FILES: [&str; 3] = ["tags.toml", "tags.json", "tags.csv"];
+
+// 錯誤訊息最多列出的筆數
+const MAX_ISSUES: usize = 20;
+
+/// TagWatcher 的預設檢查間隔
+pub const WATCH_INTERVAL: Duration = Duration::from_secs(1);
+
+// ── 設定檔內的一筆 tag ───────────────────────────────────────────────────────
+#[derive(Debug, Deserialize)]
+struct TagDef {
+    #[serde(default)]
+    name:          String,
+    #[serde(default)]
+    unit:          String,
+    #[serde(default)]
+    mqtt_topic:    String,
+    #[serde(default)]
+    historian_tag: String,
+    #[serde(default)]
+    alarm_group:   String,
+    #[serde(default)]
+    eng_low:       f64,
+    #[serde(default)]
+    eng_high:      f64,
+    #[serde(default)]
+    value_kind:    Option<String>,
+    #[serde(flatten)]
+    extra:         BTreeMap<String, serde_json::Value>,
+}
+
+#[derive(Deserialize)]
+struct TagFile {
+    #[serde(default)]
+    tags: Vec<serde_json::Value>,
+}
+
+/// 驗證過的 tag 清單
+pub struct TagTable {
+    pub path: PathBuf,
+    pub tags: Vec<TagMeta>,
+    stamp:    Stamp,
+    digest:   [u8; 32],
+}
+
+// 檔案是否可能變更（mtime + 長度）
+#[derive(Clone, Copy, PartialEq, Eq)]
+struct Stamp {
+    modified: Option<SystemTime>,
+    len:      u64,
+}
+
+impl Stamp {
+    fn of(path: &Path) -> Option<Self> {
+        let meta = std::fs::metadata(path).ok()?;
+        Some(Stamp { modified: meta.modified().ok(), len: meta.len() })
+    }
+}
+
+impl TagTable {
+    /// IIOT_FLOW_TAGS=<file>；未設定時找 dir 內的 tags.toml / tags.json / tags.csv
+    pub fn locate(dir: impl AsRef<Path>) -> Option<PathBuf> {
+        if let Ok(path) = std::env::var("IIOT_FLOW_TAGS") { return Some(path.into()); }
+        DEFAULT_FILES.iter().map(|f| dir.as_ref().join(f)).find(|p| p.is_file())
+    }
+
+    /// 讀檔 + 解析 + 驗證；格式依副檔名（.toml / .json / .csv）
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path  = path.as_ref();
+        let stamp = Stamp::of(path).with_context(|| format!("找不到 tag 設定檔 {}", path.display()))?;
+        let bytes = std::fs::read(path).with_context(|| format!("讀取 {} 失敗", path.display()))?;
+        Self::parse(path, stamp, &bytes)
+    }
+
+    fn parse(path: &Path, stamp: Stamp, bytes: &[u8]) -> Result<Self> {
+        let digest = Sha256::digest(bytes).into();
+        let tags = parse(path, bytes).with_context(|| format!("tag 設定檔 {}", path.display()))?;
+        Ok(TagTable { path: path.to_path_buf(), tags, stamp, digest })
+    }
+
+    /// 套用到 Tag Registry（第一次載入與重新載入共用）
+    pub fn apply(&self, registry: &RwLock<TagRegistry>) -> TagChanges {
+        registry.write().unwrap().apply(&self.tags)
+    }
+}
+
+/// apply 的結果
+#[derive(Debug, Default, Clone, Copy)]
+pub struct TagChanges {
+    pub added:   usize,
+    pub updated: usize,
+    pub removed: usize,
+}
+
+impl TagChanges {
+    pub fn is_empty(&self) -> bool {
+        self.added + self.updated + self.removed == 0
+    }
+}
+
+// ════════════════════════════════════════════════════════════════════════════
+// 解析 + 驗證
+// ════════════════════════════════════════════════════════════════════════════
+
+fn parse(path: &Path, bytes: &[u8]) -> Result<Vec<TagMeta>> {
+    let ext = path.extension().and_then(|e| e.to_str()).unwrap_or_default().to_ascii_lowercase();
+    // (位置, 內容)：位置用於錯誤訊息
+    let rows: Vec<(String, serde_json::Value)> = match ext.as_str() {
+        "toml" => {
+            let text = std::str::from_utf8(bytes).context("不是 UTF-8")?;
+            let file: TagFile = toml::from_str(text).context("TOML 格式錯誤")?;
+            numbered(file.tags)
+        }
+        "json" => {
+            let file: TagFile = serde_json::from_slice(bytes).context("JSON 格式錯誤")?;
+            numbered(file.tags)
+        }
+        "csv" => csv_rows(bytes)?,
+        _ => bail!("不支援的副檔名「{ext}」（tags.toml / tags.json / tags.csv）"),
+    };
+
+    let mut issues = Vec::new();
+    let mut seen: HashMap<String, String> = HashMap::new();
+    let mut tags = Vec::with_capacity(rows.len());
+    for (at, row) in rows {
+        let def: TagDef = match serde_json::from_value(row) {
+            Ok(def) => def,
+            Err(e) => { issues.push(format!("{at}：{e}")); continue; }
+        };
+        match check(&def) {
+            Ok(meta) => {
+                if let Some(first) = seen.get(&meta.name) {
+                    issues.push(format!("{at}：tag「{}」重複（已在{first}定義）", meta.name));
+                    continue;
+                }
+                seen.insert(meta.name.clone(), at);
+                tags.push(meta);
+            }
+            Err(errs) => issues.extend(errs.into_iter().map(|e| format!("{at}：{e}"))),
+        }
+    }
+    if !issues.is_empty() {
+        let more = issues.len().saturating_sub(MAX_ISSUES);
+        let mut msg = format!("{} 個錯誤：\n  {}", issues.len(), issues[..issues.len() - more].join("\n  "));
+        if more > 0 { msg.push_str(&format!("\n  …另 {more} 個")); }
+        bail!(msg);
+    }
+    Ok(tags)
+}
+
+fn numbered(rows: Vec<serde_json::Value>) -> Vec<(String, serde_json::Value)> {
+    rows.into_iter().enumerate().map(|(i, row)| (format!("第 {} 筆", i + 1), row)).collect()
+}
+
+// CSV 的每一行轉成與 TOML / JSON 相同的 object；eng_low / eng_high 轉數字，空白欄位略過
+fn csv_rows(bytes: &[u8]) -> Result<Vec<(String, serde_json::Value)>> {
+    let mut reader = csv::ReaderBuilder::new().trim(csv::Trim::All).from_reader(bytes);
+    let headers = reader.headers().context("CSV 欄位名稱錯誤")?.clone();
+    if !headers.iter().any(|h| h == "name") { bail!("CSV 缺少 name 欄位"); }
+    let mut rows = Vec::new();
+    for record in reader.records() {
+        let record = record.context("CSV 格式錯誤")?;
+        let at = format!("第 {} 行", record.position().map_or(0, |p| p.line()));
+        let mut row = serde_json::Map::new();
+        for (key, cell) in headers.iter().zip(record.iter()).filter(|(_, c)| !c.is_empty()) {
+            // 不是數字時保留字串，由 check 前的反序列化回報
+            let value = match (key, cell.parse::<f64>()) {
+                ("eng_low" | "eng_high", Ok(v)) if v.is_finite() => serde_json::json!(v),
+                _ => serde_json::Value::String(cell.to_string()),
+            };
+            row.insert(key.to_string(), value);
+        }
+        rows.push((at, serde_json::Value::Object(row)));
+    }
+    Ok(rows)
+}
+
+fn check(def: &TagDef) -> std::result::Result<TagMeta, Vec<String>> {
+    let mut errors = Vec::new();
+    let name = def.name.trim();
+    if name.is_empty() { errors.push("name 不可為空".to_string()); }
+
+    let (lo, hi) = (def.eng_low, def.eng_high);
+    if !lo.is_finite() || !hi.is_finite() {
+        errors.push(format!("工程範圍必須是有限數值（eng_low {lo} / eng_high {hi}）"));
+    } else if (lo, hi) != (0.0, 0.0) && lo >= hi {
+        errors.push(format!("eng_low {lo} 必須小於 eng_high {hi}"));
+    }
+
+    let value_kind = match def.value_kind.as_deref() {
+        None => ValueKind::F64Val,
+        Some(k) => parse_kind(k).unwrap_or_else(|| {
+            errors.push(format!("未知的 value_kind「{k}」"));
+            ValueKind::Any
+        }),
+    };
+
+    let mut extra = BTreeMap::new();
+    for (key, value) in &def.extra {
+        let text = match value {
+            serde_json::Value::String(s) => s.clone(),
+            serde_json::Value::Number(n) => n.to_string(),
+            serde_json::Value::Bool(b)   => b.to_string(),
+            serde_json::Value::Null      => continue,
+            _ => { errors.push(format!("自訂屬性 {key} 必須是字串、數字或 bool")); continue; }
+        };
+        extra.insert(key.clone(), text);
+    }
+
+    if !errors.is_empty() { return Err(errors); }
+    Ok(TagMeta {
+        name: name.to_string(), unit: def.unit.clone(), mqtt_topic: def.mqtt_topic.clone(),
+        historian_tag: def.historian_tag.clone(), alarm_group: def.alarm_group.clone(),
+        eng_low: lo, eng_high: hi, value_kind, extra,
+    })
+}
+
+// ════════════════════════════════════════════════════════════════════════════
+// 檔案變更時重新載入
+// ════════════════════════════════════════════════════════════════════════════
+
+/// 背景 thread 定期檢查 tag 設定檔；drop 時喚醒並停止
+pub struct TagWatcher {
+    stop:   Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl TagWatcher {
+    /// 從 table（已套用）的狀態開始監看
+    pub fn spawn(table: TagTable, registry: Arc<RwLock<TagRegistry>>, interval: Duration) -> Result<Self> {
+        let stop = Arc::new(AtomicBool::new(false));
+        let flag = Arc::clone(&stop);
+        let thread = std::thread::Builder::new().name("tag-watcher".into()).spawn(move || {
+            let TagTable { path, mut stamp, mut digest, .. } = table;
+            while !flag.load(Ordering::Relaxed) {
+                std::thread::park_timeout(interval);
+                if flag.load(Ordering::Relaxed) { break; }
+                // 檔案暫時不存在（例如編輯器以 rename 取代）時等下一輪
+                let Some(now) = Stamp::of(&path) else { continue; };
+                if now == stamp { continue; }
+                stamp = now;
+                // 內容沒變（只有 touch，或與上次驗證失敗的內容相同）不重複處理
+                let Ok(bytes) = std::fs::read(&path) else { continue; };
+                let next: [u8; 32] = Sha256::digest(&bytes).into();
+                if next == digest { continue; }
+                digest = next;
+                match TagTable::parse(&path, now, &bytes) {
+                    Ok(table) => {
+                        let c = table.apply(&registry);
+                        println!("🏷  Tag Registry 重新載入 {}：{} 個 tag（新增 {} / 更新 {} / 移除 {}）",
+                            path.display(), table.tags.len(), c.added, c.updated, c.removed);
+                    }
+                    Err(e) => eprintln!("⚠️  Tag Registry 未更新，保留目前內容：{e:#}"),
+                }
+            }
+        })?;
+        Ok(TagWatcher { stop, thread: Some(thread) })
+    }
+}
+
+impl Drop for TagWatcher {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(t) = self.thread.take() {
+            t.thread().unpark();
+            let _ = t.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn load(file: &str, text: &str) -> Result<Vec<TagMeta>> {
+        parse(Path::new(file), text.as_bytes())
+    }
+
+    fn error(file: &str, text: &str) -> String {
+        format!("{:#}", load(file, text).unwrap_err())
+    }
+
+    #[test]
+    fn parses_toml() {
+        let tags = load("tags.toml", r#"
+            [[tags]]
+            name        = "plant1.motor3.temp"
+            unit        = "degC"
+            eng_low     = -40.0
+            eng_high    = 150
+            value_kind  = "i32-val"
+            line        = 3
+            critical    = true
+
+            [[tags]]
+            name = "plant1.motor3.rpm"
+        "#).unwrap();
+        assert_eq!(tags.len(), 2);
+        let t = &tags[0];
+        assert_eq!((t.name.as_str(), t.unit.as_str()), ("plant1.motor3.temp", "degC"));
+        assert_eq!((t.eng_low, t.eng_high, t.value_kind), (-40.0, 150.0, ValueKind::I32Val));
+        assert_eq!(t.extra.get("line").map(String::as_str), Some("3"), "自訂屬性轉成字串");
+        assert_eq!(t.extra.get("critical").map(String::as_str), Some("true"));
+        assert_eq!(tags[1].value_kind, ValueKind::F64Val, "value_kind 預設 f64-val");
+    }
+
+    #[test]
+    fn parses_json() {
+        let tags = load("tags.json", r#"{ "tags": [
+            { "name": " a ", "mqtt_topic": "plant/a", "note": null },
+            { "name": "b", "alarm_group": "g1", "value_kind": "bool-val" }
+        ] }"#).unwrap();
+        assert_eq!(tags[0].name, "a", "name 去掉前後空白");
+        assert_eq!(tags[0].mqtt_topic, "plant/a");
+        assert!(tags[0].extra.is_empty(), "null 的自訂屬性略過");
+        assert_eq!((tags[1].alarm_group.as_str(), tags[1].value_kind), ("g1", ValueKind::BoolVal));
+    }
+
+    #[test]
+    fn parses_csv() {
+        let tags = load("tags.CSV", "name, unit, eng_low, eng_high, area\n\
+                                     a,    degC, 0,       100,      north\n\
+                                     b,    ,     ,        ,\n").unwrap();
+        assert_eq!(tags.len(), 2);
+        assert_eq!((tags[0].unit.as_str(), tags[0].eng_low, tags[0].eng_high), ("degC", 0.0, 100.0));
+        assert_eq!(tags[0].extra.get("area").map(String::as_str), Some("north"));
+        assert_eq!((tags[1].unit.as_str(), tags[1].eng_high), ("", 0.0), "空白欄位 = 預設值");
+        assert!(tags[1].extra.is_empty());
+    }
+
+    #[test]
+    fn csv_errors() {
+        assert!(error("tags.csv", "unit\ndegC\n").contains("CSV 缺少 name 欄位"));
+        let e = error("tags.csv", "name,eng_low\na,low\n");
+        assert!(e.contains("第 2 行"), "{e}");
+    }
+
+    #[test]
+    fn collects_every_validation_error() {
+        let e = error("tags.toml", r#"
+            [[tags]]
+            name = ""
+            [[tags]]
+            name     = "a"
+            eng_low  = 10
+            eng_high = 5
+            [[tags]]
+            name       = "b"
+            value_kind = "f128-val"
+            [[tags]]
+            name  = "c"
+            range = [1, 2]
+            [[tags]]
+            name = "d"
+            [[tags]]
+            name = "d"
+        "#);
+        assert!(e.starts_with("5 個錯誤"), "{e}");
+        assert!(e.contains("第 1 筆：name 不可為空"), "{e}");
+        assert!(e.contains("第 2 筆：eng_low 10 必須小於 eng_high 5"), "{e}");
+        assert!(e.contains("第 3 筆：未知的 value_kind「f128-val」"), "{e}");
+        assert!(e.contains("第 4 筆：自訂屬性 range 必須是字串、數字或 bool"), "{e}");
+        assert!(e.contains("第 6 筆：tag「d」重複（已在第 5 筆定義）"), "{e}");
+    }
+
+    #[test]
+    fn rejects_non_finite_range_and_wrong_types() {
+        let e = error("tags.json", r#"{ "tags": [{ "name": "a", "eng_low": "x" }] }"#);
+        assert!(e.contains("第 1 筆："), "{e}");
+        // TOML 的 inf 轉成 JSON 時變成 null，反序列化即失敗；CSV 的 inf 保留為字串
+        let e = error("tags.toml", "[[tags]]\nname = \"a\"\neng_low = -inf\neng_high = 1.0\n");
+        assert!(e.contains("第 1 筆："), "{e}");
+        let e = error("tags.csv", "name,eng_high\na,inf\n");
+        assert!(e.contains("第 2 行："), "{e}");
+    }
+
+    #[test]
+    fn error_list_is_capped() {
+        let text: String = (0..MAX_ISSUES + 5).map(|_| "[[tags]]\nname = \"\"\n").collect();
+        let e = error("tags.toml", &text);
+        assert!(e.contains(&format!("{} 個錯誤", MAX_ISSUES + 5)), "{e}");
+        assert!(e.ends_with("…另 5 個"), "{e}");
+        assert_eq!(e.matches("name 不可為空").count(), MAX_ISSUES);
+    }
+
+    #[test]
+    fn format_errors() {
+        assert!(error("tags.yaml", "").contains("不支援的副檔名「yaml」"));
+        assert!(error("tags.toml", "[[tags]\n").contains("TOML 格式錯誤"));
+        assert!(error("tags.json", "{").contains("JSON 格式錯誤"));
+    }
+}
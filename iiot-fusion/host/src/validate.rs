@@ -0,0 +1,640 @@
+// host/src/validate.rs
+// Deploy Stage 2：Validate（IIoTFlowArchitecture §9 / §10.2）
+//
+//   ① cycle-detection   DFS 找 back edge 即報錯，無循環時以 Kahn 產出 topo_order
+//   ② orphan            in-degree = 0 且非 source / out-degree = 0 且非 sink
+//   ③ boundary          source / sink 是否存在、edge 方向、port 是否存在
+//   ④ condition-port    condition port 只能出現在 mux / demux，數量正確
+//   ⑤ type-inference    沿 topo_order 推導 any 的實際型別（resolved_types），
+//                       檢查 edge 兩端型別相容，數值族不同則排定 Cast Node
+//   ⑥ join-strategy     all-or-initial 的 input port 必須有初始值
+//
+// 每個 pass 都跑完並收集所有錯誤，失敗時輸出 §14.1 格式的 JSON 報告，
+// 讓 UI 一次標出所有有問題的 edge / node。有損轉型只列為 warning，不阻擋 Deploy。
+
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::cast::{self, CastClass, CastEdge};
+use crate::flow::{EdgeDef, FlowDef};
+use crate::iiot::flow::node_descriptor::{JoinStrategy, NodeKind, NodeSpec, PortRole};
+use crate::iiot::flow::types::ValueKind;
+use crate::{kind_name, TagRegistry};
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct EdgeRef {
+    pub from:      String,
+    pub from_port: u32,
+    pub to:        String,
+    pub to_port:   u32,
+}
+
+impl From<&EdgeDef> for EdgeRef {
+    fn from(e: &EdgeDef) -> Self {
+        EdgeRef { from: e.from.clone(), from_port: e.from_port, to: e.to.clone(), to_port: e.to_port }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Issue {
+    pub code:     &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub node:     Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub edge:     Option<EdgeRef>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expected: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub actual:   Option<String>,
+    pub message:  String,
+}
+
+impl Issue {
+    fn node(code: &'static str, node: &str, message: String) -> Self {
+        Issue { code, node: Some(node.to_string()), edge: None, expected: None, actual: None, message }
+    }
+
+    fn edge(code: &'static str, edge: &EdgeDef, message: String) -> Self {
+        Issue { code, node: None, edge: Some(edge.into()), expected: None, actual: None, message }
+    }
+
+    fn graph(code: &'static str, message: String) -> Self {
+        Issue { code, node: None, edge: None, expected: None, actual: None, message }
+    }
+}
+
+/// 單一 pass 的錯誤報告（§14.1）
+#[derive(Debug, Clone, Serialize)]
+pub struct PassReport {
+    pub stage:  &'static str,
+    pub pass:   &'static str,
+    pub errors: Vec<Issue>,
+}
+
+/// 所有失敗 pass 的報告，Display 輸出 JSON
+#[derive(Debug)]
+pub struct ValidationFailed(pub Vec<PassReport>);
+
+impl fmt::Display for ValidationFailed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let json = serde_json::to_string_pretty(&self.0).map_err(|_| fmt::Error)?;
+        write!(f, "Deploy 驗證失敗：\n{json}")
+    }
+}
+
+impl std::error::Error for ValidationFailed {}
+
+/// 驗證通過的結果
+pub struct Validated {
+    pub topo_order:     Vec<usize>,     // flow.nodes 的 index
+    pub resolved_types: Vec<ValueKind>, // 與 flow.edges 對齊，上游 output 推導後的型別
+    pub casts:          Vec<CastEdge>,  // 需插入 Cast Node 的 edge
+    pub warnings:       Vec<Issue>,     // 有損轉型等，不阻擋 Deploy
+}
+
+/// specs 與 flow.nodes 對齊；registry 提供 Source 的 tag 型別
+pub fn validate(flow: &FlowDef, specs: &[&NodeSpec], registry: &TagRegistry)
+    -> Result<Validated, ValidationFailed>
+{
+    let g = Graph::new(flow, specs);
+    let mut reports  = Vec::new();
+    let mut resolved = vec![ValueKind::Any; flow.edges.len()];
+    let mut casts    = Vec::new();
+    let mut warnings = Vec::new();
+    let mut run = |pass: &'static str, errors: Vec<Issue>| {
+        if !errors.is_empty() { reports.push(PassReport { stage: "validate", pass, errors }); }
+    };
+
+    let (topo, cycle_errors) = g.cycle_detection();
+    run("cycle-detection", cycle_errors);
+    run("orphan",          g.orphans());
+    run("boundary",        g.boundary());
+    run("condition-port",  g.condition_ports());
+    // 型別推導需要 topo 順序，有循環時略過
+    if let Some(topo) = &topo {
+        run("type-inference", g.type_inference(topo, registry, &mut resolved, &mut casts, &mut warnings));
+    }
+    run("join-strategy",   g.join_strategy());
+
+    match topo {
+        Some(topo_order) if reports.is_empty() =>
+            Ok(Validated { topo_order, resolved_types: resolved, casts, warnings }),
+        _ => Err(ValidationFailed(reports)),
+    }
+}
+
+struct Graph<'a> {
+    flow:  &'a FlowDef,
+    specs: &'a [&'a NodeSpec],
+    index: HashMap<&'a str, usize>,
+}
+
+impl<'a> Graph<'a> {
+    fn new(flow: &'a FlowDef, specs: &'a [&'a NodeSpec]) -> Self {
+        let index = flow.nodes.iter().enumerate().map(|(i, n)| (n.id.as_str(), i)).collect();
+        Graph { flow, specs, index }
+    }
+
+    fn id(&self, i: usize) -> &str { &self.flow.nodes[i].id }
+
+    fn spec_of(&self, id: &str) -> &NodeSpec { self.specs[self.index[id]] }
+
+    fn out_edges(&self, i: usize) -> impl Iterator<Item = &'a EdgeDef> + '_ {
+        let id = self.id(i).to_string();
+        self.flow.edges.iter().filter(move |e| e.from == id)
+    }
+
+    fn in_edges(&self, i: usize) -> impl Iterator<Item = &'a EdgeDef> + '_ {
+        let id = self.id(i).to_string();
+        self.flow.edges.iter().filter(move |e| e.to == id)
+    }
+
+    // ── ① Cycle Detection ────────────────────────────────────────────────────
+    // DFS 起點依 nodes 宣告順序，回報的 back edge 可重現
+    fn cycle_detection(&self) -> (Option<Vec<usize>>, Vec<Issue>) {
+        #[derive(Clone, Copy, PartialEq)]
+        enum Mark { New, Active, Done }
+
+        // 反轉後 pop() 即依宣告順序走訪 out edge
+        let pending = |i: usize| {
+            let mut v: Vec<&EdgeDef> = self.out_edges(i).collect();
+            v.reverse();
+            v
+        };
+
+        let n = self.flow.nodes.len();
+        let mut mark   = vec![Mark::New; n];
+        let mut errors = Vec::new();
+
+        for root in 0..n {
+            if mark[root] != Mark::New { continue; }
+            // (node, 尚未走訪的 out edge)
+            let mut stack = vec![(root, pending(root))];
+            mark[root] = Mark::Active;
+            while let Some((node, edges)) = stack.last_mut() {
+                let node = *node;
+                let Some(e) = edges.pop() else {
+                    mark[node] = Mark::Done;
+                    stack.pop();
+                    continue;
+                };
+                let to = self.index[e.to.as_str()];
+                match mark[to] {
+                    Mark::New => {
+                        mark[to] = Mark::Active;
+                        stack.push((to, pending(to)));
+                    }
+                    Mark::Active => errors.push(Issue::edge("CYCLE", e,
+                        format!("{} → {} 形成循環", e.from, e.to))),
+                    Mark::Done => {}
+                }
+            }
+        }
+
+        if !errors.is_empty() { return (None, errors); }
+        (Some(self.kahn_order()), errors)
+    }
+
+    /// 無循環時的執行順序：Kahn 演算法，同層依 nodes 宣告順序，確保可重現
+    fn kahn_order(&self) -> Vec<usize> {
+        let n = self.flow.nodes.len();
+        let mut indeg = vec![0usize; n];
+        for e in &self.flow.edges { indeg[self.index[e.to.as_str()]] += 1; }
+
+        let mut order = Vec::with_capacity(n);
+        let mut done  = vec![false; n];
+        while let Some(i) = (0..n).find(|&i| !done[i] && indeg[i] == 0) {
+            done[i] = true;
+            order.push(i);
+            for e in self.out_edges(i) { indeg[self.index[e.to.as_str()]] -= 1; }
+        }
+        order
+    }
+
+    // ── ② 孤立 Node ──────────────────────────────────────────────────────────
+    fn orphans(&self) -> Vec<Issue> {
+        let mut errors = Vec::new();
+        for (i, spec) in self.specs.iter().enumerate() {
+            let id = self.id(i);
+            if spec.kind != NodeKind::Source && self.in_edges(i).next().is_none() {
+                errors.push(Issue::node("ORPHAN_NO_INPUT", id,
+                    format!("{id} 不是 source，但沒有任何上游")));
+            }
+            if !is_sink(spec.kind) && self.out_edges(i).next().is_none() {
+                errors.push(Issue::node("ORPHAN_NO_OUTPUT", id,
+                    format!("{id} 不是 sink / sink-end，但沒有任何下游")));
+            }
+        }
+        errors
+    }
+
+    // ── ③ Source / Sink / sink-end 邊界 ─────────────────────────────────────
+    fn boundary(&self) -> Vec<Issue> {
+        let mut errors = Vec::new();
+        if !self.specs.iter().any(|s| s.kind == NodeKind::Source) {
+            errors.push(Issue::graph("NO_SOURCE", "flow 至少需要一個 source".to_string()));
+        }
+        if !self.specs.iter().any(|s| is_sink(s.kind)) {
+            errors.push(Issue::graph("NO_SINK", "flow 至少需要一個 sink 或 sink-end".to_string()));
+        }
+        for e in &self.flow.edges {
+            let (from, to) = (self.spec_of(&e.from), self.spec_of(&e.to));
+            if is_sink(from.kind) {
+                errors.push(Issue::edge("EDGE_FROM_SINK", e,
+                    format!("{} 是 sink，不能有下游", e.from)));
+            } else if !from.outputs.iter().any(|p| p.port_id == e.from_port) {
+                errors.push(Issue::edge("UNKNOWN_OUTPUT_PORT", e,
+                    format!("{} 沒有 output port {}", e.from, e.from_port)));
+            }
+            if to.kind == NodeKind::Source {
+                errors.push(Issue::edge("EDGE_INTO_SOURCE", e,
+                    format!("{} 是 source，不能有上游", e.to)));
+            } else if !to.inputs.iter().any(|p| p.port_id == e.to_port) {
+                errors.push(Issue::edge("UNKNOWN_INPUT_PORT", e,
+                    format!("{} 沒有 input port {}", e.to, e.to_port)));
+            }
+        }
+        errors
+    }
+
+    // ── ④ Condition Port 對應 ────────────────────────────────────────────────
+    fn condition_ports(&self) -> Vec<Issue> {
+        let mut errors = Vec::new();
+        for (i, spec) in self.specs.iter().enumerate() {
+            let id   = self.id(i);
+            let cond = spec.inputs.iter().filter(|p| p.role == PortRole::Condition).count();
+            let data = spec.inputs.len() - cond;
+            match spec.kind {
+                NodeKind::Mux | NodeKind::Demux if cond != 1 => {
+                    errors.push(Issue::node("CONDITION_PORT_COUNT", id,
+                        format!("{id} 必須恰好有一個 condition port，實際 {cond} 個")));
+                }
+                NodeKind::Mux | NodeKind::Demux => {}
+                _ if cond > 0 => {
+                    errors.push(Issue::node("CONDITION_ON_NON_MUX", id,
+                        format!("{id} 不是 mux / demux，不能有 condition port")));
+                }
+                _ => {}
+            }
+            if spec.kind == NodeKind::Mux && data < 2 {
+                errors.push(Issue::node("MUX_DATA_PORTS", id,
+                    format!("mux {id} 至少需要兩個 data input，實際 {data} 個")));
+            }
+            if spec.kind == NodeKind::Demux && spec.outputs.len() < 2 {
+                errors.push(Issue::node("DEMUX_OUTPUT_PORTS", id,
+                    format!("demux {id} 至少需要兩個 output，實際 {} 個", spec.outputs.len())));
+            }
+        }
+
+        // edge 的 to-role 必須與下游 port 的 role 一致
+        for e in &self.flow.edges {
+            let Some(port) = self.spec_of(&e.to).inputs.iter().find(|p| p.port_id == e.to_port)
+                else { continue; };
+            let role = match port.role { PortRole::Data => "data", PortRole::Condition => "condition" };
+            if e.to_role != role {
+                errors.push(Issue {
+                    expected: Some(role.to_string()),
+                    actual:   Some(e.to_role.clone()),
+                    ..Issue::edge("ROLE_MISMATCH", e,
+                        format!("{} port-{} 是 {role} port，edge 標示為 {}", e.to, e.to_port, e.to_role))
+                });
+            }
+        }
+        errors
+    }
+
+    // ── ⑤ 型別推導與相容性 ───────────────────────────────────────────────────
+    // 沿 topo 順序 forward inference：
+    //   source 的 any output → flow.json 列出的 tags 在 Tag Registry 中的共同型別（未列出 tags 即報錯）
+    //   其他 node 的 any output → 所有 input 實際收到的型別一致時沿用
+    //   推導不出具體型別的 any 接到具體型別的 input → 報錯（執行期會被靜默丟棄）
+    fn type_inference(&self, topo: &[usize], registry: &TagRegistry, resolved: &mut [ValueKind],
+                      casts: &mut Vec<CastEdge>, warnings: &mut Vec<Issue>) -> Vec<Issue> {
+        let mut errors = Vec::new();
+        for &i in topo {
+            let spec = self.specs[i];
+            let inferred = if spec.kind != NodeKind::Source {
+                // input port 宣告具體型別時，收到的就是該型別（必要時已 cast）
+                unify(self.flow.edges.iter().enumerate().filter(|(_, e)| e.to == self.id(i)).map(|(j, e)| {
+                    match spec.inputs.iter().find(|p| p.port_id == e.to_port) {
+                        Some(p) if p.kind != ValueKind::Any => p.kind,
+                        _ => resolved[j],
+                    }
+                }))
+            } else if spec.outputs.iter().any(|p| p.kind == ValueKind::Any) {
+                // 未列出 tags 已回報，下游 edge 不再重複報錯
+                let Some(kind) = self.tag_kind(i, registry, &mut errors) else { continue; };
+                kind
+            } else {
+                ValueKind::Any // output 都是具體型別，用不到
+            };
+
+            for (edge, e) in self.flow.edges.iter().enumerate().filter(|(_, e)| e.from == self.id(i)) {
+                let from = spec.outputs.iter().find(|p| p.port_id == e.from_port);
+                let to   = self.spec_of(&e.to).inputs.iter().find(|p| p.port_id == e.to_port);
+                // port 不存在已在 ③ 回報
+                let (Some(from), Some(to)) = (from, to) else { continue; };
+                let kind = if from.kind == ValueKind::Any { inferred } else { from.kind };
+                resolved[edge] = kind;
+                if kind == to.kind || to.kind == ValueKind::Any { continue; }
+
+                let (actual, expected) = (kind_name(kind), kind_name(to.kind));
+                let mismatch = |code, message| Issue {
+                    expected: Some(expected.to_string()),
+                    actual:   Some(actual.to_string()),
+                    ..Issue::edge(code, e, message)
+                };
+                if kind == ValueKind::Any {
+                    errors.push(mismatch("UNRESOLVED_TYPE", format!(
+                        "{} port-{} 期望 {expected}，但無法推導 {} port-{} 的實際型別",
+                        e.to, e.to_port, e.from, e.from_port)));
+                    continue;
+                }
+                match cast::classify(kind, to.kind) {
+                    Some(class) => {
+                        casts.push(CastEdge { edge, from: kind, to: to.kind, class });
+                        if class == CastClass::Lossy {
+                            warnings.push(mismatch("LOSSY_CAST", format!(
+                                "edge {}→{}：{actual}→{expected} 可能精度損失或截斷", e.from, e.to)));
+                        }
+                    }
+                    None => errors.push(mismatch("TYPE_MISMATCH", format!(
+                        "{} port-{} 期望 {expected}，但 {} port-{} 輸出 {actual}，無法自動轉換",
+                        e.to, e.to_port, e.from, e.from_port))),
+                }
+            }
+        }
+        errors
+    }
+
+    /// Source 的 tag 共同型別；flow.json 未列 tags 時報錯並回傳 None
+    /// （不以整個 Tag Registry 推導：結果會隨設定檔與執行期新增的 tag 改變）
+    fn tag_kind(&self, i: usize, registry: &TagRegistry, errors: &mut Vec<Issue>) -> Option<ValueKind> {
+        let def = &self.flow.nodes[i];
+        if def.tags.is_empty() {
+            errors.push(Issue::node("MISSING_TAGS", &def.id,
+                format!("{} 的 output 為 any，需在 flow.json 列出 tags 才能推導型別", def.id)));
+            return None;
+        }
+        let mut kinds = Vec::with_capacity(def.tags.len());
+        for tag in &def.tags {
+            match registry.value_kind(tag) {
+                Some(k) => kinds.push(k),
+                None => errors.push(Issue::node("UNKNOWN_TAG", &def.id,
+                    format!("{} 的 tag「{tag}」不在 Tag Registry", def.id))),
+            }
+        }
+        Some(unify(kinds))
+    }
+
+    // ── ⑥ Join Strategy ──────────────────────────────────────────────────────
+    fn join_strategy(&self) -> Vec<Issue> {
+        let mut errors = Vec::new();
+        for (i, spec) in self.specs.iter().enumerate() {
+            let id = self.id(i);
+            match (spec.kind, spec.join_strategy) {
+                (NodeKind::Merge, JoinStrategy::All | JoinStrategy::AllOrInitial) =>
+                    errors.push(Issue::node("JOIN_STRATEGY_MISMATCH", id,
+                        format!("merge {id} 的 join-strategy 必須是 any"))),
+                (NodeKind::Join, JoinStrategy::Any) =>
+                    errors.push(Issue::node("JOIN_STRATEGY_MISMATCH", id,
+                        format!("join {id} 的 join-strategy 不能是 any"))),
+                _ => {}
+            }
+            if spec.join_strategy != JoinStrategy::AllOrInitial { continue; }
+            for port in &spec.inputs {
+                match &port.initial_value {
+                    None => errors.push(Issue::node("MISSING_INITIAL_VALUE", id, format!(
+                        "{id} port-{}（{}）缺少 initial-value，無法保證 all-or-initial 語意",
+                        port.port_id, port.name))),
+                    Some(v) if serde_json::from_str::<serde_json::Value>(v).is_err() =>
+                        errors.push(Issue::node("INVALID_INITIAL_VALUE", id, format!(
+                            "{id} port-{} 的 initial-value 不是合法 JSON：{v}", port.port_id))),
+                    Some(_) => {}
+                }
+            }
+        }
+        errors
+    }
+}
+
+fn is_sink(kind: NodeKind) -> bool {
+    matches!(kind, NodeKind::Sink | NodeKind::SinkEnd)
+}
+
+/// 全部相同且為具體型別時回傳該型別，否則 any
+fn unify(kinds: impl IntoIterator<Item = ValueKind>) -> ValueKind {
+    let mut kinds = kinds.into_iter();
+    let Some(first) = kinds.next() else { return ValueKind::Any; };
+    if kinds.all(|k| k == first) { first } else { ValueKind::Any }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::iiot::flow::node_descriptor::{InputPortDef, OutputPortDef};
+    use crate::TagMeta;
+    use ValueKind::*;
+
+    fn spec(kind: NodeKind, inputs: &[(u32, ValueKind)], outputs: &[(u32, ValueKind)]) -> NodeSpec {
+        NodeSpec {
+            name: "test".to_string(), version: "0.0.0".to_string(), kind,
+            inputs: inputs.iter().map(|&(port_id, kind)| InputPortDef {
+                port_id, name: format!("in{port_id}"), kind, role: PortRole::Data, initial_value: None,
+            }).collect(),
+            outputs: outputs.iter().map(|&(port_id, kind)| OutputPortDef {
+                port_id, name: format!("out{port_id}"), kind,
+            }).collect(),
+            join_strategy: JoinStrategy::Any, props: vec![],
+            label: String::new(), description: String::new(), icon: String::new(),
+            category: String::new(), color: String::new(),
+        }
+    }
+
+    fn source(out: ValueKind) -> NodeSpec { spec(NodeKind::Source, &[], &[(0, out)]) }
+    fn sink() -> NodeSpec { spec(NodeKind::Sink, &[(0, Any)], &[]) }
+    fn transform(input: ValueKind, out: ValueKind) -> NodeSpec {
+        spec(NodeKind::Transform, &[(0, input)], &[(0, out)])
+    }
+
+    /// nodes：id 依序為 n0、n1…；edges：(from, to)，port 皆為 0
+    fn flow(n: usize, edges: &[(usize, usize)]) -> FlowDef {
+        let nodes: Vec<_> = (0..n).map(|i| serde_json::json!({ "id": format!("n{i}"), "wasm": "x.wasm" })).collect();
+        let edges: Vec<_> = edges.iter()
+            .map(|(f, t)| serde_json::json!({ "from": format!("n{f}"), "to": format!("n{t}") }))
+            .collect();
+        FlowDef::parse(&serde_json::json!({ "nodes": nodes, "edges": edges }).to_string()).unwrap()
+    }
+
+    /// 第 i 個 node 列出 tags t{k}（對應 registry 的第 k 個 tag）
+    fn tagged(mut flow: FlowDef, i: usize, tags: &[usize]) -> FlowDef {
+        flow.nodes[i].tags = tags.iter().map(|k| format!("t{k}")).collect();
+        flow
+    }
+
+    fn registry(kinds: &[ValueKind]) -> TagRegistry {
+        let mut r = TagRegistry::new();
+        let tags: Vec<_> = kinds.iter().enumerate().map(|(i, &k)| TagMeta::new(&format!("t{i}"), k)).collect();
+        r.apply(&tags);
+        r
+    }
+
+    fn run(flow: &FlowDef, specs: &[NodeSpec], tags: &[ValueKind]) -> Result<Validated, ValidationFailed> {
+        let specs: Vec<&NodeSpec> = specs.iter().collect();
+        validate(flow, &specs, &registry(tags))
+    }
+
+    /// 失敗的 pass 與各自的錯誤碼
+    fn codes(r: Result<Validated, ValidationFailed>) -> Vec<(&'static str, Vec<&'static str>)> {
+        let Err(ValidationFailed(reports)) = r else { panic!("應該驗證失敗") };
+        reports.into_iter().map(|p| (p.pass, p.errors.iter().map(|e| e.code).collect())).collect()
+    }
+
+    #[test]
+    fn valid_chain_resolves_source_type() {
+        let f = tagged(flow(3, &[(0, 1), (1, 2)]), 0, &[0]);
+        let v = run(&f, &[source(Any), transform(F64Val, F64Val), sink()], &[F64Val]).unwrap();
+        assert_eq!(v.topo_order, vec![0, 1, 2]);
+        assert_eq!(v.resolved_types, vec![F64Val, F64Val]);
+        assert!(v.casts.is_empty() && v.warnings.is_empty());
+    }
+
+    #[test]
+    fn topo_order_follows_declaration_within_a_layer() {
+        // n0 → n2 → n3，n1 → n2：n1 宣告在 n2 之前，也要排在 n2 之前
+        let specs = [source(F64Val), source(F64Val), spec(NodeKind::Merge, &[(0, Any)], &[(0, Any)]), sink()];
+        let v = run(&flow(4, &[(0, 2), (1, 2), (2, 3)]), &specs, &[]).unwrap();
+        assert_eq!(v.topo_order, vec![0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn cycle_is_reported_and_skips_type_inference() {
+        let specs = [source(F64Val), transform(BoolVal, F64Val), transform(F64Val, F64Val), sink()];
+        let r = run(&flow(4, &[(0, 1), (1, 2), (2, 1), (2, 3)]), &specs, &[]);
+        let Err(ValidationFailed(reports)) = &r else { panic!("應該驗證失敗") };
+        let edge = reports[0].errors[0].edge.as_ref().unwrap();
+        assert_eq!((edge.from.as_str(), edge.to.as_str()), ("n2", "n1"), "回報的 back edge");
+        // n0(f64) → n1(bool) 的型別錯誤因有循環而不檢查
+        assert_eq!(codes(r), vec![("cycle-detection", vec!["CYCLE"])]);
+    }
+
+    #[test]
+    fn orphans_without_input_or_output() {
+        let specs = [source(F64Val), sink(), transform(F64Val, F64Val)];
+        assert_eq!(codes(run(&flow(3, &[(0, 1)]), &specs, &[])),
+            vec![("orphan", vec!["ORPHAN_NO_INPUT", "ORPHAN_NO_OUTPUT"])]);
+    }
+
+    #[test]
+    fn transform_without_downstream_is_an_orphan_not_a_sink() {
+        // source → transform → sink，另一個 transform 只有上游
+        let specs = [source(F64Val), transform(F64Val, F64Val), sink(), transform(F64Val, F64Val)];
+        let r = run(&flow(4, &[(0, 1), (1, 2), (0, 3)]), &specs, &[]);
+        let Err(ValidationFailed(reports)) = &r else { panic!("應該驗證失敗") };
+        assert_eq!(reports[0].errors[0].node.as_deref(), Some("n3"));
+        assert_eq!(codes(r), vec![("orphan", vec!["ORPHAN_NO_OUTPUT"])]);
+    }
+
+    #[test]
+    fn boundary_errors() {
+        // 沒有 sink、edge 進入 source
+        let specs = [source(F64Val), transform(F64Val, F64Val), source(F64Val)];
+        let r = codes(run(&flow(3, &[(0, 1), (1, 2)]), &specs, &[]));
+        assert!(r.contains(&("boundary", vec!["NO_SINK", "EDGE_INTO_SOURCE"])), "{r:?}");
+
+        // port 不存在
+        let f = FlowDef::parse(r#"{
+            "nodes": [{ "id": "s", "wasm": "x" }, { "id": "k", "wasm": "x" }],
+            "edges": [{ "from": "s", "from-port": 1, "to": "k", "to-port": 2 }]
+        }"#).unwrap();
+        assert_eq!(codes(run(&f, &[source(F64Val), sink()], &[])),
+            vec![("boundary", vec!["UNKNOWN_OUTPUT_PORT", "UNKNOWN_INPUT_PORT"])]);
+    }
+
+    #[test]
+    fn type_mismatch_reports_expected_and_actual() {
+        let r = run(&flow(3, &[(0, 1), (1, 2)]), &[source(BoolVal), transform(F64Val, F64Val), sink()], &[]);
+        let Err(ValidationFailed(reports)) = r else { panic!("應該驗證失敗") };
+        let e = &reports[0].errors[0];
+        assert_eq!((reports[0].pass, e.code), ("type-inference", "TYPE_MISMATCH"));
+        assert_eq!(e.expected.as_deref(), Some(kind_name(F64Val)));
+        assert_eq!(e.actual.as_deref(),   Some(kind_name(BoolVal)));
+    }
+
+    #[test]
+    fn unresolved_any_against_concrete_input() {
+        // source 的 tag 型別不一致 → any，無法接到 f64 input
+        let specs = [source(Any), transform(F64Val, F64Val), sink()];
+        let f = tagged(flow(3, &[(0, 1), (1, 2)]), 0, &[0, 1]);
+        assert_eq!(codes(run(&f, &specs, &[F64Val, I32Val])),
+            vec![("type-inference", vec!["UNRESOLVED_TYPE"])]);
+    }
+
+    #[test]
+    fn any_source_infers_only_from_its_own_tags() {
+        // registry 有兩種型別：只看 source 列出的 tag，不受其他 tag 影響
+        let specs = [source(Any), transform(F64Val, F64Val), sink()];
+        let v = run(&tagged(flow(3, &[(0, 1), (1, 2)]), 0, &[0]), &specs, &[F64Val, I32Val]).unwrap();
+        assert_eq!(v.resolved_types[0], F64Val);
+
+        let f = tagged(flow(3, &[(0, 1), (1, 2)]), 0, &[1]);
+        let v = run(&f, &specs, &[F64Val, I32Val]).unwrap();
+        assert_eq!((v.resolved_types[0], v.casts[0].class), (I32Val, CastClass::Lossless));
+
+        assert_eq!(codes(run(&tagged(flow(3, &[(0, 1), (1, 2)]), 0, &[2]), &specs, &[F64Val, I32Val])),
+            vec![("type-inference", vec!["UNKNOWN_TAG", "UNRESOLVED_TYPE"])]);
+    }
+
+    #[test]
+    fn any_source_without_tags_is_rejected_once() {
+        let specs = [source(Any), transform(F64Val, F64Val), sink()];
+        assert_eq!(codes(run(&flow(3, &[(0, 1), (1, 2)]), &specs, &[F64Val, I32Val])),
+            vec![("type-inference", vec!["MISSING_TAGS"])], "下游 edge 不重複報 UNRESOLVED_TYPE");
+        // output 為具體型別的 source 不需要 tags
+        assert!(run(&flow(3, &[(0, 1), (1, 2)]), &[source(F64Val), transform(F64Val, F64Val), sink()], &[])
+            .is_ok());
+    }
+
+    #[test]
+    fn numeric_mismatch_schedules_cast() {
+        let v = run(&flow(3, &[(0, 1), (1, 2)]), &[source(I16Val), transform(F64Val, I32Val), sink()], &[])
+            .unwrap();
+        assert_eq!(v.casts.len(), 1);
+        assert_eq!((v.casts[0].edge, v.casts[0].class), (0, CastClass::Lossless));
+        assert!(v.warnings.is_empty(), "lossless 不產生 warning");
+
+        let v = run(&flow(3, &[(0, 1), (1, 2)]), &[source(F64Val), transform(I32Val, I32Val), sink()], &[])
+            .unwrap();
+        assert_eq!(v.casts[0].class, CastClass::Lossy);
+        assert_eq!(v.warnings.iter().map(|w| w.code).collect::<Vec<_>>(), vec!["LOSSY_CAST"]);
+    }
+
+    #[test]
+    fn join_strategy_rules() {
+        let mut merge = spec(NodeKind::Merge, &[(0, Any), (1, Any)], &[(0, Any)]);
+        merge.join_strategy = JoinStrategy::All;
+        let mut join = spec(NodeKind::Join, &[(0, Any), (1, Any)], &[(0, Any)]);
+        join.join_strategy = JoinStrategy::AllOrInitial;
+        join.inputs[1].initial_value = Some("{not json".to_string());
+
+        let f = FlowDef::parse(r#"{
+            "nodes": [{ "id": "s", "wasm": "x" }, { "id": "m", "wasm": "x" },
+                      { "id": "j", "wasm": "x" }, { "id": "k", "wasm": "x" }],
+            "edges": [{ "from": "s", "to": "m" }, { "from": "s", "to": "m", "to-port": 1 },
+                      { "from": "m", "to": "j" }, { "from": "s", "to": "j", "to-port": 1 },
+                      { "from": "j", "to": "k" }]
+        }"#).unwrap();
+        assert_eq!(codes(run(&f, &[source(F64Val), merge, join, sink()], &[])),
+            vec![("join-strategy", vec!["JOIN_STRATEGY_MISMATCH", "MISSING_INITIAL_VALUE", "INVALID_INITIAL_VALUE"])]);
+    }
+
+    #[test]
+    fn collects_errors_from_every_pass() {
+        // 孤立 Node 與型別錯誤同時回報
+        let specs = [source(BoolVal), transform(F64Val, F64Val), sink(), transform(F64Val, F64Val)];
+        let passes: Vec<_> = codes(run(&flow(4, &[(0, 1), (1, 2)]), &specs, &[])).into_iter().map(|(p, _)| p).collect();
+        assert_eq!(passes, vec!["orphan", "type-inference"]);
+    }
+}
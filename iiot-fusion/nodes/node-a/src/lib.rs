@@ -3,45 +3,74 @@
 
 wit_bindgen::generate!({
     world: "flow-node",
-    path: "../../wit",
+    path: "../../wit/0.2.0",
 });
 
 use exports::iiot::flow::meta::Guest as MetaGuest;
 use exports::iiot::flow::node::Guest as NodeGuest;
-use iiot::flow::types::{FlowMsg, NodeOutput, TagValue, ValueKind};
+use iiot::flow::types::{FlowMsg, NodeOutput, PortMsgs, TagValue, ValueKind};
+use iiot::flow::node_descriptor::{
+    InputPortDef, JoinStrategy, NodeKind, NodeSpec, OutputPortDef, PortRole,
+};
 
 struct NodeA;
 
 impl MetaGuest for NodeA {
-    fn accepted_input_types() -> Vec<ValueKind> {
-        vec![ValueKind::F32Val, ValueKind::F64Val]
+    fn describe() -> NodeSpec {
+        NodeSpec {
+            name:          "node-a:unit-converter".to_string(),
+            version:       "0.1.0".to_string(),
+            kind:          NodeKind::Transform,
+            inputs:        vec![input(0, "in", ValueKind::F64Val)],
+            outputs:       vec![output(0, "out", ValueKind::F64Val)],
+            join_strategy: JoinStrategy::Any,
+            props:         vec![],
+            label:         "°C → °F".to_string(),
+            description:   "攝氏轉華氏，非浮點數丟棄".to_string(),
+            icon:          "thermometer".to_string(),
+            category:      "math".to_string(),
+            color:         "#4A90D9".to_string(),
+        }
     }
-    fn output_type() -> ValueKind { ValueKind::F64Val }
-    fn name()    -> String { "node-a:unit-converter".to_string() }
-    fn version() -> String { "0.1.0".to_string() }
 }
 
 impl NodeGuest for NodeA {
-    fn process(msg: FlowMsg) -> NodeOutput {
-        let raw: f64 = match msg.value {
-            TagValue::F32Val(v) => v as f64,
-            TagValue::F64Val(v) => v,
-            _ => return NodeOutput { msgs: vec![] },
-        };
-        // °C → °F
-        let converted = raw * 9.0 / 5.0 + 32.0;
-        NodeOutput { msgs: vec![FlowMsg {
-            tag_id:    msg.tag_id,
-            msg_id:    msg.msg_id,
-            value:     TagValue::F64Val(converted),
-            timestamp: msg.timestamp,
-            quality:   msg.quality,
-        }]}
+    fn init(_props: String, _wiring: String) -> Result<(), String> { Ok(()) }
+
+    fn process(_input_port: u32, msgs: Vec<FlowMsg>) -> NodeOutput {
+        let msgs: Vec<FlowMsg> = msgs.into_iter().filter_map(convert).collect();
+        if msgs.is_empty() { return NodeOutput { outputs: vec![] }; }
+        NodeOutput { outputs: vec![PortMsgs { port_id: 0, msgs }] }
     }
 
-    fn process_raw(_: u32, _: u32, _: Vec<u8>) -> NodeOutput { NodeOutput { msgs: vec![] } }
+    fn process_raw(_: u32, _: u32, _: Vec<u8>) -> NodeOutput { NodeOutput { outputs: vec![] } }
     fn save_state() -> Vec<u8>     { vec![] }
     fn load_state(_state: Vec<u8>) {}
 }
 
+// °C → °F，非浮點數丟棄
+fn convert(msg: FlowMsg) -> Option<FlowMsg> {
+    let raw: f64 = match msg.value {
+        TagValue::F32Val(v) => v as f64,
+        TagValue::F64Val(v) => v,
+        _ => return None,
+    };
+    let converted = raw * 9.0 / 5.0 + 32.0;
+    Some(FlowMsg {
+        tag_id:    msg.tag_id,
+        msg_id:    msg.msg_id,
+        value:     TagValue::F64Val(converted),
+        timestamp: msg.timestamp,
+        quality:   msg.quality,
+    })
+}
+
+fn input(port_id: u32, name: &str, kind: ValueKind) -> InputPortDef {
+    InputPortDef { port_id, name: name.to_string(), kind, role: PortRole::Data, initial_value: None }
+}
+
+fn output(port_id: u32, name: &str, kind: ValueKind) -> OutputPortDef {
+    OutputPortDef { port_id, name: name.to_string(), kind }
+}
+
 export!(NodeA);
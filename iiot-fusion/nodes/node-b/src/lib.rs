@@ -1,52 +1,120 @@
 // nodes/node-b/src/lib.rs
-// Node B：品質過濾 + 閾值警報
+// Node B：品質過濾 + 閾值警報（port 0 = 通過，port 1 = 警報）
 
 wit_bindgen::generate!({
     world: "flow-node",
-    path: "../../wit",
+    path: "../../wit/0.2.0",
 });
 
 use exports::iiot::flow::meta::Guest as MetaGuest;
 use exports::iiot::flow::node::Guest as NodeGuest;
-use iiot::flow::types::{FlowMsg, NodeOutput, TagValue, ValueKind};
+use iiot::flow::types::{FlowMsg, NodeOutput, PortMsgs, TagValue, ValueKind};
+use iiot::flow::node_descriptor::{
+    InputPortDef, JoinStrategy, NodeKind, NodeSpec, OutputPortDef, PortRole, PropDef, PropType,
+};
 
-const HIGH_ALARM:  f64 = 104.0; // °F
-const LOW_ALARM:   f64 =  32.0; // °F
+// 由 init() 依 props 設定
+static mut HIGH_ALARM: f64 = 104.0; // °F
+static mut LOW_ALARM:  f64 =  32.0; // °F
 
 struct NodeB;
 
 impl MetaGuest for NodeB {
-    fn accepted_input_types() -> Vec<ValueKind> { vec![ValueKind::F64Val] }
-    fn output_type() -> ValueKind { ValueKind::F64Val }
-    fn name()    -> String { "node-b:quality-filter".to_string() }
-    fn version() -> String { "0.1.0".to_string() }
+    fn describe() -> NodeSpec {
+        NodeSpec {
+            name:          "node-b:quality-filter".to_string(),
+            version:       "0.1.0".to_string(),
+            kind:          NodeKind::Transform,
+            inputs:        vec![input(0, "in", ValueKind::F64Val)],
+            outputs:       vec![
+                output(0, "pass",  ValueKind::F64Val),
+                output(1, "alarm", ValueKind::F64Val),
+            ],
+            join_strategy: JoinStrategy::Any,
+            props:         vec![
+                prop_f64("high-alarm", "高警報 (°F)", 104.0),
+                prop_f64("low-alarm",  "低警報 (°F)",  32.0),
+            ],
+            label:         "品質過濾".to_string(),
+            description:   "丟棄 bad quality，超出閾值者另送 alarm port".to_string(),
+            icon:          "filter".to_string(),
+            category:      "logic".to_string(),
+            color:         "#E67E22".to_string(),
+        }
+    }
 }
 
 impl NodeGuest for NodeB {
-    fn process(msg: FlowMsg) -> NodeOutput {
-        // bad quality → 丟棄
-        if msg.quality >= 2 { return NodeOutput { msgs: vec![] }; }
-
-        let val = match msg.value {
-            TagValue::F64Val(v) => v,
-            _ => return NodeOutput { msgs: vec![] },
-        };
-
-        // 超出工程量程 → quality 降為 uncertain
-        let quality = if val > HIGH_ALARM || val < LOW_ALARM { 1 } else { msg.quality };
-
-        NodeOutput { msgs: vec![FlowMsg {
-            tag_id:    msg.tag_id,
-            msg_id:    msg.msg_id,
-            value:     TagValue::F64Val(val),
-            timestamp: msg.timestamp,
-            quality,
-        }]}
+    fn init(props: String, _wiring: String) -> Result<(), String> {
+        let props: serde_json::Value = serde_json::from_str(&props).map_err(|e| e.to_string())?;
+        let high = props["high-alarm"].as_f64().ok_or("high-alarm 必須是數值")?;
+        let low  = props["low-alarm"].as_f64().ok_or("low-alarm 必須是數值")?;
+        if low >= high {
+            return Err(format!("low-alarm ({low}) 必須小於 high-alarm ({high})"));
+        }
+        unsafe { HIGH_ALARM = high; LOW_ALARM = low; }
+        Ok(())
+    }
+
+    // port 0：通過品質過濾的訊息（超閾值者 quality 降為 uncertain）
+    // port 1：超閾值的警報分支
+    fn process(_input_port: u32, msgs: Vec<FlowMsg>) -> NodeOutput {
+        let mut pass  = Vec::new();
+        let mut alarm = Vec::new();
+
+        for msg in msgs {
+            // bad quality → 丟棄
+            if msg.quality >= 2 { continue; }
+
+            let val = match msg.value {
+                TagValue::F64Val(v) => v,
+                _ => continue,
+            };
+
+            // 超出工程量程 → quality 降為 uncertain
+            let out_of_range = unsafe { !(LOW_ALARM..=HIGH_ALARM).contains(&val) };
+            let quality = if out_of_range { 1 } else { msg.quality };
+
+            let out = FlowMsg {
+                tag_id:    msg.tag_id,
+                msg_id:    msg.msg_id,
+                value:     TagValue::F64Val(val),
+                timestamp: msg.timestamp,
+                quality,
+            };
+            if out_of_range { alarm.push(out.clone()); }
+            pass.push(out);
+        }
+
+        let mut outputs = Vec::new();
+        if !pass.is_empty()  { outputs.push(PortMsgs { port_id: 0, msgs: pass }); }
+        if !alarm.is_empty() { outputs.push(PortMsgs { port_id: 1, msgs: alarm }); }
+        NodeOutput { outputs }
     }
 
-    fn process_raw(_: u32, _: u32, _: Vec<u8>) -> NodeOutput { NodeOutput { msgs: vec![] } }
+    fn process_raw(_: u32, _: u32, _: Vec<u8>) -> NodeOutput { NodeOutput { outputs: vec![] } }
     fn save_state() -> Vec<u8>     { vec![] }
     fn load_state(_state: Vec<u8>) {}
 }
 
+fn input(port_id: u32, name: &str, kind: ValueKind) -> InputPortDef {
+    InputPortDef { port_id, name: name.to_string(), kind, role: PortRole::Data, initial_value: None }
+}
+
+fn output(port_id: u32, name: &str, kind: ValueKind) -> OutputPortDef {
+    OutputPortDef { port_id, name: name.to_string(), kind }
+}
+
+fn prop_f64(key: &str, label: &str, default: f64) -> PropDef {
+    PropDef {
+        key:           key.to_string(),
+        label:         label.to_string(),
+        prop_type:     PropType::PropF64,
+        default_value: default.to_string(),
+        required:      false,
+        choices:       vec![],
+        description:   String::new(),
+    }
+}
+
 export!(NodeB);
@@ -1,18 +1,30 @@
 // nodes/node-c/src/lib.rs
-// Node C：滑動視窗平均，展示 save/load state
+// Node C：滑動視窗平均，展示 save/load state 與 state migration
+//
+// state（0.2.0 起，little-endian）：
+//   window u64 | count u64 | total u64 | 視窗內的 count 筆值（f64，由舊到新）
+// 0.1.0 的 state 是 BUF[..window] + pos + count + total，不含 window，改 window 就讀不回來；
+// migrate-state 把它轉成 0.2.0 的格式，load-state 依目前的 window 保留最新的幾筆
 
 wit_bindgen::generate!({
-    world: "flow-node",
-    path: "../../wit",
+    world: "flow-node-migratable",
+    path: "../../wit/0.2.0",
 });
 
 use exports::iiot::flow::meta::Guest as MetaGuest;
 use exports::iiot::flow::node::Guest as NodeGuest;
-use iiot::flow::types::{FlowMsg, NodeOutput, TagValue, ValueKind};
+use exports::iiot::flow::state_migration::Guest as MigrationGuest;
+use iiot::flow::types::{FlowMsg, NodeOutput, PortMsgs, TagValue, ValueKind};
+use iiot::flow::node_descriptor::{
+    InputPortDef, JoinStrategy, NodeKind, NodeSpec, OutputPortDef, PortRole, PropDef, PropType,
+};
 
-const WINDOW: usize = 8;
+const MAX_WINDOW: usize = 64;
 
-static mut BUF:   [f64; WINDOW] = [0.0; WINDOW];
+// 由 init() 依 props 設定，1..=MAX_WINDOW
+static mut WINDOW: usize = 8;
+
+static mut BUF:   [f64; MAX_WINDOW] = [0.0; MAX_WINDOW];
 static mut POS:   usize = 0;
 static mut COUNT: usize = 0;
 static mut TOTAL: u64   = 0;
@@ -20,62 +32,155 @@ static mut TOTAL: u64   = 0;
 struct NodeC;
 
 impl MetaGuest for NodeC {
-    fn accepted_input_types() -> Vec<ValueKind> { vec![ValueKind::F64Val] }
-    fn output_type() -> ValueKind { ValueKind::F64Val }
-    fn name()    -> String { "node-c:sliding-avg".to_string() }
-    fn version() -> String { "0.1.0".to_string() }
+    fn describe() -> NodeSpec {
+        NodeSpec {
+            name:          "node-c:sliding-avg".to_string(),
+            version:       "0.2.0".to_string(),
+            kind:          NodeKind::Transform,
+            inputs:        vec![input(0, "in", ValueKind::F64Val)],
+            outputs:       vec![output(0, "avg", ValueKind::F64Val)],
+            join_strategy: JoinStrategy::Any,
+            props:         vec![PropDef {
+                key:           "window".to_string(),
+                label:         "視窗大小".to_string(),
+                prop_type:     PropType::PropU32,
+                default_value: "8".to_string(),
+                required:      false,
+                choices:       vec![],
+                description:   format!("平均的筆數（1 ~ {MAX_WINDOW}）"),
+            }],
+            label:         "滑動平均".to_string(),
+            description:   "最近 N 筆的滑動視窗平均".to_string(),
+            icon:          "chart-line".to_string(),
+            category:      "math".to_string(),
+            color:         "#4A90D9".to_string(),
+        }
+    }
 }
 
 impl NodeGuest for NodeC {
-    fn process(msg: FlowMsg) -> NodeOutput {
-        let val = match msg.value {
-            TagValue::F64Val(v) => v,
-            _ => return NodeOutput { msgs: vec![] },
-        };
-
-        let avg = unsafe {
-            BUF[POS] = val;
-            POS   = (POS + 1) % WINDOW;
-            if COUNT < WINDOW { COUNT += 1; }
-            TOTAL += 1;
-            BUF[..COUNT].iter().sum::<f64>() / COUNT as f64
-        };
-
-        NodeOutput { msgs: vec![FlowMsg {
-            tag_id:    msg.tag_id,
-            msg_id:    msg.msg_id,
-            value:     TagValue::F64Val(avg),
-            timestamp: msg.timestamp,
-            quality:   msg.quality,
-        }]}
+    fn init(props: String, _wiring: String) -> Result<(), String> {
+        let props: serde_json::Value = serde_json::from_str(&props).map_err(|e| e.to_string())?;
+        let window = props["window"].as_u64().ok_or("window 必須是正整數")? as usize;
+        if !(1..=MAX_WINDOW).contains(&window) {
+            return Err(format!("window ({window}) 必須介於 1 ~ {MAX_WINDOW}"));
+        }
+        unsafe { WINDOW = window; }
+        Ok(())
     }
 
-    fn process_raw(_: u32, _: u32, _: Vec<u8>) -> NodeOutput { NodeOutput { msgs: vec![] } }
+    fn process(_input_port: u32, msgs: Vec<FlowMsg>) -> NodeOutput {
+        let msgs: Vec<FlowMsg> = msgs.into_iter().filter_map(push).collect();
+        if msgs.is_empty() { return NodeOutput { outputs: vec![] }; }
+        NodeOutput { outputs: vec![PortMsgs { port_id: 0, msgs }] }
+    }
+
+    fn process_raw(_: u32, _: u32, _: Vec<u8>) -> NodeOutput { NodeOutput { outputs: vec![] } }
 
     fn save_state() -> Vec<u8> {
-        let mut b = Vec::with_capacity(WINDOW * 8 + 24);
-        unsafe {
-            let buf = &*std::ptr::addr_of!(BUF);
-            for &v in buf { b.extend_from_slice(&v.to_le_bytes()); }
-            b.extend_from_slice(&(POS   as u64).to_le_bytes());
-            b.extend_from_slice(&(COUNT as u64).to_le_bytes());
-            b.extend_from_slice(&TOTAL.to_le_bytes());
-        }
+        let (window, count, total) = unsafe { (WINDOW, COUNT, TOTAL) };
+        let mut b = Vec::with_capacity(24 + count * 8);
+        b.extend_from_slice(&(window as u64).to_le_bytes());
+        b.extend_from_slice(&(count  as u64).to_le_bytes());
+        b.extend_from_slice(&total.to_le_bytes());
+        for v in recent() { b.extend_from_slice(&v.to_le_bytes()); }
         b
     }
 
+    // 格式不符就 panic（trap）：Host 回報還原失敗，不默默從空視窗開始
     fn load_state(s: Vec<u8>) {
-        if s.len() < WINDOW * 8 + 24 { return; }
+        let (_, values, total) = parse(&s).unwrap_or_else(|e| panic!("node-c state 無效：{e}"));
+        // window 變小時只保留最新的幾筆
+        let window = unsafe { WINDOW };
+        let keep = &values[values.len().saturating_sub(window)..];
         unsafe {
-            for i in 0..WINDOW {
-                BUF[i] = f64::from_le_bytes(s[i*8..i*8+8].try_into().unwrap());
-            }
-            let b = WINDOW * 8;
-            POS   = u64::from_le_bytes(s[b   ..b+ 8].try_into().unwrap()) as usize;
-            COUNT = u64::from_le_bytes(s[b+ 8..b+16].try_into().unwrap()) as usize;
-            TOTAL = u64::from_le_bytes(s[b+16..b+24].try_into().unwrap());
+            let buf = &mut *std::ptr::addr_of_mut!(BUF);
+            buf[..keep.len()].copy_from_slice(keep);
+            POS   = keep.len() % window;
+            COUNT = keep.len();
+            TOTAL = total;
         }
     }
 }
 
+impl MigrationGuest for NodeC {
+    fn migrate_state(from_version: String, s: Vec<u8>) -> Result<Vec<u8>, String> {
+        if from_version != "0.1.0" {
+            return Err(format!("不支援從 {from_version} 遷移（只支援 0.1.0）"));
+        }
+        // 0.1.0：BUF[..window] + pos + count + total，window 由長度推回
+        if s.len() < 32 || !(s.len() - 24).is_multiple_of(8) {
+            return Err(format!("0.1.0 的 state 長度 {} 不正確", s.len()));
+        }
+        let window = (s.len() - 24) / 8;
+        let word = |i: usize| u64::from_le_bytes(s[i * 8..i * 8 + 8].try_into().unwrap());
+        let (pos, count, total) = (word(window) as usize, word(window + 1) as usize, word(window + 2));
+        if pos >= window || count > window {
+            return Err(format!("0.1.0 的 state 內容不正確（window {window}、pos {pos}、count {count}）"));
+        }
+        // 視窗未滿時值在 BUF[..count]；滿了則 BUF[pos..] 比 BUF[..pos] 舊
+        let order: Vec<usize> = if count < window { (0..count).collect() }
+                                else { (pos..window).chain(0..pos).collect() };
+
+        let mut b = Vec::with_capacity(24 + count * 8);
+        b.extend_from_slice(&(window as u64).to_le_bytes());
+        b.extend_from_slice(&(count  as u64).to_le_bytes());
+        b.extend_from_slice(&total.to_le_bytes());
+        for i in order { b.extend_from_slice(&f64::from_bits(word(i)).to_le_bytes()); }
+        Ok(b)
+    }
+}
+
+// 視窗內的值，由舊到新
+fn recent() -> Vec<f64> {
+    unsafe {
+        let buf = &*std::ptr::addr_of!(BUF);
+        if COUNT < WINDOW { buf[..COUNT].to_vec() }
+        else { buf[POS..WINDOW].iter().chain(&buf[..POS]).copied().collect() }
+    }
+}
+
+// 0.2.0 的 state → (window, 由舊到新的值, total)
+fn parse(s: &[u8]) -> Result<(usize, Vec<f64>, u64), String> {
+    if s.len() < 24 { return Err(format!("長度 {} 不足 24 bytes", s.len())); }
+    let word = |i: usize| u64::from_le_bytes(s[i * 8..i * 8 + 8].try_into().unwrap());
+    let (window, count, total) = (word(0) as usize, word(1) as usize, word(2));
+    if count > window || s.len() != 24 + count * 8 {
+        return Err(format!("window {window}、count {count} 與長度 {} 不符", s.len()));
+    }
+    Ok((window, (0..count).map(|i| f64::from_bits(word(3 + i))).collect(), total))
+}
+
+// 放入視窗，回傳目前平均
+fn push(msg: FlowMsg) -> Option<FlowMsg> {
+    let val = match msg.value {
+        TagValue::F64Val(v) => v,
+        _ => return None,
+    };
+
+    let avg = unsafe {
+        BUF[POS] = val;
+        POS   = (POS + 1) % WINDOW;
+        if COUNT < WINDOW { COUNT += 1; }
+        TOTAL += 1;
+        BUF[..COUNT].iter().sum::<f64>() / COUNT as f64
+    };
+
+    Some(FlowMsg {
+        tag_id:    msg.tag_id,
+        msg_id:    msg.msg_id,
+        value:     TagValue::F64Val(avg),
+        timestamp: msg.timestamp,
+        quality:   msg.quality,
+    })
+}
+
+fn input(port_id: u32, name: &str, kind: ValueKind) -> InputPortDef {
+    InputPortDef { port_id, name: name.to_string(), kind, role: PortRole::Data, initial_value: None }
+}
+
+fn output(port_id: u32, name: &str, kind: ValueKind) -> OutputPortDef {
+    OutputPortDef { port_id, name: name.to_string(), kind }
+}
+
 export!(NodeC);
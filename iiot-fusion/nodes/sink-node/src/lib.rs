@@ -4,12 +4,15 @@
 
 wit_bindgen::generate!({
     world: "flow-node-with-host",
-    path: "../../wit",
+    path: "../../wit/0.2.0",
 });
 
 use exports::iiot::flow::meta::Guest as MetaGuest;
 use exports::iiot::flow::node::Guest as NodeGuest;
 use iiot::flow::types::{FlowMsg, NodeOutput, TagValue, ValueKind};
+use iiot::flow::node_descriptor::{
+    InputPortDef, JoinStrategy, NodeKind, NodeSpec, PortRole, PropDef, PropType,
+};
 use iiot::flow::host_api;
 
 mod proto {
@@ -29,62 +32,94 @@ mod proto {
 // 輸出緩衝區：process() 後 Host 呼叫 take_output_ptr/len 取走
 static mut OUTPUT_BUF: Vec<u8> = Vec::new();
 
+// 由 init() 依 props 設定
+static mut FLOW_ID: String = String::new();
+
 struct SinkNode;
 
 impl MetaGuest for SinkNode {
-    fn accepted_input_types() -> Vec<ValueKind> {
-        vec![
-            ValueKind::BoolVal, ValueKind::I32Val, ValueKind::U32Val,
-            ValueKind::F32Val,  ValueKind::F64Val, ValueKind::ShortStr,
-        ]
+    fn describe() -> NodeSpec {
+        NodeSpec {
+            name:          "sink-node:protobuf-flow-result".to_string(),
+            version:       "0.1.0".to_string(),
+            kind:          NodeKind::Sink,
+            inputs:        vec![InputPortDef {
+                port_id: 0, name: "in".to_string(), kind: ValueKind::Any,
+                role: PortRole::Data, initial_value: None,
+            }],
+            outputs:       vec![],
+            join_strategy: JoinStrategy::Any,
+            props:         vec![PropDef {
+                key:           "flow-id".to_string(),
+                label:         "Flow ID".to_string(),
+                prop_type:     PropType::PropString,
+                default_value: "\"flow-temp-pipeline-v1\"".to_string(),
+                required:      false,
+                choices:       vec![],
+                description:   "寫入 FlowResult.flow_id".to_string(),
+            }],
+            label:         "FlowResult 輸出".to_string(),
+            description:   "FlowMsg + Tag Registry 屬性 → FlowResult protobuf".to_string(),
+            icon:          "upload".to_string(),
+            category:      "io".to_string(),
+            color:         "#8E44AD".to_string(),
+        }
     }
-    fn output_type() -> ValueKind { ValueKind::Any }
-    fn name()    -> String { "sink-node:protobuf-flow-result".to_string() }
-    fn version() -> String { "0.1.0".to_string() }
 }
 
 impl NodeGuest for SinkNode {
-    fn process(msg: FlowMsg) -> NodeOutput {
-        use prost::Message;
-
-        // 唯一需要 Host Function 的地方
-        let tag_name   = host_api::get_tag_attr(msg.tag_id, "name")
-            .unwrap_or_else(|| format!("tag_{}", msg.tag_id));
-        let mqtt_topic = host_api::get_tag_attr(msg.tag_id, "mqtt_topic")
-            .unwrap_or_else(|| format!("iiot/tag/{}", msg.tag_id));
-
-        let value_f64: f64 = match msg.value {
-            TagValue::BoolVal(v) => if v { 1.0 } else { 0.0 },
-            TagValue::I32Val(v)  => v as f64,
-            TagValue::U32Val(v)  => v as f64,
-            TagValue::F32Val(v)  => v as f64,
-            TagValue::F64Val(v)  => v,
-            _ => return NodeOutput { msgs: vec![] },
-        };
-
-        let result = proto::FlowResult {
-            tag_id:    msg.tag_id,
-            tag_name,
-            mqtt_topic,
-            msg_id:    msg.msg_id,
-            value:     value_f64,
-            timestamp: msg.timestamp,
-            quality:   msg.quality as u32,
-            flow_id:   "flow-temp-pipeline-v1".to_string(),
-        };
-
-        let mut buf = Vec::with_capacity(result.encoded_len());
-        result.encode(&mut buf).ok();
-        unsafe { OUTPUT_BUF = buf; }
-
-        NodeOutput { msgs: vec![] }
+    fn init(props: String, _wiring: String) -> Result<(), String> {
+        let props: serde_json::Value = serde_json::from_str(&props).map_err(|e| e.to_string())?;
+        let flow_id = props["flow-id"].as_str().ok_or("flow-id 必須是字串")?;
+        unsafe { FLOW_ID = flow_id.to_string(); }
+        Ok(())
+    }
+
+    fn process(_input_port: u32, msgs: Vec<FlowMsg>) -> NodeOutput {
+        for msg in msgs { encode(msg); }
+        NodeOutput { outputs: vec![] }
     }
 
-    fn process_raw(_: u32, _: u32, _: Vec<u8>) -> NodeOutput { NodeOutput { msgs: vec![] } }
+    fn process_raw(_: u32, _: u32, _: Vec<u8>) -> NodeOutput { NodeOutput { outputs: vec![] } }
     fn save_state() -> Vec<u8>     { vec![] }
     fn load_state(_state: Vec<u8>) {}
 }
 
+// FlowMsg → FlowResult protobuf，寫入 OUTPUT_BUF
+fn encode(msg: FlowMsg) {
+    use prost::Message;
+
+    // 唯一需要 Host Function 的地方
+    let tag_name   = host_api::get_tag_attr(msg.tag_id, "name")
+        .unwrap_or_else(|| format!("tag_{}", msg.tag_id));
+    let mqtt_topic = host_api::get_tag_attr(msg.tag_id, "mqtt_topic")
+        .unwrap_or_else(|| format!("iiot/tag/{}", msg.tag_id));
+
+    let value_f64: f64 = match msg.value {
+        TagValue::BoolVal(v) => if v { 1.0 } else { 0.0 },
+        TagValue::I32Val(v)  => v as f64,
+        TagValue::U32Val(v)  => v as f64,
+        TagValue::F32Val(v)  => v as f64,
+        TagValue::F64Val(v)  => v,
+        _ => return,
+    };
+
+    let result = proto::FlowResult {
+        tag_id:    msg.tag_id,
+        tag_name,
+        mqtt_topic,
+        msg_id:    msg.msg_id,
+        value:     value_f64,
+        timestamp: msg.timestamp,
+        quality:   msg.quality as u32,
+        flow_id:   unsafe { (&raw const FLOW_ID).as_ref().unwrap().clone() },
+    };
+
+    let mut buf = Vec::with_capacity(result.encoded_len());
+    result.encode(&mut buf).ok();
+    unsafe { OUTPUT_BUF = buf; }
+}
+
 #[no_mangle]
 pub extern "C" fn take_output_ptr() -> u32 {
     unsafe { (&raw const OUTPUT_BUF).as_ref().unwrap().as_ptr() as u32 }
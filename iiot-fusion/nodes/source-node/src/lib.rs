@@ -4,12 +4,13 @@
 
 wit_bindgen::generate!({
     world: "flow-node",
-    path: "../../wit",
+    path: "../../wit/0.2.0",
 });
 
 use exports::iiot::flow::meta::Guest as MetaGuest;
 use exports::iiot::flow::node::Guest as NodeGuest;
-use iiot::flow::types::{FlowMsg, NodeOutput, TagValue, ValueKind};
+use iiot::flow::types::{FlowMsg, NodeOutput, PortMsgs, TagValue, ValueKind};
+use iiot::flow::node_descriptor::{JoinStrategy, NodeKind, NodeSpec, OutputPortDef};
 
 mod proto {
     #[derive(prost::Message, Clone)]
@@ -31,22 +32,38 @@ mod proto {
 struct SourceNode;
 
 impl MetaGuest for SourceNode {
-    fn accepted_input_types() -> Vec<ValueKind> { vec![] }
-    fn output_type() -> ValueKind { ValueKind::Any }
-    fn name()    -> String { "source-node:protobuf-tag-update".to_string() }
-    fn version() -> String { "0.1.0".to_string() }
+    fn describe() -> NodeSpec {
+        NodeSpec {
+            name:          "source-node:protobuf-tag-update".to_string(),
+            version:       "0.1.0".to_string(),
+            kind:          NodeKind::Source,
+            inputs:        vec![],
+            outputs:       vec![OutputPortDef {
+                port_id: 0, name: "out".to_string(), kind: ValueKind::Any,
+            }],
+            join_strategy: JoinStrategy::Any,
+            props:         vec![],
+            label:         "TagUpdate 來源".to_string(),
+            description:   "Protocol Driver 的 TagUpdate protobuf → FlowMsg".to_string(),
+            icon:          "download".to_string(),
+            category:      "io".to_string(),
+            color:         "#27AE60".to_string(),
+        }
+    }
 }
 
 impl NodeGuest for SourceNode {
-    fn process(_msg: FlowMsg) -> NodeOutput {
-        NodeOutput { msgs: vec![] }
+    fn init(_props: String, _wiring: String) -> Result<(), String> { Ok(()) }
+
+    fn process(_input_port: u32, _msgs: Vec<FlowMsg>) -> NodeOutput {
+        NodeOutput { outputs: vec![] }
     }
 
     fn process_raw(tag_id: u32, msg_id: u32, raw_bytes: Vec<u8>) -> NodeOutput {
         use prost::Message;
         let tu = match proto::TagUpdate::decode(raw_bytes.as_slice()) {
             Ok(v)  => v,
-            Err(_) => return NodeOutput { msgs: vec![] },
+            Err(_) => return NodeOutput { outputs: vec![] },
         };
 
         let value = if let Some(v) = tu.bool_val  { TagValue::BoolVal(v)   }
@@ -56,13 +73,13 @@ impl NodeGuest for SourceNode {
         else if let Some(v) = tu.f64_val           { TagValue::F64Val(v)    }
         else if let Some(v) = tu.str_val           { TagValue::ShortStr(v)  }
         else if let Some(v) = tu.blob_val          { TagValue::Blob(v)      }
-        else { return NodeOutput { msgs: vec![] }; };
+        else { return NodeOutput { outputs: vec![] }; };
 
-        NodeOutput { msgs: vec![FlowMsg {
+        NodeOutput { outputs: vec![PortMsgs { port_id: 0, msgs: vec![FlowMsg {
             tag_id, msg_id, value,
             timestamp: tu.timestamp,
             quality:   tu.quality as u8,
-        }]}
+        }]}]}
     }
 
     fn save_state() -> Vec<u8>     { vec![] }